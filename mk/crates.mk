@@ -89,7 +89,7 @@ DEPS_term := std log
 DEPS_getopts := std
 DEPS_collections := core alloc unicode
 DEPS_num := std
-DEPS_test := std getopts serialize rbml term time regex native:rust_test_helpers
+DEPS_test := std getopts serialize rbml term time regex libc rand native:rust_test_helpers
 DEPS_time := std serialize
 DEPS_rand := core
 DEPS_log := std regex
@@ -284,12 +284,21 @@ pub fn test_opts(config: &Config) -> test::TestOpts {
         ratchet_metrics: config.ratchet_metrics.clone(),
         ratchet_noise_percent: config.ratchet_noise_percent.clone(),
         save_metrics: config.save_metrics.clone(),
+        compare_metrics: None,
+        compare_confidence: 95.0,
         test_shard: config.test_shard.clone(),
+        shuffle_seed: None,
         nocapture: false,
+        isolate: false,
+        isolate_timeouts: false,
         color: test::AutoColor,
         show_boxplot: false,
         boxplot_width: 50,
         show_all_stats: false,
+        format: test::PrettyFormat,
+        junit: None,
+        test_timeout: None,
+        slow_threshold: None,
     }
 }
 
@@ -348,6 +357,7 @@ pub fn make_test(config: &Config, testfile: &Path, f: || -> test::TestFn)
             name: make_test_name(config, testfile),
             ignore: header::is_test_ignored(config, testfile),
             should_fail: test::ShouldFail::No,
+            timeout: None,
         },
         testfn: f(),
     }
@@ -38,10 +38,11 @@ if __name__ == '__main__':
         map(summarise, files)
     ok = count('ok')
     failed = count('failed')
+    timed_out = count('timeout')
     ignored = count('ignored')
     measured = count('bench')
-    print "summary of %d test runs: %d passed; %d failed; %d ignored; %d measured" % \
-            (len(logfiles), ok, failed, ignored, measured)
+    print "summary of %d test runs: %d passed; %d failed; %d timed out; %d ignored; %d measured" % \
+            (len(logfiles), ok, failed, timed_out, ignored, measured)
     print ""
     if failed > 0:
         print "failed tests:"
@@ -51,3 +52,11 @@ if __name__ == '__main__':
                 print "  %s:" % (f)
             for test in failures:
                 print "    %s" % (test)
+    if timed_out > 0:
+        print "timed out tests:"
+        for f, s in summaries:
+            timeouts = s.get('timeout', [])
+            if len(timeouts) > 0:
+                print "  %s:" % (f)
+            for test in timeouts:
+                print "    %s" % (test)
@@ -0,0 +1,588 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The gzip file format (RFC 1952).
+//!
+//! A gzip file is one or more "members", each of which is a header, a raw
+//! DEFLATE stream and a trailer with the CRC-32 and the length (modulo 2^32)
+//! of the uncompressed data. Decompressing a file with several members gives
+//! the concatenation of their data.
+
+use std::io;
+use std::io::{IoError, IoResult, BufReader, MemWriter};
+
+use crc32::{Crc32, crc32};
+use {DeflateWriter, InflateReader, DEFAULT_COMPRESSION, BEST_COMPRESSION, FAST_COMPRESSION};
+
+const ID1: u8 = 0x1f;
+const ID2: u8 = 0x8b;
+const CM_DEFLATE: u8 = 8;
+
+const FTEXT: u8 = 0x01;
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+const FRESERVED: u8 = 0xe0;
+
+// The value of `GzipHeader::os` when the operating system is unknown.
+const OS_UNKNOWN: u8 = 255;
+
+/// The header of a gzip member.
+///
+/// The file name and the comment are ISO 8859-1 (Latin-1) text, and must not
+/// contain zero bytes.
+#[deriving(Clone, PartialEq, Show)]
+pub struct GzipHeader {
+    /// The modification time of the original file, in seconds since the Unix
+    /// epoch, or 0 if there isn't one.
+    pub mtime: u32,
+    /// The name of the original file, without any directory.
+    pub filename: Option<Vec<u8>>,
+    /// A comment about the data.
+    pub comment: Option<Vec<u8>>,
+    /// Application specific extra fields, at most 65535 bytes of them.
+    pub extra: Option<Vec<u8>>,
+    /// The operating system the data was compressed on (0 for FAT, 3 for
+    /// Unix, 11 for NTFS, etc.)
+    pub os: u8,
+    /// Whether the data is probably text.
+    pub text: bool,
+}
+
+impl GzipHeader {
+    /// Creates a header with no modification time, name or comment.
+    pub fn new() -> GzipHeader {
+        GzipHeader {
+            mtime: 0,
+            filename: None,
+            comment: None,
+            extra: None,
+            os: OS_UNKNOWN,
+            text: false,
+        }
+    }
+
+    fn to_bytes(&self, level: uint) -> Vec<u8> {
+        let mut flags = 0;
+        if self.text { flags |= FTEXT }
+        if self.extra.is_some() { flags |= FEXTRA }
+        if self.filename.is_some() { flags |= FNAME }
+        if self.comment.is_some() { flags |= FCOMMENT }
+        // The extra flags say whether the slowest or fastest compression was
+        // used.
+        let xfl =
+            if level == BEST_COMPRESSION {
+                2
+            } else if level == FAST_COMPRESSION {
+                4
+            } else {
+                0
+            };
+
+        let mut w = MemWriter::new();
+        // Writing to a MemWriter never fails.
+        w.write(&[ID1, ID2, CM_DEFLATE, flags]).unwrap();
+        w.write_le_u32(self.mtime).unwrap();
+        w.write(&[xfl, self.os]).unwrap();
+        match self.extra {
+            Some(ref extra) => {
+                assert!(extra.len() <= 0xffff, "gzip extra field is too long");
+                w.write_le_u16(extra.len() as u16).unwrap();
+                w.write(extra.as_slice()).unwrap();
+            }
+            None => {}
+        }
+        for s in [&self.filename, &self.comment].iter() {
+            match **s {
+                Some(ref s) => {
+                    w.write(s.as_slice()).unwrap();
+                    w.write_u8(0).unwrap();
+                }
+                None => {}
+            }
+        }
+        w.into_inner()
+    }
+}
+
+fn invalid(desc: &'static str) -> IoError {
+    IoError {
+        kind: io::InvalidInput,
+        desc: desc,
+        detail: None,
+    }
+}
+
+// Turns the end of the input in the middle of a member into an error.
+fn truncated<T>(result: IoResult<T>) -> IoResult<T> {
+    match result {
+        Err(ref e) if e.kind == io::EndOfFile => Err(invalid("truncated gzip stream")),
+        result => result,
+    }
+}
+
+/// Compresses a buffer into a gzip member with a default header.
+pub fn gzip_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut w = GzipWriter::new(MemWriter::new(), DEFAULT_COMPRESSION);
+    // Writing to a MemWriter never fails.
+    w.write(bytes).unwrap();
+    w.finish().unwrap().into_inner()
+}
+
+/// Decompresses a buffer in the gzip format. If it has several members, their
+/// data is concatenated.
+pub fn gunzip_bytes(bytes: &[u8]) -> IoResult<Vec<u8>> {
+    let mut r = try!(GzipReader::new(BufReader::new(bytes)));
+    r.read_to_end()
+}
+
+/// A writer that compresses everything written to it into a gzip member and
+/// writes that to an underlying writer.
+///
+/// As with a `DeflateWriter`, the member must be ended by calling `finish`,
+/// or it's ended (ignoring errors) when the `GzipWriter` is dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use flate::{GzipHeader, GzipReader, GzipWriter, DEFAULT_COMPRESSION};
+/// use std::io::{MemReader, MemWriter};
+///
+/// let mut header = GzipHeader::new();
+/// header.filename = Some(b"hello.txt".to_vec());
+/// let mut w = GzipWriter::with_header(MemWriter::new(), DEFAULT_COMPRESSION, header);
+/// w.write_str("Hello, world!").unwrap();
+/// let gz = w.finish().unwrap().into_inner();
+///
+/// let mut r = GzipReader::new(MemReader::new(gz)).unwrap();
+/// assert_eq!(r.header().filename, Some(b"hello.txt".to_vec()));
+/// assert_eq!(r.read_to_string().unwrap().as_slice(), "Hello, world!");
+/// ```
+pub struct GzipWriter<W> {
+    inner: Option<DeflateWriter<W>>,
+    // The header, until it's written.
+    header: Option<Vec<u8>>,
+    crc: Crc32,
+    size: u32,
+}
+
+impl<W: Writer> GzipWriter<W> {
+    /// Creates a writer with a default header (no name, comment or
+    /// modification time) that writes to `inner`.
+    ///
+    /// `level` is the compression level, from 0 (no compression) to
+    /// `BEST_COMPRESSION`. Panics if the level is out of range.
+    pub fn new(inner: W, level: uint) -> GzipWriter<W> {
+        GzipWriter::with_header(inner, level, GzipHeader::new())
+    }
+
+    /// Creates a writer with the header given that writes to `inner`.
+    ///
+    /// `level` is the compression level, as for `new`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level is out of range, or if the extra field of the
+    /// header is longer than 65535 bytes, which a gzip header can't hold.
+    pub fn with_header(inner: W, level: uint, header: GzipHeader) -> GzipWriter<W> {
+        GzipWriter {
+            inner: Some(DeflateWriter::new(inner, level)),
+            header: Some(header.to_bytes(level)),
+            crc: Crc32::new(),
+            size: 0,
+        }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W { self.inner.as_ref().unwrap().get_ref() }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// ## Warning
+    ///
+    /// It is inadvisable to directly write to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W { self.inner.as_mut().unwrap().get_mut() }
+
+    /// Ends the gzip member, flushes the underlying writer and returns it.
+    ///
+    /// As with `DeflateWriter::finish`, the underlying writer is dropped if
+    /// this fails.
+    pub fn finish(mut self) -> IoResult<W> {
+        self.finish_member()
+    }
+
+    fn write_header(&mut self) -> IoResult<()> {
+        match self.header.take() {
+            Some(header) => self.get_mut().write(header.as_slice()),
+            None => Ok(()),
+        }
+    }
+
+    fn finish_member(&mut self) -> IoResult<W> {
+        try!(self.write_header());
+        let mut w = try!(self.inner.take().unwrap().finish());
+        try!(w.write_le_u32(self.crc.sum()));
+        try!(w.write_le_u32(self.size));
+        try!(w.flush());
+        Ok(w)
+    }
+}
+
+impl<W: Writer> Writer for GzipWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        try!(self.write_header());
+        try!(self.inner.as_mut().unwrap().write(buf));
+        self.crc.update(buf);
+        self.size += buf.len() as u32;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        try!(self.write_header());
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+#[unsafe_destructor]
+impl<W: Writer> Drop for GzipWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            // dtors should not panic, so we ignore a failed finish
+            let _ = self.finish_member();
+        }
+    }
+}
+
+/// A reader that decompresses gzip data read from an underlying reader.
+///
+/// If there are several members, the data of all of them is read in turn.
+/// The checksum and the length of the data of each member are checked when
+/// the end of the member is reached, and a mismatch gives an `InvalidInput`
+/// error, as do malformed headers and truncated or corrupt data.
+pub struct GzipReader<R> {
+    inner: InflateReader<R>,
+    header: GzipHeader,
+    crc: Crc32,
+    size: u32,
+    done: bool,
+}
+
+impl<R: Reader> GzipReader<R> {
+    /// Creates a reader that decompresses the gzip data read from `inner`.
+    ///
+    /// The header of the first member is read now, and an error is returned
+    /// if it isn't valid.
+    pub fn new(inner: R) -> IoResult<GzipReader<R>> {
+        let mut inner = InflateReader::new(inner);
+        let header = try!(truncated(read_header(&mut inner, None)));
+        Ok(GzipReader {
+            inner: inner,
+            header: header,
+            crc: Crc32::new(),
+            size: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the header of the member being read.
+    pub fn header(&self) -> &GzipHeader {
+        &self.header
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R { self.inner.get_ref() }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// ## Warning
+    ///
+    /// It is inadvisable to directly read from the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R { self.inner.get_mut() }
+
+    /// Unwraps this `GzipReader`, returning the underlying reader. Any input
+    /// that was read from it but not decompressed is lost.
+    pub fn into_inner(self) -> R { self.inner.into_inner() }
+
+    // Checks the trailer of the member that just ended, and starts on the
+    // next one if there is one. Returns false at the end of the input.
+    fn next_member(&mut self) -> IoResult<bool> {
+        let mut trailer = [0u8, ..8];
+        for b in trailer.iter_mut() {
+            *b = try!(truncated(self.inner.read_raw_byte()));
+        }
+        let crc = trailer[0] as u32 | trailer[1] as u32 << 8 |
+                  trailer[2] as u32 << 16 | trailer[3] as u32 << 24;
+        let size = trailer[4] as u32 | trailer[5] as u32 << 8 |
+                   trailer[6] as u32 << 16 | trailer[7] as u32 << 24;
+        if crc != self.crc.sum() {
+            return Err(invalid("gzip checksum mismatch"))
+        }
+        if size != self.size {
+            return Err(invalid("gzip length mismatch"))
+        }
+
+        let first = match self.inner.read_raw_byte() {
+            Ok(b) => b,
+            Err(ref e) if e.kind == io::EndOfFile => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        self.header = try!(truncated(read_header(&mut self.inner, Some(first))));
+        self.inner.reset();
+        self.crc.reset();
+        self.size = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Reader> Reader for GzipReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        loop {
+            if self.done {
+                return Err(io::standard_error(io::EndOfFile))
+            }
+            match self.inner.read(buf) {
+                Ok(n) => {
+                    self.crc.update(buf[..n]);
+                    self.size += n as u32;
+                    return Ok(n)
+                }
+                Err(ref e) if e.kind == io::EndOfFile => {}
+                Err(e) => return Err(e),
+            }
+            if !try!(self.next_member()) {
+                self.done = true;
+            }
+        }
+    }
+}
+
+// Reads a member header. `first` is its first byte, if that has already
+// been read.
+fn read_header<R: Reader>(r: &mut InflateReader<R>, first: Option<u8>) -> IoResult<GzipHeader> {
+    // All of the header is kept to check its CRC.
+    let mut bytes = vec![];
+    macro_rules! byte(
+        () => ({
+            let b = try!(r.read_raw_byte());
+            bytes.push(b);
+            b
+        })
+    )
+
+    let id1 = match first {
+        Some(b) => {
+            bytes.push(b);
+            b
+        }
+        None => byte!(),
+    };
+    let id2 = byte!();
+    if id1 != ID1 || id2 != ID2 {
+        return Err(invalid("not a gzip stream"))
+    }
+    if byte!() != CM_DEFLATE {
+        return Err(invalid("unsupported gzip compression method"))
+    }
+    let flags = byte!();
+    if flags & FRESERVED != 0 {
+        return Err(invalid("invalid gzip header flags"))
+    }
+
+    let mut header = GzipHeader::new();
+    for i in range(0, 4u) {
+        header.mtime |= byte!() as u32 << (8 * i);
+    }
+    let _xfl = byte!();
+    header.os = byte!();
+    header.text = flags & FTEXT != 0;
+    if flags & FEXTRA != 0 {
+        let len = byte!() as uint | byte!() as uint << 8;
+        let mut extra = Vec::with_capacity(len);
+        for _ in range(0, len) {
+            extra.push(byte!());
+        }
+        header.extra = Some(extra);
+    }
+    if flags & FNAME != 0 {
+        let mut name = vec![];
+        loop {
+            match byte!() {
+                0 => break,
+                b => name.push(b),
+            }
+        }
+        header.filename = Some(name);
+    }
+    if flags & FCOMMENT != 0 {
+        let mut comment = vec![];
+        loop {
+            match byte!() {
+                0 => break,
+                b => comment.push(b),
+            }
+        }
+        header.comment = Some(comment);
+    }
+    if flags & FHCRC != 0 {
+        // The header CRC is the low 16 bits of the CRC-32 of the header.
+        let crc = crc32(bytes.as_slice()) & 0xffff;
+        let expected = try!(r.read_raw_byte()) as u32 | (try!(r.read_raw_byte()) as u32) << 8;
+        if crc != expected {
+            return Err(invalid("gzip header checksum mismatch"))
+        }
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GzipHeader, GzipReader, GzipWriter, gzip_bytes, gunzip_bytes};
+    use {DEFAULT_COMPRESSION, BEST_COMPRESSION};
+    use std::io;
+    use std::io::{MemReader, MemWriter};
+
+    // `hello world\n` compressed by gzip(1) from a file named hello.txt with
+    // a modification time of 1400000000.
+    static HELLO: &'static str =
+        "1f8b0808004e7253020368656c6c6f2e74787400cb48cdc9c95728cf2fca49e102002d3b08af0c000000";
+    // `second\n` compressed by gzip -n.
+    static SECOND: &'static str = "1f8b08000000000000032b4e4dcecf4be102007ec00f0607000000";
+    // `hi there`, with a header with extra fields, a comment and a header CRC.
+    static FIELDS: &'static str =
+        "1f8b0816d202964900030400414200006120636f6d6d656e7400de29cbc85428c9482d4a0500ec76a3e3\
+         08000000";
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        let digits: Vec<u8> = hex.chars().map(|c| c.to_digit(16).unwrap() as u8).collect();
+        digits.as_slice().chunks(2).map(|d| d[0] << 4 | d[1]).collect()
+    }
+
+    #[test]
+    fn test_read_gzip_output() {
+        let mut r = GzipReader::new(MemReader::new(bytes(HELLO))).unwrap();
+        assert_eq!(*r.header(), GzipHeader {
+            mtime: 1400000000,
+            filename: Some(b"hello.txt".to_vec()),
+            comment: None,
+            extra: None,
+            os: 3,
+            text: false,
+        });
+        assert_eq!(r.read_to_end().unwrap(), b"hello world\n".to_vec());
+
+        let mut r = GzipReader::new(MemReader::new(bytes(FIELDS))).unwrap();
+        assert_eq!(*r.header(), GzipHeader {
+            mtime: 1234567890,
+            filename: None,
+            comment: Some(b"a comment".to_vec()),
+            extra: Some(b"AB\x00\x00".to_vec()),
+            os: 3,
+            text: false,
+        });
+        assert_eq!(r.read_to_end().unwrap(), b"hi there".to_vec());
+    }
+
+    #[test]
+    fn test_multiple_members() {
+        let mut gz = bytes(HELLO);
+        gz.push_all(bytes(SECOND).as_slice());
+        gz.push_all(gzip_bytes(b"").as_slice());
+        gz.push_all(bytes(FIELDS).as_slice());
+        assert_eq!(gunzip_bytes(gz.as_slice()).unwrap(),
+                   b"hello world\nsecond\nhi there".to_vec());
+
+        let mut r = GzipReader::new(MemReader::new(gz)).unwrap();
+        assert_eq!(r.read_exact(12).unwrap(), b"hello world\n".to_vec());
+        assert_eq!(r.header().filename, Some(b"hello.txt".to_vec()));
+        assert_eq!(r.read_exact(7).unwrap(), b"second\n".to_vec());
+        assert_eq!(r.header().filename, None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data = Vec::from_fn(100000, |i| (i % 251) as u8 ^ (i / 1000) as u8);
+        let header = GzipHeader {
+            mtime: 0x12345678,
+            filename: Some(b"data.bin".to_vec()),
+            comment: Some(b"test data".to_vec()),
+            extra: Some(vec![1, 2, 3]),
+            os: 0,
+            text: true,
+        };
+        for &level in [0u, DEFAULT_COMPRESSION, BEST_COMPRESSION].iter() {
+            let mut w = GzipWriter::with_header(MemWriter::new(), level, header.clone());
+            for piece in data.as_slice().chunks(777) {
+                w.write(piece).unwrap();
+            }
+            let gz = w.finish().unwrap().into_inner();
+            let mut r = GzipReader::new(MemReader::new(gz)).unwrap();
+            assert_eq!(*r.header(), header);
+            assert!(r.read_to_end().unwrap() == data);
+        }
+
+        assert_eq!(gunzip_bytes(gzip_bytes(data.as_slice()).as_slice()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_writer_drop_finishes() {
+        let mut out = MemWriter::new();
+        {
+            let mut w = GzipWriter::new(&mut out as &mut Writer, DEFAULT_COMPRESSION);
+            w.write(b"dropped").unwrap();
+        }
+        assert_eq!(gunzip_bytes(out.get_ref()).unwrap(), b"dropped".to_vec());
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_extra_too_long() {
+        let mut header = GzipHeader::new();
+        header.extra = Some(Vec::from_elem(0x10000, 0u8));
+        GzipWriter::with_header(MemWriter::new(), DEFAULT_COMPRESSION, header);
+    }
+
+    #[test]
+    fn test_errors() {
+        fn kind(gz: Vec<u8>) -> io::IoErrorKind {
+            match GzipReader::new(MemReader::new(gz)) {
+                Ok(mut r) => r.read_to_end().unwrap_err().kind,
+                Err(e) => e.kind,
+            }
+        }
+
+        let gz = bytes(HELLO);
+        assert_eq!(kind(vec![]), io::InvalidInput);
+        assert_eq!(kind(b"hello world".to_vec()), io::InvalidInput);
+        // Truncated in the header, the data, and the trailer.
+        assert_eq!(kind(gz.as_slice()[..5].to_vec()), io::InvalidInput);
+        assert_eq!(kind(gz.as_slice()[..25].to_vec()), io::InvalidInput);
+        assert_eq!(kind(gz.as_slice()[..gz.len() - 2].to_vec()), io::InvalidInput);
+
+        // A bad checksum, and a bad length.
+        let mut bad = gz.clone();
+        let n = bad.len();
+        bad[n - 8] ^= 1;
+        assert_eq!(kind(bad), io::InvalidInput);
+        let mut bad = gz.clone();
+        bad[n - 4] ^= 1;
+        assert_eq!(kind(bad), io::InvalidInput);
+
+        // A bad header checksum.
+        let mut bad = bytes(FIELDS);
+        bad[26] ^= 1;
+        assert_eq!(kind(bad), io::InvalidInput);
+
+        // Garbage after a member.
+        let mut bad = gz.clone();
+        bad.push_all(b"garbage");
+        assert_eq!(kind(bad), io::InvalidInput);
+    }
+}
@@ -11,8 +11,16 @@
 //! Simple [DEFLATE][def]-based compression. This is a wrapper around the
 //! [`miniz`][mz] library, which is a one-file pure-C implementation of zlib.
 //!
+//! Buffers can be compressed and decompressed in one go with `deflate_bytes`
+//! and `inflate_bytes`. Streams that don't fit in memory can be compressed
+//! with a `DeflateWriter` and decompressed with an `InflateReader`.
+//!
+//! The [gzip][gz] file format is supported too, by `gzip_bytes` and
+//! `gunzip_bytes`, and by `GzipWriter` and `GzipReader` for streams.
+//!
 //! [def]: https://en.wikipedia.org/wiki/DEFLATE
 //! [mz]: https://code.google.com/p/miniz/
+//! [gz]: http://tools.ietf.org/html/rfc1952
 
 #![crate_name = "flate"]
 #![experimental]
@@ -21,14 +29,22 @@
 #![doc(html_logo_url = "http://www.rust-lang.org/logos/rust-logo-128x128-blk-v2.png",
        html_favicon_url = "http://www.rust-lang.org/favicon.ico",
        html_root_url = "http://doc.rust-lang.org/nightly/")]
-#![feature(phase, unboxed_closures)]
+#![feature(phase, unboxed_closures, slicing_syntax, unsafe_destructor)]
 
 #[cfg(test)] #[phase(plugin, link)] extern crate log;
 
 extern crate libc;
 
+pub use crc32::{Crc32, crc32};
+pub use gzip::{GzipHeader, GzipReader, GzipWriter, gzip_bytes, gunzip_bytes};
+
 use std::c_vec::CVec;
-use libc::{c_void, size_t, c_int};
+use std::cmp;
+use std::io;
+use std::io::{IoError, IoResult};
+use std::ptr;
+use std::slice::bytes;
+use libc::{c_void, size_t, c_int, c_uint};
 
 #[link(name = "miniz", kind = "static")]
 extern {
@@ -45,11 +61,82 @@ extern {
                                     pout_len: *mut size_t,
                                     flags: c_int)
                                     -> *mut c_void;
+
+    fn tdefl_init(comp: *mut c_void,
+                  pput_buf_func: *const c_void,
+                  pput_buf_user: *mut c_void,
+                  flags: c_int)
+                  -> c_int;
+    /// Raw miniz streaming compression function.
+    fn tdefl_compress(comp: *mut c_void,
+                      pin_buf: *const c_void,
+                      pin_buf_size: *mut size_t,
+                      pout_buf: *mut c_void,
+                      pout_buf_size: *mut size_t,
+                      flush: c_int)
+                      -> c_int;
+
+    /// Raw miniz streaming decompression function.
+    fn tinfl_decompress(decomp: *mut c_void,
+                        pin_buf_next: *const u8,
+                        pin_buf_size: *mut size_t,
+                        pout_buf_start: *mut u8,
+                        pout_buf_next: *mut u8,
+                        pout_buf_size: *mut size_t,
+                        decomp_flags: c_uint)
+                        -> c_int;
 }
 
+// These are in rust_builtin.c, since only C knows how big miniz's states are.
+extern {
+    /// Allocates an uninitialized miniz compressor.
+    fn rust_tdefl_compressor_alloc() -> *mut c_void;
+    fn rust_tdefl_compressor_free(comp: *mut c_void);
+    /// Allocates an initialized miniz decompressor.
+    fn rust_tinfl_decompressor_alloc() -> *mut c_void;
+    fn rust_tinfl_decompressor_free(decomp: *mut c_void);
+}
+
+mod crc32;
+mod gzip;
+
 static LZ_NORM : c_int = 0x80;  // LZ with 128 probes, "normal"
 static TINFL_FLAG_PARSE_ZLIB_HEADER : c_int = 0x1; // parse zlib header and adler32 checksum
+static TINFL_FLAG_HAS_MORE_INPUT : c_int = 0x2; // there is more input after the buffer given
 static TDEFL_WRITE_ZLIB_HEADER : c_int = 0x01000; // write zlib header and adler32 checksum
+static TDEFL_GREEDY_PARSING_FLAG : c_int = 0x04000; // faster, greedy parsing
+static TDEFL_FORCE_ALL_RAW_BLOCKS : c_int = 0x80000; // don't compress at all
+
+// Flush modes of tdefl_compress.
+static TDEFL_NO_FLUSH : c_int = 0;
+static TDEFL_SYNC_FLUSH : c_int = 2;
+static TDEFL_FINISH : c_int = 4;
+
+// Return values of tdefl_compress.
+static TDEFL_STATUS_OKAY : c_int = 0;
+static TDEFL_STATUS_DONE : c_int = 1;
+
+// Return values of tinfl_decompress.
+static TINFL_STATUS_DONE : c_int = 0;
+static TINFL_STATUS_NEEDS_MORE_INPUT : c_int = 1;
+static TINFL_STATUS_HAS_MORE_OUTPUT : c_int = 2;
+
+// The size of the decompressor's dictionary, which its output buffer must
+// hold.
+const TINFL_LZ_DICT_SIZE : uint = 32768;
+
+const BUF_SIZE : uint = 32 * 1024;
+
+/// The fastest compression level.
+pub const FAST_COMPRESSION : uint = 1;
+/// The compression level used by `deflate_bytes`.
+pub const DEFAULT_COMPRESSION : uint = 6;
+/// The best (and slowest) compression level.
+pub const BEST_COMPRESSION : uint = 10;
+
+// The number of dictionary probes for each compression level, as in zlib's
+// levels (except for 10, which is better than zlib's best).
+static NUM_PROBES : [c_int, ..11] = [0, 1, 6, 32, 16, 32, 128, 256, 512, 768, 1500];
 
 fn deflate_bytes_internal(bytes: &[u8], flags: c_int) -> Option<CVec<u8>> {
     unsafe {
@@ -101,11 +188,537 @@ pub fn inflate_bytes_zlib(bytes: &[u8]) -> Option<CVec<u8>> {
     inflate_bytes_internal(bytes, TINFL_FLAG_PARSE_ZLIB_HEADER)
 }
 
+// Returns the tdefl flags for a compression level.
+fn level_flags(level: uint) -> c_int {
+    assert!(level <= BEST_COMPRESSION, "invalid compression level: {}", level);
+    let mut flags = NUM_PROBES[level];
+    if level <= 3 {
+        flags |= TDEFL_GREEDY_PARSING_FLAG;
+    }
+    if level == 0 {
+        flags |= TDEFL_FORCE_ALL_RAW_BLOCKS;
+    }
+    flags
+}
+
+fn corrupt_stream() -> IoError {
+    IoError {
+        kind: io::InvalidInput,
+        desc: "corrupt deflate stream",
+        detail: None,
+    }
+}
+
+// Owns a heap allocated tdefl_compressor.
+struct Compressor {
+    ptr: *mut c_void,
+}
+
+impl Drop for Compressor {
+    fn drop(&mut self) {
+        unsafe { rust_tdefl_compressor_free(self.ptr) }
+    }
+}
+
+// Owns a heap allocated tinfl_decompressor.
+struct Decompressor {
+    ptr: *mut c_void,
+}
+
+impl Drop for Decompressor {
+    fn drop(&mut self) {
+        unsafe { rust_tinfl_decompressor_free(self.ptr) }
+    }
+}
+
+/// A writer that compresses everything written to it and writes the
+/// compressed data to an underlying writer.
+///
+/// The compressed stream must be ended by calling `finish`, which also
+/// returns the underlying writer. If a `DeflateWriter` is dropped without
+/// being finished, the stream is ended then, but any error is ignored.
+/// Calling `flush` writes out everything written so far (as a "sync flush"),
+/// at some cost in compression.
+///
+/// # Example
+///
+/// ```rust
+/// use flate::{DeflateWriter, InflateReader, DEFAULT_COMPRESSION};
+/// use std::io::{MemReader, MemWriter};
+///
+/// let mut w = DeflateWriter::new(MemWriter::new(), DEFAULT_COMPRESSION);
+/// w.write_str("Hello, Hello, Hello!").unwrap();
+/// let compressed = w.finish().unwrap().into_inner();
+///
+/// let mut r = InflateReader::new(MemReader::new(compressed));
+/// assert_eq!(r.read_to_string().unwrap().as_slice(), "Hello, Hello, Hello!");
+/// ```
+pub struct DeflateWriter<W> {
+    inner: Option<W>,
+    comp: Compressor,
+    buf: Vec<u8>,
+    finished: bool,
+}
+
+impl<W: Writer> DeflateWriter<W> {
+    /// Creates a writer that writes raw DEFLATE data, without any sort of
+    /// header, to `inner`.
+    ///
+    /// `level` is the compression level, from 0 (no compression) to
+    /// `BEST_COMPRESSION`. Panics if the level is out of range.
+    pub fn new(inner: W, level: uint) -> DeflateWriter<W> {
+        DeflateWriter::with_flags(inner, level_flags(level))
+    }
+
+    /// Creates a writer that writes data with a header that zlib can
+    /// understand to `inner`.
+    ///
+    /// `level` is the compression level, as for `new`.
+    pub fn new_zlib(inner: W, level: uint) -> DeflateWriter<W> {
+        DeflateWriter::with_flags(inner, level_flags(level) | TDEFL_WRITE_ZLIB_HEADER)
+    }
+
+    fn with_flags(inner: W, flags: c_int) -> DeflateWriter<W> {
+        let comp = unsafe {
+            let ptr = rust_tdefl_compressor_alloc();
+            if ptr.is_null() {
+                panic!("out of memory");
+            }
+            tdefl_init(ptr, ptr::null(), ptr::null_mut(), flags);
+            Compressor { ptr: ptr }
+        };
+        DeflateWriter {
+            inner: Some(inner),
+            comp: comp,
+            buf: Vec::from_elem(BUF_SIZE, 0u8),
+            finished: false,
+        }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W { self.inner.as_ref().unwrap() }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// ## Warning
+    ///
+    /// It is inadvisable to directly write to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W { self.inner.as_mut().unwrap() }
+
+    /// Ends the compressed stream, flushes the underlying writer and returns
+    /// it.
+    ///
+    /// If writing the end of the stream fails, the error is returned and the
+    /// underlying writer is dropped.
+    pub fn finish(mut self) -> IoResult<W> {
+        self.finished = true;
+        try!(self.compress(&[], TDEFL_FINISH));
+        try!(self.inner.as_mut().unwrap().flush());
+        Ok(self.inner.take().unwrap())
+    }
+
+    // Compresses all of `input`, and writes out as much of the compressed
+    // data as `flush` calls for.
+    fn compress(&mut self, input: &[u8], flush: c_int) -> IoResult<()> {
+        let mut consumed = 0;
+        loop {
+            let mut in_size = (input.len() - consumed) as size_t;
+            let mut out_size = self.buf.len() as size_t;
+            let status = unsafe {
+                tdefl_compress(self.comp.ptr,
+                               input[consumed..].as_ptr() as *const c_void,
+                               &mut in_size,
+                               self.buf.as_mut_ptr() as *mut c_void,
+                               &mut out_size,
+                               flush)
+            };
+            if status != TDEFL_STATUS_OKAY && status != TDEFL_STATUS_DONE {
+                return Err(IoError {
+                    kind: io::OtherIoError,
+                    desc: "compression failed",
+                    detail: None,
+                });
+            }
+            consumed += in_size as uint;
+            let out_size = out_size as uint;
+            if out_size > 0 {
+                try!(self.inner.as_mut().unwrap().write(self.buf[..out_size]));
+            }
+            // A full output buffer means there may be more output waiting.
+            let more = consumed < input.len() || out_size == self.buf.len() ||
+                       (flush == TDEFL_FINISH && status != TDEFL_STATUS_DONE);
+            if !more {
+                return Ok(())
+            }
+        }
+    }
+}
+
+impl<W: Writer> Writer for DeflateWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        if self.finished {
+            return Err(io::standard_error(io::InvalidInput))
+        }
+        self.compress(buf, TDEFL_NO_FLUSH)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        if !self.finished {
+            try!(self.compress(&[], TDEFL_SYNC_FLUSH));
+        }
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+#[unsafe_destructor]
+impl<W: Writer> Drop for DeflateWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() && !self.finished {
+            // dtors should not panic, so we ignore a failed finish
+            self.finished = true;
+            let _ = self.compress(&[], TDEFL_FINISH);
+        }
+    }
+}
+
+/// A reader that decompresses the data read from an underlying reader.
+///
+/// Reading past the end of the compressed stream gives an `EndOfFile`
+/// error, and corrupt or truncated data gives an `InvalidInput` error.
+/// Since input is read from the underlying reader in blocks, it may be read
+/// past the end of the compressed stream.
+pub struct InflateReader<R> {
+    inner: R,
+    decomp: Decompressor,
+    flags: c_int,
+    // Compressed data read from `inner`, of which `input[in_pos..in_len]`
+    // hasn't been decompressed yet.
+    input: Vec<u8>,
+    in_pos: uint,
+    in_len: uint,
+    // The decompressor's output buffer, which also holds the dictionary.
+    // `dict[out_pos..out_end]` hasn't been returned yet, and the next output
+    // goes at `dict_pos`.
+    dict: Vec<u8>,
+    dict_pos: uint,
+    out_pos: uint,
+    out_end: uint,
+    // `inner` has reached its end.
+    eof: bool,
+    // The compressed stream has ended.
+    done: bool,
+    corrupt: bool,
+}
+
+impl<R: Reader> InflateReader<R> {
+    /// Creates a reader that decompresses raw DEFLATE data, without any sort
+    /// of header, read from `inner`.
+    pub fn new(inner: R) -> InflateReader<R> {
+        InflateReader::with_flags(inner, 0)
+    }
+
+    /// Creates a reader that decompresses data that starts with a zlib
+    /// header, read from `inner`. The checksum at the end of the data is
+    /// checked.
+    pub fn new_zlib(inner: R) -> InflateReader<R> {
+        InflateReader::with_flags(inner, TINFL_FLAG_PARSE_ZLIB_HEADER)
+    }
+
+    fn with_flags(inner: R, flags: c_int) -> InflateReader<R> {
+        let decomp = unsafe {
+            let ptr = rust_tinfl_decompressor_alloc();
+            if ptr.is_null() {
+                panic!("out of memory");
+            }
+            Decompressor { ptr: ptr }
+        };
+        InflateReader {
+            inner: inner,
+            decomp: decomp,
+            flags: flags,
+            input: Vec::from_elem(BUF_SIZE, 0u8),
+            in_pos: 0,
+            in_len: 0,
+            dict: Vec::from_elem(TINFL_LZ_DICT_SIZE, 0u8),
+            dict_pos: 0,
+            out_pos: 0,
+            out_end: 0,
+            eof: false,
+            done: false,
+            corrupt: false,
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R { &self.inner }
+
+    /// Gets a mutable reference to the underlying reader.
+    ///
+    /// ## Warning
+    ///
+    /// It is inadvisable to directly read from the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R { &mut self.inner }
+
+    /// Unwraps this `InflateReader`, returning the underlying reader. Any
+    /// input that was read from it but not decompressed is lost.
+    pub fn into_inner(self) -> R { self.inner }
+
+    // Reads a byte of input without decompressing it, e.g., a byte of a
+    // header or a trailer around the compressed stream.
+    fn read_raw_byte(&mut self) -> IoResult<u8> {
+        if self.in_pos == self.in_len {
+            if self.eof {
+                return Err(io::standard_error(io::EndOfFile))
+            }
+            self.in_len = try!(self.inner.read(self.input.as_mut_slice()));
+            self.in_pos = 0;
+            if self.in_len == 0 {
+                return Err(io::standard_error(io::EndOfFile))
+            }
+        }
+        self.in_pos += 1;
+        Ok(self.input[self.in_pos - 1])
+    }
+
+    // Starts decompressing a new stream from the rest of the input.
+    fn reset(&mut self) {
+        unsafe { rust_tinfl_decompressor_free(self.decomp.ptr) }
+        self.decomp.ptr = unsafe { rust_tinfl_decompressor_alloc() };
+        if self.decomp.ptr.is_null() {
+            panic!("out of memory");
+        }
+        self.dict_pos = 0;
+        self.out_pos = 0;
+        self.out_end = 0;
+        self.done = false;
+        self.corrupt = false;
+    }
+
+    // Decompresses more data into `dict`, reading more input first if all
+    // of it has been decompressed.
+    fn decompress(&mut self) -> IoResult<()> {
+        if self.in_pos == self.in_len && !self.eof {
+            match self.inner.read(self.input.as_mut_slice()) {
+                Ok(n) => {
+                    self.in_pos = 0;
+                    self.in_len = n;
+                }
+                Err(ref e) if e.kind == io::EndOfFile => self.eof = true,
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Without TINFL_FLAG_HAS_MORE_INPUT, miniz pads truncated input with
+        // zeros (and can go on producing output forever), so it's always set
+        // and running out of input is checked for here instead.
+        let flags = self.flags | TINFL_FLAG_HAS_MORE_INPUT;
+        let mut in_size = (self.in_len - self.in_pos) as size_t;
+        let mut out_size = (self.dict.len() - self.dict_pos) as size_t;
+        let status = unsafe {
+            let dict = self.dict.as_mut_ptr();
+            tinfl_decompress(self.decomp.ptr,
+                             self.input[self.in_pos..].as_ptr(),
+                             &mut in_size,
+                             dict,
+                             dict.offset(self.dict_pos as int),
+                             &mut out_size,
+                             flags as c_uint)
+        };
+        self.in_pos += in_size as uint;
+        self.out_pos = self.dict_pos;
+        self.out_end = self.dict_pos + out_size as uint;
+        self.dict_pos = self.out_end & (self.dict.len() - 1);
+
+        if status == TINFL_STATUS_DONE {
+            self.done = true;
+        } else if status == TINFL_STATUS_NEEDS_MORE_INPUT {
+            if self.eof {
+                self.corrupt = true;
+            }
+        } else if status != TINFL_STATUS_HAS_MORE_OUTPUT {
+            self.corrupt = true;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Reader> Reader for InflateReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        loop {
+            if self.out_pos < self.out_end {
+                let n = cmp::min(buf.len(), self.out_end - self.out_pos);
+                bytes::copy_memory(buf, self.dict[self.out_pos..self.out_pos + n]);
+                self.out_pos += n;
+                return Ok(n)
+            }
+            if self.corrupt {
+                return Err(corrupt_stream())
+            }
+            if self.done {
+                return Err(io::standard_error(io::EndOfFile))
+            }
+            try!(self.decompress());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{inflate_bytes, deflate_bytes};
+    use super::{inflate_bytes, deflate_bytes, inflate_bytes_zlib, deflate_bytes_zlib};
+    use super::{DeflateWriter, InflateReader};
+    use super::{DEFAULT_COMPRESSION, BEST_COMPRESSION};
+    use std::cmp;
+    use std::io;
+    use std::io::{IoResult, MemReader, MemWriter};
     use std::rand;
     use std::rand::Rng;
+    use std::slice::bytes;
+
+    // Returns `n` bytes of random words, which compress well.
+    fn random_words(n: uint) -> Vec<u8> {
+        let mut r = rand::task_rng();
+        let words = Vec::from_fn(20, |_| {
+            let len = r.gen_range(1u, 10);
+            r.gen_iter::<u8>().take(len).collect::<Vec<u8>>()
+        });
+        let mut input = vec![];
+        while input.len() < n {
+            input.push_all(r.choose(words.as_slice()).unwrap().as_slice());
+        }
+        input.truncate(n);
+        input
+    }
+
+    // A reader that returns at most `chunk` bytes at a time.
+    struct ChunkReader {
+        data: Vec<u8>,
+        pos: uint,
+        chunk: uint,
+    }
+
+    impl Reader for ChunkReader {
+        fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+            if self.pos == self.data.len() {
+                return Err(io::standard_error(io::EndOfFile))
+            }
+            let n = cmp::min(cmp::min(buf.len(), self.chunk), self.data.len() - self.pos);
+            bytes::copy_memory(buf, self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    fn deflate_chunks(input: &[u8], level: uint, chunk: uint) -> Vec<u8> {
+        let mut w = DeflateWriter::new(MemWriter::new(), level);
+        for piece in input.chunks(chunk) {
+            w.write(piece).unwrap();
+        }
+        w.finish().unwrap().into_inner()
+    }
+
+    fn inflate_chunks(input: Vec<u8>, chunk: uint) -> IoResult<Vec<u8>> {
+        let mut r = InflateReader::new(ChunkReader { data: input, pos: 0, chunk: chunk });
+        let mut out = vec![];
+        let mut buf = Vec::from_elem(chunk, 0u8);
+        loop {
+            match r.read(buf.as_mut_slice()) {
+                Ok(n) => out.push_all(buf[..n]),
+                Err(ref e) if e.kind == io::EndOfFile => return Ok(out),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deflate_writer() {
+        let input = random_words(100000);
+        for level in range(0, BEST_COMPRESSION + 1) {
+            for &chunk in [1u, 1000, 100000].iter() {
+                let cmp = deflate_chunks(input.as_slice(), level, chunk);
+                let out = inflate_bytes(cmp.as_slice()).expect("inflation failed");
+                assert_eq!(input, out.as_slice());
+            }
+        }
+    }
+
+    #[test]
+    fn test_inflate_reader() {
+        let input = random_words(200000);
+        let cmp = deflate_bytes(input.as_slice()).expect("deflation failed");
+        for &chunk in [1u, 7, 4096, 1 << 20].iter() {
+            let out = inflate_chunks(cmp.as_slice().to_vec(), chunk).unwrap();
+            assert!(out == input);
+        }
+        let empty = deflate_chunks(&[], DEFAULT_COMPRESSION, 1);
+        assert_eq!(inflate_chunks(empty, 1).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_zlib_streams() {
+        let input = random_words(50000);
+        let mut w = DeflateWriter::new_zlib(MemWriter::new(), DEFAULT_COMPRESSION);
+        w.write(input.as_slice()).unwrap();
+        let cmp = w.finish().unwrap().into_inner();
+        let out = inflate_bytes_zlib(cmp.as_slice()).expect("inflation failed");
+        assert_eq!(input, out.as_slice());
+
+        let cmp = deflate_bytes_zlib(input.as_slice()).expect("deflation failed");
+        let mut r = InflateReader::new_zlib(MemReader::new(cmp.as_slice().to_vec()));
+        assert!(r.read_to_end().unwrap() == input);
+
+        // A bad checksum is an error.
+        let mut bad = cmp.as_slice().to_vec();
+        let last = bad.len() - 1;
+        bad[last] ^= 1;
+        let mut r = InflateReader::new_zlib(MemReader::new(bad));
+        assert_eq!(r.read_to_end().unwrap_err().kind, io::InvalidInput);
+    }
+
+    #[test]
+    fn test_deflate_writer_flush() {
+        let mut w = DeflateWriter::new(MemWriter::new(), DEFAULT_COMPRESSION);
+        w.write(b"hello").unwrap();
+        w.flush().unwrap();
+        // Everything written so far can be decompressed, but the stream
+        // hasn't ended.
+        let partial = w.get_ref().get_ref().to_vec();
+        let mut r = InflateReader::new(MemReader::new(partial));
+        assert_eq!(r.read_exact(5).unwrap(), b"hello".to_vec());
+        assert_eq!(r.read_byte().unwrap_err().kind, io::InvalidInput);
+
+        w.write(b" world").unwrap();
+        let cmp = w.finish().unwrap().into_inner();
+        let mut r = InflateReader::new(MemReader::new(cmp));
+        assert_eq!(r.read_to_end().unwrap(), b"hello world".to_vec());
+    }
+
+    #[test]
+    fn test_deflate_writer_drop_finishes() {
+        let mut out = MemWriter::new();
+        {
+            let mut w = DeflateWriter::new(&mut out as &mut Writer, DEFAULT_COMPRESSION);
+            w.write(b"dropped").unwrap();
+        }
+        let out = inflate_bytes(out.get_ref()).expect("inflation failed");
+        assert_eq!(out.as_slice(), b"dropped");
+    }
+
+    #[test]
+    fn test_inflate_reader_errors() {
+        let input = random_words(10000);
+        let cmp = deflate_bytes(input.as_slice()).expect("deflation failed");
+        let truncated = cmp.as_slice()[..cmp.len() / 2].to_vec();
+        assert_eq!(inflate_chunks(truncated, 100).unwrap_err().kind, io::InvalidInput);
+        // 0xff starts a block of an invalid type.
+        assert_eq!(inflate_chunks(vec![0xff, 0xff, 0xff], 100).unwrap_err().kind,
+                   io::InvalidInput);
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_invalid_level() {
+        DeflateWriter::new(MemWriter::new(), BEST_COMPRESSION + 1);
+    }
 
     #[test]
     fn test_flate_round_trip() {
@@ -25,6 +25,10 @@
 //! argument following either a space or an equals sign. Single-character
 //! options don't require the space.
 //!
+//! Programs with subcommands (e.g., `tool build --release`) can describe each
+//! one with `command` and pass them to `getopts_commands`, which reports the
+//! subcommand that was given through `Matches::subcommand`.
+//!
 //! # Example
 //!
 //! The following example shows simple command line parsing for an application
@@ -100,10 +104,14 @@ use self::Optval::*;
 use self::SplitWithinState::*;
 use self::Whitespace::*;
 use self::LengthLimit::*;
+use self::Shell::*;
+use self::ArgCompletion::*;
 
 use std::fmt;
+use std::os;
 use std::result::Result::{Err, Ok};
 use std::result;
+use std::str::FromStr;
 use std::string::String;
 
 /// Name of an option. Either a string or a single char.
@@ -171,7 +179,29 @@ pub struct OptGroup {
     /// Whether option has an argument
     pub hasarg: HasArg,
     /// How often it can occur
-    pub occur: Occur
+    pub occur: Occur,
+    /// Value to use if the option isn't given
+    pub default: Option<String>,
+    /// Environment variable to take the value from if the option isn't given
+    pub env: Option<String>,
+}
+
+/// A subcommand, e.g., `build` in `tool build --release`, along with the
+/// options and the subcommands of its own that it accepts.
+///
+/// The options of a subcommand are accepted in addition to those of the
+/// commands it is nested in, so their names must be distinct: `subcommand`
+/// and `getopts_commands` panic if they aren't.
+#[deriving(Clone, PartialEq, Eq)]
+pub struct Command {
+    /// Name of the subcommand, as given on the command line
+    pub name: String,
+    /// Description for usage help text
+    pub desc: String,
+    /// Options accepted after the subcommand's name
+    pub opts: Vec<OptGroup>,
+    /// Subcommands nested within this one
+    pub subcommands: Vec<Command>,
 }
 
 /// Describes whether an option is given at all or has a value.
@@ -189,8 +219,13 @@ pub struct Matches {
     opts: Vec<Opt>,
     /// Values of the Options that matched
     vals: Vec<Vec<Optval>>,
+    /// Values of the Options that weren't given, from their environment
+    /// variable or their default
+    fallbacks: Vec<Option<String>>,
     /// Free string fragments
     pub free: Vec<String>,
+    /// The subcommand that was given, if any, and its matches
+    command: Option<(String, Box<Matches>)>,
 }
 
 /// The type returned when the command line does not conform to the
@@ -208,6 +243,11 @@ pub enum Fail {
     OptionDuplicated(String),
     /// There's an argument being passed to a non-argument option.
     UnexpectedArgument(String),
+    /// The passed subcommand is not declared among the possible subcommands.
+    UnrecognizedCommand(String),
+    /// The argument of an option could not be parsed as a value of the type
+    /// asked for. Holds the name of the option and the argument.
+    InvalidArgument(String, String),
 }
 
 /// The type of failure that occurred.
@@ -219,6 +259,8 @@ pub enum FailType {
     OptionMissing_,
     OptionDuplicated_,
     UnexpectedArgument_,
+    UnrecognizedCommand_,
+    InvalidArgument_,
 }
 
 impl Copy for FailType {}
@@ -285,6 +327,33 @@ impl OptGroup {
             (_,_) => panic!("something is wrong with the long-form opt")
         }
     }
+
+    /// Set the value to use if the option isn't given (and its environment
+    /// variable, if any, isn't set). It's shown in the usage help.
+    ///
+    /// Panics if the option takes no argument.
+    pub fn default_value(mut self, value: &str) -> OptGroup {
+        assert!(self.hasarg != No, "an option without an argument can't have a default");
+        self.default = Some(value.to_string());
+        self
+    }
+
+    /// Set an environment variable to take the value from if the option
+    /// isn't given. It's shown in the usage help.
+    ///
+    /// Panics if the option takes no argument.
+    pub fn env_var(mut self, name: &str) -> OptGroup {
+        assert!(self.hasarg != No, "an option without an argument can't have an environment variable");
+        self.env = Some(name.to_string());
+        self
+    }
+
+    // The value to use if the option isn't given, if any.
+    fn fallback(&self) -> Option<String> {
+        self.env.as_ref()
+            .and_then(|name| os::getenv(name.as_slice()))
+            .or_else(|| self.default.clone())
+    }
 }
 
 impl Matches {
@@ -295,8 +364,22 @@ impl Matches {
         }
     }
 
+    // Like `opt_vals`, but if the option wasn't given, its fallback value is
+    // used if it has one.
+    fn opt_vals_or_fallback(&self, nm: &str) -> Vec<Optval> {
+        match find_opt(self.opts.as_slice(), Name::from_str(nm)) {
+            Some(id) if self.vals[id].is_empty() => {
+                match self.fallbacks[id] {
+                    Some(ref s) => vec!(Val(s.clone())),
+                    None => Vec::new(),
+                }
+            }
+            _ => self.opt_vals(nm),
+        }
+    }
+
     fn opt_val(&self, nm: &str) -> Option<Optval> {
-        let vals = self.opt_vals(nm);
+        let vals = self.opt_vals_or_fallback(nm);
         if vals.is_empty() {
             None
         } else {
@@ -305,6 +388,9 @@ impl Matches {
     }
 
     /// Returns true if an option was matched.
+    ///
+    /// This is false for an option that wasn't given, even if it has a
+    /// default value or its environment variable is set.
     pub fn opt_present(&self, nm: &str) -> bool {
         !self.opt_vals(nm).is_empty()
     }
@@ -343,7 +429,7 @@ impl Matches {
     /// Used when an option accepts multiple values.
     pub fn opt_strs(&self, nm: &str) -> Vec<String> {
         let mut acc: Vec<String> = Vec::new();
-        let r = self.opt_vals(nm);
+        let r = self.opt_vals_or_fallback(nm);
         for v in r.iter() {
             match *v {
                 Val(ref s) => acc.push((*s).clone()),
@@ -354,8 +440,11 @@ impl Matches {
     }
 
     /// Returns the string argument supplied to a matching option or `None`.
+    ///
+    /// If the option wasn't given, the value of its environment variable or
+    /// its default value is returned, if it has one.
     pub fn opt_str(&self, nm: &str) -> Option<String> {
-        let vals = self.opt_vals(nm);
+        let vals = self.opt_vals_or_fallback(nm);
         if vals.is_empty() {
             return None::<String>;
         }
@@ -366,13 +455,61 @@ impl Matches {
     }
 
 
+    /// Returns the name of the subcommand that was given and the matches for
+    /// it, or `None` if there wasn't one.
+    ///
+    /// The matches for a subcommand also include the options of the commands
+    /// it is nested in, wherever they were given on the command line.
+    pub fn subcommand(&self) -> Option<(&str, &Matches)> {
+        match self.command {
+            Some((ref name, ref matches)) => Some((name.as_slice(), &**matches)),
+            None => None,
+        }
+    }
+
+    /// Returns the name of the subcommand that was given, if any.
+    pub fn subcommand_name(&self) -> Option<&str> {
+        self.subcommand().map(|(name, _)| name)
+    }
+
+    /// Returns the argument supplied to a matching option parsed as a `T`, or
+    /// `None` if there's no argument (as for `opt_str`).
+    ///
+    /// Returns `Err(InvalidArgument)` if the argument can't be parsed.
+    pub fn opt_parse<T: FromStr>(&self, nm: &str) -> result::Result<Option<T>, Fail> {
+        match self.opt_str(nm) {
+            None => Ok(None),
+            Some(s) => match FromStr::from_str(s.as_slice()) {
+                Some(v) => Ok(Some(v)),
+                None => Err(InvalidArgument(nm.to_string(), s)),
+            },
+        }
+    }
+
+    /// Returns the arguments provided to all matches of the given option
+    /// parsed as `T`s (as for `opt_strs`).
+    ///
+    /// Returns `Err(InvalidArgument)` if any argument can't be parsed.
+    pub fn opt_parses<T: FromStr>(&self, nm: &str) -> result::Result<Vec<T>, Fail> {
+        let mut acc = Vec::new();
+        for s in self.opt_strs(nm).into_iter() {
+            match FromStr::from_str(s.as_slice()) {
+                Some(v) => acc.push(v),
+                None => return Err(InvalidArgument(nm.to_string(), s)),
+            }
+        }
+        Ok(acc)
+    }
+
     /// Returns the matching string, a default, or none.
     ///
-    /// Returns none if the option was not present, `def` if the option was
-    /// present but no argument was provided, and the argument if the option was
-    /// present and an argument was provided.
+    /// Returns `def` if the option was present but no argument was provided,
+    /// and the argument if the option was present and an argument was
+    /// provided. If the option wasn't given, the value of its environment
+    /// variable or its default value is returned if it has one, and none
+    /// otherwise.
     pub fn opt_default(&self, nm: &str, def: &str) -> Option<String> {
-        let vals = self.opt_vals(nm);
+        let vals = self.opt_vals_or_fallback(nm);
         if vals.is_empty() {
             None
         } else {
@@ -422,7 +559,9 @@ pub fn reqopt(short_name: &str, long_name: &str, desc: &str, hint: &str) -> OptG
         hint: hint.to_string(),
         desc: desc.to_string(),
         hasarg: Yes,
-        occur: Req
+        occur: Req,
+        default: None,
+        env: None,
     }
 }
 
@@ -442,7 +581,9 @@ pub fn optopt(short_name: &str, long_name: &str, desc: &str, hint: &str) -> OptG
         hint: hint.to_string(),
         desc: desc.to_string(),
         hasarg: Yes,
-        occur: Optional
+        occur: Optional,
+        default: None,
+        env: None,
     }
 }
 
@@ -460,7 +601,9 @@ pub fn optflag(short_name: &str, long_name: &str, desc: &str) -> OptGroup {
         hint: "".to_string(),
         desc: desc.to_string(),
         hasarg: No,
-        occur: Optional
+        occur: Optional,
+        default: None,
+        env: None,
     }
 }
 
@@ -479,7 +622,9 @@ pub fn optflagmulti(short_name: &str, long_name: &str, desc: &str) -> OptGroup {
         hint: "".to_string(),
         desc: desc.to_string(),
         hasarg: No,
-        occur: Multi
+        occur: Multi,
+        default: None,
+        env: None,
     }
 }
 
@@ -499,7 +644,9 @@ pub fn optflagopt(short_name: &str, long_name: &str, desc: &str, hint: &str) ->
         hint: hint.to_string(),
         desc: desc.to_string(),
         hasarg: Maybe,
-        occur: Optional
+        occur: Optional,
+        default: None,
+        env: None,
     }
 }
 
@@ -520,7 +667,9 @@ pub fn optmulti(short_name: &str, long_name: &str, desc: &str, hint: &str) -> Op
         hint: hint.to_string(),
         desc: desc.to_string(),
         hasarg: Yes,
-        occur: Multi
+        occur: Multi,
+        default: None,
+        env: None,
     }
 }
 
@@ -539,7 +688,47 @@ pub fn opt(short_name: &str,
         hint: hint.to_string(),
         desc: desc.to_string(),
         hasarg: hasarg,
-        occur: occur
+        occur: occur,
+        default: None,
+        env: None,
+    }
+}
+
+/// Create a subcommand with the options given and no subcommands of its own.
+pub fn command(name: &str, desc: &str, opts: &[OptGroup]) -> Command {
+    assert!(!name.is_empty() && !name.starts_with("-"),
+            "invalid subcommand name: '{}'", name);
+    Command {
+        name: name.to_string(),
+        desc: desc.to_string(),
+        opts: opts.to_vec(),
+        subcommands: Vec::new(),
+    }
+}
+
+impl Command {
+    /// Add a subcommand nested within this one.
+    ///
+    /// Panics if an option of `cmd`, or of a subcommand nested in it, has the
+    /// same name as an option of this command.
+    pub fn subcommand(mut self, cmd: Command) -> Command {
+        check_distinct(self.opts.as_slice(), &cmd);
+        self.subcommands.push(cmd);
+        self
+    }
+
+    /// Derive a usage message for this subcommand, listing its options and
+    /// its subcommands.
+    pub fn usage(&self, brief: &str) -> String {
+        command_usage(brief, self.opts.as_slice(), self.subcommands.as_slice())
+    }
+
+    /// Derive a short one-line usage summary for this subcommand.
+    /// `program_name` should include the names of the enclosing commands,
+    /// e.g. `tool build`.
+    pub fn short_usage(&self, program_name: &str) -> String {
+        short_command_usage(program_name, self.opts.as_slice(),
+                            self.subcommands.as_slice())
     }
 }
 
@@ -569,6 +758,12 @@ impl fmt::Show for Fail {
             UnexpectedArgument(ref nm) => {
                 write!(f, "Option '{}' does not take an argument.", *nm)
             }
+            UnrecognizedCommand(ref nm) => {
+                write!(f, "Unrecognized command: '{}'.", *nm)
+            }
+            InvalidArgument(ref nm, ref arg) => {
+                write!(f, "Invalid argument to option '{}': '{}'.", *nm, *arg)
+            }
         }
     }
 }
@@ -582,19 +777,124 @@ impl fmt::Show for Fail {
 /// Returns `Err(Fail)` on failure: use the `Show` implementation of `Fail` to display
 /// information about it.
 pub fn getopts(args: &[String], optgrps: &[OptGroup]) -> Result {
-    let opts: Vec<Opt> = optgrps.iter().map(|x| x.long_to_short()).collect();
-    let n_opts = opts.len();
-
-    fn f(_x: uint) -> Vec<Optval> { return Vec::new(); }
+    getopts_commands(args, optgrps, &[])
+}
 
-    let mut vals = Vec::from_fn(n_opts, f);
+/// Parse command line arguments according to the provided options and
+/// subcommands.
+///
+/// Options in `optgrps` may be given anywhere on the command line. The first
+/// free argument must be the name of one of `commands`; the arguments after
+/// it are parsed according to the options of that subcommand (as well as
+/// `optgrps`) and its own subcommands, in the same way. The chosen
+/// subcommand is available from `Matches::subcommand`, and the free
+/// arguments following it are in its matches. If no subcommand is given, the
+/// matches have no subcommand, which the caller may treat as an error.
+///
+/// Returns `Err(Fail)` on failure: use the `Show` implementation of `Fail` to display
+/// information about it.
+///
+/// # Panics
+///
+/// Panics if an option of one of `commands`, or of a subcommand nested in
+/// it, has the same name as one of `optgrps`.
+pub fn getopts_commands(args: &[String], optgrps: &[OptGroup],
+                        commands: &[Command]) -> Result {
+    for cmd in commands.iter() {
+        check_distinct(optgrps, cmd);
+    }
+    let mut opts: Vec<Opt> = optgrps.iter().map(|x| x.long_to_short()).collect();
+    let mut vals = Vec::from_fn(opts.len(), |_| Vec::new());
+    let mut fallbacks: Vec<Option<String>> = optgrps.iter().map(|x| x.fallback()).collect();
     let mut free: Vec<String> = Vec::new();
+
+    // The names of the subcommands that were given, and how many options
+    // were accepted at each level.
+    let mut path: Vec<(String, uint)> = Vec::new();
+    let mut commands = commands;
+    let mut i = 0;
+    loop {
+        i += try!(parse_args(args.slice_from(i), opts.as_slice(), &mut vals, &mut free,
+                             !commands.is_empty()));
+        if i == args.len() || !free.is_empty() {
+            break
+        }
+        let name = args[i].clone();
+        let cmd = match commands.iter().find(|c| c.name == name) {
+            Some(cmd) => cmd,
+            None => return Err(UnrecognizedCommand(name)),
+        };
+        path.push((name, opts.len()));
+        opts.extend(cmd.opts.iter().map(|x| x.long_to_short()));
+        vals.grow(cmd.opts.len(), Vec::new());
+        fallbacks.extend(cmd.opts.iter().map(|x| x.fallback()));
+        commands = cmd.subcommands.as_slice();
+        i += 1;
+    }
+
+    for i in range(0u, opts.len()) {
+        let n = vals[i].len();
+        let occ = opts[i].occur;
+        if occ == Req && n == 0 && fallbacks[i].is_none() {
+            return Err(OptionMissing(opts[i].name.to_string()));
+        }
+        if occ != Multi && n > 1 {
+            return Err(OptionDuplicated(opts[i].name.to_string()));
+        }
+    }
+
+    let mut matches = Matches {
+        opts: opts.clone(),
+        vals: vals.clone(),
+        fallbacks: fallbacks.clone(),
+        free: free,
+        command: None,
+    };
+    for (name, n_opts) in path.into_iter().rev() {
+        matches = Matches {
+            opts: opts.slice_to(n_opts).to_vec(),
+            vals: vals.slice_to(n_opts).to_vec(),
+            fallbacks: fallbacks.slice_to(n_opts).to_vec(),
+            free: Vec::new(),
+            command: Some((name, box matches)),
+        };
+    }
+    Ok(matches)
+}
+
+// Panics if an option of `cmd`, or of a subcommand nested in it, has the same
+// short or long name as one of `outer`, the options of the commands it's
+// nested in.
+fn check_distinct(outer: &[OptGroup], cmd: &Command) {
+    fn same(a: &str, b: &str) -> bool { !a.is_empty() && a == b }
+    for o in cmd.opts.iter() {
+        for p in outer.iter() {
+            if same(o.short_name.as_slice(), p.short_name.as_slice()) ||
+               same(o.long_name.as_slice(), p.long_name.as_slice()) {
+                let name = if o.long_name.is_empty() { &o.short_name } else { &o.long_name };
+                panic!("option '{}' of subcommand '{}' is already defined", name, cmd.name);
+            }
+        }
+    }
+    for sub in cmd.subcommands.iter() {
+        check_distinct(outer, sub);
+    }
+}
+
+// Parses arguments until the end, or until the first free argument if
+// `stop_at_free` is true, and returns the number of arguments consumed.
+fn parse_args(args: &[String], opts: &[Opt], vals: &mut Vec<Vec<Optval>>,
+              free: &mut Vec<String>, stop_at_free: bool)
+              -> result::Result<uint, Fail> {
     let l = args.len();
     let mut i = 0;
     while i < l {
         let cur = args[i].clone();
         let curlen = cur.len();
         if !is_arg(cur.as_slice()) {
+            if stop_at_free {
+                return Ok(i);
+            }
             free.push(cur);
         } else if cur == "--" {
             let mut j = i + 1;
@@ -627,7 +927,7 @@ pub fn getopts(args: &[String], optgrps: &[OptGroup]) -> Result {
                        interpreted correctly
                     */
 
-                    let opt_id = match find_opt(opts.as_slice(), opt.clone()) {
+                    let opt_id = match find_opt(opts, opt.clone()) {
                       Some(id) => id,
                       None => return Err(UnrecognizedOption(opt.to_string()))
                     };
@@ -650,7 +950,7 @@ pub fn getopts(args: &[String], optgrps: &[OptGroup]) -> Result {
             let mut name_pos = 0;
             for nm in names.iter() {
                 name_pos += 1;
-                let optid = match find_opt(opts.as_slice(), (*nm).clone()) {
+                let optid = match find_opt(opts, (*nm).clone()) {
                   Some(id) => id,
                   None => return Err(UnrecognizedOption(nm.to_string()))
                 };
@@ -689,34 +989,19 @@ pub fn getopts(args: &[String], optgrps: &[OptGroup]) -> Result {
         }
         i += 1;
     }
-    for i in range(0u, n_opts) {
-        let n = vals[i].len();
-        let occ = opts[i].occur;
-        if occ == Req && n == 0 {
-            return Err(OptionMissing(opts[i].name.to_string()));
-        }
-        if occ != Multi && n > 1 {
-            return Err(OptionDuplicated(opts[i].name.to_string()));
-        }
-    }
-    Ok(Matches {
-        opts: opts,
-        vals: vals,
-        free: free
-    })
+    Ok(l)
 }
 
 /// Derive a usage message from a set of long options.
 pub fn usage(brief: &str, opts: &[OptGroup]) -> String {
-
-    let desc_sep = format!("\n{}", " ".repeat(24));
-
     let rows = opts.iter().map(|optref| {
         let OptGroup{short_name,
                      long_name,
                      hint,
-                     desc,
+                     mut desc,
                      hasarg,
+                     default,
+                     env,
                      ..} = (*optref).clone();
 
         let mut row = " ".repeat(4);
@@ -753,42 +1038,77 @@ pub fn usage(brief: &str, opts: &[OptGroup]) -> String {
             }
         }
 
-        // FIXME: #5516 should be graphemes not codepoints
-        // here we just need to indent the start of the description
-        let rowlen = row.char_len();
-        if rowlen < 24 {
-            for _ in range(0, 24 - rowlen) {
-                row.push(' ');
-            }
-        } else {
-            row.push_str(desc_sep.as_slice())
+        // fallbacks
+        match env {
+            Some(env) => desc.push_str(format!(" [env: {}]", env).as_slice()),
+            None => {}
         }
-
-        // Normalize desc to contain words separated by one space character
-        let mut desc_normalized_whitespace = String::new();
-        for word in desc.words() {
-            desc_normalized_whitespace.push_str(word);
-            desc_normalized_whitespace.push(' ');
+        match default {
+            Some(default) => desc.push_str(format!(" [default: {}]", default).as_slice()),
+            None => {}
         }
 
-        // FIXME: #5516 should be graphemes not codepoints
-        let mut desc_rows = Vec::new();
-        each_split_within(desc_normalized_whitespace.as_slice(),
-                          54,
-                          |substr| {
-            desc_rows.push(substr.to_string());
-            true
+        format_row(row, desc.as_slice())
+    });
+
+    format!("{}\n\nOptions:\n{}\n", brief,
+            rows.collect::<Vec<String>>().connect("\n"))
+}
+
+/// Derive a usage message from a set of long options and subcommands. This
+/// is the message `usage` gives, followed by a list of the subcommands.
+pub fn command_usage(brief: &str, opts: &[OptGroup], commands: &[Command]) -> String {
+    let mut text = usage(brief, opts);
+    if !commands.is_empty() {
+        let rows = commands.iter().map(|cmd| {
+            let mut row = " ".repeat(4);
+            row.push_str(cmd.name.as_slice());
+            row.push(' ');
+            format_row(row, cmd.desc.as_slice())
         });
+        text.push_str(format!("\nCommands:\n{}\n",
+                              rows.collect::<Vec<String>>().connect("\n")).as_slice());
+    }
+    text
+}
+
+// Pads the start of a usage row to the description column and appends the
+// description, wrapped.
+fn format_row(mut row: String, desc: &str) -> String {
+    let desc_sep = format!("\n{}", " ".repeat(24));
 
-        // FIXME: #5516 should be graphemes not codepoints
-        // wrapped description
-        row.push_str(desc_rows.connect(desc_sep.as_slice()).as_slice());
+    // FIXME: #5516 should be graphemes not codepoints
+    // here we just need to indent the start of the description
+    let rowlen = row.char_len();
+    if rowlen < 24 {
+        for _ in range(0, 24 - rowlen) {
+            row.push(' ');
+        }
+    } else {
+        row.push_str(desc_sep.as_slice())
+    }
+
+    // Normalize desc to contain words separated by one space character
+    let mut desc_normalized_whitespace = String::new();
+    for word in desc.words() {
+        desc_normalized_whitespace.push_str(word);
+        desc_normalized_whitespace.push(' ');
+    }
 
-        row
+    // FIXME: #5516 should be graphemes not codepoints
+    let mut desc_rows = Vec::new();
+    each_split_within(desc_normalized_whitespace.as_slice(),
+                      54,
+                      |substr| {
+        desc_rows.push(substr.to_string());
+        true
     });
 
-    format!("{}\n\nOptions:\n{}\n", brief,
-            rows.collect::<Vec<String>>().connect("\n"))
+    // FIXME: #5516 should be graphemes not codepoints
+    // wrapped description
+    row.push_str(desc_rows.connect(desc_sep.as_slice()).as_slice());
+
+    row
 }
 
 fn format_option(opt: &OptGroup) -> String {
@@ -839,6 +1159,331 @@ pub fn short_usage(program_name: &str, opts: &[OptGroup]) -> String {
     line
 }
 
+/// Derive a short one-line usage summary from a set of long options and
+/// subcommands.
+pub fn short_command_usage(program_name: &str, opts: &[OptGroup],
+                           commands: &[Command]) -> String {
+    let mut line = short_usage(program_name, opts);
+    if !commands.is_empty() {
+        if !opts.is_empty() {
+            line.push(' ');
+        }
+        line.push('<');
+        line.push_str(commands.iter()
+                              .map(|cmd| cmd.name.as_slice())
+                              .collect::<Vec<&str>>()
+                              .connect("|")
+                              .as_slice());
+        line.push_str("> ...");
+    }
+    line
+}
+
+/// A shell that completion scripts can be generated for.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum Shell {
+    /// The Bourne Again shell.
+    Bash,
+    /// The Z shell.
+    Zsh,
+}
+
+impl Copy for Shell {}
+
+/// Generate a script that makes `shell` complete the arguments of
+/// `program_name` according to a set of long options.
+///
+/// The arguments of options whose hint contains `DIR` are completed as
+/// directories, and those whose hint contains `FILE` or `PATH` as files.
+/// Free arguments are completed as files.
+pub fn completion(shell: Shell, program_name: &str, opts: &[OptGroup]) -> String {
+    command_completion(shell, program_name, opts, &[])
+}
+
+/// Generate a script that makes `shell` complete the arguments of
+/// `program_name` according to a set of long options and subcommands, as
+/// `completion` does.
+pub fn command_completion(shell: Shell, program_name: &str, opts: &[OptGroup],
+                          commands: &[Command]) -> String {
+    let mut levels = Vec::new();
+    completion_levels(shell_ident("_", program_name), Vec::new(), opts, commands,
+                      &mut levels);
+    match shell {
+        Bash => bash_completion(program_name, levels.as_slice()),
+        Zsh => zsh_completion(program_name, levels.as_slice()),
+    }
+}
+
+/// What the argument of an option is completed as.
+#[deriving(PartialEq)]
+enum ArgCompletion {
+    ArgNothing,
+    ArgFile,
+    ArgDir,
+}
+
+impl Copy for ArgCompletion {}
+
+impl ArgCompletion {
+    fn from_hint(hint: &str) -> ArgCompletion {
+        if hint.contains("DIR") {
+            ArgDir
+        } else if hint.contains("FILE") || hint.contains("PATH") {
+            ArgFile
+        } else {
+            ArgNothing
+        }
+    }
+}
+
+/// The command line of a program, or of one of its subcommands, for
+/// completion purposes.
+struct CompletionLevel<'a> {
+    /// Identifies the level in the script, e.g. `_tool__build`
+    id: String,
+    /// The options accepted, including those of the enclosing commands
+    opts: Vec<&'a OptGroup>,
+    /// The subcommands accepted, and the ids of their levels
+    commands: Vec<(&'a Command, String)>,
+}
+
+// Appends the level for a set of options and subcommands, followed by those
+// of the subcommands, to `levels`.
+fn completion_levels<'a>(id: String, mut inherited: Vec<&'a OptGroup>,
+                         opts: &'a [OptGroup], commands: &'a [Command],
+                         levels: &mut Vec<CompletionLevel<'a>>) {
+    inherited.extend(opts.iter());
+    let subs: Vec<(&'a Command, String)> = commands.iter().map(|cmd| {
+        (cmd, shell_ident(format!("{}__", id).as_slice(), cmd.name.as_slice()))
+    }).collect();
+    levels.push(CompletionLevel {
+        id: id,
+        opts: inherited.clone(),
+        commands: subs.clone(),
+    });
+    for &(cmd, ref sub_id) in subs.iter() {
+        completion_levels(sub_id.clone(), inherited.clone(), cmd.opts.as_slice(),
+                          cmd.subcommands.as_slice(), levels);
+    }
+}
+
+// Turns `name` into an identifier that can be used in a shell script.
+fn shell_ident(prefix: &str, name: &str) -> String {
+    let mut ident = prefix.to_string();
+    for c in name.chars() {
+        ident.push(match c {
+            'a'...'z' | 'A'...'Z' | '0'...'9' => c,
+            _ => '_',
+        });
+    }
+    ident
+}
+
+fn opt_names(opt: &OptGroup) -> Vec<String> {
+    let mut names = Vec::new();
+    if !opt.short_name.is_empty() {
+        names.push(format!("-{}", opt.short_name));
+    }
+    if !opt.long_name.is_empty() {
+        names.push(format!("--{}", opt.long_name));
+    }
+    names
+}
+
+// Quotes `s` for a shell script.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace("'", "'\\''"))
+}
+
+fn bash_completion(program_name: &str, levels: &[CompletionLevel]) -> String {
+    let mut script = String::new();
+    let top = &levels[0];
+    script.push_str(format!("{}() {{\n", top.id).as_slice());
+    script.push_str("    local cur prev cmd opts cmds i\n");
+    script.push_str("    COMPREPLY=()\n");
+    script.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+
+    // Options taking an argument, and what the argument is completed as.
+    let mut arg_opts: Vec<(String, ArgCompletion)> = Vec::new();
+    for level in levels.iter() {
+        for opt in level.opts.iter() {
+            if opt.hasarg == Yes {
+                let names = opt_names(*opt).connect("|");
+                if !arg_opts.iter().any(|&(ref n, _)| *n == names) {
+                    arg_opts.push((names, ArgCompletion::from_hint(opt.hint.as_slice())));
+                }
+            }
+        }
+    }
+
+    // Find the subcommand being completed, skipping the arguments of options.
+    if levels.len() > 1 {
+        script.push_str(format!("    cmd={}\n", top.id).as_slice());
+        script.push_str("    i=1\n");
+        script.push_str("    while [ $i -lt $COMP_CWORD ]; do\n");
+        script.push_str("        case \"$cmd,${COMP_WORDS[i]}\" in\n");
+        for level in levels.iter() {
+            for &(cmd, ref sub_id) in level.commands.iter() {
+                let pattern = format!("{},{}", level.id, cmd.name);
+                script.push_str(format!("            {}) cmd={} ;;\n",
+                                        shell_quote(pattern.as_slice()),
+                                        sub_id).as_slice());
+            }
+        }
+        for &(ref names, _) in arg_opts.iter() {
+            let patterns: Vec<String> = names.as_slice().split('|')
+                                             .map(|n| format!("*,{}", n))
+                                             .collect();
+            script.push_str(format!("            {}) i=$((i + 1)) ;;\n",
+                                    patterns.connect("|")).as_slice());
+        }
+        script.push_str("        esac\n");
+        script.push_str("        i=$((i + 1))\n");
+        script.push_str("    done\n");
+    } else {
+        script.push_str(format!("    cmd={}\n", top.id).as_slice());
+    }
+
+    // Complete the argument of an option.
+    if !arg_opts.is_empty() {
+        script.push_str("    case \"$prev\" in\n");
+        for &(ref names, completion) in arg_opts.iter() {
+            let action = match completion {
+                ArgNothing => "",
+                ArgFile => "COMPREPLY=($(compgen -f -- \"$cur\")); ",
+                ArgDir => "COMPREPLY=($(compgen -d -- \"$cur\")); ",
+            };
+            script.push_str(format!("        {}) {}return 0 ;;\n", names, action).as_slice());
+        }
+        script.push_str("    esac\n");
+    }
+
+    // Complete an option, a subcommand or a free argument.
+    script.push_str("    case \"$cmd\" in\n");
+    for level in levels.iter() {
+        let opts: Vec<String> = level.opts.iter()
+                                          .flat_map(|opt| opt_names(*opt).into_iter())
+                                          .collect();
+        let cmds: Vec<&str> = level.commands.iter()
+                                            .map(|&(cmd, _)| cmd.name.as_slice())
+                                            .collect();
+        script.push_str(format!("        {}) opts={}; cmds={} ;;\n", level.id,
+                                shell_quote(opts.connect(" ").as_slice()),
+                                shell_quote(cmds.connect(" ").as_slice())).as_slice());
+    }
+    script.push_str("    esac\n");
+    script.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    script.push_str("        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))\n");
+    script.push_str("    elif [ -n \"$cmds\" ]; then\n");
+    script.push_str("        COMPREPLY=($(compgen -W \"$cmds\" -- \"$cur\"))\n");
+    script.push_str("    else\n");
+    script.push_str("        COMPREPLY=($(compgen -f -- \"$cur\"))\n");
+    script.push_str("    fi\n");
+    script.push_str("    return 0\n");
+    script.push_str("}\n");
+    script.push_str(format!("complete -o filenames -F {} {}\n",
+                            top.id, shell_quote(program_name)).as_slice());
+    script
+}
+
+// The `_arguments` spec of an option.
+fn zsh_opt_spec(opt: &OptGroup) -> String {
+    let names = opt_names(opt);
+    let (short_suffix, long_suffix) = match opt.hasarg {
+        No => ("", ""),
+        Yes => ("+", "="),
+        Maybe => ("-", "=-"),
+    };
+    let mut spec = String::new();
+    if opt.occur == Multi {
+        spec.push_str("'*'");
+    } else {
+        spec.push_str(format!("'({})'", names.connect(" ")).as_slice());
+    }
+    let names: Vec<String> = names.iter().map(|name| {
+        let suffix = if name.as_slice().starts_with("--") { long_suffix } else { short_suffix };
+        format!("{}{}", name, suffix)
+    }).collect();
+    if names.len() == 1 {
+        spec.push_str(names[0].as_slice());
+    } else {
+        spec.push_str(format!("{{{}}}", names.connect(",")).as_slice());
+    }
+
+    let mut rest = String::new();
+    if !opt.desc.is_empty() {
+        let desc = opt.desc.replace("\\", "\\\\").replace("[", "\\[").replace("]", "\\]");
+        rest.push_str(format!("[{}]", desc).as_slice());
+    }
+    if opt.hasarg != No {
+        let hint = if opt.hint.is_empty() { "arg".to_string() } else { opt.hint.replace(":", "\\:") };
+        let action = match ArgCompletion::from_hint(opt.hint.as_slice()) {
+            ArgNothing => " ",
+            ArgFile => "_files",
+            ArgDir => "_files -/",
+        };
+        let colon = if opt.hasarg == Maybe { "::" } else { ":" };
+        rest.push_str(format!("{}{}:{}", colon, hint, action).as_slice());
+    }
+    if !rest.is_empty() {
+        spec.push_str(shell_quote(rest.as_slice()).as_slice());
+    }
+    spec
+}
+
+fn zsh_completion(program_name: &str, levels: &[CompletionLevel]) -> String {
+    let mut script = format!("#compdef {}\n", program_name);
+    for level in levels.iter() {
+        let mut specs: Vec<String> = level.opts.iter().map(|opt| zsh_opt_spec(*opt)).collect();
+        script.push('\n');
+        script.push_str(format!("{}() {{\n", level.id).as_slice());
+        if level.commands.is_empty() {
+            specs.push("'*:file:_files'".to_string());
+            script.push_str("    _arguments -s");
+            for spec in specs.iter() {
+                script.push_str(format!(" \\\n        {}", spec).as_slice());
+            }
+            script.push_str("\n}\n");
+            continue
+        }
+
+        specs.push(format!("': :{}_commands'", level.id));
+        specs.push("'*:: :->args'".to_string());
+        script.push_str("    local curcontext=\"$curcontext\" state line\n");
+        script.push_str("    _arguments -s -C");
+        for spec in specs.iter() {
+            script.push_str(format!(" \\\n        {}", spec).as_slice());
+        }
+        script.push('\n');
+        script.push_str("    case $state in\n");
+        script.push_str("        (args)\n");
+        script.push_str("            case $line[1] in\n");
+        for &(cmd, ref sub_id) in level.commands.iter() {
+            script.push_str(format!("                ({}) {} ;;\n",
+                                    shell_quote(cmd.name.as_slice()), sub_id).as_slice());
+        }
+        script.push_str("            esac\n");
+        script.push_str("            ;;\n");
+        script.push_str("    esac\n");
+        script.push_str("}\n");
+
+        script.push('\n');
+        script.push_str(format!("{}_commands() {{\n", level.id).as_slice());
+        script.push_str("    local -a commands\n");
+        script.push_str("    commands=(\n");
+        for &(cmd, _) in level.commands.iter() {
+            let item = format!("{}:{}", cmd.name.replace(":", "\\:"), cmd.desc);
+            script.push_str(format!("        {}\n", shell_quote(item.as_slice())).as_slice());
+        }
+        script.push_str("    )\n");
+        script.push_str("    _describe -t commands command commands\n");
+        script.push_str("}\n");
+    }
+    script.push_str(format!("\n{} \"$@\"\n", levels[0].id).as_slice());
+    script
+}
+
 enum SplitWithinState {
     A,  // leading whitespace, initial state
     B,  // words
@@ -970,6 +1615,7 @@ fn test_split_within() {
 mod tests {
     use super::*;
     use super::Fail::*;
+    use super::Shell::*;
 
     use std::result::Result::{Err, Ok};
     use std::result;
@@ -1610,4 +2256,392 @@ Options:
         debug!("generated: <<{}>>", generated_usage);
         assert_eq!(generated_usage, expected);
     }
+
+    fn tool_commands() -> Vec<Command> {
+        vec!(command("build", "Build the project",
+                     &[optflag("r", "release", "Build with optimizations")]),
+             command("deploy", "Deploy the project",
+                     &[optflag("n", "dry-run", "Only print what would be done")])
+                 .subcommand(command("rollback", "Undo the last deployment",
+                                     &[optopt("", "to", "Version to return to",
+                                              "VERSION")])))
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let opts = vec!(optflagmulti("v", "verbose", "Print more output"));
+        let commands = tool_commands();
+        let args = |args: &[&str]| -> Vec<String> {
+            args.iter().map(|s| s.to_string()).collect()
+        };
+
+        let m = getopts_commands(args(&["-v", "build", "-r", "-v", "a", "b"]).as_slice(),
+                                 opts.as_slice(), commands.as_slice()).unwrap();
+        assert_eq!(m.subcommand_name(), Some("build"));
+        assert!(m.free.is_empty());
+        assert_eq!(m.opt_count("v"), 2);
+        let (name, sub) = m.subcommand().unwrap();
+        assert_eq!(name, "build");
+        assert_eq!(sub.opt_count("verbose"), 2);
+        assert!(sub.opt_present("release"));
+        assert_eq!(sub.free, vec!("a".to_string(), "b".to_string()));
+        assert_eq!(sub.subcommand_name(), None);
+
+        let m = getopts_commands(args(&["deploy", "rollback", "--to=1.2", "-n"]).as_slice(),
+                                 opts.as_slice(), commands.as_slice()).unwrap();
+        let (_, deploy) = m.subcommand().unwrap();
+        assert!(deploy.opt_present("dry-run"));
+        let (name, rollback) = deploy.subcommand().unwrap();
+        assert_eq!(name, "rollback");
+        assert_eq!(rollback.opt_str("to"), Some("1.2".to_string()));
+        assert!(rollback.opt_present("n"));
+        assert!(!rollback.opt_present("v"));
+
+        // No subcommand, and arguments after `--`.
+        let m = getopts_commands(args(&["-v"]).as_slice(),
+                                 opts.as_slice(), commands.as_slice()).unwrap();
+        assert_eq!(m.subcommand_name(), None);
+        let m = getopts_commands(args(&["--", "build"]).as_slice(),
+                                 opts.as_slice(), commands.as_slice()).unwrap();
+        assert_eq!(m.subcommand_name(), None);
+        assert_eq!(m.free, vec!("build".to_string()));
+    }
+
+    #[test]
+    fn test_subcommand_errors() {
+        let opts = vec!(optflag("v", "verbose", "Print more output"));
+        let commands = tool_commands();
+        let check = |args: &[&str], fail: Fail| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            let rv = getopts_commands(args.as_slice(), opts.as_slice(),
+                                      commands.as_slice());
+            assert_eq!(rv, Err(fail));
+        };
+        check(&["test"], UnrecognizedCommand("test".to_string()));
+        check(&["deploy", "build"], UnrecognizedCommand("build".to_string()));
+        // Options of a subcommand aren't accepted before it or in other ones.
+        check(&["-r", "build"], UnrecognizedOption("r".to_string()));
+        check(&["deploy", "-r"], UnrecognizedOption("r".to_string()));
+        check(&["-v", "build", "--verbose"], OptionDuplicated("verbose".to_string()));
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_subcommand_duplicate_option() {
+        command("deploy", "Deploy the project",
+                &[optflag("n", "dry-run", "Only print what would be done")])
+            .subcommand(command("rollback", "Undo the last deployment",
+                                &[optflag("", "dry-run", "Only print it")]));
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_nested_subcommand_duplicate_option() {
+        let opts = vec!(optflag("v", "verbose", "Print more output"));
+        let commands = vec!(command("deploy", "Deploy the project", &[])
+            .subcommand(command("rollback", "Undo the last deployment",
+                                &[optflag("v", "version", "Version to return to")])));
+        let _ = getopts_commands(&[], opts.as_slice(), commands.as_slice());
+    }
+
+    #[test]
+    fn test_command_usage() {
+        let opts = vec!(optflag("v", "verbose", "Print more output"));
+        let commands = tool_commands();
+
+        let expected =
+"Usage: tool [options] <command>
+
+Options:
+    -v --verbose        Print more output
+
+Commands:
+    build               Build the project
+    deploy              Deploy the project
+";
+        let generated_usage = command_usage("Usage: tool [options] <command>",
+                                            opts.as_slice(), commands.as_slice());
+        debug!("generated: <<{}>>", generated_usage);
+        assert_eq!(generated_usage, expected);
+
+        let expected =
+"Usage: tool deploy [options]
+
+Options:
+    -n --dry-run        Only print what would be done
+
+Commands:
+    rollback            Undo the last deployment
+";
+        assert_eq!(commands[1].usage("Usage: tool deploy [options]"), expected);
+
+        assert_eq!(short_command_usage("tool", opts.as_slice(), commands.as_slice()),
+                   "Usage: tool [-v] <build|deploy> ...".to_string());
+        assert_eq!(commands[1].short_usage("tool deploy"),
+                   "Usage: tool deploy [-n] <rollback> ...".to_string());
+        assert_eq!(commands[0].short_usage("tool build"),
+                   "Usage: tool build [-r]".to_string());
+    }
+
+    #[test]
+    fn test_opt_parse() {
+        let opts = vec!(optopt("j", "jobs", "Number of jobs", "N"),
+                        optmulti("p", "port", "Ports to listen on", "PORT"),
+                        optopt("", "ratio", "A ratio", "R"));
+        let args = vec!("-j4".to_string(), "-p".to_string(), "80".to_string(),
+                        "--port=8080".to_string(), "--ratio=x".to_string());
+        let m = getopts(args.as_slice(), opts.as_slice()).unwrap();
+        assert_eq!(m.opt_parse::<uint>("jobs"), Ok(Some(4)));
+        assert_eq!(m.opt_parse::<u16>("j"), Ok(Some(4)));
+        assert_eq!(m.opt_parses::<u16>("port"), Ok(vec!(80, 8080)));
+        assert_eq!(m.opt_parse::<f64>("ratio"),
+                   Err(InvalidArgument("ratio".to_string(), "x".to_string())));
+        assert_eq!(m.opt_parses::<u8>("port"),
+                   Err(InvalidArgument("port".to_string(), "8080".to_string())));
+
+        let m = getopts(&[], opts.as_slice()).unwrap();
+        assert_eq!(m.opt_parse::<uint>("jobs"), Ok(None));
+        assert_eq!(m.opt_parses::<u16>("port"), Ok(vec!()));
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        use std::os;
+        os::setenv("GETOPTS_TEST_FALLBACK_JOBS", "8");
+        os::unsetenv("GETOPTS_TEST_FALLBACK_UNSET");
+        let opts = vec!(optopt("j", "jobs", "Number of jobs", "N")
+                            .env_var("GETOPTS_TEST_FALLBACK_JOBS")
+                            .default_value("1"),
+                        reqopt("o", "output", "Output file", "FILE")
+                            .env_var("GETOPTS_TEST_FALLBACK_UNSET")
+                            .default_value("a.out"),
+                        optopt("", "level", "Level", "N").default_value("3"));
+
+        let m = getopts(&[], opts.as_slice()).unwrap();
+        assert!(!m.opt_present("jobs"));
+        assert_eq!(m.opt_parse::<uint>("jobs"), Ok(Some(8)));
+        assert_eq!(m.opt_str("output"), Some("a.out".to_string()));
+        assert_eq!(m.opt_strs("level"), vec!("3".to_string()));
+
+        let args = vec!("--jobs=2".to_string(), "--level".to_string(), "5".to_string());
+        let m = getopts(args.as_slice(), opts.as_slice()).unwrap();
+        assert!(m.opt_present("jobs"));
+        assert_eq!(m.opt_str("jobs"), Some("2".to_string()));
+        assert_eq!(m.opt_str("level"), Some("5".to_string()));
+    }
+
+    #[test]
+    fn test_usage_fallbacks() {
+        let optgroups = vec!(
+            optopt("j", "jobs", "Number of jobs", "N")
+                .env_var("JOBS")
+                .default_value("1"),
+            optopt("o", "output", "Output file", "FILE").default_value("a.out"),
+            optflagopt("c", "color", "When to use colors, which can be always, never or auto",
+                       "WHEN").default_value("auto"));
+
+        let expected =
+"Usage: fruits
+
+Options:
+    -j --jobs N         Number of jobs [env: JOBS] [default: 1]
+    -o --output FILE    Output file [default: a.out]
+    -c --color [WHEN]   When to use colors, which can be always, never or
+                        auto [default: auto]
+";
+
+        let generated_usage = usage("Usage: fruits", optgroups.as_slice());
+
+        debug!("expected: <<{}>>", expected);
+        debug!("generated: <<{}>>", generated_usage);
+        assert_eq!(generated_usage, expected);
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_flag_default() {
+        optflag("v", "verbose", "Print more output").default_value("yes");
+    }
+
+    fn completion_opts() -> Vec<OptGroup> {
+        vec!(optopt("o", "output", "Output file", "FILE"),
+             optflagmulti("v", "verbose", "Print more output"),
+             optopt("", "jobs", "Number of jobs", "N"),
+             optflagopt("", "out-dir", "Write output to [DIR]", "DIR"))
+    }
+
+    #[test]
+    fn test_bash_completion() {
+        let expected =
+"_fruits() {
+    local cur prev cmd opts cmds i
+    COMPREPLY=()
+    cur=\"${COMP_WORDS[COMP_CWORD]}\"
+    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"
+    cmd=_fruits
+    case \"$prev\" in
+        -o|--output) COMPREPLY=($(compgen -f -- \"$cur\")); return 0 ;;
+        --jobs) return 0 ;;
+    esac
+    case \"$cmd\" in
+        _fruits) opts='-o --output -v --verbose --jobs --out-dir'; cmds='' ;;
+    esac
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))
+    elif [ -n \"$cmds\" ]; then
+        COMPREPLY=($(compgen -W \"$cmds\" -- \"$cur\"))
+    else
+        COMPREPLY=($(compgen -f -- \"$cur\"))
+    fi
+    return 0
+}
+complete -o filenames -F _fruits 'fruits'
+";
+        let generated = completion(Bash, "fruits", completion_opts().as_slice());
+        debug!("generated: <<{}>>", generated);
+        assert_eq!(generated, expected);
+    }
+
+    #[test]
+    fn test_zsh_completion() {
+        let expected =
+"#compdef fruits
+
+_fruits() {
+    _arguments -s \\
+        '(-o --output)'{-o+,--output=}'[Output file]:FILE:_files' \\
+        '*'{-v,--verbose}'[Print more output]' \\
+        '(--jobs)'--jobs='[Number of jobs]:N: ' \\
+        '(--out-dir)'--out-dir=-'[Write output to \\[DIR\\]]::DIR:_files -/' \\
+        '*:file:_files'
+}
+
+_fruits \"$@\"
+";
+        let generated = completion(Zsh, "fruits", completion_opts().as_slice());
+        debug!("generated: <<{}>>", generated);
+        assert_eq!(generated, expected);
+    }
+
+    #[test]
+    fn test_command_completion() {
+        let opts = vec!(optflagmulti("v", "verbose", "Print more output"));
+        let commands = tool_commands();
+
+        let expected =
+"_tool() {
+    local cur prev cmd opts cmds i
+    COMPREPLY=()
+    cur=\"${COMP_WORDS[COMP_CWORD]}\"
+    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"
+    cmd=_tool
+    i=1
+    while [ $i -lt $COMP_CWORD ]; do
+        case \"$cmd,${COMP_WORDS[i]}\" in
+            '_tool,build') cmd=_tool__build ;;
+            '_tool,deploy') cmd=_tool__deploy ;;
+            '_tool__deploy,rollback') cmd=_tool__deploy__rollback ;;
+            *,--to) i=$((i + 1)) ;;
+        esac
+        i=$((i + 1))
+    done
+    case \"$prev\" in
+        --to) return 0 ;;
+    esac
+    case \"$cmd\" in
+        _tool) opts='-v --verbose'; cmds='build deploy' ;;
+        _tool__build) opts='-v --verbose -r --release'; cmds='' ;;
+        _tool__deploy) opts='-v --verbose -n --dry-run'; cmds='rollback' ;;
+        _tool__deploy__rollback) opts='-v --verbose -n --dry-run --to'; cmds='' ;;
+    esac
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"$opts\" -- \"$cur\"))
+    elif [ -n \"$cmds\" ]; then
+        COMPREPLY=($(compgen -W \"$cmds\" -- \"$cur\"))
+    else
+        COMPREPLY=($(compgen -f -- \"$cur\"))
+    fi
+    return 0
+}
+complete -o filenames -F _tool 'tool'
+";
+        let generated = command_completion(Bash, "tool", opts.as_slice(),
+                                           commands.as_slice());
+        debug!("generated: <<{}>>", generated);
+        assert_eq!(generated, expected);
+
+        let expected =
+"#compdef tool
+
+_tool() {
+    local curcontext=\"$curcontext\" state line
+    _arguments -s -C \\
+        '*'{-v,--verbose}'[Print more output]' \\
+        ': :_tool_commands' \\
+        '*:: :->args'
+    case $state in
+        (args)
+            case $line[1] in
+                ('build') _tool__build ;;
+                ('deploy') _tool__deploy ;;
+            esac
+            ;;
+    esac
+}
+
+_tool_commands() {
+    local -a commands
+    commands=(
+        'build:Build the project'
+        'deploy:Deploy the project'
+    )
+    _describe -t commands command commands
+}
+
+_tool__build() {
+    _arguments -s \\
+        '*'{-v,--verbose}'[Print more output]' \\
+        '(-r --release)'{-r,--release}'[Build with optimizations]' \\
+        '*:file:_files'
+}
+
+_tool__deploy() {
+    local curcontext=\"$curcontext\" state line
+    _arguments -s -C \\
+        '*'{-v,--verbose}'[Print more output]' \\
+        '(-n --dry-run)'{-n,--dry-run}'[Only print what would be done]' \\
+        ': :_tool__deploy_commands' \\
+        '*:: :->args'
+    case $state in
+        (args)
+            case $line[1] in
+                ('rollback') _tool__deploy__rollback ;;
+            esac
+            ;;
+    esac
+}
+
+_tool__deploy_commands() {
+    local -a commands
+    commands=(
+        'rollback:Undo the last deployment'
+    )
+    _describe -t commands command commands
+}
+
+_tool__deploy__rollback() {
+    _arguments -s \\
+        '*'{-v,--verbose}'[Print more output]' \\
+        '(-n --dry-run)'{-n,--dry-run}'[Only print what would be done]' \\
+        '(--to)'--to='[Version to return to]:VERSION: ' \\
+        '*:file:_files'
+}
+
+_tool \"$@\"
+";
+        let generated = command_completion(Zsh, "tool", opts.as_slice(),
+                                           commands.as_slice());
+        debug!("generated: <<{}>>", generated);
+        assert_eq!(generated, expected);
+    }
 }
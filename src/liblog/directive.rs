@@ -11,13 +11,42 @@
 use regex::Regex;
 use std::ascii::AsciiExt;
 use std::cmp;
+use std::u64;
 
-#[deriving(Show, Clone)]
+/// A logging directive: the maximum level of the records logged by a module
+/// and the modules nested in it.
+#[deriving(Show, Clone, PartialEq)]
 pub struct LogDirective {
+    /// The path of the module, or `None` for all modules.
     pub name: Option<String>,
+    /// The maximum level logged.
     pub level: u32,
 }
 
+/// How log records are formatted by the sink configured with `RUST_LOG_SINK`.
+#[deriving(Show, Clone, PartialEq)]
+pub enum LogFormat {
+    /// A line of text per record, as the default logger writes it.
+    Text,
+    /// A JSON object per record, on a line of its own.
+    Json,
+}
+
+impl Copy for LogFormat {}
+
+/// Where log records are written and how, as configured by `RUST_LOG_SINK`.
+#[deriving(Show, Clone, PartialEq)]
+pub struct LogSink {
+    /// The format the records are written in.
+    pub format: LogFormat,
+    /// The file to write to instead of stderr.
+    pub file: Option<Path>,
+    /// The size at which the file is rotated.
+    pub max_size: u64,
+    /// The number of rotated files kept.
+    pub keep: uint,
+}
+
 pub static LOG_LEVEL_NAMES: [&'static str, ..4] = ["ERROR", "WARN", "INFO",
                                                "DEBUG"];
 
@@ -93,9 +122,76 @@ pub fn parse_logging_spec(spec: &str) -> (Vec<LogDirective>, Option<Regex>) {
     return (dirs, filter);
 }
 
+/// Parse a log sink specification string (e.g: "json,file=/tmp/app.log,max_size=10M")
+/// and return the sink it describes.
+///
+/// The first part is the format, either `text` or `json`. It's optionally
+/// followed by `file=PATH` to write to a file instead of stderr, `max_size=N`
+/// to rotate the file when it would grow bigger than N bytes (N may have a
+/// `K`, `M` or `G` suffix), and `keep=N` to keep N rotated files (5 by
+/// default).
+pub fn parse_sink_spec(spec: &str) -> Option<LogSink> {
+    let mut parts = spec.split(',');
+    let format = match parts.next().map(|s| s.trim()) {
+        Some(f) if f.eq_ignore_ascii_case("text") => LogFormat::Text,
+        Some(f) if f.eq_ignore_ascii_case("json") => LogFormat::Json,
+        _ => {
+            println!("warning: invalid log sink spec '{}', ignoring it \
+                     (unknown format)", spec);
+            return None
+        }
+    };
+    let mut sink = LogSink {
+        format: format,
+        file: None,
+        max_size: u64::MAX,
+        keep: 5,
+    };
+    for part in parts {
+        let mut kv = part.splitn(1, '=');
+        let ok = match (kv.next().map(|s| s.trim()), kv.next()) {
+            (Some("file"), Some(path)) if path.len() > 0 => {
+                sink.file = Some(Path::new(path));
+                true
+            }
+            (Some("max_size"), Some(size)) => {
+                match parse_size(size.trim()) {
+                    Some(size) => { sink.max_size = size; true }
+                    None => false,
+                }
+            }
+            (Some("keep"), Some(keep)) => {
+                match from_str::<uint>(keep.trim()) {
+                    Some(keep) => { sink.keep = keep; true }
+                    None => false,
+                }
+            }
+            _ => false,
+        };
+        if !ok {
+            println!("warning: invalid log sink spec '{}', ignoring it", part);
+        }
+    }
+    Some(sink)
+}
+
+/// Parse a size in bytes with an optional `K`, `M` or `G` suffix.
+fn parse_size(size: &str) -> Option<u64> {
+    let (digits, unit) = match size.chars().last() {
+        Some('k') | Some('K') => (size.slice_to(size.len() - 1), 1u64 << 10),
+        Some('m') | Some('M') => (size.slice_to(size.len() - 1), 1 << 20),
+        Some('g') | Some('G') => (size.slice_to(size.len() - 1), 1 << 30),
+        _ => (size, 1),
+    };
+    from_str::<u64>(digits).and_then(|n| {
+        if n > u64::MAX / unit { None } else { Some(n * unit) }
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_logging_spec;
+    use super::{parse_logging_spec, parse_sink_spec, LogSink, LogFormat};
+    use std::u64;
 
     #[test]
     fn parse_logging_spec_valid() {
@@ -196,4 +292,40 @@ mod tests {
         assert_eq!(dirs[0].level, ::MAX_LOG_LEVEL);
         assert!(filter.is_some() && filter.unwrap().to_string() == "a*c");
     }
+
+    #[test]
+    fn parse_sink_spec_valid() {
+        assert_eq!(parse_sink_spec("json"), Some(LogSink {
+            format: LogFormat::Json,
+            file: None,
+            max_size: u64::MAX,
+            keep: 5,
+        }));
+        assert_eq!(parse_sink_spec("text,file=/tmp/app.log,max_size=10M,keep=2"), Some(LogSink {
+            format: LogFormat::Text,
+            file: Some(Path::new("/tmp/app.log")),
+            max_size: 10 * 1024 * 1024,
+            keep: 2,
+        }));
+        assert_eq!(parse_sink_spec("JSON,file=x=y.log,max_size=100"), Some(LogSink {
+            format: LogFormat::Json,
+            file: Some(Path::new("x=y.log")),
+            max_size: 100,
+            keep: 5,
+        }));
+    }
+
+    #[test]
+    fn parse_sink_spec_invalid() {
+        assert_eq!(parse_sink_spec("xml,file=a.log"), None);
+        assert_eq!(parse_sink_spec(""), None);
+        // Invalid options are ignored.
+        assert_eq!(parse_sink_spec("text,max_size=big,keep=-1,color=always"), Some(LogSink {
+            format: LogFormat::Text,
+            file: None,
+            max_size: u64::MAX,
+            keep: 5,
+        }));
+        assert_eq!(parse_sink_spec("text,max_size=99999999999G").unwrap().max_size, u64::MAX);
+    }
 }
@@ -86,6 +86,30 @@
 //!
 //! * `log_enabled!(level)` - returns true if logging of the given level is enabled
 //!
+//! ## Structured logging
+//!
+//! Key/value pairs can be attached to a log record by giving them in braces,
+//! followed by a semicolon, before the format string:
+//!
+//! ```
+//! #![feature(phase)]
+//! #[phase(plugin, link)] extern crate log;
+//!
+//! fn main() {
+//!     let user = "alice";
+//!     warn!({"user" => user, "attempt" => 3u}; "login failed");
+//! }
+//! ```
+//!
+//! The values can be of any type that implements `Show`. They're passed to
+//! the logger in `LogRecord::fields`, and the default logger writes them
+//! after the message:
+//!
+//! ```{.bash}
+//! $ RUST_LOG=warn ./main
+//! WARN:main: login failed user=alice attempt=3
+//! ```
+//!
 //! ## Enabling logging
 //!
 //! Log levels are controlled on a per-module basis, and by default all logging is
@@ -143,6 +167,31 @@
 //!  hello. In both cases the log message must include a single digit number
 //!  followed by 'scopes'
 //!
+//! ## Log sinks
+//!
+//! By default, log records are written to stderr as text by a logger
+//! local to each task, which can be replaced with `set_logger`. Tasks that
+//! don't have a logger of their own can instead share one configured by the
+//! `RUST_LOG_SINK` environment variable. Its value is a comma-separated list
+//! whose first item is the format of the records:
+//!
+//! * `text` writes records as the default logger does (see `TextLogger`)
+//! * `json` writes each record as a JSON object on a line of its own (see
+//!   `JsonLogger`)
+//!
+//! It may be followed by these options:
+//!
+//! * `file=PATH` writes the records to the file at PATH instead of stderr
+//! * `max_size=SIZE` rotates the file when it would grow bigger than SIZE
+//!   bytes (SIZE may have a `K`, `M` or `G` suffix, see `RotatingFile`)
+//! * `keep=N` keeps N rotated files, 5 by default
+//!
+//! For example:
+//!
+//! ```{.bash}
+//! $ RUST_LOG=info RUST_LOG_SINK=json,file=main.log,max_size=10M ./main
+//! ```
+//!
 //! ## Performance and Side Effects
 //!
 //! Each of these macros will expand to code similar to:
@@ -175,17 +224,23 @@ use std::fmt;
 use std::io::LineBufferedWriter;
 use std::io;
 use std::mem;
+use std::num::Int;
 use std::os;
 use std::rt;
 use std::slice;
-use std::sync::{Once, ONCE_INIT};
+use std::sync::{Mutex, Once, ONCE_INIT, StaticRWLock, RWLOCK_INIT};
+use std::sync::atomic::{AtomicUint, INIT_ATOMIC_UINT, Relaxed};
 
 use regex::Regex;
 
-use directive::LOG_LEVEL_NAMES;
+use directive::{LOG_LEVEL_NAMES, LogFormat};
+
+pub use directive::LogDirective;
+pub use sink::{TextLogger, JsonLogger, RotatingFile};
 
 pub mod macros;
 mod directive;
+mod sink;
 
 /// Maximum logging level of a module that can be specified. Common logging
 /// levels are found in the DEBUG/INFO/WARN/ERROR constants.
@@ -194,17 +249,30 @@ pub const MAX_LOG_LEVEL: u32 = 255;
 /// The default logging level of a crate if no other is specified.
 const DEFAULT_LOG_LEVEL: u32 = 1;
 
-/// An unsafe constant that is the maximum logging level of any module
-/// specified. This is the first line of defense to determining whether a
-/// logging statement should be run.
-static mut LOG_LEVEL: u32 = MAX_LOG_LEVEL;
+/// The maximum logging level of any module specified. This is the first line
+/// of defense to determining whether a logging statement should be run.
+///
+/// It is stored as how far it is below `MAX_LOG_LEVEL`, so that it starts out
+/// at the maximum, and it is atomic because `set_directives` can change it
+/// while other tasks are logging.
+static LOG_LEVEL_BELOW_MAX: AtomicUint = INIT_ATOMIC_UINT;
 
-static mut DIRECTIVES: *const Vec<directive::LogDirective> =
-    0 as *const Vec<directive::LogDirective>;
+static mut DIRECTIVES: *const Vec<LogDirective> = 0 as *const Vec<LogDirective>;
 
 /// Optional regex filter.
 static mut FILTER: *const Regex = 0 as *const _;
 
+/// Guards `DIRECTIVES` and `FILTER`, which `set_directives` replaces while
+/// other tasks may be logging.
+static LOCK: StaticRWLock = RWLOCK_INIT;
+
+/// Initializes the globals from the environment the first time logging is
+/// used.
+static INIT: Once = ONCE_INIT;
+
+/// Optional sink configured by `RUST_LOG_SINK`, shared by all tasks.
+static mut SINK: *const Sink = 0 as *const _;
+
 /// Debug log level
 pub const DEBUG: u32 = 4;
 /// Info log level
@@ -248,11 +316,39 @@ impl fmt::Show for LogLevel {
 
 impl Logger for DefaultLogger {
     fn log(&mut self, record: &LogRecord) {
-        match writeln!(&mut self.handle,
-                       "{}:{}: {}",
-                       record.level,
-                       record.module_path,
-                       record.args) {
+        match sink::write_text(&mut self.handle, record) {
+            Err(e) => panic!("failed to log: {}", e),
+            Ok(()) => {}
+        }
+    }
+}
+
+/// Where records go when a sink is configured: the format to write them in,
+/// and the file or stderr to write them to.
+struct Sink {
+    /// The format the records are written in.
+    format: LogFormat,
+    /// The file or stderr, locked while a record is written.
+    writer: Mutex<Box<Writer + Send>>,
+}
+
+/// The default logger when a sink is configured, which passes records on to
+/// the shared sink.
+struct SinkLogger;
+
+impl Logger for SinkLogger {
+    fn log(&mut self, record: &LogRecord) {
+        let sink = unsafe { &*SINK };
+        // Format the record before taking the lock, and only panic on a
+        // failed write once it's released, so that a panicking `Show`
+        // implementation or a full disk doesn't poison the sink for every
+        // other task.
+        let line = sink::format_record(sink.format, record);
+        let res = {
+            let mut writer = sink.writer.lock();
+            writer.write(line.as_slice()).and_then(|()| writer.flush())
+        };
+        match res {
             Err(e) => panic!("failed to log: {}", e),
             Ok(()) => {}
         }
@@ -278,11 +374,28 @@ impl Drop for DefaultLogger {
 /// invoked through the logging family of macros.
 #[doc(hidden)]
 pub fn log(level: u32, loc: &'static LogLocation, args: &fmt::Arguments) {
+    log_with_fields(level, loc, args, &[])
+}
+
+/// Like `log`, but with key/value pairs attached to the record. This is
+/// called by the logging macros when they're given fields.
+#[doc(hidden)]
+pub fn log_with_fields<'a>(level: u32, loc: &'static LogLocation, args: &fmt::Arguments,
+                           fields: &'a [(&'a str, &'a (fmt::Show + 'a))]) {
     // Test the literal string from args against the current filter, if there
-    // is one.
-    match unsafe { FILTER.as_ref() } {
-        Some(filter) if !filter.is_match(args.to_string().as_slice()) => return,
-        _ => {}
+    // is one. The message is formatted without holding `LOCK`, since a `Show`
+    // implementation might log too.
+    let has_filter = {
+        let _g = LOCK.read();
+        unsafe { !FILTER.is_null() }
+    };
+    if has_filter {
+        let msg = args.to_string();
+        let _g = LOCK.read();
+        match unsafe { FILTER.as_ref() } {
+            Some(filter) if !filter.is_match(msg.as_slice()) => return,
+            _ => {}
+        }
     }
 
     // Completely remove the local logger from TLS in case anyone attempts to
@@ -291,7 +404,11 @@ pub fn log(level: u32, loc: &'static LogLocation, args: &fmt::Arguments) {
     let mut logger = LOCAL_LOGGER.with(|s| {
         s.borrow_mut().take()
     }).unwrap_or_else(|| {
-        box DefaultLogger { handle: io::stderr() } as Box<Logger + Send>
+        if unsafe { SINK.is_null() } {
+            box DefaultLogger { handle: io::stderr() } as Box<Logger + Send>
+        } else {
+            box SinkLogger as Box<Logger + Send>
+        }
     });
     logger.log(&LogRecord {
         level: LogLevel(level),
@@ -299,6 +416,7 @@ pub fn log(level: u32, loc: &'static LogLocation, args: &fmt::Arguments) {
         file: loc.file,
         module_path: loc.module_path,
         line: loc.line,
+        fields: fields,
     });
     set_logger(logger);
 }
@@ -307,7 +425,7 @@ pub fn log(level: u32, loc: &'static LogLocation, args: &fmt::Arguments) {
 /// safely
 #[doc(hidden)]
 #[inline(always)]
-pub fn log_level() -> u32 { unsafe { LOG_LEVEL } }
+pub fn log_level() -> u32 { MAX_LOG_LEVEL - LOG_LEVEL_BELOW_MAX.load(Relaxed) as u32 }
 
 /// Replaces the task-local logger with the specified logger, returning the old
 /// logger.
@@ -337,6 +455,9 @@ pub struct LogRecord<'a> {
 
     /// The line number of where the LogRecord originated.
     pub line: uint,
+
+    /// The key/value pairs given to the logging macro, in order.
+    pub fields: &'a [(&'a str, &'a (fmt::Show + 'a))],
 }
 
 #[doc(hidden)]
@@ -353,7 +474,6 @@ impl Copy for LogLocation {}
 /// module's log statement should be emitted or not.
 #[doc(hidden)]
 pub fn mod_enabled(level: u32, module: &str) -> bool {
-    static INIT: Once = ONCE_INIT;
     INIT.doit(init);
 
     // It's possible for many threads are in this function, only one of them
@@ -361,7 +481,9 @@ pub fn mod_enabled(level: u32, module: &str) -> bool {
     // again to whether they should really be here or not. Hence, despite this
     // check being expanded manually in the logging macro, this function checks
     // the log level again.
-    if level > unsafe { LOG_LEVEL } { return false }
+    if level > log_level() { return false }
+
+    let _g = LOCK.read();
 
     // This assertion should never get tripped unless we're in an at_exit
     // handler after logging has been torn down and a logging attempt was made.
@@ -386,16 +508,48 @@ fn enabled(level: u32,
     level <= DEFAULT_LOG_LEVEL
 }
 
-/// Initialize logging for the current process.
+/// Replaces the logging directives of the process with those of `spec`,
+/// which has the same syntax as the `RUST_LOG` environment variable (see the
+/// crate documentation).
 ///
-/// This is not threadsafe at all, so initialization is performed through a
-/// `Once` primitive (and this function is called from that primitive).
-fn init() {
-    let (mut directives, filter) = match os::getenv("RUST_LOG") {
-        Some(spec) => directive::parse_logging_spec(spec.as_slice()),
-        None => (Vec::new(), None),
-    };
+/// The new directives and filter take effect for all tasks at once. Invalid
+/// directives in `spec` are ignored with a warning, as they are in
+/// `RUST_LOG`.
+///
+/// # Example
+///
+/// ```
+/// // Turn on debug logging for one module of a running program.
+/// log::set_directives("error,server::conn=debug");
+/// ```
+pub fn set_directives(spec: &str) {
+    INIT.doit(init);
+    let (directives, filter) = directive::parse_logging_spec(spec);
+    let _g = LOCK.write();
+    unsafe {
+        // Logging has been torn down.
+        if DIRECTIVES.is_null() { return }
+        install_directives(directives, filter);
+    }
+}
+
+/// Returns the logging directives currently in effect, in order of
+/// increasing specificity, and the source of the filter regex, if there is
+/// one.
+pub fn directives() -> (Vec<LogDirective>, Option<String>) {
+    INIT.doit(init);
+    let _g = LOCK.read();
+    unsafe {
+        if DIRECTIVES.is_null() {
+            return (Vec::new(), None)
+        }
+        ((*DIRECTIVES).clone(), FILTER.as_ref().map(|f| f.to_string()))
+    }
+}
 
+/// Sets the global directives and filter, freeing the old ones if there were
+/// any. This must be called with `LOCK` held for writing, or from `init`.
+unsafe fn install_directives(mut directives: Vec<LogDirective>, filter: Option<Regex>) {
     // Sort the provided directives by length of their name, this allows a
     // little more efficient lookup at runtime.
     directives.sort_by(|a, b| {
@@ -409,33 +563,92 @@ fn init() {
         max.map(|d| d.level).unwrap_or(DEFAULT_LOG_LEVEL)
     };
 
+    LOG_LEVEL_BELOW_MAX.store(MAX_LOG_LEVEL.saturating_sub(max_level) as uint, Relaxed);
+
+    if !FILTER.is_null() {
+        let _filter: Box<Regex> = mem::transmute(FILTER);
+        FILTER = 0 as *const _;
+    }
+    match filter {
+        Some(f) => FILTER = mem::transmute(box f),
+        None => {}
+    }
+
+    if !DIRECTIVES.is_null() {
+        let _directives: Box<Vec<LogDirective>> = mem::transmute(DIRECTIVES);
+    }
+    DIRECTIVES = mem::transmute(box directives);
+}
+
+/// Initialize logging for the current process.
+///
+/// This is not threadsafe at all, so initialization is performed through a
+/// `Once` primitive (and this function is called from that primitive).
+fn init() {
+    let (directives, filter) = match os::getenv("RUST_LOG") {
+        Some(spec) => directive::parse_logging_spec(spec.as_slice()),
+        None => (Vec::new(), None),
+    };
+
+    let sink = match os::getenv("RUST_LOG_SINK") {
+        Some(spec) => directive::parse_sink_spec(spec.as_slice()).and_then(open_sink),
+        None => None,
+    };
+
     unsafe {
-        LOG_LEVEL = max_level;
+        assert!(DIRECTIVES.is_null());
+        install_directives(directives, filter);
 
-        assert!(FILTER.is_null());
-        match filter {
-            Some(f) => FILTER = mem::transmute(box f),
+        assert!(SINK.is_null());
+        match sink {
+            Some(s) => SINK = mem::transmute(box s),
             None => {}
         }
 
-        assert!(DIRECTIVES.is_null());
-        DIRECTIVES = mem::transmute(box directives);
-
         // Schedule the cleanup for the globals for when the runtime exits.
         rt::at_exit(move |:| {
+            let _g = LOCK.write();
             assert!(!DIRECTIVES.is_null());
-            let _directives: Box<Vec<directive::LogDirective>> =
+            let _directives: Box<Vec<LogDirective>> =
                 mem::transmute(DIRECTIVES);
-            DIRECTIVES = 0 as *const Vec<directive::LogDirective>;
+            DIRECTIVES = 0 as *const Vec<LogDirective>;
 
             if !FILTER.is_null() {
                 let _filter: Box<Regex> = mem::transmute(FILTER);
                 FILTER = 0 as *const _;
             }
+
+            if !SINK.is_null() {
+                let _sink: Box<Sink> = mem::transmute(SINK);
+                SINK = 0 as *const _;
+            }
         });
     }
 }
 
+/// Creates the sink configured by `RUST_LOG_SINK`. If the file can't be
+/// opened, a warning is printed and records are written to stderr.
+fn open_sink(sink: directive::LogSink) -> Option<Sink> {
+    let file = match sink.file {
+        Some(ref path) => {
+            match RotatingFile::open(path, sink.max_size, sink.keep) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    println!("warning: can't open log file '{}', logging to stderr: {}",
+                             path.display(), e);
+                    return None
+                }
+            }
+        }
+        None => None,
+    };
+    let writer = match file {
+        Some(file) => box file as Box<Writer + Send>,
+        None => box io::stderr() as Box<Writer + Send>,
+    };
+    Some(Sink { format: sink.format, writer: Mutex::new(writer) })
+}
+
 #[cfg(test)]
 mod tests {
     use super::enabled;
@@ -507,4 +720,26 @@ mod tests {
         assert!(!enabled(1, "crate1::mod1", dirs.iter()));
         assert!(enabled(3, "crate2::mod2", dirs.iter()));
     }
+
+    #[test]
+    fn set_directives() {
+        super::set_directives("crate2::mod=debug,warn,crate1=info/ab+c");
+        let (dirs, filter) = super::directives();
+        assert_eq!(dirs, vec![
+            LogDirective { name: None, level: 2 },
+            LogDirective { name: Some("crate1".to_string()), level: 3 },
+            LogDirective { name: Some("crate2::mod".to_string()), level: 4 }
+        ]);
+        assert_eq!(filter, Some("ab+c".to_string()));
+        assert_eq!(super::log_level(), 4);
+        assert!(super::mod_enabled(4, "crate2::mod::inner"));
+        assert!(!super::mod_enabled(4, "crate1"));
+        assert!(super::mod_enabled(2, "crate3"));
+
+        super::set_directives("");
+        assert_eq!(super::directives(), (vec![], None));
+        assert_eq!(super::log_level(), 1);
+        assert!(!super::mod_enabled(2, "crate3"));
+        assert!(super::mod_enabled(1, "crate3"));
+    }
 }
@@ -50,8 +50,43 @@
 /// WARN:main: this is a warning message
 /// 6:main: this is a custom logging level: 6
 /// ```
+///
+/// Key/value pairs can be attached to the record by giving them in braces,
+/// followed by a semicolon, before the format string. This works with all of
+/// the logging macros:
+///
+/// ```
+/// #![feature(phase)]
+/// #[phase(plugin, link)] extern crate log;
+///
+/// fn main() {
+///     let path = "/tmp/out";
+///     log!(log::WARN, {"path" => path, "retries" => 3u}; "write failed: {}", "disk full");
+///     error!({"path" => path}; "giving up");
+/// }
+/// ```
+///
+/// ```{.bash}
+/// $ RUST_LOG=warn ./main
+/// WARN:main: write failed: disk full path=/tmp/out retries=3
+/// ERROR:main: giving up path=/tmp/out
+/// ```
 #[macro_export]
 macro_rules! log(
+    ($lvl:expr, { $($key:expr => $value:expr),+ }; $($arg:tt)+) => ({
+        static LOC: ::log::LogLocation = ::log::LogLocation {
+            line: line!(),
+            file: file!(),
+            module_path: module_path!(),
+        };
+        let lvl = $lvl;
+        if log_enabled!(lvl) {
+            format_args!(|args| {
+                ::log::log_with_fields(lvl, &LOC, args,
+                                       &[$(($key, &$value as &::std::fmt::Show)),+])
+            }, $($arg)+)
+        }
+    });
     ($lvl:expr, $($arg:tt)+) => ({
         static LOC: ::log::LogLocation = ::log::LogLocation {
             line: line!(),
@@ -0,0 +1,320 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Loggers that write records as text or as JSON lines, and a file that is
+//! rotated when it grows too big.
+
+use std::io::{File, IoResult, MemWriter};
+use std::io::fs;
+use std::io;
+use std::u64;
+
+use {Logger, LogRecord};
+use directive::LogFormat;
+
+/// A logger that writes each record as a line of text to a writer, in the
+/// format of the default logger, followed by the record's fields:
+///
+/// ```text
+/// WARN:main: login failed user=alice attempt=3
+/// ```
+pub struct TextLogger<W> {
+    writer: W,
+}
+
+impl<W: Writer> TextLogger<W> {
+    /// Creates a logger that writes to `writer`.
+    pub fn new(writer: W) -> TextLogger<W> {
+        TextLogger { writer: writer }
+    }
+}
+
+impl<W: Writer> Logger for TextLogger<W> {
+    fn log(&mut self, record: &LogRecord) {
+        let line = format_record(LogFormat::Text, record);
+        write_record(&mut self.writer, line.as_slice());
+    }
+}
+
+/// A logger that writes each record as a JSON object on a line of its own:
+///
+/// ```text
+/// {"level":"WARN","module":"main","file":"main.rs","line":7,"message":"login failed","fields":{"user":"alice","attempt":"3"}}
+/// ```
+///
+/// The values of the fields are formatted with `Show` and written as strings.
+pub struct JsonLogger<W> {
+    writer: W,
+}
+
+impl<W: Writer> JsonLogger<W> {
+    /// Creates a logger that writes to `writer`.
+    pub fn new(writer: W) -> JsonLogger<W> {
+        JsonLogger { writer: writer }
+    }
+}
+
+impl<W: Writer> Logger for JsonLogger<W> {
+    fn log(&mut self, record: &LogRecord) {
+        let line = format_record(LogFormat::Json, record);
+        write_record(&mut self.writer, line.as_slice());
+    }
+}
+
+/// Formats a record as a line of text or JSON, as `TextLogger` and
+/// `JsonLogger` write it.
+pub fn format_record(format: LogFormat, record: &LogRecord) -> Vec<u8> {
+    let mut line = MemWriter::new();
+    // Writing to a MemWriter never fails.
+    let res = match format {
+        LogFormat::Text => write_text(&mut line, record),
+        LogFormat::Json => write_json(&mut line, record),
+    };
+    res.unwrap();
+    line.into_inner()
+}
+
+// Writes a formatted record in one go, so that records aren't split when a
+// `RotatingFile` is rotated.
+fn write_record<W: Writer>(w: &mut W, line: &[u8]) {
+    match w.write(line).and_then(|()| w.flush()) {
+        Err(e) => panic!("failed to log: {}", e),
+        Ok(()) => {}
+    }
+}
+
+pub fn write_text<W: Writer>(w: &mut W, record: &LogRecord) -> IoResult<()> {
+    try!(write!(w, "{}:{}: {}", record.level, record.module_path, record.args));
+    for &(key, value) in record.fields.iter() {
+        try!(write!(w, " {}={}", key, value));
+    }
+    w.write_char('\n')
+}
+
+fn write_json<W: Writer>(w: &mut W, record: &LogRecord) -> IoResult<()> {
+    try!(w.write_str("{\"level\":"));
+    try!(write_json_str(w, record.level.to_string().as_slice()));
+    try!(w.write_str(",\"module\":"));
+    try!(write_json_str(w, record.module_path));
+    try!(w.write_str(",\"file\":"));
+    try!(write_json_str(w, record.file));
+    try!(write!(w, ",\"line\":{},\"message\":", record.line));
+    try!(write_json_str(w, record.args.to_string().as_slice()));
+    try!(w.write_str(",\"fields\":{"));
+    for (i, &(key, value)) in record.fields.iter().enumerate() {
+        if i > 0 {
+            try!(w.write_char(','));
+        }
+        try!(write_json_str(w, key));
+        try!(w.write_char(':'));
+        try!(write_json_str(w, value.to_string().as_slice()));
+    }
+    w.write_str("}}\n")
+}
+
+fn write_json_str<W: Writer>(w: &mut W, s: &str) -> IoResult<()> {
+    try!(w.write_char('"'));
+    for c in s.chars() {
+        match c {
+            '"' => try!(w.write_str("\\\"")),
+            '\\' => try!(w.write_str("\\\\")),
+            '\n' => try!(w.write_str("\\n")),
+            '\r' => try!(w.write_str("\\r")),
+            '\t' => try!(w.write_str("\\t")),
+            '\x00' ... '\x1f' | '\x7f' => try!(write!(w, "\\u{:04x}", c as u32)),
+            c => try!(w.write_char(c)),
+        }
+    }
+    w.write_char('"')
+}
+
+/// A file that is rotated when writing to it would make it bigger than a
+/// maximum size.
+///
+/// When `app.log` is rotated, it's renamed to `app.log.1`, `app.log.1` is
+/// renamed to `app.log.2`, and so on, the oldest file is deleted so that at
+/// most `keep` old files are left, and a new `app.log` is started. Every
+/// write goes entirely to one file, so a logger that writes each record with
+/// one call never splits a record between two files.
+pub struct RotatingFile {
+    path: Path,
+    max_size: u64,
+    keep: uint,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Opens the file at `path` for appending, creating it if it doesn't
+    /// exist. It's rotated when it would grow bigger than `max_size` bytes,
+    /// keeping `keep` old files.
+    pub fn open(path: &Path, max_size: u64, keep: uint) -> IoResult<RotatingFile> {
+        let mut file = try!(File::open_mode(path, io::Append, io::Write));
+        let size = try!(file.stat()).size;
+        Ok(RotatingFile {
+            path: path.clone(),
+            max_size: max_size,
+            keep: keep,
+            file: file,
+            size: size,
+        })
+    }
+
+    /// Opens the file at `path` for appending, creating it if it doesn't
+    /// exist. It's never rotated.
+    pub fn open_unlimited(path: &Path) -> IoResult<RotatingFile> {
+        RotatingFile::open(path, u64::MAX, 0)
+    }
+
+    /// Returns the path of the `n`th old file (the current file if `n` is 0).
+    pub fn rotated_path(&self, n: uint) -> Path {
+        if n == 0 {
+            return self.path.clone()
+        }
+        let mut name = self.path.as_vec().to_vec();
+        name.push_all(format!(".{}", n).as_bytes());
+        Path::new(name)
+    }
+
+    fn rotate(&mut self) -> IoResult<()> {
+        try!(self.file.flush());
+        if self.keep == 0 {
+            self.file = try!(File::open_mode(&self.path, io::Truncate, io::Write));
+        } else {
+            let oldest = self.rotated_path(self.keep);
+            if oldest.exists() {
+                try!(fs::unlink(&oldest));
+            }
+            for n in range(0, self.keep).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    try!(fs::rename(&from, &self.rotated_path(n + 1)));
+                }
+            }
+            self.file = try!(File::open_mode(&self.path, io::Append, io::Write));
+        }
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Writer for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            try!(self.rotate());
+        }
+        try!(self.file.write(buf));
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextLogger, JsonLogger, RotatingFile, write_json_str};
+    use std::io::{File, MemWriter, TempDir};
+    use std::str;
+    use {Logger, LogRecord, LogLevel, WARN};
+
+    fn log_to<L: Logger>(logger: &mut L) {
+        let user = "al\"ice";
+        let attempt = 3u;
+        format_args!(|args| {
+            logger.log(&LogRecord {
+                module_path: "main",
+                level: LogLevel(WARN),
+                args: args,
+                file: "main.rs",
+                line: 7,
+                fields: &[("user", &user as &::std::fmt::Show),
+                          ("attempt", &attempt as &::std::fmt::Show)],
+            })
+        }, "login {}", "failed");
+    }
+
+    #[test]
+    fn text_logger() {
+        let mut logger = TextLogger::new(MemWriter::new());
+        log_to(&mut logger);
+        assert_eq!(str::from_utf8(logger.writer.get_ref()).unwrap(),
+                   "WARN:main: login failed user=al\"ice attempt=3\n");
+    }
+
+    #[test]
+    fn json_logger() {
+        let mut logger = JsonLogger::new(MemWriter::new());
+        log_to(&mut logger);
+        log_to(&mut logger);
+        let line = "{\"level\":\"WARN\",\"module\":\"main\",\"file\":\"main.rs\",\"line\":7,\
+                    \"message\":\"login failed\",\
+                    \"fields\":{\"user\":\"al\\\"ice\",\"attempt\":\"3\"}}\n";
+        assert_eq!(str::from_utf8(logger.writer.get_ref()).unwrap(),
+                   format!("{}{}", line, line).as_slice());
+
+        let mut w = MemWriter::new();
+        write_json_str(&mut w, "a\n\t\\\x01\u{e9}").unwrap();
+        assert_eq!(str::from_utf8(w.get_ref()).unwrap(), "\"a\\n\\t\\\\\\u0001\u{e9}\"");
+    }
+
+    #[test]
+    fn rotating_file() {
+        let dir = TempDir::new("log-rotate").unwrap();
+        let path = dir.path().join("app.log");
+        let contents = |n: uint| {
+            let mut name = path.as_vec().to_vec();
+            if n > 0 {
+                name.push_all(format!(".{}", n).as_bytes());
+            }
+            let name = Path::new(name);
+            if name.exists() {
+                Some(File::open(&name).read_to_string().unwrap())
+            } else {
+                None
+            }
+        };
+
+        {
+            let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+            file.write_str("aaaa\n").unwrap();
+            file.write_str("bbbb\n").unwrap();
+            // A write that's bigger than the maximum size still goes to a
+            // single file.
+            file.write_str("cccccccccccc\n").unwrap();
+            file.write_str("dddd\n").unwrap();
+            file.write_str("eeee\n").unwrap();
+        }
+        assert_eq!(contents(0), Some("dddd\neeee\n".to_string()));
+        assert_eq!(contents(1), Some("cccccccccccc\n".to_string()));
+        assert_eq!(contents(2), Some("aaaa\nbbbb\n".to_string()));
+
+        // Reopening appends, and the oldest file is deleted.
+        {
+            let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+            file.write_str("ffff\n").unwrap();
+        }
+        assert_eq!(contents(0), Some("ffff\n".to_string()));
+        assert_eq!(contents(1), Some("dddd\neeee\n".to_string()));
+        assert_eq!(contents(2), Some("cccccccccccc\n".to_string()));
+        assert_eq!(contents(3), None);
+
+        // Without old files, the file is started over.
+        {
+            let mut file = RotatingFile::open(&path, 10, 0).unwrap();
+            file.write_str("gggg\n").unwrap();
+            file.write_str("hhhh\n").unwrap();
+        }
+        assert_eq!(contents(0), Some("hhhh\n".to_string()));
+        assert_eq!(contents(1), Some("dddd\neeee\n".to_string()));
+    }
+}
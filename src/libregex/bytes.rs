@@ -0,0 +1,600 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Regular expressions that search arbitrary bytes.
+//!
+//! The `Regex` type in this module has the same API as the crate level
+//! `Regex`, except that it searches `&[u8]` instead of `&str`. This makes it
+//! possible to search text that is not valid UTF-8, such as log files with
+//! mixed encodings or binary protocol dumps.
+//!
+//! Matching is done one byte at a time with Unicode support turned off:
+//!
+//! * `.` and negated classes match any single byte (except `\n` for `.`).
+//! * `\xHH` and octal escapes match the byte with that value. Escapes for
+//!   characters above `\xFF` are an error.
+//! * `\d`, `\s`, `\w` and `\b` are ASCII only. Unicode classes like `\pN`
+//!   are an error.
+//! * Case insensitive matching only folds ASCII letters.
+//! * A non-ASCII character written directly in the expression (outside of a
+//!   character class) matches its UTF-8 encoding.
+//!
+//! # Example
+//!
+//! ```rust
+//! use regex::bytes::Regex;
+//! let re = Regex::new(r"\xFF(\d+)\xFF").unwrap();
+//! let data = b"\x00\xFF123\xFF\x80";
+//! let caps = re.captures(data).unwrap();
+//! assert_eq!(caps.at(1), b"123");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use compile::Program;
+use dfa;
+use parse;
+use vm;
+use vm::{CaptureLocs, MatchKind, Exists, Location, Submatches};
+
+/// A compiled regular expression for searching bytes.
+///
+/// All searching is done with an implicit `.*?` at the beginning and end of
+/// an expression, exactly as with the crate level `Regex`. All positions
+/// returned are byte indices into the haystack.
+///
+/// The lifetimes `'r` and `'t` in this module correspond to the lifetime of a
+/// compiled regular expression and bytes to search, respectively.
+#[deriving(Clone)]
+pub struct Regex {
+    original: String,
+    names: Vec<Option<String>>,
+    prog: Program,
+    dfa: dfa::Cache,
+}
+
+impl fmt::Show for Regex {
+    /// Shows the original regular expression.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Regex {
+    /// Compiles a regular expression for searching bytes. Once compiled, it
+    /// can be used repeatedly to search, split or replace bytes.
+    ///
+    /// If an invalid expression is given, then an error is returned. This
+    /// includes expressions that use Unicode classes or escapes that do not
+    /// fit in a single byte.
+    pub fn new(re: &str) -> Result<Regex, parse::Error> {
+        let ast = try!(parse::parse_bytes(re));
+        let (mut prog, names) = Program::new(ast);
+        // The literal prefix is stored as a `String`, which would encode any
+        // byte above `\x7F` as two bytes. Cut it at the first such byte so
+        // that the prefix bytes are exactly the bytes to search for.
+        let ascii = prog.prefix.chars().take_while(|&c| c < '\x80').count();
+        prog.prefix.truncate(ascii);
+        Ok(Regex {
+            original: re.to_string(),
+            names: names,
+            prog: prog,
+            dfa: dfa::Cache::new(),
+        })
+    }
+
+    /// Returns true if and only if the regex matches the bytes given.
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        has_match(&exec(self, Exists, text))
+    }
+
+    /// Returns the start and end byte range of the leftmost-first match in
+    /// `text`. If no match exists, then `None` is returned.
+    pub fn find(&self, text: &[u8]) -> Option<(uint, uint)> {
+        let caps = exec(self, Location, text);
+        if has_match(&caps) {
+            Some((caps[0].unwrap(), caps[1].unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator for each successive non-overlapping match in
+    /// `text`, returning the start and end byte indices with respect to
+    /// `text`.
+    pub fn find_iter<'r, 't>(&'r self, text: &'t [u8]) -> FindMatches<'r, 't> {
+        FindMatches {
+            re: self,
+            search: text,
+            last_end: 0,
+            last_match: None,
+        }
+    }
+
+    /// Returns the capture groups corresponding to the leftmost-first
+    /// match in `text`. Capture group `0` always corresponds to the entire
+    /// match. If no match is found, then `None` is returned.
+    pub fn captures<'t>(&self, text: &'t [u8]) -> Option<Captures<'t>> {
+        let caps = exec(self, Submatches, text);
+        Captures::new(self, text, caps)
+    }
+
+    /// Returns an iterator over all the non-overlapping capture groups matched
+    /// in `text`. This is operationally the same as `find_iter` (except it
+    /// yields information about submatches).
+    pub fn captures_iter<'r, 't>(&'r self, text: &'t [u8])
+                                -> FindCaptures<'r, 't> {
+        FindCaptures {
+            re: self,
+            search: text,
+            last_match: None,
+            last_end: 0,
+        }
+    }
+
+    /// Returns an iterator of subslices of `text` delimited by a match of the
+    /// regular expression.
+    ///
+    /// This method will *not* copy the bytes given.
+    pub fn split<'r, 't>(&'r self, text: &'t [u8]) -> RegexSplits<'r, 't> {
+        RegexSplits {
+            finder: self.find_iter(text),
+            last: 0,
+        }
+    }
+
+    /// Returns an iterator of at most `limit` subslices of `text` delimited
+    /// by a match of the regular expression. (A `limit` of `0` will return no
+    /// subslices.) The remainder of the bytes that are not split will be the
+    /// last element in the iterator.
+    pub fn splitn<'r, 't>(&'r self, text: &'t [u8], limit: uint)
+                         -> RegexSplitsN<'r, 't> {
+        RegexSplitsN {
+            splits: self.split(text),
+            cur: 0,
+            limit: limit,
+        }
+    }
+
+    /// Replaces the leftmost-first match with the replacement provided.
+    /// The replacement can be a byte string (where `$N` and `$name` are
+    /// expanded to match capture groups) or a function that takes the matches'
+    /// `Captures` and returns the replaced bytes.
+    ///
+    /// If no match is found, then a copy of the bytes is returned unchanged.
+    pub fn replace<R: Replacer>(&self, text: &[u8], rep: R) -> Vec<u8> {
+        self.replacen(text, 1, rep)
+    }
+
+    /// Replaces all non-overlapping matches in `text` with the
+    /// replacement provided. This is the same as calling `replacen` with
+    /// `limit` set to `0`.
+    pub fn replace_all<R: Replacer>(&self, text: &[u8], rep: R) -> Vec<u8> {
+        self.replacen(text, 0, rep)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches in `text` with the
+    /// replacement provided. If `limit` is 0, then all non-overlapping matches
+    /// are replaced.
+    pub fn replacen<R: Replacer>
+                   (&self, text: &[u8], limit: uint, mut rep: R) -> Vec<u8> {
+        let mut new = Vec::with_capacity(text.len());
+        let mut last_match = 0u;
+
+        for (i, cap) in self.captures_iter(text).enumerate() {
+            if limit > 0 && i >= limit {
+                break
+            }
+
+            let (s, e) = cap.pos(0).unwrap(); // captures only reports matches
+            new.push_all(text[last_match..s]);
+            new.push_all(rep.reg_replace(&cap).as_slice());
+            last_match = e;
+        }
+        new.push_all(text[last_match..]);
+        new
+    }
+
+    /// Returns the original string of this regex.
+    pub fn as_str<'a>(&'a self) -> &'a str {
+        self.original.as_slice()
+    }
+}
+
+/// NoExpand indicates literal byte string replacement.
+///
+/// It can be used with `replace` and `replace_all` to do a literal
+/// replacement without expanding `$name` to their corresponding
+/// capture groups.
+pub struct NoExpand<'t>(pub &'t [u8]);
+
+/// Replacer describes types that can be used to replace matches in a byte
+/// string.
+pub trait Replacer {
+    /// Returns the bytes that are used to replace the match corresponding to
+    /// the `caps` capture group.
+    fn reg_replace(&mut self, caps: &Captures) -> Vec<u8>;
+}
+
+impl<'t> Replacer for NoExpand<'t> {
+    fn reg_replace(&mut self, _: &Captures) -> Vec<u8> {
+        let NoExpand(s) = *self;
+        s.to_vec()
+    }
+}
+
+impl<'t> Replacer for &'t [u8] {
+    fn reg_replace(&mut self, caps: &Captures) -> Vec<u8> {
+        caps.expand(*self)
+    }
+}
+
+impl<F> Replacer for F where F: FnMut(&Captures) -> Vec<u8> {
+    fn reg_replace(&mut self, caps: &Captures) -> Vec<u8> {
+        (*self)(caps)
+    }
+}
+
+/// Yields all subslices delimited by a regular expression match.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the bytes being split.
+pub struct RegexSplits<'r, 't> {
+    finder: FindMatches<'r, 't>,
+    last: uint,
+}
+
+impl<'r, 't> Iterator<&'t [u8]> for RegexSplits<'r, 't> {
+    fn next(&mut self) -> Option<&'t [u8]> {
+        let text = self.finder.search;
+        match self.finder.next() {
+            None => {
+                if self.last >= text.len() {
+                    None
+                } else {
+                    let s = text[self.last..];
+                    self.last = text.len();
+                    Some(s)
+                }
+            }
+            Some((s, e)) => {
+                let matched = text[self.last..s];
+                self.last = e;
+                Some(matched)
+            }
+        }
+    }
+}
+
+/// Yields at most `N` subslices delimited by a regular expression match.
+///
+/// The last subslice will be whatever remains after splitting.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the bytes being split.
+pub struct RegexSplitsN<'r, 't> {
+    splits: RegexSplits<'r, 't>,
+    cur: uint,
+    limit: uint,
+}
+
+impl<'r, 't> Iterator<&'t [u8]> for RegexSplitsN<'r, 't> {
+    fn next(&mut self) -> Option<&'t [u8]> {
+        let text = self.splits.finder.search;
+        if self.cur >= self.limit {
+            None
+        } else {
+            self.cur += 1;
+            if self.cur >= self.limit {
+                Some(text[self.splits.last..])
+            } else {
+                self.splits.next()
+            }
+        }
+    }
+}
+
+/// Captures represents a group of captured byte strings for a single match.
+///
+/// The 0th capture always corresponds to the entire match. Each subsequent
+/// index corresponds to the next capture group in the regex.
+///
+/// `'t` is the lifetime of the matched bytes.
+pub struct Captures<'t> {
+    text: &'t [u8],
+    locs: CaptureLocs,
+    named: Option<HashMap<String, uint>>,
+}
+
+impl<'t> Captures<'t> {
+    fn new(re: &Regex, search: &'t [u8], locs: CaptureLocs)
+          -> Option<Captures<'t>> {
+        if !has_match(&locs) {
+            return None
+        }
+
+        let named =
+            if re.names.len() == 0 {
+                None
+            } else {
+                let mut named = HashMap::new();
+                for (i, name) in re.names.iter().enumerate() {
+                    match *name {
+                        None => {},
+                        Some(ref name) => {
+                            named.insert(name.clone(), i);
+                        }
+                    }
+                }
+                Some(named)
+            };
+        Some(Captures {
+            text: search,
+            locs: locs,
+            named: named,
+        })
+    }
+
+    /// Returns the start and end positions of the Nth capture group.
+    /// Returns `None` if `i` is not a valid capture group or if the capture
+    /// group did not match anything.
+    pub fn pos(&self, i: uint) -> Option<(uint, uint)> {
+        let (s, e) = (i * 2, i * 2 + 1);
+        if e >= self.locs.len() || self.locs[s].is_none() {
+            // VM guarantees that each pair of locations are both Some or None.
+            return None
+        }
+        Some((self.locs[s].unwrap(), self.locs[e].unwrap()))
+    }
+
+    /// Returns the matched bytes for the capture group `i`.
+    /// If `i` isn't a valid capture group or didn't match anything, then an
+    /// empty slice is returned.
+    pub fn at(&self, i: uint) -> &'t [u8] {
+        match self.pos(i) {
+            None => &[],
+            Some((s, e)) => self.text[s..e],
+        }
+    }
+
+    /// Returns the matched bytes for the capture group named `name`.
+    /// If `name` isn't a valid capture group or didn't match anything, then
+    /// an empty slice is returned.
+    pub fn name(&self, name: &str) -> &'t [u8] {
+        match self.named {
+            None => &[],
+            Some(ref h) => {
+                match h.get(name) {
+                    None => &[],
+                    Some(i) => self.at(*i),
+                }
+            }
+        }
+    }
+
+    /// Creates an iterator of all the capture groups in order of appearance
+    /// in the regular expression.
+    pub fn iter(&'t self) -> SubCaptures<'t> {
+        SubCaptures { idx: 0, caps: self, }
+    }
+
+    /// Creates an iterator of all the capture group positions in order of
+    /// appearance in the regular expression.
+    pub fn iter_pos(&'t self) -> SubCapturesPos<'t> {
+        SubCapturesPos { idx: 0, caps: self, }
+    }
+
+    /// Expands all instances of `$name` in `text` to the corresponding capture
+    /// group `name`.
+    ///
+    /// This follows the same rules as `regex::Captures::expand`: `name` may be
+    /// an index or a name made of letters, digits and underscores, unknown
+    /// names expand to nothing and `$$` is a literal `$`.
+    pub fn expand(&self, text: &[u8]) -> Vec<u8> {
+        let mut expanded = Vec::with_capacity(text.len());
+        let mut i = 0;
+        while i < text.len() {
+            if text[i] != b'$' {
+                expanded.push(text[i]);
+                i += 1;
+                continue
+            }
+            if i + 1 < text.len() && text[i + 1] == b'$' {
+                expanded.push(b'$');
+                i += 2;
+                continue
+            }
+            let start = i + 1;
+            let mut end = start;
+            while end < text.len() && is_name_byte(text[end]) {
+                end += 1;
+            }
+            if end == start {
+                expanded.push(b'$');
+                i += 1;
+                continue
+            }
+            // Names are made of ASCII bytes, so this can't fail.
+            let name = ::std::str::from_utf8(text[start..end]).unwrap();
+            match from_str::<uint>(name) {
+                None => expanded.push_all(self.name(name)),
+                Some(n) => expanded.push_all(self.at(n)),
+            }
+            i = end;
+        }
+        expanded
+    }
+
+    /// Returns the number of captured groups.
+    #[inline]
+    pub fn len(&self) -> uint { self.locs.len() / 2 }
+
+    /// Returns if there are no captured groups.
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+/// An iterator over capture groups for a particular match of a regular
+/// expression.
+///
+/// `'t` is the lifetime of the matched bytes.
+pub struct SubCaptures<'t> {
+    idx: uint,
+    caps: &'t Captures<'t>,
+}
+
+impl<'t> Iterator<&'t [u8]> for SubCaptures<'t> {
+    fn next(&mut self) -> Option<&'t [u8]> {
+        if self.idx < self.caps.len() {
+            self.idx += 1;
+            Some(self.caps.at(self.idx - 1))
+        } else {
+            None
+        }
+    }
+}
+
+/// An iterator over capture group positions for a particular match of a
+/// regular expression.
+///
+/// `'t` is the lifetime of the matched bytes.
+pub struct SubCapturesPos<'t> {
+    idx: uint,
+    caps: &'t Captures<'t>,
+}
+
+impl<'t> Iterator<Option<(uint, uint)>> for SubCapturesPos<'t> {
+    fn next(&mut self) -> Option<Option<(uint, uint)>> {
+        if self.idx < self.caps.len() {
+            self.idx += 1;
+            Some(self.caps.pos(self.idx - 1))
+        } else {
+            None
+        }
+    }
+}
+
+/// An iterator that yields all non-overlapping capture groups matching a
+/// particular regular expression.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched bytes.
+pub struct FindCaptures<'r, 't> {
+    re: &'r Regex,
+    search: &'t [u8],
+    last_match: Option<uint>,
+    last_end: uint,
+}
+
+impl<'r, 't> Iterator<Captures<'t>> for FindCaptures<'r, 't> {
+    fn next(&mut self) -> Option<Captures<'t>> {
+        if self.last_end > self.search.len() {
+            return None
+        }
+
+        let caps = exec_slice(self.re, Submatches, self.search,
+                              self.last_end, self.search.len());
+        let (s, e) =
+            if !has_match(&caps) {
+                return None
+            } else {
+                (caps[0].unwrap(), caps[1].unwrap())
+            };
+
+        // Don't accept empty matches immediately following a match.
+        // i.e., no infinite loops please.
+        if e == s && Some(self.last_end) == self.last_match {
+            self.last_end += 1;
+            return self.next()
+        }
+        self.last_end = e;
+        self.last_match = Some(self.last_end);
+        Captures::new(self.re, self.search, caps)
+    }
+}
+
+/// An iterator over all non-overlapping matches for a particular byte string.
+///
+/// The iterator yields a tuple of integers corresponding to the start and end
+/// of the match. The indices are byte offsets.
+///
+/// `'r` is the lifetime of the compiled expression and `'t` is the lifetime
+/// of the matched bytes.
+pub struct FindMatches<'r, 't> {
+    re: &'r Regex,
+    search: &'t [u8],
+    last_match: Option<uint>,
+    last_end: uint,
+}
+
+impl<'r, 't> Iterator<(uint, uint)> for FindMatches<'r, 't> {
+    fn next(&mut self) -> Option<(uint, uint)> {
+        if self.last_end > self.search.len() {
+            return None
+        }
+
+        let caps = exec_slice(self.re, Location, self.search,
+                              self.last_end, self.search.len());
+        let (s, e) =
+            if !has_match(&caps) {
+                return None
+            } else {
+                (caps[0].unwrap(), caps[1].unwrap())
+            };
+
+        // Don't accept empty matches immediately following a match.
+        // i.e., no infinite loops please.
+        if e == s && Some(self.last_end) == self.last_match {
+            self.last_end += 1;
+            return self.next()
+        }
+        self.last_end = e;
+        self.last_match = Some(self.last_end);
+        Some((s, e))
+    }
+}
+
+fn exec(re: &Regex, which: MatchKind, input: &[u8]) -> CaptureLocs {
+    exec_slice(re, which, input, 0, input.len())
+}
+
+fn exec_slice(re: &Regex, which: MatchKind,
+              input: &[u8], s: uint, e: uint) -> CaptureLocs {
+    // See `exec_slice` in re.rs for how the DFA and NFA are combined.
+    let found = match which {
+        Exists | Location => dfa::run_bytes(&re.dfa, which, &re.prog, input, s, e),
+        Submatches => None,
+    };
+    match (which, found) {
+        (_, Some(None)) => vec![None, None],
+        (Exists, Some(Some(_))) => vec![Some(0), Some(0)],
+        (_, Some(Some(end))) => {
+            match dfa::run_bytes_reverse(&re.dfa, &re.prog, input, s, end) {
+                Some(start) => vec![Some(start), Some(end)],
+                None => vm::run_bytes(which, &re.prog, input, s, end),
+            }
+        }
+        (_, None) => vm::run_bytes(which, &re.prog, input, s, e),
+    }
+}
+
+#[inline]
+fn has_match(caps: &CaptureLocs) -> bool {
+    caps.len() >= 2 && caps[0].is_some() && caps[1].is_some()
+}
+
+#[inline]
+fn is_name_byte(b: u8) -> bool {
+    match b {
+        b'_' | b'0' ... b'9' | b'a' ... b'z' | b'A' ... b'Z' => true,
+        _ => false,
+    }
+}
@@ -119,6 +119,57 @@ impl Program {
         (prog, names)
     }
 
+    /// Compiles many expressions into a single program that can find which
+    /// of them match in one pass over the search text.
+    ///
+    /// Each expression is compiled exactly as by `new` (with its own `Save`
+    /// and `Match` instructions) and they are joined with a chain of `Split`
+    /// instructions. The second value returned maps each instruction index to
+    /// the index of the expression whose `Match` instruction is there, if any.
+    ///
+    /// There must be at least one expression.
+    pub fn new_set(asts: Vec<parse::Ast>) -> (Program, Vec<Option<uint>>) {
+        assert!(asts.len() > 0);
+        let mut c = Compiler {
+            insts: Vec::with_capacity(100 * asts.len()),
+            names: Vec::with_capacity(10),
+        };
+
+        let mut match_pcs = Vec::with_capacity(asts.len());
+        let last = asts.len() - 1;
+        for (i, ast) in asts.into_iter().enumerate() {
+            let split =
+                if i < last {
+                    Some(c.empty_split())
+                } else {
+                    None
+                };
+            let j1 = c.insts.len();
+            c.insts.push(Save(0));
+            c.compile(ast);
+            c.insts.push(Save(1));
+            c.insts.push(Match);
+            match_pcs.push(c.insts.len() - 1);
+            match split {
+                Some(split) => {
+                    let j2 = c.insts.len();
+                    c.set_split(split, j1, j2);
+                }
+                None => {}
+            }
+        }
+
+        let mut pats = Vec::from_elem(c.insts.len(), None);
+        for (i, &pc) in match_pcs.iter().enumerate() {
+            pats[pc] = Some(i);
+        }
+        let prog = Program {
+            insts: c.insts,
+            prefix: String::new(),
+        };
+        (prog, pats)
+    }
+
     /// Returns the total number of capture groups in the regular expression.
     /// This includes the zeroth capture.
     pub fn num_captures(&self) -> uint {
@@ -0,0 +1,745 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// This is a lazily built DFA that is simulated on the same `Program` as the
+// NFA in vm.rs. It can only answer "is there a match" and "where does the
+// match end", but it answers them much faster than the NFA since every state
+// it computes is cached and reused.
+//
+// A DFA state is the *ordered* list of NFA threads (i.e., the `clist` of the
+// NFA) at some position in the input. Keeping the order (rather than using
+// a plain set) is what lets the DFA implement the same leftmost-first
+// semantics as the NFA: when a `Match` instruction is stepped, every thread
+// after it has a lower priority and is dropped, exactly as `StepMatch` does.
+//
+// Empty width assertions depend on the characters on either side of the
+// current position, so the epsilon closure of a state is computed from a
+// small set of "context" flags: whether the previous character is the
+// beginning of input, a new line or a word character, and likewise for the
+// next character. A transition is therefore keyed on the character consumed
+// *and* the flags of the character after it. Programs without assertions
+// ignore the flags entirely so that they don't multiply the number of
+// transitions.
+//
+// States are built on demand and cached across searches. If the cache grows
+// too big, it is flushed and the search continues. If it has to be flushed
+// too often during a single search, the DFA gives up and the caller falls
+// back to the NFA.
+//
+// Since a DFA state doesn't remember where its threads started, the forward
+// DFA only finds where the leftmost-first match ends. Its start is then found
+// by running a second DFA backwards from the end: the start of the match is
+// the leftmost position from which the program matches exactly up to the end
+// (any earlier one would be the start of a match further left). Thread
+// priorities don't matter for that, so a reverse state is just the set of
+// instructions from which the text already scanned leads to a `Match`, and a
+// match is seen whenever the first instruction is in it.
+//
+// See http://swtch.com/~rsc/regex/regex3.html for the general idea, which is
+// what RE2 implements.
+
+use std::collections::HashMap;
+use std::mem;
+use std::slice::SlicePrelude;
+use std::sync::Mutex;
+
+use compile::{
+    Program, Inst,
+    Match, OneChar, CharClass, Any, EmptyBegin, EmptyEnd, EmptyWordBoundary,
+    Save, Jump, Split,
+};
+use parse::{FLAG_NOCASE, FLAG_MULTI, FLAG_DOTNL, FLAG_NEGATED};
+use vm::{MatchKind, Exists, Input, CharReader, ByteReader};
+use vm::{class_cmp, find_prefix};
+
+macro_rules! try_opt(
+    ($e:expr) => (match $e { Some(e) => e, None => return None })
+)
+
+type StatePtr = u32;
+
+/// Marks a transition that hasn't been computed yet.
+static STATE_UNKNOWN: StatePtr = 0xFFFFFFFF;
+
+/// An approximate upper bound (in bytes) on the memory used by the cached
+/// states of a single DFA.
+static CACHE_LIMIT: uint = 1 << 21;
+
+/// The number of times the cache may be flushed during one search before the
+/// DFA gives up.
+static MAX_FLUSHES: uint = 3;
+
+/// Transitions on characters below this are stored in a table in each state.
+/// Everything else goes through a hash map.
+static TABLE_CHARS: uint = 128;
+
+// Flags describing the context of a position in the input. The `PREV_*`
+// flags describe the character before the position and the `CUR_*` flags
+// describe the character after it.
+type Context = u8;
+
+static PREV_BEGIN: Context = 1 << 0;
+static PREV_NL:    Context = 1 << 1;
+static PREV_WORD:  Context = 1 << 2;
+static CUR_END:    Context = 1 << 3;
+static CUR_NL:     Context = 1 << 4;
+static CUR_WORD:   Context = 1 << 5;
+
+static PREV_LOOK: Context = PREV_BEGIN | PREV_NL | PREV_WORD;
+static CUR_LOOK: Context = CUR_END | CUR_NL | CUR_WORD;
+
+static CUR_SHIFT: uint = 3;
+static NUM_CONTEXTS: uint = 1 << 6;
+
+/// The cache of DFA states for a single compiled expression.
+///
+/// It is created empty and filled in lazily by searches. Since it lives
+/// inside a `Regex`, it is guarded by a mutex. A search that finds the cache
+/// busy (because another task is using the same `Regex`) simply uses the NFA.
+pub struct Cache {
+    dfa: Mutex<Option<Dfa>>,
+    reverse: Mutex<Option<Dfa>>,
+}
+
+impl Cache {
+    /// Creates a new empty cache.
+    pub fn new() -> Cache {
+        Cache { dfa: Mutex::new(None), reverse: Mutex::new(None) }
+    }
+}
+
+impl Clone for Cache {
+    /// The states of the DFA are never shared. A clone starts empty.
+    fn clone(&self) -> Cache {
+        Cache::new()
+    }
+}
+
+/// Runs the DFA on the search text `input` between the byte indices `start`
+/// and `end`.
+///
+/// If the DFA can't be used, `None` is returned and the caller should run the
+/// NFA instead. Otherwise, the result is the end of the leftmost-first match
+/// (or `None` if there is no match). When `which` is `Exists`, the search
+/// stops as soon as any match is found, so the end returned is only
+/// meaningful as a witness that a match exists.
+pub fn run<'t>(cache: &Cache, which: MatchKind, prog: &Program, input: &'t str,
+               start: uint, end: uint) -> Option<Option<uint>> {
+    exec(cache, which, prog, CharReader::new(input), input.as_bytes(),
+         start, end)
+}
+
+/// Runs the DFA exactly like `run`, except that `input` is a sequence of raw
+/// bytes. (See `vm::run_bytes`.)
+pub fn run_bytes<'t>(cache: &Cache, which: MatchKind, prog: &Program,
+                     input: &'t [u8], start: uint, end: uint)
+                    -> Option<Option<uint>> {
+    exec(cache, which, prog, ByteReader::new(input), input, start, end)
+}
+
+/// Finds where the leftmost-first match that ends at the byte index `end`
+/// starts, given that it starts at or after `start`. `end` must be the end
+/// found by `run`.
+///
+/// This is `None` if the DFA can't be used, in which case the caller should
+/// run the NFA instead.
+pub fn run_reverse<'t>(cache: &Cache, prog: &Program, input: &'t str,
+                       start: uint, end: uint) -> Option<uint> {
+    exec_reverse(cache, prog, CharReader::new(input), start, end)
+}
+
+/// Runs the reverse DFA exactly like `run_reverse`, except that `input` is a
+/// sequence of raw bytes.
+pub fn run_bytes_reverse<'t>(cache: &Cache, prog: &Program, input: &'t [u8],
+                             start: uint, end: uint) -> Option<uint> {
+    exec_reverse(cache, prog, ByteReader::new(input), start, end)
+}
+
+fn exec<I: Input>(cache: &Cache, which: MatchKind, prog: &Program, chars: I,
+                  input: &[u8], start: uint, end: uint)
+                 -> Option<Option<uint>> {
+    let mut guard = match cache.dfa.try_lock() {
+        Some(guard) => guard,
+        None => return None,
+    };
+    if guard.is_none() {
+        *guard = Some(Dfa::new(prog, false));
+    }
+    guard.as_mut().unwrap().exec(which, prog, chars, input, start, end)
+}
+
+fn exec_reverse<I: Input>(cache: &Cache, prog: &Program, chars: I,
+                          start: uint, end: uint) -> Option<uint> {
+    let mut guard = match cache.reverse.try_lock() {
+        Some(guard) => guard,
+        None => return None,
+    };
+    if guard.is_none() {
+        *guard = Some(Dfa::new(prog, true));
+    }
+    guard.as_mut().unwrap().exec_reverse(prog, chars, start, end)
+}
+
+struct State {
+    /// The non-empty instructions of the threads in this state, in priority
+    /// order. In a reverse DFA, this is every instruction in the state, in
+    /// ascending order.
+    insts: Vec<uint>,
+    /// Whether a match has been seen. Once one has been, no new threads are
+    /// started.
+    matched: bool,
+    /// Whether every thread in this state was started at this position.
+    /// (This is when the literal prefix can be used to skip ahead.)
+    restart: bool,
+    /// Whether stepping this state will hit a `Match` instruction. In a
+    /// reverse DFA, whether a match starts at this position.
+    is_match: bool,
+    /// Transitions on small characters, indexed by character and then by
+    /// the context flags of the character after it (before it, in a reverse
+    /// DFA).
+    table: Vec<StatePtr>,
+}
+
+struct Dfa {
+    states: Vec<State>,
+    /// Maps the contents of a state to its index in `states`.
+    cache: HashMap<(Vec<uint>, bool, bool), StatePtr>,
+    /// Transitions on characters that don't fit in a state's table. The key
+    /// is the state, the character and the context flags used by `table`.
+    trans: HashMap<(StatePtr, char, Context), StatePtr>,
+    /// Start states, indexed by the context of the starting position.
+    starts: Vec<StatePtr>,
+    /// The context flags that actually matter to the program.
+    context_mask: Context,
+    /// The number of context flag combinations in each state's table.
+    num_look: uint,
+    /// Whether the program can only match at the beginning of the input.
+    anchored: bool,
+    /// Whether this DFA runs backwards from the end of a match.
+    reverse: bool,
+    /// The instructions with an empty transition to each instruction. This
+    /// is only used by a reverse DFA.
+    preds: Vec<Vec<uint>>,
+    /// Scratch space for computing epsilon closures.
+    set: SparseSet,
+    /// The approximate memory used by `states`.
+    size: uint,
+}
+
+impl Dfa {
+    fn new(prog: &Program, reverse: bool) -> Dfa {
+        let mut mask = 0;
+        for inst in prog.insts.iter() {
+            match *inst {
+                EmptyBegin(_) => mask |= PREV_BEGIN | PREV_NL,
+                EmptyEnd(_) => mask |= CUR_END | CUR_NL,
+                EmptyWordBoundary(_) => mask |= PREV_WORD | CUR_WORD,
+                _ => {}
+            }
+        }
+        let anchored =
+            match prog.insts[1] {
+                EmptyBegin(flags) if flags & FLAG_MULTI == 0 => true,
+                _ => false,
+            };
+        let mut preds = Vec::from_elem(prog.insts.len(), vec![]);
+        if reverse {
+            for (pc, inst) in prog.insts.iter().enumerate() {
+                match *inst {
+                    EmptyBegin(_) | EmptyEnd(_) | EmptyWordBoundary(_)
+                    | Save(_) => preds[pc + 1].push(pc),
+                    Jump(to) => preds[to].push(pc),
+                    Split(x, y) => {
+                        preds[x].push(pc);
+                        preds[y].push(pc);
+                    }
+                    Match | OneChar(_, _) | CharClass(_, _) | Any(_) => {}
+                }
+            }
+        }
+        let look = if reverse { PREV_LOOK } else { CUR_LOOK };
+        Dfa {
+            states: vec![],
+            cache: HashMap::new(),
+            trans: HashMap::new(),
+            starts: Vec::from_elem(NUM_CONTEXTS, STATE_UNKNOWN),
+            context_mask: mask,
+            num_look: if mask & look > 0 { 8 } else { 1 },
+            anchored: anchored,
+            reverse: reverse,
+            preds: preds,
+            set: SparseSet::new(prog.insts.len()),
+            size: 0,
+        }
+    }
+
+    fn exec<I: Input>(&mut self, which: MatchKind, prog: &Program, mut chars: I,
+                      input: &[u8], start: uint, end: uint)
+                     -> Option<Option<uint>> {
+        let mut flushes = 0u;
+        let mut last_match = None;
+
+        let mut ic = start;
+        let mut next_ic = chars.set(start);
+        let mut si = match self.start_state(prog, &chars) {
+            Some(si) => si,
+            None => {
+                self.flush();
+                try_opt!(self.start_state(prog, &chars))
+            }
+        };
+        while ic <= end {
+            if self.states[si as uint].restart && prog.prefix.len() > 0 {
+                let needle = prog.prefix.as_bytes();
+                match find_prefix(needle, input[ic..]) {
+                    None => break,
+                    Some(0) => {}
+                    Some(i) => {
+                        ic += i;
+                        next_ic = chars.set(ic);
+                        si = match self.start_state(prog, &chars) {
+                            Some(si) => si,
+                            None => {
+                                flushes += 1;
+                                if flushes > MAX_FLUSHES {
+                                    return None
+                                }
+                                self.flush();
+                                try_opt!(self.start_state(prog, &chars))
+                            }
+                        };
+                        continue
+                    }
+                }
+            }
+
+            {
+                let state = &self.states[si as uint];
+                if state.is_match {
+                    match which {
+                        Exists => return Some(Some(ic)),
+                        _ => last_match = Some(ic),
+                    }
+                }
+                if state.insts.len() == 0 && (state.matched || self.anchored) {
+                    // No thread can ever be started again, so we're done.
+                    break
+                }
+            }
+
+            // Consume the character at `ic`. At the end of the input, there
+            // is nothing left to step over.
+            let c = match chars.cur() {
+                None => break,
+                Some(c) => c,
+            };
+            ic = next_ic;
+            next_ic = chars.advance();
+            let look = self.context(&chars) & CUR_LOOK;
+            si = match self.next_state(prog, &chars, si, c, look) {
+                Some(next) => next,
+                None => {
+                    flushes += 1;
+                    if flushes > MAX_FLUSHES {
+                        return None
+                    }
+                    try_opt!(self.flush_and_step(prog, &chars, si, c, look))
+                }
+            };
+        }
+        Some(last_match)
+    }
+
+    /// Runs the reverse DFA from `end` back to `start` and returns the
+    /// leftmost position from which the program matches up to `end`.
+    fn exec_reverse<I: Input>(&mut self, prog: &Program, mut chars: I,
+                              start: uint, end: uint) -> Option<uint> {
+        let mut flushes = 0u;
+        let mut first_match = None;
+
+        let mut ic = end;
+        chars.set(end);
+        let mut si = match self.start_state(prog, &chars) {
+            Some(si) => si,
+            None => {
+                self.flush();
+                try_opt!(self.start_state(prog, &chars))
+            }
+        };
+        loop {
+            {
+                let state = &self.states[si as uint];
+                if state.is_match {
+                    first_match = Some(ic);
+                }
+                if state.insts.len() == 0 {
+                    break
+                }
+            }
+            if ic <= start {
+                break
+            }
+
+            // Step back over the character before `ic`.
+            let c = match chars.prev() {
+                None => break,
+                Some(c) => c,
+            };
+            ic -= chars.unit_len(c);
+            chars.set(ic);
+            let look = self.context(&chars) & PREV_LOOK;
+            si = match self.next_state(prog, &chars, si, c, look) {
+                Some(next) => next,
+                None => {
+                    flushes += 1;
+                    if flushes > MAX_FLUSHES {
+                        return None
+                    }
+                    try_opt!(self.flush_and_step(prog, &chars, si, c, look))
+                }
+            };
+        }
+        first_match
+    }
+
+    /// Flushes the cache and then steps over `c` from the state `si`, which
+    /// flushing would otherwise invalidate. This is `None` only if the two
+    /// states alone don't fit in the cache.
+    fn flush_and_step<I: Input>(&mut self, prog: &Program, chars: &I,
+                                si: StatePtr, c: char, look: Context)
+                               -> Option<StatePtr> {
+        let (insts, matched, restart) = {
+            let state = &self.states[si as uint];
+            (state.insts.clone(), state.matched, state.restart)
+        };
+        self.flush();
+        let si = try_opt!(self.add_state(prog, insts, matched, restart));
+        self.next_state(prog, chars, si, c, look)
+    }
+
+    /// Returns the state that starts a search at the current position of
+    /// `chars`. This is `None` only when the cache is full.
+    fn start_state<I: Input>(&mut self, prog: &Program, chars: &I)
+                            -> Option<StatePtr> {
+        let ctx = self.context(chars);
+        let cached = self.starts[ctx as uint];
+        if cached != STATE_UNKNOWN {
+            return Some(cached)
+        }
+        self.set.clear();
+        if self.reverse {
+            for (pc, inst) in prog.insts.iter().enumerate() {
+                match *inst {
+                    Match => add_reverse(prog, &self.preds, &mut self.set, pc, ctx),
+                    _ => {}
+                }
+            }
+        } else {
+            add(prog, &mut self.set, 0, ctx);
+        }
+        let insts = self.set_insts(prog);
+        let si = match self.add_state(prog, insts, false, !self.reverse) {
+            Some(si) => si,
+            None => return None,
+        };
+        self.starts[ctx as uint] = si;
+        Some(si)
+    }
+
+    /// Returns the state reached by stepping over `c` from the state `si`.
+    /// `look` has the `CUR_*` flags of the character following `c` (or, in a
+    /// reverse DFA, the `PREV_*` flags of the character preceding it). This
+    /// is `None` only when the cache is full.
+    fn next_state<I: Input>(&mut self, prog: &Program, chars: &I,
+                            si: StatePtr, c: char, look: Context)
+                           -> Option<StatePtr> {
+        let cached = self.cached_next(si, c, look);
+        if cached != STATE_UNKNOWN {
+            return Some(cached)
+        }
+
+        let mut ctx = look;
+        self.set.clear();
+        let insts = mem::replace(&mut self.states[si as uint].insts, vec![]);
+        let mut matched = self.states[si as uint].matched;
+        let restart;
+        if self.reverse {
+            if c == '\n' { ctx |= CUR_NL }
+            if chars.is_word(Some(c)) { ctx |= CUR_WORD }
+            ctx &= self.context_mask;
+
+            for &pc in insts.iter() {
+                if pc > 0 && matches_char(chars, &prog.insts[pc - 1], c) {
+                    add_reverse(prog, &self.preds, &mut self.set, pc - 1, ctx);
+                }
+            }
+            restart = false;
+        } else {
+            if c == '\n' { ctx |= PREV_NL }
+            if chars.is_word(Some(c)) { ctx |= PREV_WORD }
+            ctx &= self.context_mask;
+
+            for &pc in insts.iter() {
+                let ok = match prog.insts[pc] {
+                    Match => { matched = true; break }
+                    ref inst => matches_char(chars, inst, c),
+                };
+                if ok {
+                    add(prog, &mut self.set, pc + 1, ctx);
+                }
+            }
+
+            restart = self.set.len() == 0 && !matched;
+            if !matched && !self.anchored {
+                // This simulates the preceding '.*?', as in the NFA.
+                add(prog, &mut self.set, 0, ctx);
+            }
+        }
+        self.states[si as uint].insts = insts;
+
+        let insts = self.set_insts(prog);
+        let next = match self.add_state(prog, insts, matched, restart) {
+            Some(next) => next,
+            None => return None,
+        };
+        self.set_next(si, c, look, next);
+        Some(next)
+    }
+
+    /// Returns the index in a state's table of the context flags `look`.
+    fn look_index(&self, look: Context) -> uint {
+        let look = (look & self.context_mask) as uint;
+        if self.reverse { look } else { look >> CUR_SHIFT }
+    }
+
+    fn cached_next(&self, si: StatePtr, c: char, look: Context) -> StatePtr {
+        let look = self.look_index(look);
+        if (c as uint) < TABLE_CHARS {
+            self.states[si as uint].table[(c as uint) * self.num_look + look]
+        } else {
+            match self.trans.get(&(si, c, look as Context)) {
+                Some(&next) => next,
+                None => STATE_UNKNOWN,
+            }
+        }
+    }
+
+    fn set_next(&mut self, si: StatePtr, c: char, look: Context,
+                next: StatePtr) {
+        let look = self.look_index(look);
+        if (c as uint) < TABLE_CHARS {
+            self.states[si as uint].table[(c as uint) * self.num_look + look] = next;
+        } else {
+            self.trans.insert((si, c, look as Context), next);
+        }
+    }
+
+    /// Returns the existing state with the contents given or adds a new one.
+    /// This is `None` only when the cache is full.
+    fn add_state(&mut self, prog: &Program, insts: Vec<uint>, matched: bool,
+                 restart: bool) -> Option<StatePtr> {
+        let key = (insts, matched, restart);
+        match self.cache.get(&key) {
+            Some(&si) => return Some(si),
+            None => {}
+        }
+        let table_len = TABLE_CHARS * self.num_look;
+        let cost = table_len * mem::size_of::<StatePtr>()
+                   + 2 * key.0.len() * mem::size_of::<uint>()
+                   + mem::size_of::<State>();
+        if self.size + cost > CACHE_LIMIT && self.states.len() > 0 {
+            return None
+        }
+        self.size += cost;
+
+        let is_match =
+            if self.reverse {
+                key.0.first() == Some(&0)
+            } else {
+                key.0.iter().any(|&pc| match prog.insts[pc] {
+                    Match => true,
+                    _ => false,
+                })
+            };
+        let si = self.states.len() as StatePtr;
+        self.states.push(State {
+            insts: key.0.clone(),
+            matched: matched,
+            restart: restart,
+            is_match: is_match,
+            table: Vec::from_elem(table_len, STATE_UNKNOWN),
+        });
+        self.cache.insert(key, si);
+        Some(si)
+    }
+
+    fn flush(&mut self) {
+        self.states.clear();
+        self.cache.clear();
+        self.trans.clear();
+        for si in self.starts.iter_mut() {
+            *si = STATE_UNKNOWN;
+        }
+        self.size = 0;
+    }
+
+    /// Returns the non-empty instructions in the scratch set, in order. A
+    /// reverse DFA needs all of them, sorted.
+    fn set_insts(&self, prog: &Program) -> Vec<uint> {
+        if self.reverse {
+            let mut insts = self.set.as_slice().to_vec();
+            insts.sort();
+            return insts
+        }
+        self.set.as_slice().iter().map(|&pc| pc).filter(|&pc| match prog.insts[pc] {
+            Match | OneChar(_, _) | CharClass(_, _) | Any(_) => true,
+            _ => false,
+        }).collect()
+    }
+
+    /// Returns the context flags of the current position of `chars`.
+    fn context<I: Input>(&self, chars: &I) -> Context {
+        let mut ctx = 0;
+        match chars.prev() {
+            None => ctx |= PREV_BEGIN,
+            Some('\n') => ctx |= PREV_NL,
+            c => if chars.is_word(c) { ctx |= PREV_WORD },
+        }
+        match chars.cur() {
+            None => ctx |= CUR_END,
+            Some('\n') => ctx |= CUR_NL,
+            c => if chars.is_word(c) { ctx |= CUR_WORD },
+        }
+        ctx & self.context_mask
+    }
+}
+
+/// Returns true if the instruction `inst` consumes the character `c`.
+fn matches_char<I: Input>(chars: &I, inst: &Inst, c: char) -> bool {
+    match *inst {
+        OneChar(rc, flags) => {
+            rc == c
+            || (flags & FLAG_NOCASE > 0 && chars.fold(rc) == chars.fold(c))
+        }
+        CharClass(ref ranges, flags) => {
+            let negate = flags & FLAG_NEGATED > 0;
+            let casei = flags & FLAG_NOCASE > 0;
+            let found = ranges.as_slice();
+            let found = found.binary_search(|&rc| {
+                class_cmp(chars, casei, c, rc)
+            }).found().is_some();
+            found ^ negate
+        }
+        Any(flags) => flags & FLAG_DOTNL > 0 || c != '\n',
+        Match | EmptyBegin(_) | EmptyEnd(_) | EmptyWordBoundary(_)
+        | Save(_) | Jump(_) | Split(_, _) => false,
+    }
+}
+
+/// Returns true if the empty width assertion `inst` holds in the context
+/// given. Any other instruction always holds.
+fn holds(inst: &Inst, ctx: Context) -> bool {
+    match *inst {
+        EmptyBegin(flags) => {
+            let multi = flags & FLAG_MULTI > 0;
+            ctx & PREV_BEGIN > 0 || (multi && ctx & PREV_NL > 0)
+        }
+        EmptyEnd(flags) => {
+            let multi = flags & FLAG_MULTI > 0;
+            ctx & CUR_END > 0 || (multi && ctx & CUR_NL > 0)
+        }
+        EmptyWordBoundary(flags) => {
+            let boundary = (ctx & PREV_WORD > 0) != (ctx & CUR_WORD > 0);
+            boundary == !(flags & FLAG_NEGATED > 0)
+        }
+        _ => true,
+    }
+}
+
+/// Follows all empty transitions from `pc` in the context given and adds
+/// every instruction visited to `set`, in the same order as the NFA's `add`.
+fn add(prog: &Program, set: &mut SparseSet, pc: uint, ctx: Context) {
+    if set.contains(pc) {
+        return
+    }
+    set.insert(pc);
+    match prog.insts[pc] {
+        EmptyBegin(_) | EmptyEnd(_) | EmptyWordBoundary(_) => {
+            if holds(&prog.insts[pc], ctx) {
+                add(prog, set, pc + 1, ctx)
+            }
+        }
+        Save(_) => add(prog, set, pc + 1, ctx),
+        Jump(to) => add(prog, set, to, ctx),
+        Split(x, y) => {
+            add(prog, set, x, ctx);
+            add(prog, set, y, ctx);
+        }
+        Match | OneChar(_, _) | CharClass(_, _) | Any(_) => {}
+    }
+}
+
+/// Follows all empty transitions *to* `pc` backwards in the context given and
+/// adds every instruction visited to `set`. `preds` is the `preds` of a
+/// reverse `Dfa`.
+fn add_reverse(prog: &Program, preds: &[Vec<uint>], set: &mut SparseSet,
+               pc: uint, ctx: Context) {
+    if set.contains(pc) {
+        return
+    }
+    set.insert(pc);
+    for &from in preds[pc].iter() {
+        if holds(&prog.insts[from], ctx) {
+            add_reverse(prog, preds, set, from, ctx)
+        }
+    }
+}
+
+/// An ordered set of instruction indices with constant time insertion and
+/// membership tests. (This is the same trick used by `Threads` in vm.rs.)
+struct SparseSet {
+    dense: Vec<uint>,
+    sparse: Vec<uint>,
+    size: uint,
+}
+
+impl SparseSet {
+    fn new(size: uint) -> SparseSet {
+        SparseSet {
+            dense: Vec::from_elem(size, 0u),
+            sparse: Vec::from_elem(size, 0u),
+            size: 0,
+        }
+    }
+
+    fn len(&self) -> uint {
+        self.size
+    }
+
+    fn insert(&mut self, pc: uint) {
+        self.dense[self.size] = pc;
+        self.sparse[pc] = self.size;
+        self.size += 1;
+    }
+
+    #[inline]
+    fn contains(&self, pc: uint) -> bool {
+        let s = self.sparse[pc];
+        s < self.size && self.dense[s] == pc
+    }
+
+    fn clear(&mut self) {
+        self.size = 0;
+    }
+
+    fn as_slice<'a>(&'a self) -> &'a [uint] {
+        self.dense[..self.size]
+    }
+}
@@ -191,6 +191,37 @@
 //! # }
 //! ```
 //!
+//! # Searching bytes
+//!
+//! The `bytes` module provides a `Regex` with the same API that searches
+//! `&[u8]` instead of `&str`, for haystacks that aren't valid UTF-8. Its
+//! matching semantics are byte oriented with Unicode support turned off. See
+//! the `bytes` module documentation for the details.
+//!
+//! ```rust
+//! use regex::bytes::Regex;
+//! let re = Regex::new(r"(?i)error:\s+(\w+)").unwrap();
+//! let line = b"\xFE\xFFERROR: disk";
+//! assert_eq!(re.captures(line).unwrap().at(1), b"disk");
+//! ```
+//!
+//! # Matching many expressions at once
+//!
+//! A `RegexSet` reports which of a list of expressions match a string, using
+//! a single pass over the string. Sets known at compile time can be built with
+//! the `regex_set!` macro, which takes a comma separated list of string
+//! literals:
+//!
+//! ```rust
+//! # #![feature(phase)]
+//! # extern crate regex; #[phase(plugin)] extern crate regex_macros;
+//! # fn main() {
+//! let set = regex_set!(r"^GET ", r"^POST ", r"\.png\b");
+//! let matches = set.matches("GET /logo.png HTTP/1.1");
+//! assert_eq!(matches.iter().collect::<Vec<uint>>(), vec![0, 2]);
+//! # }
+//! ```
+//!
 //! # Syntax
 //!
 //! The syntax supported in this crate is almost in an exact correspondence
@@ -358,6 +389,13 @@
 //! implementation provides `O(nm)` search where `n` is the number of
 //! characters in the search text and `m` is the number of instructions in a
 //! compiled expression.
+//!
+//! When only the existence or the location of a match is needed (e.g., with
+//! `is_match` or `find`), a dynamic regex uses a lazily built DFA instead of
+//! simulating the NFA. (Native regexes compiled by `regex!` always use their
+//! own NFA.) Its cache of states is bounded to a few megabytes per expression.
+//! If an expression and its search text would need more states than that, the
+//! search falls back to the NFA.
 
 #![crate_name = "regex"]
 #![crate_type = "rlib"]
@@ -391,10 +429,17 @@ pub use re::{Regex, Captures, SubCaptures, SubCapturesPos};
 pub use re::{FindCaptures, FindMatches};
 pub use re::{Replacer, NoExpand, RegexSplits, RegexSplitsN};
 pub use re::{quote, is_match};
+pub use set::{RegexSet, SetMatches, SetMatchesIter};
+pub use stream::FindMatchesReader;
+
+pub mod bytes;
 
 mod compile;
+mod dfa;
 mod parse;
 mod re;
+mod set;
+mod stream;
 mod vm;
 
 #[cfg(test)]
@@ -428,6 +473,7 @@ pub mod native {
         FLAG_SWAP_GREED, FLAG_NEGATED,
     };
     pub use re::{Dynamic, ExDynamic, Native, ExNative};
+    pub use set::{DynamicSet, ExDynamicSet, NativeSet, ExNativeSet};
     pub use vm::{
         MatchKind, Exists, Location, Submatches,
         StepState, StepMatchEarlyReturn, StepMatch, StepContinue,
@@ -191,6 +191,10 @@ struct Parser<'a> {
     caps: uint,
     // A set of all capture group names used only to detect duplicates.
     names: Vec<String>,
+    // When false, the expression is meant to match raw bytes: Perl classes
+    // are ASCII only, Unicode classes are rejected and escapes must fit in
+    // a single byte.
+    unicode: bool,
 }
 
 pub fn parse(s: &str) -> Result<Ast, Error> {
@@ -201,6 +205,24 @@ pub fn parse(s: &str) -> Result<Ast, Error> {
         flags: FLAG_EMPTY,
         caps: 0,
         names: vec!(),
+        unicode: true,
+    }.parse()
+}
+
+/// Parses an expression that will be matched against raw bytes.
+///
+/// Every character in the resulting AST is at most `\xFF` and stands for
+/// the byte of the same value. Non-ASCII literals written directly in the
+/// expression are expanded to their UTF-8 encoding.
+pub fn parse_bytes(s: &str) -> Result<Ast, Error> {
+    Parser {
+        chars: s.chars().collect(),
+        chari: 0,
+        stack: vec!(),
+        flags: FLAG_EMPTY,
+        caps: 0,
+        names: vec!(),
+        unicode: false,
     }.parse()
 }
 
@@ -361,6 +383,16 @@ impl<'a> Parser<'a> {
             '$' => {
                 self.push(End(flags))
             }
+            _ if !self.unicode && c > '\x7F' => {
+                // There is no single byte for this character, so match its
+                // UTF-8 encoding instead.
+                let mut encoded = String::with_capacity(4);
+                encoded.push(c);
+                let lits = encoded.as_bytes().iter()
+                                  .map(|&b| Literal(b as char, flags))
+                                  .collect();
+                self.push(Cat(lits))
+            }
             _ => {
                 self.push(Literal(c, flags))
             }
@@ -431,7 +463,7 @@ impl<'a> Parser<'a> {
                     }
                     return Ok(())
                 }
-                _ => {}
+                _ => try!(self.check_class_char(c)),
             }
 
             if self.peek_is(1, '-') && !self.peek_is(2, ']') {
@@ -447,6 +479,8 @@ impl<'a> Parser<'a> {
                             return self.err(format!("Expected a literal, but got {}.",
                                                     ast).as_slice()),
                     }
+                } else {
+                    try!(self.check_class_char(c2));
                 }
                 if c2 < c {
                     return self.err(format!("Invalid character class \
@@ -616,9 +650,18 @@ impl<'a> Parser<'a> {
             'B' => Ok(WordBoundary(FLAG_NEGATED)),
             '0'|'1'|'2'|'3'|'4'|'5'|'6'|'7' => Ok(try!(self.parse_octal())),
             'x' => Ok(try!(self.parse_hex())),
+            'p' | 'P' if !self.unicode => {
+                self.err("Unicode character classes are not supported when \
+                          matching bytes.")
+            }
             'p' | 'P' => Ok(try!(self.parse_unicode_name())),
             'd' | 'D' | 's' | 'S' | 'w' | 'W' => {
-                let ranges = perl_unicode_class(c);
+                let ranges =
+                    if self.unicode {
+                        perl_unicode_class(c)
+                    } else {
+                        perl_ascii_class(c)
+                    };
                 let mut flags = self.flags & FLAG_NOCASE;
                 if c.is_uppercase() { flags |= FLAG_NEGATED }
                 Ok(AstClass(ranges, flags))
@@ -918,6 +961,11 @@ impl<'a> Parser<'a> {
     }
 
     fn char_from_u32(&self, n: u32) -> Result<char, Error> {
+        if !self.unicode && n > 0xFF {
+            return self.err(format!("Character code '{}' does not fit in a \
+                                     single byte.",
+                                    n).as_slice())
+        }
         match char::from_u32(n) {
             Some(c) => Ok(c),
             None => {
@@ -928,6 +976,18 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // A character written directly inside a class must name a single byte
+    // when matching bytes. (Escapes are checked by `char_from_u32`.)
+    fn check_class_char(&self, c: char) -> Result<(), Error> {
+        if !self.unicode && c > '\x7F' {
+            return self.err(format!("Non-ASCII character '{}' cannot be used \
+                                     in a byte character class. Use a \\x \
+                                     escape instead.",
+                                    c).as_slice())
+        }
+        Ok(())
+    }
+
     fn pos(&self, c: char) -> Option<uint> {
         self.chars.iter()
             .skip(self.chari).position(|&c2| c2 == c).map(|i| self.chari + i)
@@ -1005,6 +1065,17 @@ fn perl_unicode_class(which: char) -> Vec<(char, char)> {
     }
 }
 
+// Constructs an ASCII only Perl character class from \d, \s or \w (or any
+// of their negated forms). This is used when matching bytes.
+fn perl_ascii_class(which: char) -> Vec<(char, char)> {
+    match which.to_lowercase() {
+        'd' => DIGIT.to_vec(),
+        's' => combine_ranges(SPACE.to_vec()),
+        'w' => combine_ranges(WORD.to_vec()),
+        _ => unreachable!(),
+    }
+}
+
 // Returns a concatenation of two expressions. This also guarantees that a
 // `Cat` expression will never be a direct child of another `Cat` expression.
 fn concat_flatten(x: Ast, y: Ast) -> Ast {
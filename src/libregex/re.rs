@@ -13,10 +13,13 @@ pub use self::Regex::*;
 
 use std::collections::HashMap;
 use std::fmt;
+use std::io::Buffer;
 use std::str::CowString;
 
 use compile::Program;
+use dfa;
 use parse;
+use stream::FindMatchesReader;
 use vm;
 use vm::{CaptureLocs, MatchKind, Exists, Location, Submatches};
 
@@ -122,7 +125,8 @@ pub struct ExDynamic {
     original: String,
     names: Vec<Option<String>>,
     #[doc(hidden)]
-    pub prog: Program
+    pub prog: Program,
+    dfa: dfa::Cache,
 }
 
 #[doc(hidden)]
@@ -165,6 +169,7 @@ impl Regex {
             original: re.to_string(),
             names: names,
             prog: prog,
+            dfa: dfa::Cache::new(),
         }))
     }
 
@@ -251,6 +256,39 @@ impl Regex {
         }
     }
 
+    /// Returns an iterator for each successive non-overlapping match in the
+    /// stream `rdr`, returning the start and end byte offsets with respect to
+    /// the beginning of the stream.
+    ///
+    /// The stream is read a buffer at a time, so it can be much larger than
+    /// memory. The matches found are exactly those `find_iter` would find in
+    /// the whole stream, including matches that cross the boundaries of the
+    /// buffers read. Only as much of the stream as a match (or a possible
+    /// match) spans is kept in memory.
+    ///
+    /// The stream must contain valid UTF-8. If it doesn't, or if reading from
+    /// it fails, the iterator yields the error and stops.
+    ///
+    /// # Example
+    ///
+    /// Find every word with exactly 13 characters in a file:
+    ///
+    /// ```rust,no_run
+    /// # #![feature(phase)]
+    /// # extern crate regex; #[phase(plugin)] extern crate regex_macros;
+    /// # fn main() {
+    /// use std::io::{BufferedReader, File};
+    ///
+    /// let file = File::open(&Path::new("huge.txt"));
+    /// for pos in regex!(r"\b\w{13}\b").find_iter_reader(BufferedReader::new(file)) {
+    ///     println!("{}", pos.unwrap());
+    /// }
+    /// # }
+    /// ```
+    pub fn find_iter_reader<B: Buffer>(&self, rdr: B) -> FindMatchesReader<B> {
+        FindMatchesReader::new(self, rdr)
+    }
+
     /// Returns the capture groups corresponding to the leftmost-first
     /// match in `text`. Capture group `0` always corresponds to the entire
     /// match. If no match is found, then `None` is returned.
@@ -921,7 +959,27 @@ fn exec(re: &Regex, which: MatchKind, input: &str) -> CaptureLocs {
 fn exec_slice(re: &Regex, which: MatchKind,
               input: &str, s: uint, e: uint) -> CaptureLocs {
     match *re {
-        Dynamic(ExDynamic { ref prog, .. }) => vm::run(which, prog, input, s, e),
+        Dynamic(ExDynamic { ref prog, ref dfa, .. }) => {
+            // The DFA is used whenever submatches aren't needed. It can't tell
+            // where a match starts, so the reverse DFA is then run back from
+            // the end of the match to find that out. If either gives up, the
+            // NFA is run instead.
+            let found = match which {
+                Exists | Location => dfa::run(dfa, which, prog, input, s, e),
+                Submatches => None,
+            };
+            match (which, found) {
+                (_, Some(None)) => vec![None, None],
+                (Exists, Some(Some(_))) => vec![Some(0), Some(0)],
+                (_, Some(Some(end))) => {
+                    match dfa::run_reverse(dfa, prog, input, s, end) {
+                        Some(start) => vec![Some(start), Some(end)],
+                        None => vm::run(which, prog, input, s, end),
+                    }
+                }
+                (_, None) => vm::run(which, prog, input, s, e),
+            }
+        }
         Native(ExNative { ref prog, .. }) => (*prog)(which, input, s, e),
     }
 }
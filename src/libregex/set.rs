@@ -0,0 +1,231 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+pub use self::RegexSet::*;
+
+use std::fmt;
+use std::slice;
+
+use compile::Program;
+use parse;
+use vm;
+use vm::{MatchKind, Exists, Location};
+
+/// A set of regular expressions that are matched against a string together.
+///
+/// Every expression in the set is compiled into a single program, so finding
+/// out which of the expressions match some text takes one pass over the text,
+/// no matter how many expressions there are. This is much faster than trying
+/// each expression in turn when there are many of them, e.g., when routing a
+/// request path or classifying log lines.
+///
+/// A set can only report *which* expressions match. To find where they match
+/// (or their submatches), compile the matching expressions individually.
+///
+/// Expressions are identified by their index in the list used to build the
+/// set. A set can also be compiled to native Rust code at compile time with
+/// the `regex_set!` macro.
+///
+/// # Example
+///
+/// ```rust
+/// # use regex::RegexSet;
+/// let set = RegexSet::new(&[r"\w+", r"\d+", r"foo", r"bar$"]).unwrap();
+/// let matches = set.matches("foobar");
+/// assert!(matches.matched(0));
+/// assert!(!matches.matched(1));
+/// assert!(matches.matched(2));
+/// assert!(matches.matched(3));
+/// assert_eq!(matches.iter().collect::<Vec<uint>>(), vec![0, 2, 3]);
+/// ```
+#[deriving(Clone)]
+pub enum RegexSet {
+    // The representation of `RegexSet` is exported to support the
+    // `regex_set!` syntax extension. Do not rely on it.
+    #[doc(hidden)]
+    DynamicSet(ExDynamicSet),
+    #[doc(hidden)]
+    NativeSet(ExNativeSet),
+}
+
+#[deriving(Clone)]
+#[doc(hidden)]
+pub struct ExDynamicSet {
+    originals: Vec<String>,
+    #[doc(hidden)]
+    pub prog: Program,
+    #[doc(hidden)]
+    pub pats: Vec<Option<uint>>,
+}
+
+#[doc(hidden)]
+pub struct ExNativeSet {
+    #[doc(hidden)]
+    pub originals: &'static &'static [&'static str],
+    #[doc(hidden)]
+    pub prog: fn(MatchKind, &str, uint, uint) -> Vec<Option<uint>>
+}
+
+impl Copy for ExNativeSet {}
+
+impl Clone for ExNativeSet {
+    fn clone(&self) -> ExNativeSet {
+        *self
+    }
+}
+
+impl fmt::Show for RegexSet {
+    /// Shows the original regular expressions.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "["));
+        for i in range(0, self.len()) {
+            if i > 0 {
+                try!(write!(f, ", "));
+            }
+            try!(write!(f, "{}", self.pattern(i)));
+        }
+        write!(f, "]")
+    }
+}
+
+impl RegexSet {
+    /// Compiles a set of dynamic regular expressions.
+    ///
+    /// If any of the expressions is invalid, then an error is returned. Its
+    /// message says which expression in the list it came from.
+    pub fn new(res: &[&str]) -> Result<RegexSet, parse::Error> {
+        let mut asts = Vec::with_capacity(res.len());
+        for (i, re) in res.iter().enumerate() {
+            match parse::parse(*re) {
+                Ok(ast) => asts.push(ast),
+                Err(err) => {
+                    return Err(parse::Error {
+                        pos: err.pos,
+                        msg: format!("In expression {}: {}", i, err.msg),
+                    })
+                }
+            }
+        }
+        let (prog, pats) =
+            if asts.len() == 0 {
+                (Program { insts: vec![], prefix: String::new() }, vec![])
+            } else {
+                Program::new_set(asts)
+            };
+        Ok(DynamicSet(ExDynamicSet {
+            originals: res.iter().map(|re| re.to_string()).collect(),
+            prog: prog,
+            pats: pats,
+        }))
+    }
+
+    /// Returns true if and only if at least one expression in the set matches
+    /// the string given.
+    ///
+    /// The search stops as soon as any expression matches.
+    pub fn is_match(&self, text: &str) -> bool {
+        exec(self, Exists, text).iter().any(|&m| m)
+    }
+
+    /// Returns the expressions in the set that match the string given.
+    ///
+    /// Each expression may match anywhere in `text`, independently of the
+    /// others (overlapping matches are fine).
+    pub fn matches(&self, text: &str) -> SetMatches {
+        SetMatches { matches: exec(self, Location, text) }
+    }
+
+    /// Returns the number of expressions in the set.
+    pub fn len(&self) -> uint {
+        match *self {
+            DynamicSet(ExDynamicSet { ref originals, .. }) => originals.len(),
+            NativeSet(ExNativeSet { ref originals, .. }) => originals.len(),
+        }
+    }
+
+    /// Returns the original string of the `i`th expression in the set.
+    ///
+    /// Panics if `i` is not less than `len()`.
+    pub fn pattern<'a>(&'a self, i: uint) -> &'a str {
+        match *self {
+            DynamicSet(ExDynamicSet { ref originals, .. }) => {
+                originals[i].as_slice()
+            }
+            NativeSet(ExNativeSet { ref originals, .. }) => originals[i],
+        }
+    }
+}
+
+/// The result of matching a `RegexSet` against a string: for every expression
+/// in the set, whether it matched.
+#[deriving(Clone, PartialEq, Show)]
+pub struct SetMatches {
+    matches: Vec<bool>,
+}
+
+impl SetMatches {
+    /// Returns true if and only if the `i`th expression in the set matched.
+    ///
+    /// Panics if `i` is not less than `len()`.
+    pub fn matched(&self, i: uint) -> bool {
+        self.matches[i]
+    }
+
+    /// Returns true if and only if any expression in the set matched.
+    pub fn matched_any(&self) -> bool {
+        self.matches.iter().any(|&m| m)
+    }
+
+    /// Returns the number of expressions in the set that was matched.
+    /// (This is *not* the number of expressions that matched.)
+    pub fn len(&self) -> uint {
+        self.matches.len()
+    }
+
+    /// Returns an iterator over the indices of the expressions that matched,
+    /// in ascending order.
+    pub fn iter<'a>(&'a self) -> SetMatchesIter<'a> {
+        SetMatchesIter { it: self.matches.iter().enumerate() }
+    }
+}
+
+/// An iterator over the indices of the expressions in a set that matched.
+///
+/// `'a` is the lifetime of the `SetMatches` value.
+pub struct SetMatchesIter<'a> {
+    it: ::std::iter::Enumerate<slice::Items<'a, bool>>,
+}
+
+impl<'a> Iterator<uint> for SetMatchesIter<'a> {
+    fn next(&mut self) -> Option<uint> {
+        loop {
+            match self.it.next() {
+                None => return None,
+                Some((i, &true)) => return Some(i),
+                Some((_, &false)) => {}
+            }
+        }
+    }
+}
+
+fn exec(set: &RegexSet, which: MatchKind, input: &str) -> Vec<bool> {
+    match *set {
+        DynamicSet(ExDynamicSet { ref prog, ref pats, .. }) => {
+            if pats.len() == 0 {
+                return vec![]
+            }
+            vm::run_set(which, prog, pats.as_slice(), input)
+        }
+        NativeSet(ExNativeSet { ref prog, .. }) => {
+            let found = (*prog)(which, input, 0, input.len());
+            found.iter().map(|m| m.is_some()).collect()
+        }
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// Searching a stream works by keeping a window of the stream in memory and
+// running the NFA over it with `vm::run_partial`, which only reports a match
+// once more input can't change it. Otherwise, it says where the earliest
+// match could still start, so everything before that (except for one
+// character, which `^` and `\b` need to look at) is dropped from the window
+// and more input is read.
+//
+// A window is grown by at least its own size on every read. Since the NFA is
+// rerun over the whole window every time, this keeps the total amount of
+// work linear in the size of the stream even when a match (or a thread that
+// might become one) spans a lot of input.
+
+use std::cmp;
+use std::io::{Buffer, IoError, IoResult, EndOfFile, InvalidInput};
+use std::str;
+
+use compile::Program;
+use re::{Regex, Dynamic, ExDynamic, Native, ExNative};
+use vm;
+use vm::{Location, Done, NeedMore};
+
+/// An iterator over all non-overlapping matches in a stream.
+///
+/// Each match is given as the start and end byte offsets, relative to the
+/// beginning of the stream. If reading from the stream fails, the error is
+/// yielded and iteration stops. The stream must be valid UTF-8; if it isn't,
+/// an `InvalidInput` error is yielded.
+///
+/// `B` is the type of the stream.
+pub struct FindMatchesReader<B> {
+    prog: Program,
+    rdr: B,
+    // The window of the stream that is searched.
+    buf: String,
+    // The stream offset of `buf[0]`.
+    base: u64,
+    // Where the next search begins in `buf`.
+    pos: uint,
+    // The end of the last match in `buf`, if it is still in the window.
+    last_match: Option<uint>,
+    // Bytes of an incomplete UTF-8 sequence at the end of the last read.
+    partial: Vec<u8>,
+    eof: bool,
+    done: bool,
+}
+
+impl<B: Buffer> FindMatchesReader<B> {
+    #[doc(hidden)]
+    pub fn new(re: &Regex, rdr: B) -> FindMatchesReader<B> {
+        // Native regexes don't carry their program around, so it's compiled
+        // again from the original expression, which is known to be valid.
+        let prog = match *re {
+            Dynamic(ExDynamic { ref prog, .. }) => prog.clone(),
+            Native(ExNative { original, .. }) => {
+                match Regex::new(original) {
+                    Ok(Dynamic(ExDynamic { prog, .. })) => prog,
+                    _ => unreachable!(),
+                }
+            }
+        };
+        FindMatchesReader {
+            prog: prog,
+            rdr: rdr,
+            buf: String::new(),
+            base: 0,
+            pos: 0,
+            last_match: None,
+            partial: vec![],
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Returns the underlying stream. Any input that was read but not yet
+    /// searched is lost.
+    pub fn unwrap(self) -> B {
+        self.rdr
+    }
+
+    // Drops everything in the window before the character preceding `keep`
+    // and reads more input. No match can start before `keep`.
+    fn fill(&mut self, keep: uint) -> IoResult<()> {
+        self.pos = cmp::max(self.pos, keep);
+        let mut cut = keep;
+        if cut > 0 {
+            cut = self.buf.as_slice().char_range_at_reverse(cut).next;
+        }
+        if cut > 0 {
+            self.buf = self.buf.as_slice()[cut..].to_string();
+            self.base += cut as u64;
+            self.pos -= cut;
+            self.last_match = match self.last_match {
+                Some(e) if e >= cut => Some(e - cut),
+                _ => None,
+            };
+        }
+
+        // Read whatever the stream has buffered, and keep going until the
+        // window has at least doubled.
+        let want = self.buf.len();
+        let mut bytes = ::std::mem::replace(&mut self.partial, vec![]);
+        loop {
+            let n = match self.rdr.fill_buf() {
+                Ok(data) => {
+                    bytes.push_all(data);
+                    data.len()
+                }
+                Err(ref err) if err.kind == EndOfFile => 0,
+                Err(err) => return Err(err),
+            };
+            if n == 0 {
+                self.eof = true;
+                break
+            }
+            self.rdr.consume(n);
+            if bytes.len() >= want {
+                break
+            }
+        }
+
+        // Hold back a character that was cut in half by the end of the read.
+        let mut complete = bytes.len();
+        if !self.eof {
+            for i in range(1, cmp::min(4, bytes.len() + 1)) {
+                let b = bytes[bytes.len() - i];
+                if b & 0xC0 != 0x80 {
+                    if str::utf8_char_width(b) > i {
+                        complete = bytes.len() - i;
+                    }
+                    break
+                }
+            }
+        }
+        match str::from_utf8(bytes[..complete]) {
+            Some(s) => self.buf.push_str(s),
+            None => {
+                return Err(IoError {
+                    kind: InvalidInput,
+                    desc: "stream did not contain valid UTF-8",
+                    detail: None,
+                })
+            }
+        }
+        self.partial = bytes[complete..].to_vec();
+        Ok(())
+    }
+
+    // Returns the next match in the window (as indices into `buf`), reading
+    // more input as needed.
+    fn next_match(&mut self) -> IoResult<Option<(uint, uint)>> {
+        loop {
+            if !self.eof && self.pos >= self.buf.len() {
+                let pos = self.pos;
+                try!(self.fill(pos));
+                continue
+            }
+            if self.pos > self.buf.len() {
+                return Ok(None)
+            }
+
+            let (s, e) =
+                if self.eof {
+                    let caps = vm::run(Location, &self.prog,
+                                       self.buf.as_slice(),
+                                       self.pos, self.buf.len());
+                    match (caps[0], caps[1]) {
+                        (Some(s), Some(e)) => (s, e),
+                        _ => return Ok(None),
+                    }
+                } else {
+                    match vm::run_partial(&self.prog, self.buf.as_slice(),
+                                          self.pos) {
+                        Done(caps) => (caps[0].unwrap(), caps[1].unwrap()),
+                        NeedMore(keep) => {
+                            try!(self.fill(keep));
+                            continue
+                        }
+                    }
+                };
+
+            // Don't accept empty matches immediately following a match.
+            // i.e., no infinite loops please.
+            if e == s && Some(self.pos) == self.last_match {
+                self.pos =
+                    if self.pos < self.buf.len() {
+                        self.buf.as_slice().char_range_at(self.pos).next
+                    } else {
+                        self.buf.len() + 1
+                    };
+                continue
+            }
+            self.pos = e;
+            self.last_match = Some(e);
+            return Ok(Some((s, e)))
+        }
+    }
+}
+
+impl<B: Buffer> Iterator<IoResult<(u64, u64)>> for FindMatchesReader<B> {
+    fn next(&mut self) -> Option<IoResult<(u64, u64)>> {
+        if self.done {
+            return None
+        }
+        match self.next_match() {
+            Ok(Some((s, e))) => {
+                Some(Ok((self.base + s as u64, self.base + e as u64)))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
@@ -180,3 +180,53 @@ throughput!(medium_32K,medium(), 32<<10)
 throughput!(hard_32, hard(), 32)
 throughput!(hard_1K, hard(), 1<<10)
 throughput!(hard_32K,hard(), 32<<10)
+
+// A dynamic regex's `captures` always uses the NFA, while `is_match` (see
+// `throughput!`) uses the lazy DFA. Comparing the two on the same input shows
+// what the DFA buys.
+macro_rules! throughput_nfa(
+    ($name:ident, $regex:expr, $size:expr) => (
+        #[bench]
+        fn $name(b: &mut Bencher) {
+            let text = gen_text($size);
+            b.bytes = $size;
+            b.iter(|| if $regex.captures(text.as_slice()).is_some() {
+                panic!("match")
+            });
+        }
+    );
+)
+
+throughput_nfa!(easy1_32K_nfa, easy1(), 32<<10)
+throughput_nfa!(medium_32K_nfa, medium(), 32<<10)
+throughput_nfa!(hard_32K_nfa, hard(), 32<<10)
+
+#[bench]
+fn alternation_no_match(b: &mut Bencher) {
+    let re = regex!("foo[0-9]+bar|quux[a-z]+baz|zap");
+    let text = "abcdefghijklmnopqrstuvwxy ".repeat(100);
+    b.iter(|| if re.is_match(text.as_slice()) { panic!("match") });
+}
+
+#[bench]
+fn alternation_no_match_nfa(b: &mut Bencher) {
+    let re = regex!("foo[0-9]+bar|quux[a-z]+baz|zap");
+    let text = "abcdefghijklmnopqrstuvwxy ".repeat(100);
+    b.iter(|| if re.captures(text.as_slice()).is_some() { panic!("match") });
+}
+
+// The match is at the very end, so only the reverse DFA keeps `find` from
+// scanning the whole text twice.
+#[bench]
+fn find_late_match(b: &mut Bencher) {
+    let re = regex!(r"\bquux\b");
+    let text = format!("{}quux", "quuxx ".repeat(100));
+    b.iter(|| re.find(text.as_slice()));
+}
+
+#[bench]
+fn find_late_match_nfa(b: &mut Bencher) {
+    let re = regex!(r"\bquux\b");
+    let text = format!("{}quux", "quuxx ".repeat(100));
+    b.iter(|| re.captures(text.as_slice()).map(|caps| caps.pos(0)));
+}
@@ -0,0 +1,135 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use regex::bytes::{Regex, NoExpand, Captures};
+
+fn re(s: &str) -> Regex {
+    match Regex::new(s) {
+        Ok(re) => re,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+#[test]
+fn invalid_utf8_haystack() {
+    let r = re(r"\d+");
+    let text = b"\xFF\xFE42\x80";
+    assert_eq!(r.find(text), Some((2, 4)));
+    assert!(r.is_match(text));
+}
+
+#[test]
+fn hex_escape_matches_byte() {
+    let r = re(r"\xFF+");
+    assert_eq!(r.find(b"a\xFF\xFFb"), Some((1, 3)));
+    let r = re(r"[\x80-\xFF]+");
+    assert_eq!(r.find(b"ab\x80\x90\xFFcd"), Some((2, 5)));
+}
+
+#[test]
+fn dot_matches_any_byte() {
+    let r = re(r"a.b");
+    assert_eq!(r.find(b"a\xC3b"), Some((0, 3)));
+    assert_eq!(r.find(b"a\nb"), None);
+}
+
+#[test]
+fn utf8_literal_matches_encoding() {
+    let r = re(r"é+");
+    assert_eq!(r.find(b"caf\xC3\xA9\xC3\xA9!"), Some((3, 7)));
+    assert_eq!(r.find(b"caf\xE9"), None);
+}
+
+#[test]
+fn perl_classes_are_ascii() {
+    let r = re(r"\w+");
+    assert_eq!(r.find(b"\xC3\xA9abc"), Some((2, 5)));
+    let r = re(r"\b\d\b");
+    assert_eq!(r.find(b"6\xCE"), Some((0, 1)));
+}
+
+#[test]
+fn case_insensitive_ascii_only() {
+    let r = re(r"(?i)abc");
+    assert_eq!(r.find(b"xABCx"), Some((1, 4)));
+    let r = re(r"(?i)\xE9");
+    assert_eq!(r.find(b"\xC9"), None);
+    assert_eq!(r.find(b"\xE9"), Some((0, 1)));
+}
+
+#[test]
+fn captures_named() {
+    let r = re(r"(?P<key>\w+)=(?P<val>[^;]*)");
+    let caps = r.captures(b"\x00k1=\xFFv;").unwrap();
+    assert_eq!(caps.name("key"), b"k1");
+    assert_eq!(caps.name("val"), b"\xFFv");
+    assert_eq!(caps.at(0), b"k1=\xFFv");
+    assert_eq!(caps.pos(2), Some((4, 6)));
+    assert_eq!(caps.len(), 3);
+}
+
+#[test]
+fn find_iter_empty() {
+    let r = re("");
+    let ms = r.find_iter(b"\xFF\xFF").collect::<Vec<(uint, uint)>>();
+    assert_eq!(ms, vec![(0, 0), (1, 1), (2, 2)]);
+}
+
+#[test]
+fn split() {
+    let r = re(r"\x00+");
+    let subs: Vec<&[u8]> = r.split(b"a\x00\x00b\xFF\x00c").collect();
+    assert_eq!(subs, vec![b"a", b"b\xFF", b"c"]);
+    let subs: Vec<&[u8]> = r.splitn(b"a\x00\x00b\xFF\x00c", 2).collect();
+    assert_eq!(subs, vec![b"a", b"b\xFF\x00c"]);
+}
+
+#[test]
+fn replace_all_expand() {
+    let r = re(r"(\w+)=(\w+)");
+    assert_eq!(r.replace_all(b"a=b \xFF c=d", b"$2=$1"),
+               b"b=a \xFF d=c".to_vec());
+    assert_eq!(r.replace(b"a=b", b"$$1"), b"$1".to_vec());
+    assert_eq!(r.replace(b"a=b", NoExpand(b"$2")), b"$2".to_vec());
+}
+
+#[test]
+fn replace_closure() {
+    let r = re(r"[\x80-\xFF]");
+    let out = r.replace_all(b"a\xFFb\x80", |&mut: caps: &Captures| {
+        format!("<{:x}>", caps.at(0)[0]).into_bytes()
+    });
+    assert_eq!(out, b"a<ff>b<80>".to_vec());
+}
+
+#[test]
+fn prefix_with_high_byte() {
+    let r = re(r"ab\xFFc");
+    assert_eq!(r.find(b"xxab\xFFc"), Some((2, 6)));
+    assert_eq!(r.find(b"xxab\xC3\xBFc"), None);
+}
+
+macro_rules! noparse(
+    ($name:ident, $re:expr) => (
+        #[test]
+        fn $name() {
+            let re = $re;
+            match Regex::new(re) {
+                Err(_) => {},
+                Ok(_) => panic!("Regex '{}' should cause a parse error.", re),
+            }
+        }
+    );
+)
+
+noparse!(fail_unicode_class, r"\pN")
+noparse!(fail_unicode_class_named, r"\p{Greek}")
+noparse!(fail_wide_hex, r"\x{100}")
+noparse!(fail_non_ascii_in_class, r"[é]")
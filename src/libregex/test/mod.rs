@@ -20,6 +20,10 @@ mod native_bench;
 #[path = "tests.rs"]
 mod native_tests;
 
+#[cfg(not(stage1))]
+#[path = "set.rs"]
+mod native_set;
+
 #[cfg(not(stage1))]
 mod native_static;
 
@@ -35,8 +39,21 @@ macro_rules! regex(
     );
 )
 
+macro_rules! regex_set(
+    ($($re:expr),+) => (
+        match ::regex::RegexSet::new(&[$($re),+]) {
+            Ok(set) => set,
+            Err(err) => panic!("{}", err),
+        }
+    );
+)
+
 #[path = "bench.rs"]
 mod dynamic_bench;
 #[path = "tests.rs"]
 mod dynamic_tests;
+#[path = "set.rs"]
+mod dynamic_set;
 
+mod bytes;
+mod stream;
@@ -8,7 +8,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use regex::Regex;
+use regex::{Regex, RegexSet};
 static RE: Regex = regex!(r"\d+");
 
 #[test]
@@ -24,3 +24,21 @@ fn static_split() {
     let subs: Vec<&str> = RE.split(text).collect();
     assert_eq!(subs, vec!("cauchy", "plato", "tyler", "binx"));
 }
+
+static SET: RegexSet = regex_set!(r"^\d+$", r"[a-z]+", r"\d");
+
+#[test]
+fn static_set() {
+    let matched: Vec<uint> = SET.matches("abc123").iter().collect();
+    assert_eq!(matched, vec![1, 2]);
+    assert!(SET.matches("123").matched(0));
+    assert!(!SET.is_match("ABC"));
+}
+
+#[test]
+fn native_find_iter_reader() {
+    use std::io::{BufferedReader, MemReader};
+    let rdr = BufferedReader::with_capacity(2, MemReader::new(b"a1b22c333".to_vec()));
+    let got: Vec<(u64, u64)> = RE.find_iter_reader(rdr).map(|m| m.unwrap()).collect();
+    assert_eq!(got, vec![(1, 2), (3, 5), (6, 9)]);
+}
@@ -0,0 +1,78 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use regex::RegexSet;
+
+macro_rules! set_mat(
+    ($name:ident, [$($re:expr),+], $text:expr, [$($i:expr),*]) => (
+        #[test]
+        fn $name() {
+            let set = regex_set!($($re),+);
+            let expected: Vec<uint> = vec![$($i),*];
+            let got: Vec<uint> = set.matches($text).iter().collect();
+            assert_eq!(got, expected);
+            assert_eq!(set.is_match($text), expected.len() > 0);
+            assert_eq!(set.matches($text).matched_any(), expected.len() > 0);
+        }
+    );
+)
+
+set_mat!(set1, ["a", "a"], "a", [0, 1])
+set_mat!(set2, ["a", "a"], "ba", [0, 1])
+set_mat!(set3, ["a", "b"], "a", [0])
+set_mat!(set4, ["a", "b"], "b", [1])
+set_mat!(set5, ["a|b", "b|a"], "b", [0, 1])
+set_mat!(set6, ["foo", "oo"], "foo", [0, 1])
+set_mat!(set7, ["^foo", "bar$"], "foo", [0])
+set_mat!(set8, ["^foo", "bar$"], "foo bar", [0, 1])
+set_mat!(set9, ["^foo", "bar$"], "bar", [1])
+set_mat!(set10, [r"[a-z]+$", "foo"], "01234 foo", [0, 1])
+set_mat!(set11, [r"[a-z]+$", "foo"], "foo 01234", [1])
+set_mat!(set12, [r".*?", "a"], "zzzzzza", [0, 1])
+set_mat!(set13, [r".*", "a"], "zzzzzza", [0, 1])
+set_mat!(set14, [r"\ba\b", r"\Ba\B"], "a ba", [0])
+set_mat!(set15, [r"(?i)ABC", r"(x)(y)?z"], "xzabc", [0, 1])
+set_mat!(set16, ["a", "b", "c"], "", [])
+set_mat!(set17, ["", "a"], "", [0])
+set_mat!(set18, ["a", "(?m)^$"], "x\n\ny", [1])
+
+#[test]
+fn set_len_and_patterns() {
+    let set = regex_set!("a", r"\d+", "c");
+    assert_eq!(set.len(), 3);
+    assert_eq!(set.pattern(1), r"\d+");
+    let matches = set.matches("c42");
+    assert_eq!(matches.len(), 3);
+    assert!(!matches.matched(0));
+    assert!(matches.matched(1));
+    assert!(matches.matched(2));
+}
+
+#[test]
+fn set_show() {
+    let set = regex_set!("a", "b+");
+    assert_eq!(set.to_string(), "[a, b+]".to_string());
+}
+
+#[test]
+fn set_empty() {
+    let set = RegexSet::new(&[]).unwrap();
+    assert_eq!(set.len(), 0);
+    assert!(!set.is_match("a"));
+    assert_eq!(set.matches("a").iter().count(), 0);
+}
+
+#[test]
+fn set_error_names_expression() {
+    match RegexSet::new(&["a", "(b"]) {
+        Ok(_) => panic!("expected a parse error"),
+        Err(err) => assert!(err.msg.as_slice().starts_with("In expression 1:")),
+    }
+}
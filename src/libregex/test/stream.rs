@@ -0,0 +1,99 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{BufferedReader, MemReader, InvalidInput};
+use regex::Regex;
+
+fn reader(text: &[u8], cap: uint) -> BufferedReader<MemReader> {
+    BufferedReader::with_capacity(cap, MemReader::new(text.to_vec()))
+}
+
+// Checks that searching the stream finds the same matches as `find_iter`,
+// no matter how the stream is split up into buffers.
+macro_rules! stream(
+    ($name:ident, $re:expr, $text:expr) => (
+        #[test]
+        fn $name() {
+            let re = match Regex::new($re) {
+                Ok(re) => re,
+                Err(err) => panic!("{}", err),
+            };
+            let text = $text;
+            let expected: Vec<(u64, u64)> = re.find_iter(text)
+                .map(|(s, e)| (s as u64, e as u64))
+                .collect();
+            for cap in range(1u, 9).chain(Some(1024u).into_iter()) {
+                let got: Vec<(u64, u64)> =
+                    re.find_iter_reader(reader(text.as_bytes(), cap))
+                      .map(|m| m.unwrap())
+                      .collect();
+                assert_eq!((cap, got), (cap, expected.clone()));
+            }
+        }
+    );
+)
+
+stream!(stream_literal, "abc", "xxabcxxabcabc")
+stream!(stream_plus, "a+", "baaaabaaaaaaaaab")
+stream!(stream_greedy_end, "a+", "baaaaaaaaa")
+stream!(stream_alternation_prefers_left, "abcd|c", "xxabcxxabcdxx")
+stream!(stream_dollar, r"a$", "aaa")
+stream!(stream_multi_dollar, r"(?m)\w+$", "foo bar\nbaz\nqux")
+stream!(stream_anchor, r"^a", "aaa")
+stream!(stream_multi_anchor, r"(?m)^\w", "ab\ncd\n\nef")
+stream!(stream_word_boundary, r"\b\w{3}\b", "abc abcd ab xyz")
+stream!(stream_word_boundary_split, r"=\bfoo", "a=foo b=foo")
+stream!(stream_not_word_boundary, r"\w\B", "ab cd e fgh")
+stream!(stream_empty, r"", "abc")
+stream!(stream_empty_after_match, r"a*", "baaab")
+stream!(stream_unicode, r"\pL+", "αβγ δεζ 123 ηθ")
+stream!(stream_dot_star, r"<.*>", "xx<aa>bb<cc>dd")
+stream!(stream_lazy, r"<.*?>", "xx<aa>bb<cc>dd")
+stream!(stream_no_match, r"zzz", "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaazz")
+
+#[test]
+fn stream_large() {
+    let re = Regex::new(r"needle\d+").unwrap();
+    let mut text = String::new();
+    for i in range(0u, 10000) {
+        text.push_str("hay ");
+        if i % 997 == 0 {
+            text.push_str(format!("needle{}", i).as_slice());
+        }
+    }
+    let expected: Vec<(u64, u64)> = re.find_iter(text.as_slice())
+        .map(|(s, e)| (s as u64, e as u64))
+        .collect();
+    let got: Vec<(u64, u64)> =
+        re.find_iter_reader(reader(text.as_bytes(), 100))
+          .map(|m| m.unwrap())
+          .collect();
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn stream_invalid_utf8() {
+    let re = Regex::new(r"a").unwrap();
+    let mut it = re.find_iter_reader(reader(b"ab\xFFa", 1));
+    assert_eq!(it.next().unwrap().unwrap(), (0, 1));
+    assert_eq!(it.next().unwrap().unwrap_err().kind, InvalidInput);
+    assert!(it.next().is_none());
+}
+
+#[test]
+fn stream_truncated_utf8() {
+    let re = Regex::new(r"a").unwrap();
+    let mut it = re.find_iter_reader(reader(b"a\xCE", 1));
+    match it.next() {
+        Some(Err(ref err)) if err.kind == InvalidInput => {}
+        r => panic!("expected an InvalidInput error, got {}", r),
+    }
+    assert!(it.next().is_none());
+}
@@ -43,6 +43,24 @@ fn empty_regex_nonempty_match() {
     assert_eq!(ms, vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
 }
 
+#[test]
+fn find_with_full_dfa_cache() {
+    // The DFA for this needs far more states than fit in its cache, so it
+    // gives up part way through and the NFA has to take over.
+    let re = regex!("[ab]*a[ab]{20}c");
+    let mut text = String::new();
+    let mut x = 1u32;
+    for _ in range(0u, 1 << 16) {
+        x = x * 1103515245 + 12345;
+        text.push(if x & 0x10000 == 0 { 'a' } else { 'b' });
+    }
+    text.push_str("a".repeat(21).as_slice());
+    text.push('c');
+    let expected = re.captures(text.as_slice()).map(|caps| caps.pos(0).unwrap());
+    assert_eq!(re.find(text.as_slice()), expected);
+    assert_eq!(expected, Some((0, text.len())));
+}
+
 #[test]
 fn quoted_bracket_set() {
     let re = regex!(r"([\x{5b}\x{5d}])");
@@ -164,6 +182,17 @@ macro_rules! mat(
                 panic!("For RE '{}' against '{}', expected '{}' but got '{}'",
                       $re, text, expected, sgot);
             }
+            // `find` and `is_match` don't need submatches, so they may take a
+            // different path through the matching engines. They must agree.
+            let found = r.find(text);
+            if found != expected[0] {
+                panic!("For RE '{}' against '{}', find expected '{}' but got '{}'",
+                      $re, text, expected[0], found);
+            }
+            if r.is_match(text) != expected[0].is_some() {
+                panic!("For RE '{}' against '{}', is_match expected '{}'",
+                      $re, text, expected[0].is_some());
+            }
         }
     );
 )
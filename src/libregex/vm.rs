@@ -8,8 +8,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-// FIXME: Currently, the VM simulates an NFA. It would be nice to have another
-// VM that simulates a DFA.
+// The VM here simulates an NFA. A lazily built DFA, which is used when only
+// the existence or the extent of a match is needed, lives in dfa.rs.
 //
 // According to Russ Cox[1], a DFA performs better than an NFA, principally
 // because it reuses states previously computed by the machine *and* doesn't
@@ -24,10 +24,10 @@
 // 1) quickly answer "no" is there's no match and 2) discover the substring
 // that matches, which means running the NFA on smaller input.
 //
-// Currently, the NFA simulation implemented below does some dirty tricks to
-// avoid tracking capture groups when they aren't needed (which only works
-// for 'is_match', not 'find'). This is a half-measure, but does provide some
-// perf improvement.
+// Currently, the DFA finds the end of a match and a second DFA, run backwards
+// from there, finds where it starts. The NFA below is only needed for capture
+// groups (or when the DFAs give up). It also does some dirty tricks to avoid
+// tracking capture groups when they aren't needed.
 //
 // AFAIK, the DFA/NFA approach is implemented in RE2/C++ but *not* in RE2/Go.
 //
@@ -35,6 +35,7 @@
 
 pub use self::MatchKind::*;
 pub use self::StepState::*;
+pub use self::Partial::*;
 
 use std::cmp;
 use std::mem;
@@ -76,7 +77,7 @@ pub fn run<'r, 't>(which: MatchKind, prog: &'r Program, input: &'t str,
     Nfa {
         which: which,
         prog: prog,
-        input: input,
+        input: input.as_bytes(),
         start: start,
         end: end,
         ic: 0,
@@ -84,14 +85,113 @@ pub fn run<'r, 't>(which: MatchKind, prog: &'r Program, input: &'t str,
     }.run()
 }
 
-struct Nfa<'r, 't> {
+/// Runs an NFA simulation exactly like `run`, except that `input` is a
+/// sequence of raw bytes rather than UTF-8 encoded text. Each byte is
+/// treated as the character with the same value, and case folding and word
+/// boundaries only consider ASCII.
+pub fn run_bytes<'r, 't>(which: MatchKind, prog: &'r Program, input: &'t [u8],
+                         start: uint, end: uint) -> CaptureLocs {
+    Nfa {
+        which: which,
+        prog: prog,
+        input: input,
+        start: start,
+        end: end,
+        ic: 0,
+        chars: ByteReader::new(input),
+    }.run()
+}
+
+/// Runs an NFA simulation of a program compiled from a set of expressions
+/// (see `Program::new_set`) and returns which of the expressions match
+/// `input`. `pats` maps each `Match` instruction to its expression.
+///
+/// The search text is scanned at most once. If `which` is `Exists`, then the
+/// search stops as soon as any expression matches; otherwise it stops once
+/// every expression has matched or the input is exhausted.
+pub fn run_set<'r, 't>(which: MatchKind, prog: &'r Program,
+                       pats: &[Option<uint>], input: &'t str) -> Vec<bool> {
+    let mut matched = Vec::from_elem(pats.iter().filter(|p| p.is_some())
+                                                .count(), false);
+    Nfa {
+        which: Exists,
+        prog: prog,
+        input: input.as_bytes(),
+        start: 0,
+        end: input.len(),
+        ic: 0,
+        chars: CharReader::new(input),
+    }.run_set(which, pats, matched.as_mut_slice());
+    matched
+}
+
+/// The result of searching text that may be followed by more input.
+pub enum Partial {
+    /// The location of the leftmost-first match, which more input can't
+    /// change.
+    Done(CaptureLocs),
+    /// More input is needed to decide. No match can start before the byte
+    /// index given.
+    NeedMore(uint),
+}
+
+/// Finds the location of the leftmost-first match in `input`, starting at
+/// byte index `start`, when `input` is only a prefix of the text being
+/// searched (e.g., the contents of a buffer read from a stream).
+///
+/// A match is only reported if no amount of further input could change it.
+/// In particular, a match that ends at the end of `input` is never reported,
+/// since `$` and `\b` can't be decided there.
+pub fn run_partial<'r, 't>(prog: &'r Program, input: &'t str,
+                           start: uint) -> Partial {
+    Nfa {
+        which: Location,
+        prog: prog,
+        input: input.as_bytes(),
+        start: start,
+        end: input.len(),
+        ic: 0,
+        chars: CharReader::new(input),
+    }.run_partial()
+}
+
+struct Nfa<'r, 't, I> {
     which: MatchKind,
     prog: &'r Program,
-    input: &'t str,
+    input: &'t [u8],
     start: uint,
     end: uint,
     ic: uint,
-    chars: CharReader<'t>,
+    chars: I,
+}
+
+/// Input abstracts over the unit of text that the VM steps over. It is
+/// implemented by `CharReader` for Unicode text and by `ByteReader` for raw
+/// bytes.
+pub trait Input {
+    /// Moves to the byte index given and returns the index of the next
+    /// unit of input. See `CharReader::set`.
+    fn set(&mut self, ic: uint) -> uint;
+    /// Moves to the next unit of input and returns the index of the one
+    /// after it. See `CharReader::advance`.
+    fn advance(&mut self) -> uint;
+    /// The unit of input before the current position.
+    fn prev(&self) -> Option<char>;
+    /// The unit of input at the current position.
+    fn cur(&self) -> Option<char>;
+    /// Returns true if and only if the current position is a word boundary.
+    fn is_word_boundary(&self) -> bool;
+    /// Returns true if the unit of input given is a word character.
+    fn is_word(&self, c: Option<char>) -> bool;
+    /// Returns the length in bytes of the unit of input given.
+    fn unit_len(&self, c: char) -> uint;
+    /// Maps a character to the form used in case insensitive comparisons.
+    fn fold(&self, c: char) -> char;
+
+    /// Returns true if and only if this is the beginning of the input.
+    fn is_begin(&self) -> bool { self.prev().is_none() }
+    /// Returns true if and only if this is the end of the input.
+    fn is_end(&self) -> bool { self.cur().is_none() }
 }
 
 /// Indicates the next action to take after a single non-empty instruction
@@ -111,7 +211,7 @@ pub enum StepState {
 
 impl Copy for StepState {}
 
-impl<'r, 't> Nfa<'r, 't> {
+impl<'r, 't, I: Input> Nfa<'r, 't, I> {
     fn run(&mut self) -> CaptureLocs {
         let ncaps = match self.which {
             Exists => 0,
@@ -152,7 +252,7 @@ impl<'r, 't> Nfa<'r, 't> {
                 // out early.
                 if self.prog.prefix.len() > 0 && clist.size == 0 {
                     let needle = self.prog.prefix.as_bytes();
-                    let haystack = self.input.as_bytes()[self.ic..];
+                    let haystack = self.input[self.ic..];
                     match find_prefix(needle, haystack) {
                         None => break,
                         Some(i) => {
@@ -196,6 +296,150 @@ impl<'r, 't> Nfa<'r, 't> {
         }
     }
 
+    // This is like `run` with `Location`, except that it stops when it gets
+    // to the end of the input (instead of stepping past it) and looks at the
+    // threads that are still alive. Any of them might still lead to a match
+    // that is preferred over the one found so far.
+    fn run_partial(&mut self) -> Partial {
+        let ninsts = self.prog.insts.len();
+        let mut clist = &mut Threads::new(Location, ninsts, 1);
+        let mut nlist = &mut Threads::new(Location, ninsts, 1);
+        let mut groups = [None, None];
+        let mut matched = false;
+        let prefix_anchor =
+            match self.prog.insts[1] {
+                EmptyBegin(flags) if flags & FLAG_MULTI == 0 => true,
+                _ => false,
+            };
+
+        self.ic = self.start;
+        let mut next_ic = self.chars.set(self.start);
+        loop {
+            if clist.size == 0 {
+                if matched {
+                    return Done(groups.to_vec())
+                }
+                if self.prog.prefix.len() > 0 {
+                    let needle = self.prog.prefix.as_bytes();
+                    let haystack = self.input[self.ic..];
+                    match find_prefix(needle, haystack) {
+                        None => {
+                            // The prefix may still start in the last few
+                            // bytes of the input.
+                            let tail = cmp::min(haystack.len(), needle.len() - 1);
+                            return NeedMore(self.char_start(self.end - tail))
+                        }
+                        Some(i) => {
+                            self.ic += i;
+                            next_ic = self.chars.set(self.ic);
+                        }
+                    }
+                }
+            }
+            if clist.size == 0 || (!prefix_anchor && !matched) {
+                self.add(clist, 0, &mut groups)
+            }
+
+            if self.ic == self.end {
+                break
+            }
+            self.ic = next_ic;
+            next_ic = self.chars.advance();
+
+            for i in range(0, clist.size) {
+                let pc = clist.pc(i);
+                match self.step(&mut groups, nlist, clist.groups(i), pc) {
+                    StepMatch => { matched = true; break },
+                    StepMatchEarlyReturn | StepContinue => {},
+                }
+            }
+            mem::swap(&mut clist, &mut nlist);
+            nlist.empty();
+        }
+
+        // Every thread in `clist` that is waiting for a character, that
+        // matches right at the end of the input, or that is stopped at a `$`
+        // or `\b` which can't be decided until the next character is known,
+        // is preferred over the match found so far (lower priority threads
+        // were dropped when it was found). So the search has to resume at
+        // the earliest of them.
+        let mut keep = self.end;
+        let mut alive = false;
+        for i in range(0, clist.size) {
+            let pc = clist.pc(i);
+            match self.prog.insts[pc] {
+                Match | OneChar(_, _) | CharClass(_, _) | Any(_)
+                | EmptyEnd(_) | EmptyWordBoundary(_) => {
+                    alive = true;
+                    match clist.groups(i)[0] {
+                        Some(s) => keep = cmp::min(keep, s),
+                        None => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        match (matched, alive) {
+            (true, false) => Done(groups.to_vec()),
+            (true, true) => NeedMore(cmp::min(keep, groups[0].unwrap())),
+            (false, _) => NeedMore(keep),
+        }
+    }
+
+    // Returns the start of the character that contains the byte at `i`.
+    fn char_start(&self, mut i: uint) -> uint {
+        while i > 0 && i < self.input.len() && self.input[i] & 0xC0 == 0x80 {
+            i -= 1;
+        }
+        i
+    }
+
+    // This is like `run`, except that there is no leftmost-first priority
+    // between threads: a thread that reaches the `Match` instruction of one
+    // expression doesn't cut off the threads of the others, and a new thread
+    // is started at every position regardless of what has matched so far.
+    fn run_set(&mut self, which: MatchKind, pats: &[Option<uint>],
+               matched: &mut [bool]) {
+        let ninsts = self.prog.insts.len();
+        let mut clist = &mut Threads::new(Exists, ninsts, 0);
+        let mut nlist = &mut Threads::new(Exists, ninsts, 0);
+        let mut groups: [Option<uint>, ..0] = [];
+        let mut nmatched = 0u;
+
+        self.ic = self.start;
+        let mut next_ic = self.chars.set(self.start);
+        while self.ic <= self.end {
+            self.add(clist, 0, &mut groups);
+
+            self.ic = next_ic;
+            next_ic = self.chars.advance();
+
+            for i in range(0, clist.size) {
+                let pc = clist.pc(i);
+                match pats[pc] {
+                    Some(pat) => {
+                        if !matched[pat] {
+                            matched[pat] = true;
+                            nmatched += 1;
+                        }
+                    }
+                    None => {
+                        self.step(&mut groups, nlist, clist.groups(i), pc);
+                    }
+                }
+            }
+            if nmatched == matched.len() {
+                break
+            }
+            match which {
+                Exists if nmatched > 0 => break,
+                _ => {}
+            }
+            mem::swap(&mut clist, &mut nlist);
+            nlist.empty();
+        }
+    }
+
     fn step(&self, groups: &mut [Option<uint>], nlist: &mut Threads,
             caps: &mut [Option<uint>], pc: uint)
            -> StepState {
@@ -219,18 +463,19 @@ impl<'r, 't> Nfa<'r, 't> {
                 }
             }
             OneChar(c, flags) => {
-                if self.char_eq(flags & FLAG_NOCASE > 0, self.chars.prev, c) {
+                if self.char_eq(flags & FLAG_NOCASE > 0, self.chars.prev(), c) {
                     self.add(nlist, pc+1, caps);
                 }
             }
             CharClass(ref ranges, flags) => {
-                if self.chars.prev.is_some() {
-                    let c = self.chars.prev.unwrap();
+                if self.chars.prev().is_some() {
+                    let c = self.chars.prev().unwrap();
                     let negate = flags & FLAG_NEGATED > 0;
                     let casei = flags & FLAG_NOCASE > 0;
                     let found = ranges.as_slice();
-                    let found = found.binary_search(|&rc| class_cmp(casei, c, rc))
-                        .found().is_some();
+                    let found = found.binary_search(|&rc| {
+                        class_cmp(&self.chars, casei, c, rc)
+                    }).found().is_some();
                     if found ^ negate {
                         self.add(nlist, pc+1, caps);
                     }
@@ -238,7 +483,7 @@ impl<'r, 't> Nfa<'r, 't> {
             }
             Any(flags) => {
                 if flags & FLAG_DOTNL > 0
-                   || !self.char_eq(false, self.chars.prev, '\n') {
+                   || !self.char_eq(false, self.chars.prev(), '\n') {
                     self.add(nlist, pc+1, caps)
                 }
             }
@@ -271,20 +516,22 @@ impl<'r, 't> Nfa<'r, 't> {
                 let multi = flags & FLAG_MULTI > 0;
                 nlist.add(pc, groups, true);
                 if self.chars.is_begin()
-                   || (multi && self.char_is(self.chars.prev, '\n')) {
+                   || (multi && self.char_is(self.chars.prev(), '\n')) {
                     self.add(nlist, pc + 1, groups)
                 }
             }
             EmptyEnd(flags) => {
                 let multi = flags & FLAG_MULTI > 0;
-                nlist.add(pc, groups, true);
+                // At the end of the input, the groups are kept so that
+                // `run_partial` knows where the thread started.
+                nlist.add(pc, groups, self.ic < self.end);
                 if self.chars.is_end()
-                   || (multi && self.char_is(self.chars.cur, '\n')) {
+                   || (multi && self.char_is(self.chars.cur(), '\n')) {
                     self.add(nlist, pc + 1, groups)
                 }
             }
             EmptyWordBoundary(flags) => {
-                nlist.add(pc, groups, true);
+                nlist.add(pc, groups, self.ic < self.end);
                 if self.chars.is_word_boundary() == !(flags & FLAG_NEGATED > 0) {
                     self.add(nlist, pc + 1, groups)
                 }
@@ -322,18 +569,13 @@ impl<'r, 't> Nfa<'r, 't> {
         }
     }
 
-    // FIXME: For case insensitive comparisons, it uses the uppercase
-    // character and tests for equality. IIUC, this does not generalize to
-    // all of Unicode. I believe we need to check the entire fold for each
-    // character. This will be easy to add if and when it gets added to Rust's
-    // standard library.
     #[inline]
     fn char_eq(&self, casei: bool, textc: Option<char>, regc: char) -> bool {
         match textc {
             None => false,
             Some(textc) => {
                 regc == textc
-                    || (casei && regc.to_uppercase() == textc.to_uppercase())
+                    || (casei && self.chars.fold(regc) == self.chars.fold(textc))
             }
         }
     }
@@ -436,6 +678,111 @@ impl<'t> CharReader<'t> {
     }
 }
 
+impl<'t> Input for CharReader<'t> {
+    fn set(&mut self, ic: uint) -> uint { self.set(ic) }
+    fn advance(&mut self) -> uint { self.advance() }
+    fn prev(&self) -> Option<char> { self.prev }
+    fn cur(&self) -> Option<char> { self.cur }
+    fn is_word_boundary(&self) -> bool { self.is_word_boundary() }
+    fn is_word(&self, c: Option<char>) -> bool { is_word(c) }
+    fn unit_len(&self, c: char) -> uint { c.len_utf8() }
+
+    // FIXME: For case insensitive comparisons, it uses the uppercase
+    // character and tests for equality. IIUC, this does not generalize to
+    // all of Unicode. I believe we need to check the entire fold for each
+    // character. This will be easy to add if and when it gets added to Rust's
+    // standard library.
+    #[inline]
+    fn fold(&self, c: char) -> char { c.to_uppercase() }
+}
+
+/// ByteReader is the counterpart of `CharReader` for searching raw bytes.
+/// Each byte is presented to the VM as the character with the same value,
+/// so that `OneChar` and `CharClass` instructions compare byte values.
+pub struct ByteReader<'t> {
+    /// The previous byte read. It is None only when processing the first
+    /// byte of the input.
+    pub prev: Option<char>,
+    /// The current byte.
+    pub cur: Option<char>,
+    input: &'t [u8],
+    next: uint,
+}
+
+impl<'t> ByteReader<'t> {
+    /// Returns a new ByteReader that advances through the input given.
+    pub fn new(input: &'t [u8]) -> ByteReader<'t> {
+        ByteReader {
+            prev: None,
+            cur: None,
+            input: input,
+            next: 0,
+        }
+    }
+}
+
+impl<'t> Input for ByteReader<'t> {
+    #[inline]
+    fn set(&mut self, ic: uint) -> uint {
+        self.prev = None;
+        self.cur = None;
+        self.next = 0;
+
+        if self.input.len() == 0 {
+            return 1
+        }
+        if ic > 0 {
+            let i = cmp::min(ic, self.input.len());
+            self.prev = Some(self.input[i - 1] as char);
+        }
+        if ic < self.input.len() {
+            self.cur = Some(self.input[ic] as char);
+            self.next = ic + 1;
+            self.next
+        } else {
+            self.input.len() + 1
+        }
+    }
+
+    #[inline]
+    fn advance(&mut self) -> uint {
+        self.prev = self.cur;
+        if self.next < self.input.len() {
+            self.cur = Some(self.input[self.next] as char);
+            self.next += 1;
+        } else {
+            self.cur = None;
+            self.next = self.input.len() + 1;
+        }
+        self.next
+    }
+
+    #[inline]
+    fn prev(&self) -> Option<char> { self.prev }
+
+    #[inline]
+    fn cur(&self) -> Option<char> { self.cur }
+
+    fn is_word_boundary(&self) -> bool {
+        is_ascii_word(self.prev) != is_ascii_word(self.cur)
+    }
+
+    #[inline]
+    fn is_word(&self, c: Option<char>) -> bool { is_ascii_word(c) }
+
+    #[inline]
+    fn unit_len(&self, _: char) -> uint { 1 }
+
+    #[inline]
+    fn fold(&self, c: char) -> char {
+        if c >= 'a' && c <= 'z' {
+            ((c as u8) - b'a' + b'A') as char
+        } else {
+            c
+        }
+    }
+}
+
 struct Thread {
     pc: uint,
     groups: Vec<Option<uint>>,
@@ -532,16 +879,26 @@ pub fn is_word(c: Option<char>) -> bool {
     }
 }
 
+/// Returns true if the byte is an ASCII word character (`[0-9A-Za-z_]`).
+/// This is what `\b` uses when matching bytes.
+fn is_ascii_word(c: Option<char>) -> bool {
+    match c {
+        Some('_') | Some('0' ... '9') | Some('a' ... 'z') | Some('A' ... 'Z') => true,
+        _ => false,
+    }
+}
+
 /// Given a character and a single character class range, return an ordering
 /// indicating whether the character is less than the start of the range,
 /// in the range (inclusive) or greater than the end of the range.
 ///
-/// If `casei` is `true`, then this ordering is computed case insensitively.
+/// If `casei` is `true`, then this ordering is computed case insensitively
+/// using the case folding of `input`.
 ///
 /// This function is meant to be used with a binary search.
 #[inline]
-fn class_cmp(casei: bool, mut textc: char,
-             (mut start, mut end): (char, char)) -> Ordering {
+pub fn class_cmp<I: Input>(input: &I, casei: bool, mut textc: char,
+                       (mut start, mut end): (char, char)) -> Ordering {
     if casei {
         // FIXME: This is pretty ridiculous. All of this case conversion
         // can be moved outside this function:
@@ -550,9 +907,9 @@ fn class_cmp(casei: bool, mut textc: char,
         //    parser or the compiler.
         // FIXME: This is too simplistic for correct Unicode support.
         //        See also: char_eq
-        textc = textc.to_uppercase();
-        start = start.to_uppercase();
-        end = end.to_uppercase();
+        textc = input.fold(textc);
+        start = input.fold(start);
+        end = input.fold(end);
     }
     if textc >= start && textc <= end {
         Equal
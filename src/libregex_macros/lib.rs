@@ -8,8 +8,8 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! This crate provides the `regex!` macro. Its use is documented in the
-//! `regex` crate.
+//! This crate provides the `regex!` and `regex_set!` macros. Their use is
+//! documented in the `regex` crate.
 
 #![crate_name = "regex_macros"]
 #![crate_type = "dylib"]
@@ -38,19 +38,21 @@ use syntax::ptr::P;
 
 use rustc::plugin::Registry;
 
-use regex::Regex;
+use regex::{Regex, RegexSet};
 use regex::native::{
     OneChar, CharClass, Any, Save, Jump, Split,
     Match, EmptyBegin, EmptyEnd, EmptyWordBoundary,
     Program, Dynamic, ExDynamic, Native,
+    DynamicSet, ExDynamicSet, NativeSet,
     FLAG_NOCASE, FLAG_MULTI, FLAG_DOTNL, FLAG_NEGATED,
 };
 
-/// For the `regex!` syntax extension. Do not use.
+/// For the `regex!` and `regex_set!` syntax extensions. Do not use.
 #[plugin_registrar]
 #[doc(hidden)]
 pub fn plugin_registrar(reg: &mut Registry) {
     reg.register_macro("regex", native);
+    reg.register_macro("regex_set", native_set);
 }
 
 /// Generates specialized code for the Pike VM for a particular regular
@@ -98,6 +100,46 @@ fn native(cx: &mut ExtCtxt, sp: codemap::Span, tts: &[ast::TokenTree])
     let mut gen = NfaGen {
         cx: &*cx, sp: sp, prog: prog,
         names: re.names_iter().collect(), original: re.as_str().to_string(),
+        set: None,
+    };
+    MacExpr::new(gen.code())
+}
+
+/// Generates specialized code for a set of regular expressions, which are
+/// given as a comma separated list of string literals.
+///
+/// The expressions are compiled into one program (see `Program::new_set`) and
+/// the code is generated exactly as for `regex!`, except for the main loop of
+/// the VM, which mirrors `run_set` in vm.rs.
+#[allow(experimental)]
+fn native_set(cx: &mut ExtCtxt, sp: codemap::Span, tts: &[ast::TokenTree])
+              -> Box<MacResult+'static> {
+    let regexes = match parse_set(cx, sp, tts) {
+        Some(rs) => rs,
+        // error is logged in 'parse_set' with cx.span_err
+        None => return DummyResult::any(sp),
+    };
+    let set = {
+        let strs: Vec<&str> = regexes.iter().map(|r| r.as_slice()).collect();
+        match RegexSet::new(strs.as_slice()) {
+            Ok(set) => set,
+            Err(err) => {
+                cx.span_err(sp, err.to_string().as_slice());
+                return DummyResult::any(sp)
+            }
+        }
+    };
+    let (prog, pats) = match set {
+        DynamicSet(ExDynamicSet { ref prog, ref pats, .. }) => {
+            (prog.clone(), pats.clone())
+        }
+        NativeSet(_) => unreachable!(),
+    };
+
+    let mut gen = NfaGen {
+        cx: &*cx, sp: sp, prog: prog,
+        names: vec![], original: String::new(),
+        set: Some(SetGen { pats: pats, originals: regexes }),
     };
     MacExpr::new(gen.code())
 }
@@ -108,6 +150,14 @@ struct NfaGen<'a> {
     prog: Program,
     names: Vec<Option<String>>,
     original: String,
+    // Only present when generating code for `regex_set!`.
+    set: Option<SetGen>,
+}
+
+struct SetGen {
+    // Maps each instruction to the expression whose `Match` it is, if any.
+    pats: Vec<Option<uint>>,
+    originals: Vec<String>,
 }
 
 impl<'a> NfaGen<'a> {
@@ -139,7 +189,39 @@ impl<'a> NfaGen<'a> {
         let check_prefix = self.check_prefix();
         let step_insts = self.step_insts();
         let add_insts = self.add_insts();
-        let regex = self.original.as_slice();
+        let run_body = match self.set {
+            None => self.run_body(prefix_anchor, init_groups, check_prefix),
+            Some(ref set) => self.run_set_body(set, init_groups),
+        };
+        let patterns = match self.set {
+            None => self.vec_expr(Some(self.original.as_slice()).into_iter(),
+                                  |cx, re| quote_expr!(cx, $re)),
+            Some(ref set) => self.vec_expr(set.originals.iter(),
+                                           |cx, re| {
+                                               let re = re.as_slice();
+                                               quote_expr!(cx, $re)
+                                           }),
+        };
+        let result = match self.set {
+            None => {
+                let regex = self.original.as_slice();
+                quote_expr!(self.cx,
+                    ::regex::native::Native(::regex::native::ExNative {
+                        original: $regex,
+                        names: &CAP_NAMES,
+                        prog: exec,
+                    })
+                )
+            }
+            Some(_) => {
+                quote_expr!(self.cx,
+                    ::regex::native::NativeSet(::regex::native::ExNativeSet {
+                        originals: &PATTERNS,
+                        prog: exec,
+                    })
+                )
+            }
+        };
 
         quote_expr!(self.cx, {
 // When `regex!` is bound to a name that is not used, we have to make sure
@@ -150,6 +232,9 @@ impl<'a> NfaGen<'a> {
 #[allow(dead_code)]
 static CAP_NAMES: &'static [Option<&'static str>] = &$cap_names;
 
+#[allow(dead_code)]
+static PATTERNS: &'static [&'static str] = &$patterns;
+
 #[allow(dead_code)]
 fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
             start: uint, end: uint) -> Vec<Option<uint>> {
@@ -181,48 +266,8 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
     impl<'t> Nfa<'t> {
         #[allow(unused_variables)]
         fn run(&mut self, start: uint, end: uint) -> Vec<Option<uint>> {
-            let mut matched = false;
             let prefix_bytes: &[u8] = $prefix_bytes;
-            let mut clist = &mut Threads::new(self.which);
-            let mut nlist = &mut Threads::new(self.which);
-
-            let mut groups = $init_groups;
-
-            self.ic = start;
-            let mut next_ic = self.chars.set(start);
-            while self.ic <= end {
-                if clist.size == 0 {
-                    if matched {
-                        break
-                    }
-                    $check_prefix
-                }
-                if clist.size == 0 || (!$prefix_anchor && !matched) {
-                    self.add(clist, 0, &mut groups)
-                }
-
-                self.ic = next_ic;
-                next_ic = self.chars.advance();
-
-                for i in range(0, clist.size) {
-                    let pc = clist.pc(i);
-                    let step_state = self.step(&mut groups, nlist,
-                                               clist.groups(i), pc);
-                    match step_state {
-                        StepMatchEarlyReturn =>
-                            return vec![Some(0u), Some(0u)],
-                        StepMatch => { matched = true; break },
-                        StepContinue => {},
-                    }
-                }
-                ::std::mem::swap(&mut clist, &mut nlist);
-                nlist.empty();
-            }
-            match self.which {
-                Exists if matched     => vec![Some(0u), Some(0u)],
-                Exists                => vec![None, None],
-                Location | Submatches => groups.iter().map(|x| *x).collect(),
-            }
+            $run_body
         }
 
         // Sometimes `nlist` is never used (for empty regexes).
@@ -322,11 +367,117 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
     }
 }
 
-::regex::native::Native(::regex::native::ExNative {
-    original: $regex,
-    names: &CAP_NAMES,
-    prog: exec,
-})
+$result
+        })
+    }
+
+    // Generates the body of the `run` method, which is the main loop of the
+    // VM for a single regular expression.
+    fn run_body(&self, prefix_anchor: bool, init_groups: P<ast::Expr>,
+                check_prefix: P<ast::Expr>) -> P<ast::Expr> {
+        quote_expr!(self.cx, {
+            let mut matched = false;
+            let mut clist = &mut Threads::new(self.which);
+            let mut nlist = &mut Threads::new(self.which);
+
+            let mut groups = $init_groups;
+
+            self.ic = start;
+            let mut next_ic = self.chars.set(start);
+            while self.ic <= end {
+                if clist.size == 0 {
+                    if matched {
+                        break
+                    }
+                    $check_prefix
+                }
+                if clist.size == 0 || (!$prefix_anchor && !matched) {
+                    self.add(clist, 0, &mut groups)
+                }
+
+                self.ic = next_ic;
+                next_ic = self.chars.advance();
+
+                for i in range(0, clist.size) {
+                    let pc = clist.pc(i);
+                    let step_state = self.step(&mut groups, nlist,
+                                               clist.groups(i), pc);
+                    match step_state {
+                        StepMatchEarlyReturn =>
+                            return vec![Some(0u), Some(0u)],
+                        StepMatch => { matched = true; break },
+                        StepContinue => {},
+                    }
+                }
+                ::std::mem::swap(&mut clist, &mut nlist);
+                nlist.empty();
+            }
+            match self.which {
+                Exists if matched     => vec![Some(0u), Some(0u)],
+                Exists                => vec![None, None],
+                Location | Submatches => groups.iter().map(|x| *x).collect(),
+            }
+        })
+    }
+
+    // Generates the body of the `run` method for a set of expressions. It
+    // returns one element per expression, which is `Some(0)` if and only if
+    // that expression matched. Threads that reach a `Match` instruction are
+    // recorded here instead of in `step`, so that no thread is ever cut off.
+    fn run_set_body(&self, set: &SetGen, init_groups: P<ast::Expr>)
+                   -> P<ast::Expr> {
+        let num_pats = set.originals.len();
+        let mut arms = set.pats.iter().enumerate().filter_map(|(pc, pat)| {
+            pat.map(|pat| {
+                self.arm_inst(pc, quote_expr!(self.cx, Some($pat)))
+            })
+        }).collect::<Vec<ast::Arm>>();
+        arms.push(self.wild_arm_expr(quote_expr!(self.cx, None)));
+        let match_pat = self.cx.expr_match(self.sp, quote_expr!(self.cx, pc),
+                                           arms);
+
+        quote_expr!(self.cx, {
+            let mut matched = [false, ..$num_pats];
+            let mut nmatched = 0u;
+            let mut clist = &mut Threads::new(Exists);
+            let mut nlist = &mut Threads::new(Exists);
+
+            let mut groups = $init_groups;
+
+            self.ic = start;
+            let mut next_ic = self.chars.set(start);
+            while self.ic <= end {
+                self.add(clist, 0, &mut groups);
+
+                self.ic = next_ic;
+                next_ic = self.chars.advance();
+
+                for i in range(0, clist.size) {
+                    let pc = clist.pc(i);
+                    let pat: Option<uint> = $match_pat;
+                    match pat {
+                        Some(pat) => {
+                            if !matched[pat] {
+                                matched[pat] = true;
+                                nmatched += 1;
+                            }
+                        }
+                        None => {
+                            self.step(&mut groups, nlist, clist.groups(i), pc);
+                        }
+                    }
+                }
+                if nmatched == $num_pats {
+                    break
+                }
+                match self.which {
+                    Exists if nmatched > 0 => break,
+                    _ => {}
+                }
+                ::std::mem::swap(&mut clist, &mut nlist);
+                nlist.empty();
+            }
+            matched.iter().map(|&m| if m { Some(0u) } else { None }).collect()
         })
     }
 
@@ -616,6 +767,44 @@ fn exec<'t>(which: ::regex::native::MatchKind, input: &'t str,
 fn parse(cx: &mut ExtCtxt, tts: &[ast::TokenTree]) -> Option<String> {
     let mut parser = cx.new_parser_from_tts(tts);
     let entry = cx.expander().fold_expr(parser.parse_expr());
+    let regex = match str_lit(cx, &*entry) {
+        Some(regex) => regex,
+        None => return None,
+    };
+    if !parser.eat(&token::Eof) {
+        cx.span_err(parser.span, "only one string literal allowed");
+        return None;
+    }
+    Some(regex)
+}
+
+/// Looks for a non-empty, comma separated list of string literals and
+/// returns them. Otherwise, logs an error with cx.span_err and returns None.
+fn parse_set(cx: &mut ExtCtxt, sp: codemap::Span, tts: &[ast::TokenTree])
+            -> Option<Vec<String>> {
+    let mut parser = cx.new_parser_from_tts(tts);
+    let mut regexes = vec![];
+    while parser.token != token::Eof {
+        let entry = cx.expander().fold_expr(parser.parse_expr());
+        match str_lit(cx, &*entry) {
+            Some(regex) => regexes.push(regex),
+            None => return None,
+        }
+        if !parser.eat(&token::Comma) && parser.token != token::Eof {
+            cx.span_err(parser.span, "expected `,` after string literal");
+            return None;
+        }
+    }
+    if regexes.len() == 0 {
+        cx.span_err(sp, "at least one string literal is required");
+        return None;
+    }
+    Some(regexes)
+}
+
+/// Returns the string in a string literal expression.
+/// Otherwise, logs an error with cx.span_err and returns None.
+fn str_lit(cx: &mut ExtCtxt, entry: &ast::Expr) -> Option<String> {
     let regex = match entry.node {
         ast::ExprLit(ref lit) => {
             match lit.node {
@@ -631,13 +820,9 @@ fn parse(cx: &mut ExtCtxt, tts: &[ast::TokenTree]) -> Option<String> {
         _ => {
             cx.span_err(entry.span, format!(
                 "expected string literal but got `{}`",
-                pprust::expr_to_string(&*entry)).as_slice());
+                pprust::expr_to_string(entry)).as_slice());
             return None
         }
     };
-    if !parser.eat(&token::Eof) {
-        cx.span_err(parser.span, "only one string literal allowed");
-        return None;
-    }
     Some(regex)
 }
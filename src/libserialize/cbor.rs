@@ -0,0 +1,1226 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#![forbid(non_camel_case_types)]
+
+//! CBOR serialization
+//!
+//! CBOR (Concise Binary Object Representation, RFC 7049) is a binary data
+//! format with the same data model as JSON, plus byte strings and a few
+//! other extensions. It is much more compact and faster to read and write
+//! than JSON.
+//!
+//! The `Encoder` and `Decoder` here implement the `serialize` traits, so any
+//! type that implements `Encodable` and `Decodable` (e.g., with
+//! `#[deriving(Encodable, Decodable)]`) can be written and read as CBOR.
+//!
+//! Rust values are represented as follows:
+//!
+//! * Integers, booleans, floats and strings map to the corresponding CBOR
+//!   types. A `char` is a one character text string.
+//! * `()` and `None` are `null`. `Some(x)` is just `x`, unless `x` is itself
+//!   written as `null` (e.g., `Some(None)` or `Some(())`); then it is tagged
+//!   with the tag `SOME_TAG`, so that it doesn't decode as `None`.
+//! * Vectors and tuples are arrays, and maps are maps.
+//! * A struct is a map from field names to values. When decoding, fields can
+//!   be in any order, and a missing field is decoded as `null` (so missing
+//!   `Option` fields are `None`).
+//! * A unit enum variant is its name as a text string. A variant with
+//!   arguments is an array of its name followed by its arguments.
+//!
+//! The decoder accepts any well-formed CBOR that fits this model, including
+//! indefinite-length items and half precision floats. Semantic tags are
+//! ignored.
+//!
+//! # Example
+//!
+//! ```rust
+//! extern crate serialize;
+//! use serialize::cbor;
+//!
+//! #[deriving(Decodable, Encodable, PartialEq, Show)]
+//! pub struct Point {
+//!     x: i32,
+//!     y: i32,
+//!     label: Option<String>,
+//! }
+//!
+//! fn main() {
+//!     let point = Point { x: 1, y: -2, label: None };
+//!     let bytes: Vec<u8> = cbor::encode(&point);
+//!     let decoded: Point = cbor::decode(bytes.as_slice()).unwrap();
+//!     assert_eq!(decoded, point);
+//! }
+//! ```
+
+use self::DecoderError::*;
+
+use std::{i64, io, num};
+use std::mem::{replace, transmute};
+use std::num::Float;
+use std::string;
+
+use Encodable;
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+
+const FALSE: u8 = 0xf4;
+const TRUE: u8 = 0xf5;
+const NULL: u8 = 0xf6;
+const UNDEFINED: u8 = 0xf7;
+const FLOAT16: u8 = 0xf9;
+const FLOAT32: u8 = 0xfa;
+const FLOAT64: u8 = 0xfb;
+const BREAK: u8 = 0xff;
+
+/// The tag in front of a `Some` whose value is written as `null`.
+pub const SOME_TAG: u64 = 0x736f6d65; // "some"
+
+// What a missing struct field is decoded from.
+static NULL_ITEM: [u8, ..1] = [NULL];
+
+/// The errors that can arise while decoding CBOR.
+#[deriving(Clone, PartialEq, Show)]
+pub enum DecoderError {
+    /// The input ended in the middle of a data item.
+    EndOfInputError,
+    /// The input isn't well-formed CBOR. The description says why.
+    MalformedError(string::String),
+    /// A value of the first type was expected, but the second was found.
+    ExpectedError(string::String, string::String),
+    /// An integer doesn't fit in the type it is decoded as.
+    OverflowError(string::String),
+    /// A text string isn't valid UTF-8.
+    NotUtf8Error,
+    /// A struct field with the given name isn't in the map.
+    MissingFieldError(string::String),
+    /// An enum has no variant with the given name.
+    UnknownVariantError(string::String),
+    /// A `Decodable` implementation reported an error, or used the decoder in
+    /// a way it doesn't support.
+    ApplicationError(string::String),
+    /// There are bytes left over after the value.
+    TrailingBytesError,
+}
+
+pub type EncodeResult = io::IoResult<()>;
+pub type DecodeResult<T> = Result<T, DecoderError>;
+
+/// Shortcut function to encode a `T` into a CBOR byte vector
+pub fn encode<'a, T: Encodable<Encoder<'a>, io::IoError>>(object: &T) -> Vec<u8> {
+    Encoder::buffer_encode(object)
+}
+
+/// Shortcut function to decode a `T` from a CBOR byte slice, which must
+/// contain exactly one value
+pub fn decode<'a, T: ::Decodable<Decoder<'a>, DecoderError>>(data: &'a [u8]) -> DecodeResult<T> {
+    let mut decoder = Decoder::new(data);
+    let value = try!(::Decodable::decode(&mut decoder));
+    if !decoder.is_empty() {
+        return Err(TrailingBytesError);
+    }
+    Ok(value)
+}
+
+/// A structure for implementing serialization to CBOR.
+pub struct Encoder<'a> {
+    writer: &'a mut (io::Writer+'a),
+    // The number of `Some`s whose value hasn't been started yet. They are
+    // only written out (as tags) if the value turns out to be `null`.
+    pending_some: uint,
+}
+
+impl<'a> Encoder<'a> {
+    /// Creates a new CBOR encoder whose output will be written to the writer
+    /// specified.
+    pub fn new(writer: &'a mut io::Writer) -> Encoder<'a> {
+        Encoder { writer: writer, pending_some: 0 }
+    }
+
+    /// Encode the specified struct into a CBOR [u8]
+    pub fn buffer_encode<T: Encodable<Encoder<'a>, io::IoError>>(object: &T) -> Vec<u8> {
+        let mut m = Vec::new();
+        // FIXME(14302) remove the transmute and unsafe block.
+        unsafe {
+            let mut encoder = Encoder::new(&mut m as &mut io::Writer);
+            // Vec<u8> never Errs
+            let _ = object.encode(transmute(&mut encoder));
+        }
+        m
+    }
+
+    /// Writes the head of a data item: its major type and its argument (a
+    /// value, a length or a count), in the shortest form.
+    fn emit_head(&mut self, major: u8, n: u64) -> EncodeResult {
+        self.pending_some = 0;
+        let major = major << 5;
+        if n < 24 {
+            self.writer.write_u8(major | n as u8)
+        } else if n <= 0xff {
+            try!(self.writer.write_u8(major | 24));
+            self.writer.write_u8(n as u8)
+        } else if n <= 0xffff {
+            try!(self.writer.write_u8(major | 25));
+            self.writer.write_be_u16(n as u16)
+        } else if n <= 0xffff_ffff {
+            try!(self.writer.write_u8(major | 26));
+            self.writer.write_be_u32(n as u32)
+        } else {
+            try!(self.writer.write_u8(major | 27));
+            self.writer.write_be_u64(n)
+        }
+    }
+
+    fn emit_signed(&mut self, v: i64) -> EncodeResult {
+        if v >= 0 {
+            self.emit_head(MAJOR_UNSIGNED, v as u64)
+        } else {
+            // -1 - v, without overflowing.
+            self.emit_head(MAJOR_NEGATIVE, !v as u64)
+        }
+    }
+}
+
+impl<'a> ::Encoder<io::IoError> for Encoder<'a> {
+    fn emit_nil(&mut self) -> EncodeResult {
+        for _ in range(0, self.pending_some) {
+            try!(self.emit_head(MAJOR_TAG, SOME_TAG));
+        }
+        self.writer.write_u8(NULL)
+    }
+
+    fn emit_uint(&mut self, v: uint) -> EncodeResult { self.emit_head(MAJOR_UNSIGNED, v as u64) }
+    fn emit_u64(&mut self, v: u64) -> EncodeResult { self.emit_head(MAJOR_UNSIGNED, v) }
+    fn emit_u32(&mut self, v: u32) -> EncodeResult { self.emit_head(MAJOR_UNSIGNED, v as u64) }
+    fn emit_u16(&mut self, v: u16) -> EncodeResult { self.emit_head(MAJOR_UNSIGNED, v as u64) }
+    fn emit_u8(&mut self, v: u8) -> EncodeResult { self.emit_head(MAJOR_UNSIGNED, v as u64) }
+
+    fn emit_int(&mut self, v: int) -> EncodeResult { self.emit_signed(v as i64) }
+    fn emit_i64(&mut self, v: i64) -> EncodeResult { self.emit_signed(v) }
+    fn emit_i32(&mut self, v: i32) -> EncodeResult { self.emit_signed(v as i64) }
+    fn emit_i16(&mut self, v: i16) -> EncodeResult { self.emit_signed(v as i64) }
+    fn emit_i8(&mut self, v: i8) -> EncodeResult { self.emit_signed(v as i64) }
+
+    fn emit_bool(&mut self, v: bool) -> EncodeResult {
+        self.pending_some = 0;
+        self.writer.write_u8(if v { TRUE } else { FALSE })
+    }
+
+    fn emit_f64(&mut self, v: f64) -> EncodeResult {
+        self.pending_some = 0;
+        try!(self.writer.write_u8(FLOAT64));
+        self.writer.write_be_f64(v)
+    }
+    fn emit_f32(&mut self, v: f32) -> EncodeResult {
+        self.pending_some = 0;
+        try!(self.writer.write_u8(FLOAT32));
+        self.writer.write_be_f32(v)
+    }
+
+    fn emit_char(&mut self, v: char) -> EncodeResult {
+        let mut buf = [0, .. 4];
+        let n = v.encode_utf8(&mut buf).unwrap_or(0);
+        try!(self.emit_head(MAJOR_TEXT, n as u64));
+        self.writer.write(buf[..n])
+    }
+    fn emit_str(&mut self, v: &str) -> EncodeResult {
+        try!(self.emit_head(MAJOR_TEXT, v.len() as u64));
+        self.writer.write_str(v)
+    }
+
+    fn emit_enum<F>(&mut self, _name: &str, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        f(self)
+    }
+
+    fn emit_enum_variant<F>(&mut self,
+                            name: &str,
+                            _id: uint,
+                            cnt: uint,
+                            f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        // unit variants are encoded as their name, other variants as an
+        // array of their name followed by their arguments:
+        // ["Name", arg0, arg1, ...]
+        if cnt == 0 {
+            self.emit_str(name)
+        } else {
+            try!(self.emit_head(MAJOR_ARRAY, cnt as u64 + 1));
+            try!(self.emit_str(name));
+            f(self)
+        }
+    }
+
+    fn emit_enum_variant_arg<F>(&mut self, _idx: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        f(self)
+    }
+
+    fn emit_enum_struct_variant<F>(&mut self,
+                                   name: &str,
+                                   id: uint,
+                                   cnt: uint,
+                                   f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        self.emit_enum_variant(name, id, cnt, f)
+    }
+
+    fn emit_enum_struct_variant_field<F>(&mut self,
+                                         _: &str,
+                                         idx: uint,
+                                         f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        self.emit_enum_variant_arg(idx, f)
+    }
+
+    fn emit_struct<F>(&mut self, _: &str, len: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        try!(self.emit_head(MAJOR_MAP, len as u64));
+        f(self)
+    }
+
+    fn emit_struct_field<F>(&mut self, name: &str, _idx: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        try!(self.emit_str(name));
+        f(self)
+    }
+
+    fn emit_tuple<F>(&mut self, len: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        self.emit_seq(len, f)
+    }
+    fn emit_tuple_arg<F>(&mut self, idx: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_tuple_struct<F>(&mut self, _name: &str, len: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        self.emit_seq(len, f)
+    }
+    fn emit_tuple_struct_arg<F>(&mut self, idx: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        f(self)
+    }
+    fn emit_option_none(&mut self) -> EncodeResult { self.emit_nil() }
+    fn emit_option_some<F>(&mut self, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        self.pending_some += 1;
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, len: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        try!(self.emit_head(MAJOR_ARRAY, len as u64));
+        f(self)
+    }
+
+    fn emit_seq_elt<F>(&mut self, _idx: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, len: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        try!(self.emit_head(MAJOR_MAP, len as u64));
+        f(self)
+    }
+
+    fn emit_map_elt_key<F>(&mut self, _idx: uint, mut f: F) -> EncodeResult where
+        F: FnMut(&mut Encoder<'a>) -> EncodeResult,
+    {
+        f(self)
+    }
+
+    fn emit_map_elt_val<F>(&mut self, _idx: uint, f: F) -> EncodeResult where
+        F: FnOnce(&mut Encoder<'a>) -> EncodeResult,
+    {
+        f(self)
+    }
+}
+
+/// A structure to decode CBOR to values in rust.
+pub struct Decoder<'a> {
+    data: &'a [u8],
+    pos: uint,
+    // For every struct being decoded, where the value of each of its fields
+    // starts and where the struct ends.
+    structs: Vec<(Vec<(string::String, uint)>, uint)>,
+    // The name of the enum variant being decoded, if it was written on its own
+    // rather than in an array with its arguments, so it can't have any.
+    bare_variant: Option<string::String>,
+}
+
+impl<'a> Decoder<'a> {
+    /// Creates a new decoder instance for decoding the CBOR data given.
+    pub fn new(data: &'a [u8]) -> Decoder<'a> {
+        Decoder { data: data, pos: 0, structs: Vec::new(), bare_variant: None }
+    }
+
+    /// Returns true if all of the input has been decoded.
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn peek_byte(&self) -> DecodeResult<u8> {
+        if self.pos < self.data.len() {
+            Ok(self.data[self.pos])
+        } else {
+            Err(EndOfInputError)
+        }
+    }
+
+    fn read_byte(&mut self) -> DecodeResult<u8> {
+        let b = try!(self.peek_byte());
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn take(&mut self, n: uint) -> DecodeResult<&'a [u8]> {
+        if self.data.len() - self.pos < n {
+            return Err(EndOfInputError);
+        }
+        let bytes = self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn read_be(&mut self, n: uint) -> DecodeResult<u64> {
+        let bytes = try!(self.take(n));
+        Ok(bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    /// Reads the head of a data item. Returns its major type, its additional
+    /// information and its argument, which is None for indefinite lengths.
+    fn read_head(&mut self) -> DecodeResult<(u8, u8, Option<u64>)> {
+        let b = try!(self.read_byte());
+        let (major, info) = (b >> 5, b & 0x1f);
+        let arg = match info {
+            0...23 => Some(info as u64),
+            24 => Some(try!(self.read_be(1))),
+            25 => Some(try!(self.read_be(2))),
+            26 => Some(try!(self.read_be(4))),
+            27 => Some(try!(self.read_be(8))),
+            31 if major >= MAJOR_BYTES && major <= MAJOR_MAP => None,
+            _ => return Err(MalformedError(format!("invalid initial byte {:x}", b))),
+        };
+        Ok((major, info, arg))
+    }
+
+    // Skips over the semantic tags in front of the next data item.
+    fn skip_tags(&mut self) -> DecodeResult<()> {
+        while try!(self.peek_byte()) >> 5 == MAJOR_TAG {
+            try!(self.read_head());
+        }
+        Ok(())
+    }
+
+    // Reads the head of the next data item, which must be of the major type
+    // given.
+    fn expect_head(&mut self, major: u8, expected: &str) -> DecodeResult<Option<u64>> {
+        try!(self.skip_tags());
+        let b = try!(self.peek_byte());
+        if b >> 5 != major {
+            return Err(ExpectedError(expected.to_string(), describe(b).to_string()));
+        }
+        let (_, _, arg) = try!(self.read_head());
+        Ok(arg)
+    }
+
+    // Reads the break byte that ends an indefinite-length item.
+    fn expect_break(&mut self) -> DecodeResult<()> {
+        match try!(self.read_byte()) {
+            BREAK => Ok(()),
+            b => Err(ExpectedError("break".to_string(), describe(b).to_string())),
+        }
+    }
+
+    // Reads the head of an array or a map and returns the number of items
+    // (or pairs) in it, and whether it has an indefinite length.
+    fn read_container(&mut self, major: u8, expected: &str) -> DecodeResult<(uint, bool)> {
+        match try!(self.expect_head(major, expected)) {
+            Some(n) => {
+                // Every item takes at least a byte, so a length that doesn't
+                // fit in the rest of the input is an error, rather than
+                // something for the caller to allocate room for.
+                let n = try!(to_uint(n));
+                let left = self.data.len() - self.pos;
+                let per_item = if major == MAJOR_MAP { 2 } else { 1 };
+                if n > left / per_item {
+                    return Err(EndOfInputError);
+                }
+                Ok((n, false))
+            }
+            None => {
+                // Count the items by skipping over them.
+                let start = self.pos;
+                let mut n = 0u;
+                while try!(self.peek_byte()) != BREAK {
+                    try!(self.skip_value());
+                    if major == MAJOR_MAP {
+                        try!(self.skip_value());
+                    }
+                    n += 1;
+                }
+                self.pos = start;
+                Ok((n, true))
+            }
+        }
+    }
+
+    // Skips over the next data item.
+    fn skip_value(&mut self) -> DecodeResult<()> {
+        let (major, _, arg) = try!(self.read_head());
+        match (major, arg) {
+            (MAJOR_BYTES, Some(n)) | (MAJOR_TEXT, Some(n)) => {
+                try!(self.take(try!(to_uint(n))));
+            }
+            (MAJOR_ARRAY, Some(n)) => {
+                for _ in range(0, n) {
+                    try!(self.skip_value());
+                }
+            }
+            (MAJOR_MAP, Some(n)) => {
+                for _ in range(0, n) {
+                    try!(self.skip_value());
+                    try!(self.skip_value());
+                }
+            }
+            (_, None) => {
+                while try!(self.peek_byte()) != BREAK {
+                    try!(self.skip_value());
+                }
+                self.pos += 1;
+            }
+            (MAJOR_TAG, _) => try!(self.skip_value()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn read_unsigned(&mut self, expected: &str) -> DecodeResult<u64> {
+        match try!(self.expect_head(MAJOR_UNSIGNED, expected)) {
+            Some(n) => Ok(n),
+            None => unreachable!(),
+        }
+    }
+
+    fn read_signed(&mut self) -> DecodeResult<i64> {
+        try!(self.skip_tags());
+        let b = try!(self.peek_byte());
+        let (major, _, arg) = match b >> 5 {
+            MAJOR_UNSIGNED | MAJOR_NEGATIVE => try!(self.read_head()),
+            _ => return Err(ExpectedError("integer".to_string(), describe(b).to_string())),
+        };
+        let n = arg.unwrap();
+        if n > i64::MAX as u64 {
+            return Err(OverflowError("i64".to_string()));
+        }
+        let n = n as i64;
+        Ok(if major == MAJOR_UNSIGNED { n } else { -1 - n })
+    }
+
+    fn read_float(&mut self) -> DecodeResult<f64> {
+        try!(self.skip_tags());
+        let b = try!(self.peek_byte());
+        match b {
+            FLOAT16 => {
+                self.pos += 1;
+                Ok(f16_to_f64(try!(self.read_be(2)) as u16))
+            }
+            FLOAT32 => {
+                self.pos += 1;
+                let bits = try!(self.read_be(4)) as u32;
+                Ok(unsafe { transmute::<u32, f32>(bits) } as f64)
+            }
+            FLOAT64 => {
+                self.pos += 1;
+                let bits = try!(self.read_be(8));
+                Ok(unsafe { transmute::<u64, f64>(bits) })
+            }
+            _ if b >> 5 == MAJOR_UNSIGNED || b >> 5 == MAJOR_NEGATIVE => {
+                Ok(try!(self.read_signed()) as f64)
+            }
+            _ => Err(ExpectedError("float".to_string(), describe(b).to_string())),
+        }
+    }
+
+    fn read_text(&mut self) -> DecodeResult<string::String> {
+        let bytes = match try!(self.expect_head(MAJOR_TEXT, "text string")) {
+            Some(n) => try!(self.take(try!(to_uint(n)))).to_vec(),
+            None => {
+                // An indefinite-length string is a sequence of definite-length
+                // chunks.
+                let mut bytes = Vec::new();
+                while try!(self.peek_byte()) != BREAK {
+                    match try!(self.expect_head(MAJOR_TEXT, "text string chunk")) {
+                        Some(n) => bytes.push_all(try!(self.take(try!(to_uint(n))))),
+                        None => {
+                            return Err(MalformedError("nested indefinite-length string".to_string()))
+                        }
+                    }
+                }
+                self.pos += 1;
+                bytes
+            }
+        };
+        match string::String::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(NotUtf8Error),
+        }
+    }
+
+    fn read_cast<T: num::NumCast>(&mut self, v: u64, ty: &str) -> DecodeResult<T> {
+        match num::cast(v) {
+            Some(v) => Ok(v),
+            None => Err(OverflowError(ty.to_string())),
+        }
+    }
+
+    fn read_signed_cast<T: num::NumCast>(&mut self, ty: &str) -> DecodeResult<T> {
+        let v = try!(self.read_signed());
+        match num::cast(v) {
+            Some(v) => Ok(v),
+            None => Err(OverflowError(ty.to_string())),
+        }
+    }
+
+    fn variant_index(&self, names: &[&str], name: string::String) -> DecodeResult<uint> {
+        match names.iter().position(|n| *n == name.as_slice()) {
+            Some(idx) => Ok(idx),
+            None => Err(UnknownVariantError(name)),
+        }
+    }
+}
+
+fn to_uint(n: u64) -> DecodeResult<uint> {
+    match num::cast(n) {
+        Some(n) => Ok(n),
+        None => Err(OverflowError("uint".to_string())),
+    }
+}
+
+// Describes the type of the data item whose initial byte is given.
+fn describe(b: u8) -> &'static str {
+    match b {
+        FALSE | TRUE => "boolean",
+        NULL => "null",
+        UNDEFINED => "undefined",
+        FLOAT16 | FLOAT32 | FLOAT64 => "float",
+        BREAK => "break",
+        _ => match b >> 5 {
+            MAJOR_UNSIGNED => "unsigned integer",
+            MAJOR_NEGATIVE => "negative integer",
+            MAJOR_BYTES => "byte string",
+            MAJOR_TEXT => "text string",
+            MAJOR_ARRAY => "array",
+            MAJOR_MAP => "map",
+            MAJOR_TAG => "tag",
+            _ => "simple value",
+        },
+    }
+}
+
+// Converts a half precision float to a double.
+fn f16_to_f64(h: u16) -> f64 {
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let mant = (h & 0x3ff) as f64;
+    let v = if exp == 0 {
+        mant * 2f64.powi(-24)
+    } else if exp != 31 {
+        (mant + 1024.0) * 2f64.powi(exp - 25)
+    } else if mant == 0.0 {
+        Float::infinity()
+    } else {
+        Float::nan()
+    };
+    if h & 0x8000 != 0 { -v } else { v }
+}
+
+impl<'a> ::Decoder<DecoderError> for Decoder<'a> {
+    fn read_nil(&mut self) -> DecodeResult<()> {
+        try!(self.skip_tags());
+        match try!(self.peek_byte()) {
+            NULL | UNDEFINED => {
+                self.pos += 1;
+                Ok(())
+            }
+            b => Err(ExpectedError("null".to_string(), describe(b).to_string())),
+        }
+    }
+
+    fn read_u64(&mut self) -> DecodeResult<u64> { self.read_unsigned("unsigned integer") }
+    fn read_u32(&mut self) -> DecodeResult<u32> {
+        let v = try!(self.read_u64());
+        self.read_cast(v, "u32")
+    }
+    fn read_u16(&mut self) -> DecodeResult<u16> {
+        let v = try!(self.read_u64());
+        self.read_cast(v, "u16")
+    }
+    fn read_u8(&mut self) -> DecodeResult<u8> {
+        let v = try!(self.read_u64());
+        self.read_cast(v, "u8")
+    }
+    fn read_uint(&mut self) -> DecodeResult<uint> {
+        let v = try!(self.read_u64());
+        self.read_cast(v, "uint")
+    }
+
+    fn read_i64(&mut self) -> DecodeResult<i64> { self.read_signed() }
+    fn read_i32(&mut self) -> DecodeResult<i32> { self.read_signed_cast("i32") }
+    fn read_i16(&mut self) -> DecodeResult<i16> { self.read_signed_cast("i16") }
+    fn read_i8(&mut self) -> DecodeResult<i8> { self.read_signed_cast("i8") }
+    fn read_int(&mut self) -> DecodeResult<int> { self.read_signed_cast("int") }
+
+    fn read_bool(&mut self) -> DecodeResult<bool> {
+        try!(self.skip_tags());
+        match try!(self.peek_byte()) {
+            FALSE => { self.pos += 1; Ok(false) }
+            TRUE => { self.pos += 1; Ok(true) }
+            b => Err(ExpectedError("boolean".to_string(), describe(b).to_string())),
+        }
+    }
+
+    fn read_f64(&mut self) -> DecodeResult<f64> { self.read_float() }
+    fn read_f32(&mut self) -> DecodeResult<f32> {
+        Ok(try!(self.read_float()) as f32)
+    }
+
+    fn read_char(&mut self) -> DecodeResult<char> {
+        let s = try!(self.read_text());
+        {
+            let mut it = s.chars();
+            match (it.next(), it.next()) {
+                // exactly one character
+                (Some(c), None) => return Ok(c),
+                _ => ()
+            }
+        }
+        Err(ExpectedError("single character string".to_string(), format!("{}", s)))
+    }
+
+    fn read_str(&mut self) -> DecodeResult<string::String> {
+        self.read_text()
+    }
+
+    fn read_enum<T, F>(&mut self, _name: &str, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        f(self)
+    }
+
+    fn read_enum_variant<T, F>(&mut self, names: &[&str],
+                               f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Decoder<'a>, uint) -> DecodeResult<T>,
+    {
+        try!(self.skip_tags());
+        let b = try!(self.peek_byte());
+        match b >> 5 {
+            MAJOR_TEXT => {
+                let name = try!(self.read_text());
+                let idx = try!(self.variant_index(names, name));
+                let outer = replace(&mut self.bare_variant, Some(names[idx].to_string()));
+                let value = f(self, idx);
+                self.bare_variant = outer;
+                value
+            }
+            MAJOR_ARRAY => {
+                let (n, indefinite) = try!(self.read_container(MAJOR_ARRAY, "array"));
+                if n == 0 {
+                    return Err(ExpectedError("variant name".to_string(), "empty array".to_string()));
+                }
+                let name = try!(self.read_text());
+                let idx = try!(self.variant_index(names, name));
+                // Find where the arguments end, to check that the variant
+                // takes as many as there are.
+                let start = self.pos;
+                for _ in range(1, n) {
+                    try!(self.skip_value());
+                }
+                let end = self.pos;
+                self.pos = start;
+                let outer = replace(&mut self.bare_variant, None);
+                let value = f(self, idx);
+                self.bare_variant = outer;
+                let value = try!(value);
+                if self.pos != end {
+                    return Err(ExpectedError(format!("arguments of {}", names[idx]),
+                                             format!("array of length {}", n)));
+                }
+                if indefinite {
+                    try!(self.expect_break());
+                }
+                Ok(value)
+            }
+            _ => Err(ExpectedError("variant".to_string(), describe(b).to_string())),
+        }
+    }
+
+    fn read_enum_variant_arg<T, F>(&mut self, _idx: uint, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        // Otherwise the arguments would be read from whatever follows the
+        // variant name.
+        match self.bare_variant {
+            Some(ref name) => Err(ExpectedError(format!("arguments of {}", name),
+                                                "text string".to_string())),
+            None => f(self),
+        }
+    }
+
+    fn read_enum_struct_variant<T, F>(&mut self, names: &[&str], f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>, uint) -> DecodeResult<T>,
+    {
+        self.read_enum_variant(names, f)
+    }
+
+
+    fn read_enum_struct_variant_field<T, F>(&mut self,
+                                         _name: &str,
+                                         idx: uint,
+                                         f: F)
+                                         -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        self.read_enum_variant_arg(idx, f)
+    }
+
+    fn read_struct<T, F>(&mut self, _name: &str, _len: uint, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        // Find where the value of each field starts, so that the fields can be
+        // decoded in any order.
+        let (n, indefinite) = try!(self.read_container(MAJOR_MAP, "map"));
+        let mut fields = Vec::with_capacity(n);
+        for _ in range(0, n) {
+            let name = try!(self.read_text());
+            fields.push((name, self.pos));
+            try!(self.skip_value());
+        }
+        if indefinite {
+            try!(self.expect_break());
+        }
+        let end = self.pos;
+
+        self.structs.push((fields, end));
+        let value = f(self);
+        self.structs.pop();
+        self.pos = end;
+        value
+    }
+
+    fn read_struct_field<T, F>(&mut self,
+                               name: &str,
+                               _idx: uint,
+                               f: F)
+                               -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        let pos = match self.structs.last() {
+            Some(&(ref fields, _)) => {
+                fields.iter().find(|&&(ref n, _)| n.as_slice() == name).map(|&(_, pos)| pos)
+            }
+            None => return Err(ApplicationError("field outside of a struct".to_string())),
+        };
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                f(self)
+            }
+            None => {
+                // Decode a missing field as null, which works for options.
+                let (data, pos) = (self.data, self.pos);
+                self.data = &NULL_ITEM;
+                self.pos = 0;
+                let value = f(self);
+                self.data = data;
+                self.pos = pos;
+                match value {
+                    Ok(value) => Ok(value),
+                    Err(_) => Err(MissingFieldError(name.to_string())),
+                }
+            }
+        }
+    }
+
+    fn read_tuple<T, F>(&mut self, tuple_len: uint, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        let (n, indefinite) = try!(self.read_container(MAJOR_ARRAY, "array"));
+        if n != tuple_len {
+            return Err(ExpectedError(format!("tuple of length {}", tuple_len),
+                                     format!("array of length {}", n)));
+        }
+        let value = try!(f(self));
+        if indefinite {
+            try!(self.expect_break());
+        }
+        Ok(value)
+    }
+
+    fn read_tuple_arg<T, F>(&mut self, idx: uint, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        self.read_seq_elt(idx, f)
+    }
+
+    fn read_tuple_struct<T, F>(&mut self,
+                               _name: &str,
+                               len: uint,
+                               f: F)
+                               -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        self.read_tuple(len, f)
+    }
+
+    fn read_tuple_struct_arg<T, F>(&mut self,
+                                   idx: uint,
+                                   f: F)
+                                   -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        self.read_tuple_arg(idx, f)
+    }
+
+    fn read_option<T, F>(&mut self, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>, bool) -> DecodeResult<T>,
+    {
+        loop {
+            match try!(self.peek_byte()) {
+                NULL | UNDEFINED => {
+                    self.pos += 1;
+                    return f(self, false)
+                }
+                b if b >> 5 == MAJOR_TAG => {
+                    let (_, _, tag) = try!(self.read_head());
+                    if tag == Some(SOME_TAG) {
+                        return f(self, true)
+                    }
+                }
+                _ => return f(self, true),
+            }
+        }
+    }
+
+    fn read_seq<T, F>(&mut self, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>, uint) -> DecodeResult<T>,
+    {
+        let (n, indefinite) = try!(self.read_container(MAJOR_ARRAY, "array"));
+        let value = try!(f(self, n));
+        if indefinite {
+            try!(self.expect_break());
+        }
+        Ok(value)
+    }
+
+    fn read_seq_elt<T, F>(&mut self, _idx: uint, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        f(self)
+    }
+
+    fn read_map<T, F>(&mut self, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder<'a>, uint) -> DecodeResult<T>,
+    {
+        let (n, indefinite) = try!(self.read_container(MAJOR_MAP, "map"));
+        let value = try!(f(self, n));
+        if indefinite {
+            try!(self.expect_break());
+        }
+        Ok(value)
+    }
+
+    fn read_map_elt_key<T, F>(&mut self, _idx: uint, f: F) -> DecodeResult<T> where
+       F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        f(self)
+    }
+
+    fn read_map_elt_val<T, F>(&mut self, _idx: uint, f: F) -> DecodeResult<T> where
+       F: FnOnce(&mut Decoder<'a>) -> DecodeResult<T>,
+    {
+        f(self)
+    }
+
+    fn error(&mut self, err: &str) -> DecoderError {
+        ApplicationError(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+    use self::test::Bencher;
+    use self::Shape::*;
+    use super::{encode, decode, DecodeResult};
+    use super::DecoderError::*;
+    use Encodable;
+    use hex::{FromHex, ToHex};
+    use std::collections::HashMap;
+    use std::{f64, i64, u32, u64};
+
+    #[deriving(PartialEq, Encodable, Decodable, Show)]
+    enum Shape {
+        Origin,
+        Circle(f64),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[deriving(PartialEq, Encodable, Decodable, Show)]
+    struct Item {
+        name: String,
+        tags: Vec<String>,
+        shape: Shape,
+        parent: Option<Box<Item>>,
+        weight: f32,
+        code: char,
+        pair: (i8, bool),
+    }
+
+    macro_rules! from_hex(
+        ($s:expr, $t:ty) => ({
+            let bytes = $s.from_hex().unwrap();
+            let value: DecodeResult<$t> = decode(bytes.as_slice());
+            value
+        })
+    )
+
+    fn hex<'a, T: Encodable<super::Encoder<'a>, ::std::io::IoError>>(value: &T) -> String {
+        encode(value).as_slice().to_hex()
+    }
+
+    // The examples from RFC 7049, appendix A.
+    #[test]
+    fn test_encode_rfc_examples() {
+        assert_eq!(hex(&0u), "00");
+        assert_eq!(hex(&1u), "01");
+        assert_eq!(hex(&10u), "0a");
+        assert_eq!(hex(&23u), "17");
+        assert_eq!(hex(&24u), "1818");
+        assert_eq!(hex(&25u), "1819");
+        assert_eq!(hex(&100u), "1864");
+        assert_eq!(hex(&1000u), "1903e8");
+        assert_eq!(hex(&1000000u), "1a000f4240");
+        assert_eq!(hex(&1000000000000u64), "1b000000e8d4a51000");
+        assert_eq!(hex(&u64::MAX), "1bffffffffffffffff");
+        assert_eq!(hex(&-1i), "20");
+        assert_eq!(hex(&-10i), "29");
+        assert_eq!(hex(&-100i), "3863");
+        assert_eq!(hex(&-1000i), "3903e7");
+        assert_eq!(hex(&i64::MIN), "3b7fffffffffffffff");
+        assert_eq!(hex(&1.1f64), "fb3ff199999999999a");
+        assert_eq!(hex(&100000.0f32), "fa47c35000");
+        assert_eq!(hex(&false), "f4");
+        assert_eq!(hex(&true), "f5");
+        assert_eq!(hex(&()), "f6");
+        assert_eq!(hex(&""), "60");
+        assert_eq!(hex(&"a"), "6161");
+        assert_eq!(hex(&"IETF"), "6449455446");
+        assert_eq!(hex(&"\"\\"), "62225c");
+        assert_eq!(hex(&"ü"), "62c3bc");
+        assert_eq!(hex(&"水"), "63e6b0b4");
+        assert_eq!(hex(&Vec::<uint>::new()), "80");
+        assert_eq!(hex(&vec![1u, 2, 3]), "83010203");
+        assert_eq!(hex(&(1u, vec![2u, 3], vec![4u, 5])), "8301820203820405");
+        assert_eq!(hex(&range(1u, 26).collect::<Vec<uint>>()),
+                   "98190102030405060708090a0b0c0d0e0f101112131415161718181819");
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), 1u);
+        assert_eq!(hex(&map), "a1616101");
+    }
+
+    #[test]
+    fn test_decode_rfc_examples() {
+        assert_eq!(from_hex!("1b000000e8d4a51000", u64), Ok(1000000000000));
+        assert_eq!(from_hex!("3903e7", i64), Ok(-1000));
+        assert_eq!(from_hex!("3b7fffffffffffffff", i64), Ok(i64::MIN));
+        assert_eq!(from_hex!("f90000", f64), Ok(0.0));
+        assert_eq!(from_hex!("f93c00", f64), Ok(1.0));
+        assert_eq!(from_hex!("f93e00", f64), Ok(1.5));
+        assert_eq!(from_hex!("f97bff", f64), Ok(65504.0));
+        assert_eq!(from_hex!("f90001", f64), Ok(5.960464477539063e-8));
+        assert_eq!(from_hex!("f90400", f64), Ok(0.00006103515625));
+        assert_eq!(from_hex!("f9c400", f64), Ok(-4.0));
+        assert_eq!(from_hex!("f97c00", f64), Ok(f64::INFINITY));
+        assert!(from_hex!("f97e00", f64).unwrap().is_nan());
+        assert_eq!(from_hex!("fa47c35000", f64), Ok(100000.0));
+        assert_eq!(from_hex!("fbc010666666666666", f64), Ok(-4.1));
+        // Tags are ignored.
+        assert_eq!(from_hex!("c11a514b67b0", u64), Ok(1363896240));
+        assert_eq!(from_hex!("c074323031332d30332d32315432303a30343a30305a", String),
+                   Ok("2013-03-21T20:04:00Z".to_string()));
+        // Indefinite lengths.
+        assert_eq!(from_hex!("7f657374726561646d696e67ff", String), Ok("streaming".to_string()));
+        assert_eq!(from_hex!("9fff", Vec<uint>), Ok(vec![]));
+        assert_eq!(from_hex!("9f018202039f0405ffff", (uint, Vec<uint>, Vec<uint>)),
+                   Ok((1, vec![2, 3], vec![4, 5])));
+        assert_eq!(from_hex!("83018202039f0405ff", (uint, Vec<uint>, Vec<uint>)),
+                   Ok((1, vec![2, 3], vec![4, 5])));
+        let map = from_hex!("bf6346756ef563416d7421ff", HashMap<String, bool>).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["Fun".to_string()], true);
+        assert_eq!(map["Amt".to_string()], false);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let item = Item {
+            name: "leaf".to_string(),
+            tags: vec!["green".to_string(), "small".to_string()],
+            shape: Rect { w: 3, h: 400 },
+            parent: Some(box Item {
+                name: "branch".to_string(),
+                tags: vec![],
+                shape: Circle(-2.5),
+                parent: None,
+                weight: 1e10,
+                code: 'é',
+                pair: (-128, false),
+            }),
+            weight: 0.5,
+            code: 'x',
+            pair: (127, true),
+        };
+        let bytes = encode(&item);
+        let decoded: Item = decode(bytes.as_slice()).unwrap();
+        assert_eq!(decoded, item);
+
+        let shapes = vec![Origin, Circle(1.0), Rect { w: 0, h: u32::MAX }];
+        let bytes = encode(&shapes);
+        let decoded: Vec<Shape> = decode(bytes.as_slice()).unwrap();
+        assert_eq!(decoded, shapes);
+    }
+
+    #[test]
+    fn test_enum_encoding() {
+        assert_eq!(hex(&Origin), "664f726967696e");
+        // ["Circle", 1.0]
+        assert_eq!(hex(&Circle(1.0)), "8266436972636c65fb3ff0000000000000");
+    }
+
+    #[test]
+    fn test_struct_field_order_and_missing_fields() {
+        #[deriving(PartialEq, Decodable, Show)]
+        struct S { a: uint, b: Option<uint> }
+        // {"b": 2, "a": 1}
+        assert_eq!(from_hex!("a2616202616101", S), Ok(S { a: 1, b: Some(2) }));
+        // {"a": 1, "c": [1]}
+        assert_eq!(from_hex!("a26161016163820101", S), Ok(S { a: 1, b: None }));
+        // {"b": 2}
+        assert_eq!(from_hex!("a1616202", S), Err(MissingFieldError("a".to_string())));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(from_hex!("", u64), Err(EndOfInputError));
+        assert_eq!(from_hex!("1903", u64), Err(EndOfInputError));
+        assert_eq!(from_hex!("20", u64),
+                   Err(ExpectedError("unsigned integer".to_string(),
+                                     "negative integer".to_string())));
+        assert_eq!(from_hex!("190100", u8), Err(OverflowError("u8".to_string())));
+        assert_eq!(from_hex!("3880", i8), Err(OverflowError("i8".to_string())));
+        assert_eq!(from_hex!("1b8000000000000000", i64), Err(OverflowError("i64".to_string())));
+        assert_eq!(from_hex!("62c328", String), Err(NotUtf8Error));
+        assert_eq!(from_hex!("f6", bool),
+                   Err(ExpectedError("boolean".to_string(), "null".to_string())));
+        assert_eq!(from_hex!("1c", u64), Err(MalformedError("invalid initial byte 1c".to_string())));
+        assert_eq!(from_hex!("0101", u64), Err(TrailingBytesError));
+        assert_eq!(from_hex!("6653717561726565", Shape),
+                   Err(UnknownVariantError("Squaree".to_string())));
+        assert_eq!(from_hex!("83010203", (uint, uint)),
+                   Err(ExpectedError("tuple of length 2".to_string(),
+                                     "array of length 3".to_string())));
+        // Lengths longer than the input.
+        assert_eq!(from_hex!("9bffffffffffffffff", Vec<u8>), Err(EndOfInputError));
+        assert_eq!(from_hex!("bb7fffffffffffffff", HashMap<String, bool>),
+                   Err(EndOfInputError));
+        assert_eq!(from_hex!("a3010203", HashMap<uint, uint>), Err(EndOfInputError));
+        // ["Circle", 1.0, 2.0] and ["Origin", 1.0]
+        assert_eq!(from_hex!("8366436972636c65f93c00f94000", Shape),
+                   Err(ExpectedError("arguments of Circle".to_string(),
+                                     "array of length 3".to_string())));
+        assert_eq!(from_hex!("82664f726967696ef93c00", Shape),
+                   Err(ExpectedError("arguments of Origin".to_string(),
+                                     "array of length 2".to_string())));
+        // ["Circle", 1.0] without the array
+        assert_eq!(from_hex!("66436972636c65", Shape),
+                   Err(ExpectedError("arguments of Circle".to_string(),
+                                     "text string".to_string())));
+        assert_eq!(from_hex!("8266436972636c65f93c00", Vec<Shape>),
+                   Err(ExpectedError("arguments of Circle".to_string(),
+                                     "text string".to_string())));
+    }
+
+    #[test]
+    fn test_nested_options() {
+        assert_eq!(hex(&None::<uint>), "f6");
+        assert_eq!(hex(&Some(1u)), "01");
+        assert_eq!(hex(&Some(Some(1u))), "01");
+        assert_eq!(hex(&Some(None::<uint>)), "da736f6d65f6");
+        assert_eq!(hex(&Some(vec![None::<uint>])), "81f6");
+
+        let values = vec![None, Some(None), Some(Some(None)), Some(Some(Some(1u)))];
+        let decoded: Vec<Option<Option<Option<uint>>>> =
+            decode(encode(&values).as_slice()).unwrap();
+        assert_eq!(decoded, values);
+        let decoded: Option<()> = decode(encode(&Some(())).as_slice()).unwrap();
+        assert_eq!(decoded, Some(()));
+    }
+
+    #[bench]
+    fn bench_encode(b: &mut Bencher) {
+        let v: Vec<(String, Option<u64>)> = range(0u64, 100).map(|i| {
+            (i.to_string(), if i % 2 == 0 { Some(i * 1000) } else { None })
+        }).collect();
+        b.iter(|| encode(&v));
+    }
+
+    #[bench]
+    fn bench_decode(b: &mut Bencher) {
+        let v: Vec<(String, Option<u64>)> = range(0u64, 100).map(|i| {
+            (i.to_string(), if i % 2 == 0 { Some(i * 1000) } else { None })
+        }).collect();
+        let bytes = encode(&v);
+        b.iter(|| {
+            let v: Vec<(String, Option<u64>)> = decode(bytes.as_slice()).unwrap();
+            v
+        });
+    }
+}
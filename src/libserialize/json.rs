@@ -194,7 +194,9 @@ use self::StackElement::*;
 use self::ErrorCode::*;
 use self::ParserError::*;
 use self::DecoderError::*;
+use self::PatchError::*;
 use self::ParserState::*;
+use self::PathElement::*;
 use self::InternalStackElement::*;
 
 use std;
@@ -270,6 +272,39 @@ pub enum DecoderError {
     ApplicationError(string::String)
 }
 
+/// An error returned by `decode`, along with where the value that it is about
+/// is in the document.
+#[deriving(Clone, PartialEq, Show)]
+pub struct LocatedError {
+    /// The error itself.
+    pub error: DecoderError,
+    /// The path to the value, e.g. `.servers[3].port`, or an empty string for
+    /// the whole document.
+    pub path: string::String,
+    /// The line and column in the source where the value starts, if known.
+    pub position: Option<(uint, uint)>,
+}
+
+/// The errors that can arise while applying a JSON Patch (RFC 6902). Apart
+/// from `PatchNotArray`, each one has the index of the operation in the patch
+/// that caused it and a JSON Pointer or a description.
+#[deriving(Clone, PartialEq, Show)]
+pub enum PatchError {
+    /// The patch isn't an array of operations.
+    PatchNotArray,
+    /// The operation isn't an object, has an unknown `op`, or is missing a
+    /// member it needs (or has one of the wrong type).
+    InvalidOperation(uint, string::String),
+    /// A JSON Pointer in the operation isn't valid.
+    InvalidPointer(uint, string::String),
+    /// The location given by a JSON Pointer doesn't exist.
+    PathNotFound(uint, string::String),
+    /// A `move` operation tried to move a value into one of its children.
+    MoveIntoChild(uint, string::String),
+    /// The value at the location given didn't match in a `test` operation.
+    TestFailed(uint, string::String),
+}
+
 /// Returns a readable error string for a given error code.
 pub fn error_str(error: ErrorCode) -> &'static str {
     match error {
@@ -293,15 +328,37 @@ pub fn error_str(error: ErrorCode) -> &'static str {
     }
 }
 
-/// Shortcut function to decode a JSON `&str` into an object
-pub fn decode<T: ::Decodable<Decoder, DecoderError>>(s: &str) -> DecodeResult<T> {
+/// Shortcut function to decode a JSON `&str` into an object. An error says
+/// where in `s` the value that it is about is.
+pub fn decode<T: ::Decodable<Decoder, DecoderError>>(s: &str) -> Result<T, LocatedError> {
     let json = match from_str(s) {
         Ok(x) => x,
-        Err(e) => return Err(ParseError(e))
+        Err(e) => {
+            let position = match e {
+                SyntaxError(_, line, col) => Some((line, col)),
+                IoError(..) => None,
+            };
+            return Err(LocatedError {
+                error: ParseError(e),
+                path: string::String::new(),
+                position: position,
+            })
+        }
     };
 
     let mut decoder = Decoder::new(json);
-    ::Decodable::decode(&mut decoder)
+    match ::Decodable::decode(&mut decoder) {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            let mut path = decoder.error_path.take().unwrap_or(vec![]);
+            path.reverse();
+            Err(LocatedError {
+                error: err,
+                path: format_path(path.as_slice()),
+                position: locate(s, path.as_slice()),
+            })
+        }
+    }
 }
 
 /// Shortcut function to encode a `T` into a JSON `String`
@@ -325,6 +382,11 @@ impl std::error::Error for DecoderError {
     fn detail(&self) -> Option<std::string::String> { Some(self.to_string()) }
 }
 
+impl std::error::Error for LocatedError {
+    fn description(&self) -> &str { "decoder error" }
+    fn detail(&self) -> Option<std::string::String> { Some(self.to_string()) }
+}
+
 pub type EncodeResult = io::IoResult<()>;
 pub type DecodeResult<T> = Result<T, DecoderError>;
 
@@ -886,6 +948,271 @@ impl<'a> ::Encoder<io::IoError> for PrettyEncoder<'a> {
     }
 }
 
+/// A streaming JSON writer driven by `JsonEvent`s.
+///
+/// Events are written out as soon as they're received, so a document of any
+/// size can be written (or transformed event by event, in combination with
+/// `Parser`) while only keeping one small record per level of nesting in
+/// memory. The output is either compact, as produced by `Encoder`, or
+/// indented, as produced by `PrettyEncoder`.
+///
+/// Inside an object, every value must be preceded by a call to `emit_key`.
+/// Events that don't form a valid document (e.g., a value without a key in
+/// an object, an `ArrayEnd` that closes an object or a second top-level
+/// value) are rejected with an `InvalidInput` error, and so is the `Error`
+/// event.
+///
+/// ```rust
+/// use serialize::json::{StreamEncoder, Parser};
+///
+/// let mut out = Vec::new();
+/// {
+///     let mut encoder = StreamEncoder::new(&mut out);
+///     let mut parser = Parser::new(r#"{ "a" : [1, 2], "b": null }"#.chars());
+///     loop {
+///         let event = match parser.next() {
+///             Some(event) => event,
+///             None => break,
+///         };
+///         encoder.emit_parser_event(parser.stack(), &event).unwrap();
+///     }
+/// }
+/// assert_eq!(out.as_slice(), br#"{"a":[1,2],"b":null}"#);
+/// ```
+pub struct StreamEncoder<'a> {
+    writer: &'a mut (io::Writer+'a),
+    // One element for each object or array that is open.
+    stack: Vec<StreamLevel>,
+    // The number of spaces to indent for each level, if pretty printing.
+    indent: Option<uint>,
+    curr_indent: uint,
+    // True once a complete top-level value has been written.
+    done: bool,
+}
+
+struct StreamLevel {
+    object: bool,
+    // True until the first member or element is written.
+    first: bool,
+    // True if a key was written and its value hasn't been.
+    has_key: bool,
+}
+
+impl<'a> StreamEncoder<'a> {
+    /// Creates a new streaming encoder whose output is written to the
+    /// writer specified on a single line.
+    pub fn new(writer: &'a mut io::Writer) -> StreamEncoder<'a> {
+        StreamEncoder {
+            writer: writer,
+            stack: Vec::new(),
+            indent: None,
+            curr_indent: 0,
+            done: false,
+        }
+    }
+
+    /// Creates a new streaming encoder whose output is written to the
+    /// writer specified in the same human-readable format as `PrettyEncoder`.
+    pub fn new_pretty(writer: &'a mut io::Writer) -> StreamEncoder<'a> {
+        StreamEncoder {
+            writer: writer,
+            stack: Vec::new(),
+            indent: Some(2),
+            curr_indent: 0,
+            done: false,
+        }
+    }
+
+    /// Set the number of spaces to indent for each level when pretty
+    /// printing. This is safe to set during encoding.
+    pub fn set_indent(&mut self, indent: uint) {
+        match self.indent {
+            Some(old) => {
+                let level = self.curr_indent.checked_div(old).unwrap_or(0);
+                self.indent = Some(indent);
+                self.curr_indent = level * indent;
+            }
+            None => {}
+        }
+    }
+
+    /// Returns true once a complete top-level value has been written.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Writes the key of the next member of the object that is open.
+    pub fn emit_key(&mut self, key: &str) -> EncodeResult {
+        let first = match self.stack.last_mut() {
+            Some(level) => {
+                if !level.object || level.has_key {
+                    return Err(invalid_event("a key can only be written in an object"))
+                }
+                let first = level.first;
+                level.first = false;
+                level.has_key = true;
+                first
+            }
+            None => return Err(invalid_event("a key can only be written in an object")),
+        };
+        try!(self.separator(first));
+        try!(escape_str(self.writer, key));
+        match self.indent {
+            Some(_) => write!(self.writer, ": "),
+            None => write!(self.writer, ":"),
+        }
+    }
+
+    /// Writes the event given.
+    pub fn emit_event(&mut self, event: &JsonEvent) -> EncodeResult {
+        match *event {
+            ObjectEnd => self.end_container(true),
+            ArrayEnd => self.end_container(false),
+            ObjectStart => {
+                try!(self.begin_value());
+                self.begin_container(true)
+            }
+            ArrayStart => {
+                try!(self.begin_value());
+                self.begin_container(false)
+            }
+            BooleanValue(v) => {
+                try!(self.begin_value());
+                try!(write!(self.writer, "{}", v));
+                Ok(self.end_value())
+            }
+            I64Value(v) => {
+                try!(self.begin_value());
+                try!(write!(self.writer, "{}", v));
+                Ok(self.end_value())
+            }
+            U64Value(v) => {
+                try!(self.begin_value());
+                try!(write!(self.writer, "{}", v));
+                Ok(self.end_value())
+            }
+            F64Value(v) => {
+                try!(self.begin_value());
+                try!(write!(self.writer, "{}", fmt_number_or_null(v)));
+                Ok(self.end_value())
+            }
+            StringValue(ref v) => {
+                try!(self.begin_value());
+                try!(escape_str(self.writer, v.as_slice()));
+                Ok(self.end_value())
+            }
+            NullValue => {
+                try!(self.begin_value());
+                try!(write!(self.writer, "null"));
+                Ok(self.end_value())
+            }
+            Error(_) => Err(invalid_event("cannot write a parser error")),
+        }
+    }
+
+    /// Writes an event produced by a `Parser`, whose stack is given. If the
+    /// event begins a member of an object, the member's key is taken from the
+    /// top of the stack and written first.
+    pub fn emit_parser_event(&mut self, stack: &Stack, event: &JsonEvent) -> EncodeResult {
+        let needs_key = match self.stack.last() {
+            Some(level) => level.object && !level.has_key,
+            None => false,
+        };
+        match *event {
+            ObjectEnd | ArrayEnd | Error(_) => {}
+            _ if needs_key => {
+                match stack.top() {
+                    Some(Key(key)) => try!(self.emit_key(key)),
+                    _ => return Err(invalid_event("expected a key on the parser stack")),
+                }
+            }
+            _ => {}
+        }
+        self.emit_event(event)
+    }
+
+    // Writes what comes before a member or an element: a comma if it isn't
+    // the first one and, when pretty printing, a new line and indentation.
+    fn separator(&mut self, first: bool) -> EncodeResult {
+        if !first {
+            try!(write!(self.writer, ","));
+        }
+        match self.indent {
+            Some(_) => {
+                try!(write!(self.writer, "\n"));
+                spaces(self.writer, self.curr_indent)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn begin_value(&mut self) -> EncodeResult {
+        if self.done {
+            return Err(invalid_event("only one top-level value can be written"))
+        }
+        let first = match self.stack.last_mut() {
+            None => return Ok(()),
+            Some(level) => {
+                if level.object {
+                    if !level.has_key {
+                        return Err(invalid_event("expected a key before a value in an object"))
+                    }
+                    return Ok(())
+                }
+                let first = level.first;
+                level.first = false;
+                first
+            }
+        };
+        self.separator(first)
+    }
+
+    fn end_value(&mut self) {
+        match self.stack.last_mut() {
+            None => self.done = true,
+            Some(level) => level.has_key = false,
+        }
+    }
+
+    fn begin_container(&mut self, object: bool) -> EncodeResult {
+        self.stack.push(StreamLevel { object: object, first: true, has_key: false });
+        self.curr_indent += self.indent.unwrap_or(0);
+        if object {
+            write!(self.writer, "{{")
+        } else {
+            write!(self.writer, "[")
+        }
+    }
+
+    fn end_container(&mut self, object: bool) -> EncodeResult {
+        let first = match self.stack.last() {
+            Some(level) if level.object == object && !level.has_key => level.first,
+            _ if object => return Err(invalid_event("no object to end")),
+            _ => return Err(invalid_event("no array to end")),
+        };
+        self.stack.pop();
+        self.curr_indent -= self.indent.unwrap_or(0);
+        if !first && self.indent.is_some() {
+            try!(write!(self.writer, "\n"));
+            try!(spaces(self.writer, self.curr_indent));
+        }
+        try!(if object {
+            write!(self.writer, "}}")
+        } else {
+            write!(self.writer, "]")
+        });
+        Ok(self.end_value())
+    }
+}
+
+fn invalid_event(detail: &str) -> io::IoError {
+    io::IoError {
+        kind: io::InvalidInput,
+        desc: "invalid JSON event",
+        detail: Some(detail.to_string()),
+    }
+}
+
 impl<E: ::Encoder<S>, S> Encodable<E, S> for Json {
     fn encode(&self, e: &mut E) -> Result<(), S> {
         match *self {
@@ -968,6 +1295,57 @@ impl Json {
         }
     }
 
+    /// Looks up a value by a JSON Pointer (RFC 6901), such as `/a/0/b`. The
+    /// empty pointer refers to the whole value. Returns None if the pointer
+    /// isn't valid or there is no value at that location.
+    pub fn pointer<'a>(&'a self, pointer: &str) -> Option<&'a Json> {
+        let tokens = match parse_pointer(pointer) {
+            Some(tokens) => tokens,
+            None => return None,
+        };
+        let mut target = self;
+        for token in tokens.iter() {
+            target = match *target {
+                Json::Object(ref map) => match map.get(token.as_slice()) {
+                    Some(value) => value,
+                    None => return None,
+                },
+                Json::Array(ref array) => match array_index(token.as_slice(), array.len(), false) {
+                    Some(idx) => &array[idx],
+                    None => return None,
+                },
+                _ => return None,
+            };
+        }
+        Some(target)
+    }
+
+    /// Like `pointer`, but returns a mutable reference to the value.
+    pub fn pointer_mut<'a>(&'a mut self, pointer: &str) -> Option<&'a mut Json> {
+        match parse_pointer(pointer) {
+            Some(tokens) => find_tokens_mut(self, tokens.as_slice()),
+            None => None,
+        }
+    }
+
+    /// Applies a JSON Patch (RFC 6902), which is an array of `add`, `remove`,
+    /// `replace`, `move`, `copy` and `test` operations, to this value.
+    ///
+    /// Either every operation is applied or, if one of them fails, none are
+    /// and the error is returned.
+    pub fn apply_patch(&mut self, patch: &Json) -> Result<(), PatchError> {
+        let ops = match *patch {
+            Json::Array(ref ops) => ops,
+            _ => return Err(PatchNotArray),
+        };
+        let mut doc = self.clone();
+        for (i, op) in ops.iter().enumerate() {
+            try!(apply_operation(&mut doc, i, op));
+        }
+        *self = doc;
+        Ok(())
+    }
+
     /// Returns true if the Json value is an Object. Returns false otherwise.
     pub fn is_object<'a>(&'a self) -> bool {
         self.as_object().is_some()
@@ -1102,6 +1480,238 @@ impl Json {
     }
 }
 
+// Splits a JSON Pointer into its reference tokens, with `~1` and `~0`
+// unescaped. Returns None if the pointer isn't valid.
+fn parse_pointer(pointer: &str) -> Option<Vec<string::String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    if !pointer.starts_with("/") {
+        return None;
+    }
+    let mut tokens = Vec::new();
+    for part in pointer[1..].split('/') {
+        let mut token = string::String::with_capacity(part.len());
+        let mut chars = part.chars();
+        loop {
+            match chars.next() {
+                Some('~') => match chars.next() {
+                    Some('0') => token.push('~'),
+                    Some('1') => token.push('/'),
+                    _ => return None,
+                },
+                Some(c) => token.push(c),
+                None => break,
+            }
+        }
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+// Parses a reference token that refers to an element of an array of length
+// `len`. The index must be in bounds, unless `append` is true, in which case
+// the index just past the end (which can also be written as `-`) is allowed.
+fn array_index(token: &str, len: uint, append: bool) -> Option<uint> {
+    if token == "-" {
+        return if append { Some(len) } else { None };
+    }
+    // Leading zeros are not allowed.
+    if token.is_empty() || (token.len() > 1 && token.starts_with("0"))
+        || !token.chars().all(|c| c >= '0' && c <= '9') {
+        return None;
+    }
+    match from_str::<uint>(token) {
+        Some(idx) if idx < len || (append && idx == len) => Some(idx),
+        _ => None,
+    }
+}
+
+fn find_tokens_mut<'a>(mut target: &'a mut Json, tokens: &[string::String])
+                       -> Option<&'a mut Json> {
+    for token in tokens.iter() {
+        let current = target;
+        target = match *current {
+            Json::Object(ref mut map) => match map.get_mut(token.as_slice()) {
+                Some(value) => value,
+                None => return None,
+            },
+            Json::Array(ref mut array) => match array_index(token.as_slice(), array.len(), false) {
+                Some(idx) => &mut array[idx],
+                None => return None,
+            },
+            _ => return None,
+        };
+    }
+    Some(target)
+}
+
+// Adds `value` at the location given, replacing an existing member of an
+// object or shifting the elements of an array. Returns false if the parent
+// of the location doesn't exist.
+fn add_tokens(doc: &mut Json, tokens: &[string::String], value: Json) -> bool {
+    let last = match tokens.last() {
+        Some(last) => last.as_slice(),
+        None => {
+            *doc = value;
+            return true;
+        }
+    };
+    match find_tokens_mut(doc, tokens[..tokens.len() - 1]) {
+        Some(parent) => match *parent {
+            Json::Object(ref mut map) => {
+                map.insert(last.to_string(), value);
+                true
+            }
+            Json::Array(ref mut array) => match array_index(last, array.len(), true) {
+                Some(idx) => {
+                    array.insert(idx, value);
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+// Removes and returns the value at the location given. The whole document
+// can't be removed.
+fn remove_tokens(doc: &mut Json, tokens: &[string::String]) -> Option<Json> {
+    let last = match tokens.last() {
+        Some(last) => last.as_slice(),
+        None => return None,
+    };
+    match find_tokens_mut(doc, tokens[..tokens.len() - 1]) {
+        Some(parent) => match *parent {
+            Json::Object(ref mut map) => map.remove(last),
+            Json::Array(ref mut array) => match array_index(last, array.len(), false) {
+                Some(idx) => array.remove(idx),
+                None => None,
+            },
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+// Compares two values for the `test` operation, which considers numbers to be
+// equal if they have the same value, whatever their representation.
+fn patch_eq(a: &Json, b: &Json) -> bool {
+    match (a, b) {
+        (&Json::Array(ref x), &Json::Array(ref y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| patch_eq(a, b))
+        }
+        (&Json::Object(ref x), &Json::Object(ref y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|((ka, va), (kb, vb))| {
+                ka == kb && patch_eq(va, vb)
+            })
+        }
+        (&Json::I64(x), &Json::U64(y)) | (&Json::U64(y), &Json::I64(x)) => {
+            x >= 0 && x as u64 == y
+        }
+        (&Json::F64(x), _) if b.is_number() => b.as_f64() == Some(x),
+        (_, &Json::F64(y)) if a.is_number() => a.as_f64() == Some(y),
+        _ => a == b,
+    }
+}
+
+// Returns the member of the `i`th operation of a patch with the name given.
+fn operation_member<'a>(op: &'a Json, i: uint, name: &str) -> Result<&'a Json, PatchError> {
+    match op.find(name) {
+        Some(value) => Ok(value),
+        None => Err(InvalidOperation(i, format!("missing `{}`", name))),
+    }
+}
+
+// Returns the JSON Pointer in the member of the `i`th operation of a patch
+// with the name given, along with its reference tokens.
+fn operation_pointer<'a>(op: &'a Json, i: uint, name: &str)
+                         -> Result<(&'a str, Vec<string::String>), PatchError> {
+    let pointer = match try!(operation_member(op, i, name)).as_string() {
+        Some(pointer) => pointer,
+        None => return Err(InvalidOperation(i, format!("`{}` must be a string", name))),
+    };
+    match parse_pointer(pointer) {
+        Some(tokens) => Ok((pointer, tokens)),
+        None => Err(InvalidPointer(i, pointer.to_string())),
+    }
+}
+
+fn apply_operation(doc: &mut Json, i: uint, op: &Json) -> Result<(), PatchError> {
+    if !op.is_object() {
+        return Err(InvalidOperation(i, "operation must be an object".to_string()));
+    }
+    let name = match try!(operation_member(op, i, "op")).as_string() {
+        Some(name) => name,
+        None => return Err(InvalidOperation(i, "`op` must be a string".to_string())),
+    };
+    let (path, to) = try!(operation_pointer(op, i, "path"));
+    match name {
+        "add" => {
+            let value = try!(operation_member(op, i, "value")).clone();
+            if !add_tokens(doc, to.as_slice(), value) {
+                return Err(PathNotFound(i, path.to_string()));
+            }
+        }
+        "remove" => {
+            if remove_tokens(doc, to.as_slice()).is_none() {
+                return Err(PathNotFound(i, path.to_string()));
+            }
+        }
+        "replace" => {
+            let value = try!(operation_member(op, i, "value")).clone();
+            match find_tokens_mut(doc, to.as_slice()) {
+                Some(target) => *target = value,
+                None => return Err(PathNotFound(i, path.to_string())),
+            }
+        }
+        "move" => {
+            let (from_path, from) = try!(operation_pointer(op, i, "from"));
+            if find_tokens_mut(doc, from.as_slice()).is_none() {
+                return Err(PathNotFound(i, from_path.to_string()));
+            }
+            if from == to {
+                return Ok(());
+            }
+            if to.len() > from.len() && to[..from.len()] == from.as_slice() {
+                return Err(MoveIntoChild(i, path.to_string()));
+            }
+            let value = match remove_tokens(doc, from.as_slice()) {
+                Some(value) => value,
+                None => return Err(PathNotFound(i, from_path.to_string())),
+            };
+            if !add_tokens(doc, to.as_slice(), value) {
+                return Err(PathNotFound(i, path.to_string()));
+            }
+        }
+        "copy" => {
+            let (from_path, from) = try!(operation_pointer(op, i, "from"));
+            let value = match find_tokens_mut(doc, from.as_slice()) {
+                Some(value) => value.clone(),
+                None => return Err(PathNotFound(i, from_path.to_string())),
+            };
+            if !add_tokens(doc, to.as_slice(), value) {
+                return Err(PathNotFound(i, path.to_string()));
+            }
+        }
+        "test" => {
+            let value = try!(operation_member(op, i, "value"));
+            match find_tokens_mut(doc, to.as_slice()) {
+                Some(target) => {
+                    if !patch_eq(&*target, value) {
+                        return Err(TestFailed(i, path.to_string()));
+                    }
+                }
+                None => return Err(PathNotFound(i, path.to_string())),
+            }
+        }
+        _ => return Err(InvalidOperation(i, format!("unknown op `{}`", name))),
+    }
+    Ok(())
+}
+
 impl<'a> ops::Index<&'a str, Json>  for Json {
     fn index<'a>(&'a self, idx: & &str) -> &'a Json {
         self.find(*idx).unwrap()
@@ -1296,6 +1906,8 @@ pub struct Parser<T> {
     ch: Option<char>,
     line: uint,
     col: uint,
+    // The line and column where the last value started.
+    value_start: (uint, uint),
     // We maintain a stack representing where we are in the logical structure
     // of the JSON stream.
     stack: Stack,
@@ -1332,6 +1944,7 @@ impl<T: Iterator<char>> Parser<T> {
             ch: Some('\x00'),
             line: 1,
             col: 0,
+            value_start: (1, 0),
             stack: Stack::new(),
             state: ParseStart,
         };
@@ -1803,6 +2416,7 @@ impl<T: Iterator<char>> Parser<T> {
     }
 
     fn parse_value(&mut self) -> JsonEvent {
+        self.value_start = (self.line, self.col);
         if self.eof() { return self.error_event(EOFWhileParsingValue); }
         match self.ch_or_null() {
             'n' => { self.parse_ident("ull", NullValue) }
@@ -1955,12 +2569,29 @@ pub fn from_str(s: &str) -> Result<Json, BuilderError> {
 /// A structure to decode JSON to values in rust.
 pub struct Decoder {
     stack: Vec<Json>,
+    // The path to the value that the last error was about, innermost element
+    // first. It's only built as the error is returned through the values that
+    // contain it, so that decoding doesn't pay for it when there's no error.
+    error_path: Option<Vec<PathElement>>,
+    // The key of the map entry being decoded.
+    map_key: Option<string::String>,
+}
+
+// An element of the path to a value in a document.
+#[deriving(Clone)]
+enum PathElement {
+    PathKey(string::String),
+    PathIndex(uint),
 }
 
 impl Decoder {
     /// Creates a new decoder instance for decoding the specified JSON value.
     pub fn new(json: Json) -> Decoder {
-        Decoder { stack: vec![json] }
+        Decoder {
+            stack: vec![json],
+            error_path: None,
+            map_key: None,
+        }
     }
 }
 
@@ -1968,22 +2599,117 @@ impl Decoder {
     fn pop(&mut self) -> Json {
         self.stack.pop().unwrap()
     }
+
+    // Records that `err` is about the value being decoded.
+    fn fail(&mut self, err: DecoderError) -> DecoderError {
+        self.error_path = Some(vec![]);
+        err
+    }
+
+    // Adds `elt` to the path of the error being returned.
+    fn add_to_error_path(&mut self, elt: PathElement) {
+        match self.error_path {
+            Some(ref mut path) => path.push(elt),
+            None => {}
+        }
+    }
+
+    // Decodes a value that is at index `idx` in the value being decoded.
+    fn nested_index<T, F>(&mut self, idx: uint, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder) -> DecodeResult<T>,
+    {
+        let value = f(self);
+        if value.is_err() {
+            self.add_to_error_path(PathIndex(idx));
+        }
+        value
+    }
+
+    // Decodes a value that is at `key` in the value being decoded.
+    fn nested_key<T, F>(&mut self, key: &str, f: F) -> DecodeResult<T> where
+        F: FnOnce(&mut Decoder) -> DecodeResult<T>,
+    {
+        let value = f(self);
+        if value.is_err() {
+            self.add_to_error_path(PathKey(key.to_string()));
+        }
+        value
+    }
+}
+
+// Formats a path like `.servers[3].port`. Keys that aren't identifiers are
+// written as JSON strings in brackets, e.g. `["a key"]`.
+fn format_path(path: &[PathElement]) -> string::String {
+    let mut s = string::String::new();
+    for elt in path.iter() {
+        match *elt {
+            PathIndex(i) => s.push_str(format!("[{}]", i).as_slice()),
+            PathKey(ref key) => {
+                let ident = key.len() > 0 && key.chars().all(|c| match c {
+                    'a' ... 'z' | 'A' ... 'Z' | '0' ... '9' | '_' => true,
+                    _ => false,
+                });
+                if ident {
+                    s.push('.');
+                    s.push_str(key.as_slice());
+                } else {
+                    s.push_str(format!("[{}]", Json::String(key.clone())).as_slice());
+                }
+            }
+        }
+    }
+    s
+}
+
+// Finds the line and column where the value at `path` starts in `src`. If
+// there is no such value (e.g., for a missing field), the position of the
+// innermost value on the path that exists is returned instead.
+fn locate(src: &str, path: &[PathElement]) -> Option<(uint, uint)> {
+    let mut parser = Parser::new(src.chars());
+    let mut found = None;
+    let mut depth = 0;
+    loop {
+        match parser.next() {
+            None | Some(Error(_)) => break,
+            Some(ArrayEnd) | Some(ObjectEnd) => {}
+            Some(_) => {
+                let stack = parser.stack();
+                let n = stack.len();
+                if n > path.len() || n < depth {
+                    continue
+                }
+                let on_path = range(0, n).all(|i| {
+                    match (stack.get(i), &path[i]) {
+                        (Index(a), &PathIndex(b)) => a as uint == b,
+                        (Key(k), &PathKey(ref key)) => k == key.as_slice(),
+                        _ => false,
+                    }
+                });
+                if on_path {
+                    // Later duplicate keys win, as they do when building.
+                    found = Some(parser.value_start);
+                    depth = n;
+                }
+            }
+        }
+    }
+    found
 }
 
 macro_rules! expect(
-    ($e:expr, Null) => ({
+    ($d:expr, $e:expr, Null) => ({
         match $e {
             Json::Null => Ok(()),
-            other => Err(ExpectedError("Null".into_string(),
-                                       format!("{}", other)))
+            other => Err($d.fail(ExpectedError("Null".into_string(),
+                                                  format!("{}", other))))
         }
     });
-    ($e:expr, $t:ident) => ({
+    ($d:expr, $e:expr, $t:ident) => ({
         match $e {
             Json::$t(v) => Ok(v),
             other => {
-                Err(ExpectedError(stringify!($t).to_string(),
-                                  format!("{}", other)))
+                Err($d.fail(ExpectedError(stringify!($t).to_string(),
+                                             format!("{}", other))))
             }
         }
     })
@@ -1992,24 +2718,25 @@ macro_rules! expect(
 macro_rules! read_primitive {
     ($name:ident, $ty:ty) => {
         fn $name(&mut self) -> DecodeResult<$ty> {
-            match self.pop() {
+            let err = match self.pop() {
                 Json::I64(f) => match num::cast(f) {
-                    Some(f) => Ok(f),
-                    None => Err(ExpectedError("Number".into_string(), format!("{}", f))),
+                    Some(f) => return Ok(f),
+                    None => ExpectedError("Number".into_string(), format!("{}", f)),
                 },
                 Json::U64(f) => match num::cast(f) {
-                    Some(f) => Ok(f),
-                    None => Err(ExpectedError("Number".into_string(), format!("{}", f))),
+                    Some(f) => return Ok(f),
+                    None => ExpectedError("Number".into_string(), format!("{}", f)),
                 },
-                Json::F64(f) => Err(ExpectedError("Integer".into_string(), format!("{}", f))),
+                Json::F64(f) => ExpectedError("Integer".into_string(), format!("{}", f)),
                 // re: #12967.. a type w/ numeric keys (ie HashMap<uint, V> etc)
                 // is going to have a string here, as per JSON spec.
                 Json::String(s) => match std::str::from_str(s.as_slice()) {
-                    Some(f) => Ok(f),
-                    None => Err(ExpectedError("Number".into_string(), s)),
+                    Some(f) => return Ok(f),
+                    None => ExpectedError("Number".into_string(), s),
                 },
-                value => Err(ExpectedError("Number".into_string(), format!("{}", value))),
-            }
+                value => ExpectedError("Number".into_string(), format!("{}", value)),
+            };
+            Err(self.fail(err))
         }
     }
 }
@@ -2017,7 +2744,7 @@ macro_rules! read_primitive {
 impl ::Decoder<DecoderError> for Decoder {
     fn read_nil(&mut self) -> DecodeResult<()> {
         debug!("read_nil");
-        expect!(self.pop(), Null)
+        expect!(self, self.pop(), Null)
     }
 
     read_primitive!(read_uint, uint)
@@ -2035,26 +2762,27 @@ impl ::Decoder<DecoderError> for Decoder {
 
     fn read_f64(&mut self) -> DecodeResult<f64> {
         debug!("read_f64");
-        match self.pop() {
-            Json::I64(f) => Ok(f as f64),
-            Json::U64(f) => Ok(f as f64),
-            Json::F64(f) => Ok(f),
+        let err = match self.pop() {
+            Json::I64(f) => return Ok(f as f64),
+            Json::U64(f) => return Ok(f as f64),
+            Json::F64(f) => return Ok(f),
             Json::String(s) => {
                 // re: #12967.. a type w/ numeric keys (ie HashMap<uint, V> etc)
                 // is going to have a string here, as per JSON spec.
                 match std::str::from_str(s.as_slice()) {
-                    Some(f) => Ok(f),
-                    None => Err(ExpectedError("Number".into_string(), s)),
+                    Some(f) => return Ok(f),
+                    None => ExpectedError("Number".into_string(), s),
                 }
             },
-            Json::Null => Ok(f64::NAN),
-            value => Err(ExpectedError("Number".into_string(), format!("{}", value)))
-        }
+            Json::Null => return Ok(f64::NAN),
+            value => ExpectedError("Number".into_string(), format!("{}", value))
+        };
+        Err(self.fail(err))
     }
 
     fn read_bool(&mut self) -> DecodeResult<bool> {
         debug!("read_bool");
-        expect!(self.pop(), Boolean)
+        expect!(self, self.pop(), Boolean)
     }
 
     fn read_char(&mut self) -> DecodeResult<char> {
@@ -2067,12 +2795,13 @@ impl ::Decoder<DecoderError> for Decoder {
                 _ => ()
             }
         }
-        Err(ExpectedError("single character string".into_string(), format!("{}", s)))
+        Err(self.fail(ExpectedError("single character string".into_string(),
+                                       format!("{}", s))))
     }
 
     fn read_str(&mut self) -> DecodeResult<string::String> {
         debug!("read_str");
-        expect!(self.pop(), String)
+        expect!(self, self.pop(), String)
     }
 
     fn read_enum<T, F>(&mut self, name: &str, f: F) -> DecodeResult<T> where
@@ -2092,10 +2821,11 @@ impl ::Decoder<DecoderError> for Decoder {
                 let n = match o.remove(&"variant".into_string()) {
                     Some(Json::String(s)) => s,
                     Some(val) => {
-                        return Err(ExpectedError("String".into_string(), format!("{}", val)))
+                        let err = ExpectedError("String".into_string(), format!("{}", val));
+                        return Err(self.fail(err))
                     }
                     None => {
-                        return Err(MissingFieldError("variant".into_string()))
+                        return Err(self.fail(MissingFieldError("variant".into_string())))
                     }
                 };
                 match o.remove(&"fields".into_string()) {
@@ -2105,22 +2835,24 @@ impl ::Decoder<DecoderError> for Decoder {
                         }
                     },
                     Some(val) => {
-                        return Err(ExpectedError("Array".into_string(), format!("{}", val)))
+                        let err = ExpectedError("Array".into_string(), format!("{}", val));
+                        return Err(self.fail(err))
                     }
                     None => {
-                        return Err(MissingFieldError("fields".into_string()))
+                        return Err(self.fail(MissingFieldError("fields".into_string())))
                     }
                 }
                 n
             }
             json => {
-                return Err(ExpectedError("String or Object".into_string(), format!("{}", json)))
+                let err = ExpectedError("String or Object".into_string(), format!("{}", json));
+                return Err(self.fail(err))
             }
         };
         let idx = match names.iter()
                              .position(|n| str::eq_slice(*n, name.as_slice())) {
             Some(idx) => idx,
-            None => return Err(UnknownVariantError(name))
+            None => return Err(self.fail(UnknownVariantError(name)))
         };
         f(self, idx)
     }
@@ -2129,7 +2861,8 @@ impl ::Decoder<DecoderError> for Decoder {
         F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
         debug!("read_enum_variant_arg(idx={})", idx);
-        f(self)
+        // The arguments are in the `fields` member of the enum's object.
+        self.nested_key("fields", move |d| d.nested_index(idx, f))
     }
 
     fn read_enum_struct_variant<T, F>(&mut self, names: &[&str], f: F) -> DecodeResult<T> where
@@ -2168,7 +2901,7 @@ impl ::Decoder<DecoderError> for Decoder {
         F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
         debug!("read_struct_field(name={}, idx={})", name, idx);
-        let mut obj = try!(expect!(self.pop(), Object));
+        let mut obj = try!(expect!(self, self.pop(), Object));
 
         let value = match obj.remove(&name.to_string()) {
             None => {
@@ -2177,12 +2910,16 @@ impl ::Decoder<DecoderError> for Decoder {
                 self.stack.push(Json::Null);
                 match f(self) {
                     Ok(x) => x,
-                    Err(_) => return Err(MissingFieldError(name.to_string())),
+                    Err(_) => {
+                        let err = self.fail(MissingFieldError(name.to_string()));
+                        self.add_to_error_path(PathKey(name.to_string()));
+                        return Err(err)
+                    }
                 }
             },
             Some(json) => {
                 self.stack.push(json);
-                try!(f(self))
+                try!(self.nested_key(name, f))
             }
         };
         self.stack.push(Json::Object(obj));
@@ -2197,7 +2934,8 @@ impl ::Decoder<DecoderError> for Decoder {
             if len == tuple_len {
                 f(d)
             } else {
-                Err(ExpectedError(format!("Tuple{}", tuple_len), format!("Tuple{}", len)))
+                Err(d.fail(ExpectedError(format!("Tuple{}", tuple_len),
+                                            format!("Tuple{}", len))))
             }
         })
     }
@@ -2244,7 +2982,7 @@ impl ::Decoder<DecoderError> for Decoder {
         F: FnOnce(&mut Decoder, uint) -> DecodeResult<T>,
     {
         debug!("read_seq()");
-        let array = try!(expect!(self.pop(), Array));
+        let array = try!(expect!(self, self.pop(), Array));
         let len = array.len();
         for v in array.into_iter().rev() {
             self.stack.push(v);
@@ -2256,14 +2994,14 @@ impl ::Decoder<DecoderError> for Decoder {
         F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
         debug!("read_seq_elt(idx={})", idx);
-        f(self)
+        self.nested_index(idx, f)
     }
 
     fn read_map<T, F>(&mut self, f: F) -> DecodeResult<T> where
         F: FnOnce(&mut Decoder, uint) -> DecodeResult<T>,
     {
         debug!("read_map()");
-        let obj = try!(expect!(self.pop(), Object));
+        let obj = try!(expect!(self, self.pop(), Object));
         let len = obj.len();
         for (key, value) in obj.into_iter() {
             self.stack.push(value);
@@ -2276,18 +3014,32 @@ impl ::Decoder<DecoderError> for Decoder {
        F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
         debug!("read_map_elt_key(idx={})", idx);
-        f(self)
+        self.map_key = match self.stack.last() {
+            Some(&Json::String(ref key)) => Some(key.clone()),
+            _ => None,
+        };
+        let value = f(self);
+        if value.is_err() {
+            let key = self.map_key.take().unwrap_or(string::String::new());
+            self.add_to_error_path(PathKey(key));
+        }
+        value
     }
 
     fn read_map_elt_val<T, F>(&mut self, idx: uint, f: F) -> DecodeResult<T> where
        F: FnOnce(&mut Decoder) -> DecodeResult<T>,
     {
         debug!("read_map_elt_val(idx={})", idx);
-        f(self)
+        let key = self.map_key.take();
+        let value = f(self);
+        if value.is_err() {
+            self.add_to_error_path(PathKey(key.unwrap_or(string::String::new())));
+        }
+        value
     }
 
     fn error(&mut self, err: &str) -> DecoderError {
-        ApplicationError(err.to_string())
+        self.fail(ApplicationError(err.to_string()))
     }
 }
 
@@ -2443,12 +3195,14 @@ mod tests {
     use super::ErrorCode::*;
     use super::ParserError::*;
     use super::DecoderError::*;
+    use super::PatchError::*;
     use super::JsonEvent::*;
     use super::ParserState::*;
     use super::StackElement::*;
     use super::InternalStackElement::*;
-    use super::{PrettyEncoder, Json, from_str, DecodeResult, DecoderError, JsonEvent, Parser,
-                StackElement, Stack, Encoder, Decoder};
+    use super::{PrettyEncoder, Json, from_str, DecoderError, JsonEvent, Parser,
+                StackElement, Stack, Encoder, Decoder, StreamEncoder, PatchError,
+                LocatedError};
     use std::{i64, u64, f32, f64, io};
     use std::collections::TreeMap;
     use std::num::Float;
@@ -2475,9 +3229,9 @@ mod tests {
 
     #[test]
     fn test_decode_option_malformed() {
-        check_err::<OptionData>("{ \"opt\": [] }",
+        check_err::<OptionData>("{ \"opt\": [] }", ".opt",
                                 ExpectedError("Number".into_string(), "[]".into_string()));
-        check_err::<OptionData>("{ \"opt\": false }",
+        check_err::<OptionData>("{ \"opt\": false }", ".opt",
                                 ExpectedError("Number".into_string(), "false".into_string()));
     }
 
@@ -2864,8 +3618,8 @@ mod tests {
         let v: i64 = super::decode("9223372036854775807").unwrap();
         assert_eq!(v, i64::MAX);
 
-        let res: DecodeResult<i64> = super::decode("765.25252");
-        assert_eq!(res, Err(ExpectedError("Integer".into_string(), "765.25252".into_string())));
+        let res = super::decode::<i64>("765.25252").unwrap_err().error;
+        assert_eq!(res, ExpectedError("Integer".into_string(), "765.25252".into_string()));
     }
 
     #[test]
@@ -3098,47 +3852,95 @@ mod tests {
         B(string::String)
     }
     fn check_err<T: Decodable<Decoder, DecoderError>>(to_parse: &'static str,
+                                                      path: &'static str,
                                                       expected: DecoderError) {
-        let res: DecodeResult<T> = match from_str(to_parse) {
-            Err(e) => Err(ParseError(e)),
-            Ok(json) => Decodable::decode(&mut Decoder::new(json))
-        };
-        match res {
+        match super::decode::<T>(to_parse) {
             Ok(_) => panic!("`{}` parsed & decoded ok, expecting error `{}`",
                               to_parse, expected),
-            Err(ParseError(e)) => panic!("`{}` is not valid json: {}",
-                                           to_parse, e),
+            Err(LocatedError { error: ParseError(e), .. }) => panic!("`{}` is not valid json: {}",
+                                                                    to_parse, e),
             Err(e) => {
-                assert_eq!(e, expected);
+                assert_eq!(e.error, expected);
+                assert_eq!(e.path.as_slice(), path);
             }
         }
     }
     #[test]
     fn test_decode_errors_struct() {
-        check_err::<DecodeStruct>("[]", ExpectedError("Object".into_string(), "[]".into_string()));
-        check_err::<DecodeStruct>("{\"x\": true, \"y\": true, \"z\": \"\", \"w\": []}",
+        check_err::<DecodeStruct>("[]", "",
+                                  ExpectedError("Object".into_string(), "[]".into_string()));
+        check_err::<DecodeStruct>("{\"x\": true, \"y\": true, \"z\": \"\", \"w\": []}", ".x",
                                   ExpectedError("Number".into_string(), "true".into_string()));
-        check_err::<DecodeStruct>("{\"x\": 1, \"y\": [], \"z\": \"\", \"w\": []}",
+        check_err::<DecodeStruct>("{\"x\": 1, \"y\": [], \"z\": \"\", \"w\": []}", ".y",
                                   ExpectedError("Boolean".into_string(), "[]".into_string()));
-        check_err::<DecodeStruct>("{\"x\": 1, \"y\": true, \"z\": {}, \"w\": []}",
+        check_err::<DecodeStruct>("{\"x\": 1, \"y\": true, \"z\": {}, \"w\": []}", ".z",
                                   ExpectedError("String".into_string(), "{}".into_string()));
-        check_err::<DecodeStruct>("{\"x\": 1, \"y\": true, \"z\": \"\", \"w\": null}",
+        check_err::<DecodeStruct>("{\"x\": 1, \"y\": true, \"z\": \"\", \"w\": null}", ".w",
                                   ExpectedError("Array".into_string(), "null".into_string()));
-        check_err::<DecodeStruct>("{\"x\": 1, \"y\": true, \"z\": \"\"}",
+        check_err::<DecodeStruct>("{\"x\": 1, \"y\": true, \"z\": \"\"}", ".w",
                                   MissingFieldError("w".into_string()));
+        check_err::<DecodeStruct>("{\"x\": 1, \"y\": true, \"z\": \"\", \"w\": [{\"x\": 1, \"y\": true, \"z\": \"\", \"w\": []}, {\"x\": 1, \"y\": 2, \"z\": \"\", \"w\": []}]}",
+                                  ".w[1].y",
+                                  ExpectedError("Boolean".into_string(), "2".into_string()));
     }
     #[test]
     fn test_decode_errors_enum() {
-        check_err::<DecodeEnum>("{}",
+        check_err::<DecodeEnum>("{}", "",
                                 MissingFieldError("variant".into_string()));
-        check_err::<DecodeEnum>("{\"variant\": 1}",
+        check_err::<DecodeEnum>("{\"variant\": 1}", "",
                                 ExpectedError("String".into_string(), "1".into_string()));
-        check_err::<DecodeEnum>("{\"variant\": \"A\"}",
+        check_err::<DecodeEnum>("{\"variant\": \"A\"}", "",
                                 MissingFieldError("fields".into_string()));
-        check_err::<DecodeEnum>("{\"variant\": \"A\", \"fields\": null}",
+        check_err::<DecodeEnum>("{\"variant\": \"A\", \"fields\": null}", "",
                                 ExpectedError("Array".into_string(), "null".into_string()));
-        check_err::<DecodeEnum>("{\"variant\": \"C\", \"fields\": []}",
+        check_err::<DecodeEnum>("{\"variant\": \"C\", \"fields\": []}", "",
                                 UnknownVariantError("C".into_string()));
+        check_err::<DecodeEnum>("{\"variant\": \"A\", \"fields\": [\"x\"]}", ".fields[0]",
+                                ExpectedError("Number".into_string(), "x".into_string()));
+    }
+
+    #[deriving(Decodable, Show)]
+    struct Server {
+        host: string::String,
+        port: u16,
+    }
+    #[deriving(Decodable, Show)]
+    struct Config {
+        name: string::String,
+        servers: Vec<Server>,
+    }
+
+    #[test]
+    fn test_decode_error_location() {
+        use std::collections::HashMap;
+        let src = "{\n  \"name\": \"prod\",\n  \"servers\": [\n    \
+                   {\"host\": \"a\", \"port\": 80},\n    \
+                   {\"host\": \"b\", \"port\": \"http\"}\n  ]\n}";
+        let loc = super::decode::<Config>(src).unwrap_err();
+        assert_eq!(loc.error, ExpectedError("Number".into_string(), "http".into_string()));
+        assert_eq!(loc.path.as_slice(), ".servers[1].port");
+        assert_eq!(loc.position, Some((5, 28)));
+
+        // A missing field is located at the object it is missing from.
+        let src = "{\"name\": \"prod\", \"servers\": [\n {\"host\": \"a\"}]}";
+        let loc = super::decode::<Config>(src).unwrap_err();
+        assert_eq!(loc, LocatedError { error: MissingFieldError("port".into_string()),
+                                       path: ".servers[0].port".into_string(),
+                                       position: Some((2, 3)) });
+
+        // Later duplicate keys are the ones that are decoded.
+        let src = "{\"name\": 1, \"name\": 2, \"servers\": []}";
+        let loc = super::decode::<Config>(src).unwrap_err();
+        assert_eq!(loc.position, Some((1, 21)));
+
+        let loc = super::decode::<HashMap<string::String, uint>>("{\"a b\": true}").unwrap_err();
+        assert_eq!(loc.path.as_slice(), "[\"a b\"]");
+        assert_eq!(loc.position, Some((1, 9)));
+
+        let loc = super::decode::<Config>("[1,").unwrap_err();
+        assert_eq!(loc, LocatedError { error: ParseError(SyntaxError(EOFWhileParsingValue, 1, 4)),
+                                       path: "".into_string(),
+                                       position: Some((1, 4)) });
     }
 
     #[test]
@@ -3424,6 +4226,9 @@ mod tests {
         let mut decoder = Decoder::new(json_obj);
         let result: Result<HashMap<uint, bool>, DecoderError> = Decodable::decode(&mut decoder);
         assert_eq!(result, Err(ExpectedError("Number".into_string(), "a".into_string())));
+
+        let result = super::decode::<HashMap<uint, bool>>(json_str);
+        assert_eq!(result.unwrap_err().path.as_slice(), ".a");
     }
 
     fn assert_stream_equal(src: &str,
@@ -3714,6 +4519,258 @@ mod tests {
         assert!(stack.get(1) == Key("foo"));
     }
 
+    fn stream_encode(src: &str, pretty: bool) -> string::String {
+        let mut out = Vec::new();
+        {
+            let mut encoder = if pretty {
+                StreamEncoder::new_pretty(&mut out)
+            } else {
+                StreamEncoder::new(&mut out)
+            };
+            let mut parser = Parser::new(src.chars());
+            loop {
+                let evt = match parser.next() {
+                    Some(e) => e,
+                    None => break,
+                };
+                encoder.emit_parser_event(parser.stack(), &evt).unwrap();
+            }
+            assert!(encoder.is_done());
+        }
+        string::String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_stream_encoder() {
+        // Keys are sorted so that the output matches encoding a `Json`.
+        let srcs = [
+            "null",
+            "-3",
+            "1.5",
+            r#""a\"b\n""#,
+            "[]",
+            "{}",
+            "[[], {}, [[]]]",
+            r#"{ "a": 1, "b": [true, false, null], "c": { "d": { "e": [] } } }"#,
+            r#"[ { "a": 18446744073709551615 }, -9223372036854775808, 0.25 ]"#,
+        ];
+        for src in srcs.iter() {
+            let json = from_str(*src).unwrap();
+            assert_eq!(stream_encode(*src, false), json.to_string());
+            assert_eq!(stream_encode(*src, true), json.to_pretty_str());
+        }
+    }
+
+    #[test]
+    fn test_stream_encoder_events() {
+        let mut out = Vec::new();
+        {
+            let mut encoder = StreamEncoder::new_pretty(&mut out);
+            encoder.set_indent(4);
+            encoder.emit_event(&ObjectStart).unwrap();
+            encoder.emit_key("name").unwrap();
+            encoder.emit_event(&StringValue("x".into_string())).unwrap();
+            encoder.emit_key("list").unwrap();
+            encoder.emit_event(&ArrayStart).unwrap();
+            encoder.emit_event(&U64Value(1)).unwrap();
+            encoder.emit_event(&F64Value(2.5)).unwrap();
+            encoder.emit_event(&ArrayEnd).unwrap();
+            assert!(!encoder.is_done());
+            encoder.emit_event(&ObjectEnd).unwrap();
+            assert!(encoder.is_done());
+        }
+        assert_eq!(string::String::from_utf8(out).unwrap().as_slice(),
+                   "{\n    \"name\": \"x\",\n    \"list\": [\n        1,\n        2.5\n    ]\n}");
+    }
+
+    #[test]
+    fn test_stream_encoder_errors() {
+        let mut out = Vec::new();
+        let mut encoder = StreamEncoder::new(&mut out);
+        assert!(encoder.emit_key("a").is_err());
+        assert!(encoder.emit_event(&ObjectEnd).is_err());
+        encoder.emit_event(&ObjectStart).unwrap();
+        // A value needs a key in an object.
+        assert!(encoder.emit_event(&NullValue).is_err());
+        assert!(encoder.emit_event(&ArrayEnd).is_err());
+        encoder.emit_key("a").unwrap();
+        assert!(encoder.emit_key("b").is_err());
+        assert!(encoder.emit_event(&ObjectEnd).is_err());
+        encoder.emit_event(&NullValue).unwrap();
+        assert!(encoder.emit_event(&Error(SyntaxError(InvalidSyntax, 1, 1))).is_err());
+        encoder.emit_event(&ObjectEnd).unwrap();
+        // Only one top-level value.
+        assert!(encoder.emit_event(&NullValue).is_err());
+    }
+
+    #[test]
+    fn test_pointer() {
+        // The examples from RFC 6901, section 5.
+        let doc = from_str(r#"{
+            "foo": ["bar", "baz"],
+            "": 0,
+            "a/b": 1,
+            "c%d": 2,
+            "e^f": 3,
+            "g|h": 4,
+            "i\\j": 5,
+            "k\"l": 6,
+            " ": 7,
+            "m~n": 8
+        }"#).unwrap();
+        assert_eq!(doc.pointer(""), Some(&doc));
+        assert_eq!(doc.pointer("/foo"),
+                   Some(&Array(vec![String("bar".into_string()), String("baz".into_string())])));
+        assert_eq!(doc.pointer("/foo/0"), Some(&String("bar".into_string())));
+        assert_eq!(doc.pointer("/"), Some(&U64(0)));
+        assert_eq!(doc.pointer("/a~1b"), Some(&U64(1)));
+        assert_eq!(doc.pointer("/c%d"), Some(&U64(2)));
+        assert_eq!(doc.pointer("/e^f"), Some(&U64(3)));
+        assert_eq!(doc.pointer("/g|h"), Some(&U64(4)));
+        assert_eq!(doc.pointer("/i\\j"), Some(&U64(5)));
+        assert_eq!(doc.pointer("/k\"l"), Some(&U64(6)));
+        assert_eq!(doc.pointer("/ "), Some(&U64(7)));
+        assert_eq!(doc.pointer("/m~0n"), Some(&U64(8)));
+
+        assert_eq!(doc.pointer("foo"), None);
+        assert_eq!(doc.pointer("/foo/2"), None);
+        assert_eq!(doc.pointer("/foo/-"), None);
+        assert_eq!(doc.pointer("/foo/01"), None);
+        assert_eq!(doc.pointer("/foo/0/x"), None);
+        assert_eq!(doc.pointer("/m~2n"), None);
+        assert_eq!(doc.pointer("/nope"), None);
+    }
+
+    #[test]
+    fn test_pointer_mut() {
+        let mut doc = from_str(r#"{"a": [1, {"b": 2}]}"#).unwrap();
+        *doc.pointer_mut("/a/1/b").unwrap() = Boolean(true);
+        assert_eq!(doc, from_str(r#"{"a": [1, {"b": true}]}"#).unwrap());
+        assert!(doc.pointer_mut("/a/2").is_none());
+    }
+
+    fn check_patch(doc: &str, patch: &str, expected: &str) {
+        let mut doc = from_str(doc).unwrap();
+        doc.apply_patch(&from_str(patch).unwrap()).unwrap();
+        assert_eq!(doc, from_str(expected).unwrap());
+    }
+
+    fn check_patch_err(doc: &str, patch: &str, expected: PatchError) {
+        let mut json = from_str(doc).unwrap();
+        assert_eq!(json.apply_patch(&from_str(patch).unwrap()), Err(expected));
+        // Nothing is applied when an operation fails.
+        assert_eq!(json, from_str(doc).unwrap());
+    }
+
+    // The examples from RFC 6902, appendix A.
+    #[test]
+    fn test_patch_rfc_examples() {
+        // A.1. Adding an Object Member
+        check_patch(r#"{"foo": "bar"}"#,
+                    r#"[{"op": "add", "path": "/baz", "value": "qux"}]"#,
+                    r#"{"baz": "qux", "foo": "bar"}"#);
+        // A.2. Adding an Array Element
+        check_patch(r#"{"foo": ["bar", "baz"]}"#,
+                    r#"[{"op": "add", "path": "/foo/1", "value": "qux"}]"#,
+                    r#"{"foo": ["bar", "qux", "baz"]}"#);
+        // A.3. Removing an Object Member
+        check_patch(r#"{"baz": "qux", "foo": "bar"}"#,
+                    r#"[{"op": "remove", "path": "/baz"}]"#,
+                    r#"{"foo": "bar"}"#);
+        // A.4. Removing an Array Element
+        check_patch(r#"{"foo": ["bar", "qux", "baz"]}"#,
+                    r#"[{"op": "remove", "path": "/foo/1"}]"#,
+                    r#"{"foo": ["bar", "baz"]}"#);
+        // A.5. Replacing a Value
+        check_patch(r#"{"baz": "qux", "foo": "bar"}"#,
+                    r#"[{"op": "replace", "path": "/baz", "value": "boo"}]"#,
+                    r#"{"baz": "boo", "foo": "bar"}"#);
+        // A.6. Moving a Value
+        check_patch(r#"{"foo": {"bar": "baz", "waldo": "fred"}, "qux": {"corge": "grault"}}"#,
+                    r#"[{"op": "move", "from": "/foo/waldo", "path": "/qux/thud"}]"#,
+                    r#"{"foo": {"bar": "baz"}, "qux": {"corge": "grault", "thud": "fred"}}"#);
+        // A.7. Moving an Array Element
+        check_patch(r#"{"foo": ["all", "grass", "cows", "eat"]}"#,
+                    r#"[{"op": "move", "from": "/foo/1", "path": "/foo/3"}]"#,
+                    r#"{"foo": ["all", "cows", "eat", "grass"]}"#);
+        // A.8. Testing a Value: Success
+        check_patch(r#"{"baz": "qux", "foo": ["a", 2, "c"]}"#,
+                    r#"[{"op": "test", "path": "/baz", "value": "qux"},
+                        {"op": "test", "path": "/foo/1", "value": 2}]"#,
+                    r#"{"baz": "qux", "foo": ["a", 2, "c"]}"#);
+        // A.9. Testing a Value: Error
+        check_patch_err(r#"{"baz": "qux"}"#,
+                        r#"[{"op": "test", "path": "/baz", "value": "bar"}]"#,
+                        TestFailed(0, "/baz".into_string()));
+        // A.10. Adding a Nested Member Object
+        check_patch(r#"{"foo": "bar"}"#,
+                    r#"[{"op": "add", "path": "/child", "value": {"grandchild": {}}}]"#,
+                    r#"{"foo": "bar", "child": {"grandchild": {}}}"#);
+        // A.11. Ignoring Unrecognized Elements
+        check_patch(r#"{"foo": "bar"}"#,
+                    r#"[{"op": "add", "path": "/baz", "value": "qux", "xyz": 123}]"#,
+                    r#"{"foo": "bar", "baz": "qux"}"#);
+        // A.12. Adding to a Nonexistent Target
+        check_patch_err(r#"{"foo": "bar"}"#,
+                        r#"[{"op": "add", "path": "/baz/bat", "value": "qux"}]"#,
+                        PathNotFound(0, "/baz/bat".into_string()));
+        // A.14. ~ Escape Ordering
+        check_patch(r#"{"/": 9, "~1": 10}"#,
+                    r#"[{"op": "test", "path": "/~01", "value": 10}]"#,
+                    r#"{"/": 9, "~1": 10}"#);
+        // A.15. Comparing Strings and Numbers
+        check_patch_err(r#"{"/": 9, "~1": 10}"#,
+                        r#"[{"op": "test", "path": "/~01", "value": "10"}]"#,
+                        TestFailed(0, "/~01".into_string()));
+        // A.16. Adding an Array Value
+        check_patch(r#"{"foo": ["bar"]}"#,
+                    r#"[{"op": "add", "path": "/foo/-", "value": ["abc", "def"]}]"#,
+                    r#"{"foo": ["bar", ["abc", "def"]]}"#);
+    }
+
+    #[test]
+    fn test_patch() {
+        // Replacing and copying, including the whole document.
+        check_patch(r#"{"a": 1}"#,
+                    r#"[{"op": "replace", "path": "", "value": {"b": [1]}},
+                        {"op": "copy", "from": "/b", "path": "/c"},
+                        {"op": "add", "path": "/c/0", "value": 0}]"#,
+                    r#"{"b": [1], "c": [0, 1]}"#);
+        // Moving a value onto itself does nothing.
+        check_patch(r#"{"a": {"b": 1}}"#,
+                    r#"[{"op": "move", "from": "/a", "path": "/a"}]"#,
+                    r#"{"a": {"b": 1}}"#);
+        // Numbers are compared by value.
+        check_patch(r#"{"a": 1, "b": -1}"#,
+                    r#"[{"op": "test", "path": "/a", "value": 1.0},
+                        {"op": "test", "path": "/b", "value": -1.0}]"#,
+                    r#"{"a": 1, "b": -1}"#);
+    }
+
+    #[test]
+    fn test_patch_errors() {
+        check_patch_err("{}", r#"{"op": "add"}"#, PatchNotArray);
+        check_patch_err("{}", "[1]", InvalidOperation(0, "operation must be an object".into_string()));
+        check_patch_err("{}", r#"[{"path": "/a"}]"#, InvalidOperation(0, "missing `op`".into_string()));
+        check_patch_err("{}", r#"[{"op": "add", "path": "/a"}]"#,
+                        InvalidOperation(0, "missing `value`".into_string()));
+        check_patch_err("{}", r#"[{"op": "frob", "path": "/a"}]"#,
+                        InvalidOperation(0, "unknown op `frob`".into_string()));
+        check_patch_err("{}", r#"[{"op": "remove", "path": "a"}]"#,
+                        InvalidPointer(0, "a".into_string()));
+        // The first operation is undone when the second one fails.
+        check_patch_err(r#"{"a": [1, 2]}"#,
+                        r#"[{"op": "remove", "path": "/a/0"},
+                            {"op": "remove", "path": "/a/1"}]"#,
+                        PathNotFound(1, "/a/1".into_string()));
+        check_patch_err(r#"{"a": {"b": 1}}"#,
+                        r#"[{"op": "move", "from": "/a", "path": "/a/b/c"}]"#,
+                        MoveIntoChild(0, "/a/b/c".into_string()));
+        check_patch_err(r#"{"a": [1]}"#,
+                        r#"[{"op": "add", "path": "/a/2", "value": 0}]"#,
+                        PathNotFound(0, "/a/2".into_string()));
+    }
+
     #[test]
     fn test_to_json() {
         use std::collections::{HashMap,TreeMap};
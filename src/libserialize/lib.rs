@@ -40,5 +40,6 @@ mod serialize;
 mod collection_impls;
 
 pub mod base64;
+pub mod cbor;
 pub mod hex;
 pub mod json;
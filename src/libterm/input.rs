@@ -0,0 +1,593 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reading from a terminal: raw mode and key decoding.
+//!
+//! ```no_run
+//! extern crate term;
+//!
+//! use term::input::{RawMode, KeyDecoder, Char, Ctrl, Up};
+//!
+//! fn main() {
+//!     let _raw = RawMode::new().unwrap();
+//!     for key in KeyDecoder::from_env().keys(std::io::stdin()) {
+//!         match key.unwrap() {
+//!             Up => print!("up\r\n"),
+//!             Char(c) => print!("{}\r\n", c),
+//!             Ctrl('c') => break,
+//!             _ => {}
+//!         }
+//!     }
+//!     // The terminal is restored when `_raw` goes out of scope.
+//! }
+//! ```
+
+pub use self::Key::*;
+pub use self::Mode::*;
+
+use std::cmp;
+use std::io::{IoResult, EndOfFile};
+use std::os;
+use std::str;
+
+use terminfo::TermInfo;
+use terminfo::searcher::open;
+use terminfo::parser::compiled::parse;
+
+/// A key (or a combination of keys) pressed on a terminal.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Key {
+    /// A printable character
+    Char(char),
+    /// A letter, or one of `@`, `\`, `]`, `^` and `_`, typed with control
+    /// held down. Letters are given in lower case
+    Ctrl(char),
+    /// A character typed with alt (or meta) held down
+    Alt(char),
+    /// The enter (or return) key
+    Enter,
+    /// The tab key
+    Tab,
+    /// The tab key, with shift held down
+    BackTab,
+    /// The backspace key
+    Backspace,
+    /// The escape key
+    Escape,
+    /// The up arrow key
+    Up,
+    /// The down arrow key
+    Down,
+    /// The left arrow key
+    Left,
+    /// The right arrow key
+    Right,
+    /// The home key
+    Home,
+    /// The end key
+    End,
+    /// The page up key
+    PageUp,
+    /// The page down key
+    PageDown,
+    /// The insert key
+    Insert,
+    /// The delete key
+    Delete,
+    /// A function key, numbered from 1
+    F(u8),
+    /// An escape sequence (or a byte) that isn't known
+    Unknown,
+}
+
+impl Copy for Key {}
+
+// The terminfo capabilities of the keys, by their short and long names. The
+// parser uses the long name for those that it doesn't have a short name for.
+static KEY_CAPS: &'static [(&'static str, &'static str, Key)] = &[
+    ("kbs", "key_backspace", Backspace),
+    ("kcuu1", "key_up", Up),
+    ("kcud1", "key_down", Down),
+    ("kcub1", "key_left", Left),
+    ("kcuf1", "key_right", Right),
+    ("khome", "key_home", Home),
+    ("kend", "key_end", End),
+    ("kpp", "key_ppage", PageUp),
+    ("knp", "key_npage", PageDown),
+    ("kich1", "key_ic", Insert),
+    ("kdch1", "key_dc", Delete),
+    ("kcbt", "key_btab", BackTab),
+    ("kent", "key_enter", Enter),
+];
+
+// The sequences most terminals send, whether or not the keypad is in
+// application mode (which is what the terminfo sequences are for).
+static ANSI_KEYS: &'static [(&'static str, Key)] = &[
+    ("\x1b[A", Up), ("\x1b[B", Down), ("\x1b[C", Right), ("\x1b[D", Left),
+    ("\x1bOA", Up), ("\x1bOB", Down), ("\x1bOC", Right), ("\x1bOD", Left),
+    ("\x1b[H", Home), ("\x1b[F", End), ("\x1bOH", Home), ("\x1bOF", End),
+    ("\x1b[1~", Home), ("\x1b[2~", Insert), ("\x1b[3~", Delete), ("\x1b[4~", End),
+    ("\x1b[5~", PageUp), ("\x1b[6~", PageDown), ("\x1b[Z", BackTab),
+    ("\x1bOP", F(1)), ("\x1bOQ", F(2)), ("\x1bOR", F(3)), ("\x1bOS", F(4)),
+    ("\x1b[15~", F(5)), ("\x1b[17~", F(6)), ("\x1b[18~", F(7)), ("\x1b[19~", F(8)),
+    ("\x1b[20~", F(9)), ("\x1b[21~", F(10)), ("\x1b[23~", F(11)), ("\x1b[24~", F(12)),
+];
+
+/// Decodes the bytes a terminal sends when keys are pressed into `Key`s.
+///
+/// The escape sequences of the special keys come from the terminal's
+/// terminfo entry, along with the ANSI sequences that most terminals send.
+#[deriving(Clone)]
+pub struct KeyDecoder {
+    // Known sequences, in order of preference when two are the same.
+    seqs: Vec<(Vec<u8>, Key)>,
+}
+
+impl KeyDecoder {
+    /// Creates a decoder that only knows the ANSI sequences.
+    pub fn ansi() -> KeyDecoder {
+        KeyDecoder {
+            seqs: ANSI_KEYS.iter().map(|&(s, k)| (s.as_bytes().to_vec(), k)).collect(),
+        }
+    }
+
+    /// Creates a decoder for the sequences in the given terminfo entry.
+    pub fn new(ti: &TermInfo) -> KeyDecoder {
+        let mut seqs = Vec::new();
+        {
+            let mut add = |short: &str, long: &str, key: Key| {
+                match ti.strings.get(short).or_else(|| ti.strings.get(long)) {
+                    Some(seq) if seq.len() > 0 => seqs.push((seq.clone(), key)),
+                    _ => {}
+                }
+            };
+            for &(short, long, key) in KEY_CAPS.iter() {
+                add(short, long, key);
+            }
+            for n in range(1u8, 64) {
+                add(format!("kf{}", n).as_slice(), format!("key_f{}", n).as_slice(), F(n));
+            }
+        }
+        seqs.extend(KeyDecoder::ansi().seqs.into_iter());
+        KeyDecoder { seqs: seqs }
+    }
+
+    /// Creates a decoder for the terminal named by the `TERM` environment
+    /// variable, or one that only knows the ANSI sequences if its terminfo
+    /// entry can't be loaded.
+    pub fn from_env() -> KeyDecoder {
+        let ti = os::getenv("TERM").and_then(|term| {
+            open(term.as_slice()).ok().and_then(|mut file| parse(&mut file, false).ok())
+        });
+        match ti {
+            Some(ti) => KeyDecoder::new(&*ti),
+            None => KeyDecoder::ansi(),
+        }
+    }
+
+    /// Decodes the key at the start of `buf`, returning it and the number of
+    /// bytes it took.
+    ///
+    /// If `more` is true, more bytes may follow `buf` right away, so `None` is
+    /// returned if `buf` could be the start of a longer sequence. Otherwise,
+    /// `buf` is taken to be all there is, which is how a terminal sends an
+    /// escape sequence: an escape byte on its own is the escape key, and an
+    /// escape followed by a character is that character typed with alt.
+    /// `None` is also returned if `buf` is empty.
+    pub fn decode(&self, buf: &[u8], more: bool) -> Option<(Key, uint)> {
+        if buf.len() == 0 {
+            return None
+        }
+        if more && self.seqs.iter().any(|&(ref seq, _)| {
+            seq.len() > buf.len() && seq.starts_with(buf)
+        }) {
+            return None
+        }
+
+        let mut best: Option<(Key, uint)> = None;
+        for &(ref seq, key) in self.seqs.iter() {
+            if buf.starts_with(seq.as_slice()) && best.map_or(true, |(_, n)| seq.len() > n) {
+                best = Some((key, seq.len()));
+            }
+        }
+        if best.is_some() {
+            return best
+        }
+
+        match buf[0] {
+            0x1b if buf.len() == 1 => if more { None } else { Some((Escape, 1)) },
+            0x1b if buf[1] == b'[' || buf[1] == b'O' => {
+                // An unknown escape sequence, which ends with a byte in the
+                // range `@` to `~`.
+                match buf.iter().skip(2).position(|&b| b >= 0x40 && b <= 0x7e) {
+                    Some(i) => Some((Unknown, i + 3)),
+                    None if more => None,
+                    None => Some((Unknown, buf.len())),
+                }
+            }
+            0x1b => match decode_char(buf[1..], more) {
+                Some((Char(c), n)) => Some((Alt(c), n + 1)),
+                Some((_, n)) => Some((Unknown, n + 1)),
+                None => None,
+            },
+            _ => decode_char(buf, more),
+        }
+    }
+
+    /// Returns an iterator over the keys read from `reader`, which should be
+    /// a terminal in raw (or cbreak) mode.
+    pub fn keys<R: Reader>(self, reader: R) -> Keys<R> {
+        Keys { decoder: self, reader: reader, buf: Vec::new() }
+    }
+}
+
+// Decodes a control character or a UTF-8 encoded character.
+fn decode_char(buf: &[u8], more: bool) -> Option<(Key, uint)> {
+    let key = match buf[0] {
+        b'\r' | b'\n' => Enter,
+        b'\t' => Tab,
+        0x7f | 0x08 => Backspace,
+        0x01 ... 0x1a => Ctrl((buf[0] + 0x60) as char),
+        0x00 ... 0x1f => Ctrl((buf[0] + 0x40) as char),
+        b => {
+            let width = str::utf8_char_width(b);
+            if width == 0 {
+                return Some((Unknown, 1))
+            }
+            if buf.len() < width {
+                return if more { None } else { Some((Unknown, buf.len())) }
+            }
+            return match str::from_utf8(buf[..width]) {
+                Some(s) => Some((Char(s.char_at(0)), width)),
+                None => Some((Unknown, width)),
+            }
+        }
+    };
+    Some((key, 1))
+}
+
+/// An iterator over the keys read from a terminal. If reading fails, the
+/// error is yielded, and iteration stops at the end of the input.
+pub struct Keys<R> {
+    decoder: KeyDecoder,
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<R: Reader> Keys<R> {
+    /// Reads the next key, waiting for one to be pressed if needed.
+    pub fn read_key(&mut self) -> IoResult<Key> {
+        loop {
+            if self.buf.len() == 0 {
+                let mut chunk = [0u8, ..64];
+                let n = try!(self.reader.read(&mut chunk));
+                self.buf.push_all(chunk[..n]);
+            }
+            // A terminal sends each escape sequence in one go, so everything
+            // that's been read is all there is for now.
+            match self.decoder.decode(self.buf.as_slice(), false) {
+                Some((key, n)) => {
+                    let n = cmp::min(n, self.buf.len());
+                    self.buf = self.buf[n..].to_vec();
+                    return Ok(key)
+                }
+                // Nothing was read; try again.
+                None => {}
+            }
+        }
+    }
+}
+
+impl<R: Reader> Iterator<IoResult<Key>> for Keys<R> {
+    fn next(&mut self) -> Option<IoResult<Key>> {
+        match self.read_key() {
+            Err(ref e) if e.kind == EndOfFile => None,
+            r => Some(r),
+        }
+    }
+}
+
+/// The mode `RawMode` puts a terminal in.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Mode {
+    /// Input is available byte by byte, without being echoed, and no
+    /// characters are special: ctrl-C, ctrl-Z and so on are read like other
+    /// keys. Output isn't processed either, so a newline has to be written as
+    /// `\r\n` to go to the start of the next line.
+    Raw,
+    /// Input is available byte by byte, without being echoed, but ctrl-C and
+    /// ctrl-Z still send signals, and output is processed as usual.
+    Cbreak,
+}
+
+impl Copy for Mode {}
+
+/// A guard that puts the terminal connected to stdin in raw or cbreak mode,
+/// and restores its previous mode when it's dropped, including when the task
+/// panics.
+pub struct RawMode {
+    saved: imp::Saved,
+}
+
+impl RawMode {
+    /// Puts the terminal in raw mode.
+    ///
+    /// Fails if stdin isn't a terminal.
+    pub fn new() -> IoResult<RawMode> {
+        RawMode::with_mode(Raw)
+    }
+
+    /// Puts the terminal in cbreak mode.
+    ///
+    /// Fails if stdin isn't a terminal.
+    pub fn cbreak() -> IoResult<RawMode> {
+        RawMode::with_mode(Cbreak)
+    }
+
+    /// Puts the terminal in the given mode.
+    ///
+    /// Fails if stdin isn't a terminal.
+    pub fn with_mode(mode: Mode) -> IoResult<RawMode> {
+        let saved = try!(imp::enable(mode));
+        Ok(RawMode { saved: saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // There's nothing to do about an error here.
+        let _ = imp::restore(&self.saved);
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use libc::c_int;
+    use libc;
+    use std::io::{IoResult, IoError};
+
+    use super::{Mode, Raw};
+
+    // The layout of glibc's `struct termios`. Bionic's is a prefix of it.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    mod os {
+        use libc::{c_int, c_uchar, c_uint};
+
+        pub type tcflag_t = c_uint;
+
+        #[repr(C)]
+        pub struct termios {
+            pub c_iflag: tcflag_t,
+            pub c_oflag: tcflag_t,
+            pub c_cflag: tcflag_t,
+            pub c_lflag: tcflag_t,
+            pub c_line: c_uchar,
+            pub c_cc: [c_uchar, ..32],
+            pub c_ispeed: c_uint,
+            pub c_ospeed: c_uint,
+        }
+
+        pub const BRKINT: tcflag_t = 0o2;
+        pub const ICRNL: tcflag_t = 0o400;
+        pub const INPCK: tcflag_t = 0o20;
+        pub const ISTRIP: tcflag_t = 0o40;
+        pub const IXON: tcflag_t = 0o2000;
+        pub const OPOST: tcflag_t = 0o1;
+        pub const CS8: tcflag_t = 0o60;
+        pub const ISIG: tcflag_t = 0o1;
+        pub const ICANON: tcflag_t = 0o2;
+        pub const ECHO: tcflag_t = 0o10;
+        pub const IEXTEN: tcflag_t = 0o100000;
+        pub const VTIME: uint = 5;
+        pub const VMIN: uint = 6;
+        pub const TCSAFLUSH: c_int = 2;
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+              target_os = "dragonfly", target_os = "openbsd"))]
+    mod os {
+        use libc::{c_int, c_uchar};
+
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        pub type tcflag_t = ::libc::c_ulong;
+        #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+        pub type tcflag_t = ::libc::c_uint;
+
+        #[repr(C)]
+        pub struct termios {
+            pub c_iflag: tcflag_t,
+            pub c_oflag: tcflag_t,
+            pub c_cflag: tcflag_t,
+            pub c_lflag: tcflag_t,
+            pub c_cc: [c_uchar, ..20],
+            pub c_ispeed: tcflag_t,
+            pub c_ospeed: tcflag_t,
+        }
+
+        pub const BRKINT: tcflag_t = 0x2;
+        pub const ICRNL: tcflag_t = 0x100;
+        pub const INPCK: tcflag_t = 0x10;
+        pub const ISTRIP: tcflag_t = 0x20;
+        pub const IXON: tcflag_t = 0x200;
+        pub const OPOST: tcflag_t = 0x1;
+        pub const CS8: tcflag_t = 0x300;
+        pub const ISIG: tcflag_t = 0x80;
+        pub const ICANON: tcflag_t = 0x100;
+        pub const ECHO: tcflag_t = 0x8;
+        pub const IEXTEN: tcflag_t = 0x400;
+        pub const VMIN: uint = 16;
+        pub const VTIME: uint = 17;
+        pub const TCSAFLUSH: c_int = 2;
+    }
+
+    extern {
+        fn tcgetattr(fd: c_int, termios: *mut os::termios) -> c_int;
+        fn tcsetattr(fd: c_int, action: c_int, termios: *const os::termios) -> c_int;
+    }
+
+    pub type Saved = os::termios;
+
+    pub fn enable(mode: Mode) -> IoResult<Saved> {
+        unsafe {
+            let mut saved: os::termios = ::std::mem::zeroed();
+            if tcgetattr(libc::STDIN_FILENO, &mut saved) != 0 {
+                return Err(IoError::last_error())
+            }
+            let mut t: os::termios = ::std::mem::zeroed();
+            if tcgetattr(libc::STDIN_FILENO, &mut t) != 0 {
+                return Err(IoError::last_error())
+            }
+            t.c_lflag &= !(os::ECHO | os::ICANON);
+            if mode == Raw {
+                t.c_iflag &= !(os::BRKINT | os::ICRNL | os::INPCK | os::ISTRIP | os::IXON);
+                t.c_oflag &= !os::OPOST;
+                t.c_cflag |= os::CS8;
+                t.c_lflag &= !(os::IEXTEN | os::ISIG);
+            }
+            t.c_cc[os::VMIN] = 1;
+            t.c_cc[os::VTIME] = 0;
+            if tcsetattr(libc::STDIN_FILENO, os::TCSAFLUSH, &t) != 0 {
+                return Err(IoError::last_error())
+            }
+            Ok(saved)
+        }
+    }
+
+    pub fn restore(saved: &Saved) -> IoResult<()> {
+        if unsafe { tcsetattr(libc::STDIN_FILENO, os::TCSAFLUSH, saved) } != 0 {
+            return Err(IoError::last_error())
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use libc;
+    use std::io::{IoResult, IoError};
+
+    use super::{Mode, Raw};
+
+    const ENABLE_PROCESSED_INPUT: libc::DWORD = 0x1;
+    const ENABLE_LINE_INPUT: libc::DWORD = 0x2;
+    const ENABLE_ECHO_INPUT: libc::DWORD = 0x4;
+
+    #[allow(non_snake_case)]
+    extern "system" {
+        fn GetStdHandle(which: libc::DWORD) -> libc::HANDLE;
+        fn GetConsoleMode(handle: libc::HANDLE, mode: *mut libc::DWORD) -> libc::BOOL;
+        fn SetConsoleMode(handle: libc::HANDLE, mode: libc::DWORD) -> libc::BOOL;
+    }
+
+    pub type Saved = libc::DWORD;
+
+    pub fn enable(mode: Mode) -> IoResult<Saved> {
+        unsafe {
+            // Magic -10 means stdin
+            let handle = GetStdHandle(-10);
+            let mut saved = 0;
+            if GetConsoleMode(handle, &mut saved) == 0 {
+                return Err(IoError::last_error())
+            }
+            let mut new = saved & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT);
+            if mode == Raw {
+                new &= !ENABLE_PROCESSED_INPUT;
+            }
+            if SetConsoleMode(handle, new) == 0 {
+                return Err(IoError::last_error())
+            }
+            Ok(saved)
+        }
+    }
+
+    pub fn restore(saved: &Saved) -> IoResult<()> {
+        unsafe {
+            if SetConsoleMode(GetStdHandle(-10), *saved) == 0 {
+                return Err(IoError::last_error())
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::io::MemReader;
+
+    use terminfo::TermInfo;
+    use super::*;
+
+    fn xterm() -> KeyDecoder {
+        let mut strings = HashMap::new();
+        for &(name, value) in [("kcuu1", "\x1bOA"), ("key_down", "\x1bOB"), ("kbs", "\x7f"),
+                               ("kdch1", "\x1b[3~"), ("key_f1", "\x1bOP"),
+                               ("kf13", "\x1b[1;2P")].iter() {
+            strings.insert(name.to_string(), value.as_bytes().to_vec());
+        }
+        KeyDecoder::new(&TermInfo {
+            names: vec!["xterm".to_string()],
+            bools: HashMap::new(),
+            numbers: HashMap::new(),
+            strings: strings,
+        })
+    }
+
+    #[test]
+    fn test_decode_sequences() {
+        let d = xterm();
+        assert_eq!(d.decode(b"\x1bOA", false), Some((Up, 3)));
+        assert_eq!(d.decode(b"\x1b[A", false), Some((Up, 3)));
+        assert_eq!(d.decode(b"\x1bOBx", false), Some((Down, 3)));
+        assert_eq!(d.decode(b"\x1b[3~", false), Some((Delete, 4)));
+        assert_eq!(d.decode(b"\x1bOP", false), Some((F(1), 3)));
+        assert_eq!(d.decode(b"\x1b[1;2P", false), Some((F(13), 6)));
+        assert_eq!(d.decode(b"\x1b[24~", false), Some((F(12), 5)));
+        assert_eq!(d.decode(b"\x1b[1;5A", false), Some((Unknown, 6)));
+        assert_eq!(d.decode(b"\x1b[1;5", false), Some((Unknown, 5)));
+
+        // Partial sequences.
+        assert_eq!(d.decode(b"\x1b", true), None);
+        assert_eq!(d.decode(b"\x1b[", true), None);
+        assert_eq!(d.decode(b"\x1b[1;5", true), None);
+        assert_eq!(d.decode(b"\x1b", false), Some((Escape, 1)));
+        assert_eq!(d.decode(b"\x1bx", false), Some((Alt('x'), 2)));
+        assert_eq!(d.decode(b"", false), None);
+
+        // Without terminfo, only the ANSI sequences are known.
+        let d = KeyDecoder::ansi();
+        assert_eq!(d.decode(b"\x1bOA", false), Some((Up, 3)));
+        assert_eq!(d.decode(b"\x1b[1;2P", false), Some((Unknown, 6)));
+    }
+
+    #[test]
+    fn test_decode_chars() {
+        let d = KeyDecoder::ansi();
+        assert_eq!(d.decode(b"a", false), Some((Char('a'), 1)));
+        assert_eq!(d.decode(b"\r", false), Some((Enter, 1)));
+        assert_eq!(d.decode(b"\t", false), Some((Tab, 1)));
+        assert_eq!(d.decode(b"\x7f", false), Some((Backspace, 1)));
+        assert_eq!(d.decode(b"\x03", false), Some((Ctrl('c'), 1)));
+        assert_eq!(d.decode(b"\x00", false), Some((Ctrl('@'), 1)));
+        assert_eq!(d.decode(b"\x1f", false), Some((Ctrl('_'), 1)));
+        assert_eq!(d.decode("é!".as_bytes(), false), Some((Char('é'), 2)));
+        assert_eq!(d.decode("é".as_bytes()[..1], true), None);
+        assert_eq!(d.decode("é".as_bytes()[..1], false), Some((Unknown, 1)));
+        assert_eq!(d.decode(b"\xff", false), Some((Unknown, 1)));
+        assert_eq!(d.decode("\x1bé".as_bytes(), false), Some((Alt('é'), 3)));
+    }
+
+    #[test]
+    fn test_keys() {
+        let input = MemReader::new(b"ab\x1b[A\x7f".to_vec());
+        let keys: Vec<Key> = xterm().keys(input).map(|k| k.unwrap()).collect();
+        assert_eq!(keys, vec![Char('a'), Char('b'), Up, Backspace]);
+    }
+}
@@ -14,7 +14,8 @@
 //! Terminal][ansi] to provide color printing, among other things. There are two implementations,
 //! the `TerminfoTerminal`, which uses control characters from a
 //! [terminfo][ti] database, and `WinConsole`, which uses the [Win32 Console
-//! API][win].
+//! API][win]. The `input` module puts a terminal in raw mode and decodes the
+//! keys that are pressed.
 //!
 //! ## Example
 //!
@@ -53,6 +54,7 @@
 #![deny(missing_docs)]
 
 #[phase(plugin, link)] extern crate log;
+extern crate libc;
 
 pub use terminfo::TerminfoTerminal;
 #[cfg(windows)]
@@ -61,6 +63,7 @@ pub use win::WinConsole;
 use std::io::IoResult;
 
 pub mod terminfo;
+pub mod input;
 
 #[cfg(windows)]
 mod win;
@@ -137,9 +140,50 @@ pub fn stderr() -> Option<Box<Terminal<WriterWrapper> + Send>> {
     }
 }
 
+#[cfg(unix)]
+/// Return the size of the terminal that stdout (or else stderr or stdin) is
+/// connected to, as `(columns, rows)`, or None if none of them is a terminal.
+pub fn size() -> Option<(u16, u16)> {
+    #[repr(C)]
+    struct winsize {
+        ws_row: libc::c_ushort,
+        ws_col: libc::c_ushort,
+        ws_xpixel: libc::c_ushort,
+        ws_ypixel: libc::c_ushort,
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: libc::c_ulong = 0x5413;
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd",
+              target_os = "dragonfly", target_os = "openbsd"))]
+    const TIOCGWINSZ: libc::c_ulong = 0x40087468;
+
+    extern {
+        fn ioctl(fd: libc::c_int, request: libc::c_ulong, ...) -> libc::c_int;
+    }
+
+    for &fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO].iter() {
+        let mut ws = winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+        let ret = unsafe { ioctl(fd, TIOCGWINSZ, &mut ws as *mut winsize) };
+        if ret == 0 && ws.ws_col > 0 && ws.ws_row > 0 {
+            return Some((ws.ws_col as u16, ws.ws_row as u16))
+        }
+    }
+    None
+}
+
+#[cfg(windows)]
+/// Return the size of the console window that stdout is connected to, as
+/// `(columns, rows)`, or None if it isn't a console.
+pub fn size() -> Option<(u16, u16)> {
+    win::size()
+}
 
 /// Terminal color definitions
 pub mod color {
+    use std::cmp;
+    use std::kinds::Copy;
+
     /// Number for a terminal color
     pub type Color = u16;
 
@@ -160,6 +204,84 @@ pub mod color {
     pub const BRIGHT_MAGENTA: Color = 13u16;
     pub const BRIGHT_CYAN:    Color = 14u16;
     pub const BRIGHT_WHITE:   Color = 15u16;
+
+    /// A 24-bit color, given by its red, green and blue components.
+    #[deriving(Clone, PartialEq, Show)]
+    pub struct Rgb(pub u8, pub u8, pub u8);
+
+    impl Copy for Rgb {}
+
+    // The 16 basic colors, as xterm shows them by default.
+    static BASIC: [(u8, u8, u8), ..16] = [
+        (0x00, 0x00, 0x00), (0xcd, 0x00, 0x00), (0x00, 0xcd, 0x00), (0xcd, 0xcd, 0x00),
+        (0x00, 0x00, 0xee), (0xcd, 0x00, 0xcd), (0x00, 0xcd, 0xcd), (0xe5, 0xe5, 0xe5),
+        (0x7f, 0x7f, 0x7f), (0xff, 0x00, 0x00), (0x00, 0xff, 0x00), (0xff, 0xff, 0x00),
+        (0x5c, 0x5c, 0xff), (0xff, 0x00, 0xff), (0x00, 0xff, 0xff), (0xff, 0xff, 0xff),
+    ];
+
+    // The levels of each component in the 6x6x6 color cube (colors 16 to 231).
+    static CUBE: [u8, ..6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+    /// Returns the RGB value of a color in the 256-color palette, as xterm
+    /// shows it by default: the 16 basic colors, a 6x6x6 color cube and 24
+    /// shades of gray.
+    ///
+    /// Panics if `color` isn't less than 256.
+    pub fn to_rgb(color: Color) -> Rgb {
+        assert!(color < 256, "not a 256-color palette color: {}", color);
+        match color {
+            0...15 => {
+                let (r, g, b) = BASIC[color as uint];
+                Rgb(r, g, b)
+            }
+            16...231 => {
+                let c = color as uint - 16;
+                Rgb(CUBE[c / 36], CUBE[c / 6 % 6], CUBE[c % 6])
+            }
+            _ => {
+                let level = 8 + (color as u8 - 232) * 10;
+                Rgb(level, level, level)
+            }
+        }
+    }
+
+    /// Returns the color that's nearest to `rgb` in a palette of `num_colors`
+    /// colors: the 16 basic colors (or the first 8, if there are fewer), or
+    /// with 256 colors or more, the color cube and the grays of the 256-color
+    /// palette. The basic colors are left out of the bigger palette since many
+    /// terminals let users change them.
+    pub fn nearest(rgb: Rgb, num_colors: u16) -> Color {
+        let Rgb(r, g, b) = rgb;
+        let distance = |other: Rgb| {
+            let Rgb(r2, g2, b2) = other;
+            let (dr, dg, db) = (r as int - r2 as int, g as int - g2 as int, b as int - b2 as int);
+            dr * dr + dg * dg + db * db
+        };
+        let candidates = if num_colors >= 256 {
+            // The nearest color in the cube, and the nearest gray.
+            let level = |c: u8| {
+                let mut best = 0;
+                for i in range(1, CUBE.len()) {
+                    if (CUBE[i] as int - c as int).abs() < (CUBE[best] as int - c as int).abs() {
+                        best = i;
+                    }
+                }
+                best as u16
+            };
+            let avg = (r as uint + g as uint + b as uint) / 3;
+            let gray = if avg < 8 { 0 } else { cmp::min((avg - 8 + 5) / 10, 23) as u16 };
+            vec![16 + 36 * level(r) + 6 * level(g) + level(b), 232 + gray]
+        } else {
+            range(0, if num_colors >= 16 { 16 } else { 8 }).collect()
+        };
+        let mut best = candidates[0];
+        for &c in candidates.iter().skip(1) {
+            if distance(to_rgb(c)) < distance(to_rgb(best)) {
+                best = c;
+            }
+        }
+        best
+    }
 }
 
 /// Terminal attributes
@@ -204,7 +326,9 @@ pub trait Terminal<T: Writer>: Writer {
     /// Sets the foreground color to the given color.
     ///
     /// If the color is a bright color, but the terminal only supports 8 colors,
-    /// the corresponding normal color will be used instead.
+    /// the corresponding normal color will be used instead. Likewise, colors
+    /// of the 256-color palette are shown as the nearest color the terminal
+    /// supports.
     ///
     /// Returns `Ok(true)` if the color was set, `Ok(false)` otherwise, and `Err(e)`
     /// if there was an I/O error.
@@ -213,12 +337,42 @@ pub trait Terminal<T: Writer>: Writer {
     /// Sets the background color to the given color.
     ///
     /// If the color is a bright color, but the terminal only supports 8 colors,
-    /// the corresponding normal color will be used instead.
+    /// the corresponding normal color will be used instead. Likewise, colors
+    /// of the 256-color palette are shown as the nearest color the terminal
+    /// supports.
     ///
     /// Returns `Ok(true)` if the color was set, `Ok(false)` otherwise, and `Err(e)`
     /// if there was an I/O error.
     fn bg(&mut self, color: color::Color) -> IoResult<bool>;
 
+    /// Sets the foreground color to the given 24-bit color.
+    ///
+    /// If the terminal doesn't support 24-bit colors, the nearest color in its
+    /// palette will be used instead. By default, that's the nearest of the 16
+    /// basic colors, set with `fg`.
+    ///
+    /// Returns `Ok(true)` if the color was set, `Ok(false)` otherwise, and `Err(e)`
+    /// if there was an I/O error.
+    fn fg_rgb(&mut self, color: color::Rgb) -> IoResult<bool> {
+        self.fg(color::nearest(color, 16))
+    }
+
+    /// Sets the background color to the given 24-bit color.
+    ///
+    /// If the terminal doesn't support 24-bit colors, the nearest color in its
+    /// palette will be used instead. By default, that's the nearest of the 16
+    /// basic colors, set with `bg`.
+    ///
+    /// Returns `Ok(true)` if the color was set, `Ok(false)` otherwise, and `Err(e)`
+    /// if there was an I/O error.
+    fn bg_rgb(&mut self, color: color::Rgb) -> IoResult<bool> {
+        self.bg(color::nearest(color, 16))
+    }
+
+    /// Returns whether 24-bit colors are shown as they are, rather than as the
+    /// nearest color in the terminal's palette. Returns `false` by default.
+    fn supports_truecolor(&self) -> bool { false }
+
     /// Sets the given terminal attribute, if supported.  Returns `Ok(true)`
     /// if the attribute was supported, `Ok(false)` otherwise, and `Err(e)` if
     /// there was an I/O error.
@@ -231,6 +385,33 @@ pub trait Terminal<T: Writer>: Writer {
     /// Returns `Ok()`.
     fn reset(&mut self) -> IoResult<()>;
 
+    /// Moves the cursor to the given row and column, counted from 0 at the
+    /// top left corner of the screen.
+    ///
+    /// Returns `Ok(true)` if the cursor was moved, `Ok(false)` if the terminal
+    /// can't do it, and `Err(e)` if there was an I/O error. The same goes for
+    /// the other cursor and clearing operations, which all return `Ok(false)`
+    /// unless the terminal implements them.
+    fn cursor_to(&mut self, _row: u16, _col: u16) -> IoResult<bool> { Ok(false) }
+
+    /// Moves the cursor up `n` lines, staying in the same column.
+    fn cursor_up(&mut self, _n: u16) -> IoResult<bool> { Ok(false) }
+
+    /// Moves the cursor down `n` lines, staying in the same column.
+    fn cursor_down(&mut self, _n: u16) -> IoResult<bool> { Ok(false) }
+
+    /// Clears the screen from the cursor to the end of the line.
+    fn clear_to_end_of_line(&mut self) -> IoResult<bool> { Ok(false) }
+
+    /// Clears the screen from the cursor to the end of the screen.
+    fn clear_to_end_of_screen(&mut self) -> IoResult<bool> { Ok(false) }
+
+    /// Saves the position of the cursor, to be restored by `restore_cursor`.
+    fn save_cursor(&mut self) -> IoResult<bool> { Ok(false) }
+
+    /// Moves the cursor back to the position saved by `save_cursor`.
+    fn restore_cursor(&mut self) -> IoResult<bool> { Ok(false) }
+
     /// Gets an immutable reference to the stream inside
     fn get_ref<'a>(&'a self) -> &'a T;
 
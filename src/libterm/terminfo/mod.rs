@@ -20,7 +20,7 @@ use Terminal;
 use UnwrappableTerminal;
 use self::searcher::open;
 use self::parser::compiled::{parse, msys_terminfo};
-use self::parm::{expand, Number, Variables};
+use self::parm::{expand, Number, Param, Variables};
 
 
 /// A parsed terminfo database entry.
@@ -64,47 +64,68 @@ fn cap_for_attr(attr: attr::Attr) -> &'static str {
     }
 }
 
+/// How a terminal shows 24-bit colors.
+#[deriving(PartialEq)]
+enum RgbMode {
+    /// As the nearest color in its palette.
+    Palette,
+    /// By passing `setaf` and `setab` the color as `0xRRGGBB`, as terminfo
+    /// entries with the `RGB` capability expect.
+    Direct,
+    /// With the `38;2;R;G;B` and `48;2;R;G;B` SGR sequences, which terminals
+    /// that set `COLORTERM` to `truecolor` or `24bit` (or that have the `Tc`
+    /// capability) understand.
+    Sgr,
+}
+
+impl Copy for RgbMode {}
+
+/// Returns how a terminal with the given terminfo entry and number of colors
+/// shows 24-bit colors, given the value of `COLORTERM`.
+fn rgb_mode(ti: &TermInfo, num_colors: u16, colorterm: Option<&str>) -> RgbMode {
+    if num_colors == 0 {
+        return RgbMode::Palette
+    }
+    let has_rgb = ti.bools.contains_key("RGB") || ti.numbers.contains_key("RGB")
+                  || ti.strings.contains_key("RGB");
+    if has_rgb && num_colors > 256 {
+        RgbMode::Direct
+    } else if ti.bools.contains_key("Tc") || colorterm == Some("truecolor")
+              || colorterm == Some("24bit") {
+        RgbMode::Sgr
+    } else {
+        RgbMode::Palette
+    }
+}
+
 /// A Terminal that knows how many colors it supports, with a reference to its
 /// parsed Terminfo database record.
 pub struct TerminfoTerminal<T> {
     num_colors: u16,
+    rgb_mode: RgbMode,
     out: T,
     ti: Box<TermInfo>
 }
 
 impl<T: Writer+Send> Terminal<T> for TerminfoTerminal<T> {
     fn fg(&mut self, color: color::Color) -> IoResult<bool> {
-        let color = self.dim_if_necessary(color);
-        if self.num_colors > color {
-            let s = expand(self.ti
-                               .strings
-                               .get("setaf")
-                               .unwrap()
-                               .as_slice(),
-                           &[Number(color as int)], &mut Variables::new());
-            if s.is_ok() {
-                try!(self.out.write(s.unwrap().as_slice()));
-                return Ok(true)
-            }
-        }
-        Ok(false)
+        self.set_color("setaf", color)
     }
 
     fn bg(&mut self, color: color::Color) -> IoResult<bool> {
-        let color = self.dim_if_necessary(color);
-        if self.num_colors > color {
-            let s = expand(self.ti
-                               .strings
-                               .get("setab")
-                               .unwrap()
-                               .as_slice(),
-                           &[Number(color as int)], &mut Variables::new());
-            if s.is_ok() {
-                try!(self.out.write(s.unwrap().as_slice()));
-                return Ok(true)
-            }
-        }
-        Ok(false)
+        self.set_color("setab", color)
+    }
+
+    fn fg_rgb(&mut self, color: color::Rgb) -> IoResult<bool> {
+        self.set_rgb("setaf", color)
+    }
+
+    fn bg_rgb(&mut self, color: color::Rgb) -> IoResult<bool> {
+        self.set_rgb("setab", color)
+    }
+
+    fn supports_truecolor(&self) -> bool {
+        self.rgb_mode != RgbMode::Palette
     }
 
     fn attr(&mut self, attr: attr::Attr) -> IoResult<bool> {
@@ -159,6 +180,34 @@ impl<T: Writer+Send> Terminal<T> for TerminfoTerminal<T> {
         Ok(())
     }
 
+    fn cursor_to(&mut self, row: u16, col: u16) -> IoResult<bool> {
+        self.apply_cap("cup", &[Number(row as int), Number(col as int)])
+    }
+
+    fn cursor_up(&mut self, n: u16) -> IoResult<bool> {
+        self.move_cursor("cuu", "cuu1", n)
+    }
+
+    fn cursor_down(&mut self, n: u16) -> IoResult<bool> {
+        self.move_cursor("cud", "cud1", n)
+    }
+
+    fn clear_to_end_of_line(&mut self) -> IoResult<bool> {
+        self.apply_cap("el", &[])
+    }
+
+    fn clear_to_end_of_screen(&mut self) -> IoResult<bool> {
+        self.apply_cap("ed", &[])
+    }
+
+    fn save_cursor(&mut self) -> IoResult<bool> {
+        self.apply_cap("sc", &[])
+    }
+
+    fn restore_cursor(&mut self) -> IoResult<bool> {
+        self.apply_cap("rc", &[])
+    }
+
     fn get_ref<'a>(&'a self) -> &'a T { &self.out }
 
     fn get_mut<'a>(&'a mut self) -> &'a mut T { &mut self.out }
@@ -180,15 +229,19 @@ impl<T: Writer+Send> TerminfoTerminal<T> {
             }
         };
 
+        let colorterm = os::getenv("COLORTERM");
         let entry = open(term.as_slice());
         if entry.is_err() {
             if os::getenv("MSYSCON").map_or(false, |s| {
                     "mintty.exe" == s
                 }) {
                 // msys terminal
+                let ti = msys_terminfo();
+                let rgb_mode = rgb_mode(&*ti, 8, colorterm.as_ref().map(|s| s.as_slice()));
                 return Some(box TerminfoTerminal {out: out,
-                                                  ti: msys_terminfo(),
-                                                  num_colors: 8} as Box<Terminal<T>+Send>);
+                                                  ti: ti,
+                                                  num_colors: 8,
+                                                  rgb_mode: rgb_mode} as Box<Terminal<T>+Send>);
             }
             debug!("error finding terminfo entry: {}", entry.err().unwrap());
             return None;
@@ -207,15 +260,107 @@ impl<T: Writer+Send> TerminfoTerminal<T> {
                      inf.numbers.get("colors").map_or(0, |&n| n)
                  } else { 0 };
 
+        let rgb_mode = rgb_mode(&*inf, nc, colorterm.as_ref().map(|s| s.as_slice()));
         return Some(box TerminfoTerminal {out: out,
                                           ti: inf,
-                                          num_colors: nc} as Box<Terminal<T>+Send>);
+                                          num_colors: nc,
+                                          rgb_mode: rgb_mode} as Box<Terminal<T>+Send>);
     }
 
-    fn dim_if_necessary(&self, color: color::Color) -> color::Color {
-        if color >= self.num_colors && color >= 8 && color < 16 {
-            color-8
-        } else { color }
+    /// Expands the string capability `cap` with `params` and writes it out.
+    /// Returns `Ok(false)` if the terminal doesn't have the capability.
+    fn apply_cap(&mut self, cap: &str, params: &[Param]) -> IoResult<bool> {
+        let s = match self.ti.strings.get(cap) {
+            Some(parm) => expand(parm.as_slice(), params, &mut Variables::new()),
+            None => return Ok(false),
+        };
+        match s {
+            Ok(s) => {
+                try!(self.out.write(s.as_slice()));
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Moves the cursor `n` lines with the parameterized capability `cap`,
+    /// or else by repeating `cap1`, which moves it one line.
+    fn move_cursor(&mut self, cap: &str, cap1: &str, n: u16) -> IoResult<bool> {
+        if n == 0 {
+            return Ok(self.ti.strings.contains_key(cap) || self.ti.strings.contains_key(cap1))
+        }
+        if n > 1 || !self.ti.strings.contains_key(cap1) {
+            if try!(self.apply_cap(cap, &[Number(n as int)])) {
+                return Ok(true)
+            }
+        }
+        let s = match self.ti.strings.get(cap1) {
+            Some(parm) => expand(parm.as_slice(), &[], &mut Variables::new()),
+            None => return Ok(false),
+        };
+        match s {
+            Ok(s) => {
+                for _ in range(0, n) {
+                    try!(self.out.write(s.as_slice()));
+                }
+                Ok(true)
+            }
+            Err(_) => Ok(false),
+        }
+    }
+
+    fn set_color(&mut self, cap: &str, color: color::Color) -> IoResult<bool> {
+        if color >= 8 && color < 256 {
+            // A direct color terminal takes anything but the first 8 colors
+            // as an RGB value, and a terminal that understands the SGR
+            // sequences may still have fewer colors in its palette.
+            match self.rgb_mode {
+                RgbMode::Direct => return self.set_rgb(cap, color::to_rgb(color)),
+                RgbMode::Sgr if color >= self.num_colors => {
+                    return self.set_rgb(cap, color::to_rgb(color))
+                }
+                _ => {}
+            }
+        }
+        let color = self.downgrade(color);
+        if self.num_colors > color {
+            self.apply_cap(cap, &[Number(color as int)])
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn set_rgb(&mut self, cap: &str, rgb: color::Rgb) -> IoResult<bool> {
+        let color::Rgb(r, g, b) = rgb;
+        match self.rgb_mode {
+            RgbMode::Direct => {
+                let value = ((r as int) << 16) | ((g as int) << 8) | b as int;
+                self.apply_cap(cap, &[Number(value)])
+            }
+            RgbMode::Sgr => {
+                let kind = if cap == "setaf" { 38u } else { 48 };
+                try!(write!(self.out, "\x1b[{};2;{};{};{}m", kind, r, g, b));
+                Ok(true)
+            }
+            RgbMode::Palette => {
+                if self.num_colors < 8 {
+                    return Ok(false)
+                }
+                let color = color::nearest(rgb, self.num_colors);
+                self.apply_cap(cap, &[Number(color as int)])
+            }
+        }
+    }
+
+    /// Maps a color the terminal doesn't have to the nearest one it does.
+    fn downgrade(&self, color: color::Color) -> color::Color {
+        if color < self.num_colors || self.num_colors < 8 || color >= 256 {
+            color
+        } else if color < 16 {
+            color - 8
+        } else {
+            color::nearest(color::to_rgb(color), self.num_colors)
+        }
     }
 }
 
@@ -230,3 +375,158 @@ impl<T: Writer> Writer for TerminfoTerminal<T> {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::io::MemWriter;
+
+    use super::{TermInfo, TerminfoTerminal, RgbMode, rgb_mode};
+    use color;
+    use Terminal;
+
+    fn terminal(strings: &[(&str, &str)]) -> TerminfoTerminal<MemWriter> {
+        colored_terminal(0, RgbMode::Palette, strings)
+    }
+
+    fn colored_terminal(num_colors: u16, rgb_mode: RgbMode,
+                        strings: &[(&str, &str)]) -> TerminfoTerminal<MemWriter> {
+        let mut map = HashMap::new();
+        for &(name, value) in strings.iter() {
+            map.insert(name.to_string(), value.as_bytes().to_vec());
+        }
+        TerminfoTerminal {
+            num_colors: num_colors,
+            rgb_mode: rgb_mode,
+            out: MemWriter::new(),
+            ti: box TermInfo {
+                names: vec!["test".to_string()],
+                bools: HashMap::new(),
+                numbers: HashMap::new(),
+                strings: map,
+            },
+        }
+    }
+
+    // From the xterm-256color entry.
+    static SETAF: &'static str =
+        "\x1b[%?%p1%{8}%<%t3%p1%d%e%p1%{16}%<%t9%p1%{8}%-%d%e38;5;%p1%d%;m";
+
+    fn output(t: TerminfoTerminal<MemWriter>) -> String {
+        String::from_utf8(t.out.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_palette_colors() {
+        let mut t = colored_terminal(256, RgbMode::Palette, &[("setaf", SETAF)]);
+        assert_eq!(t.fg(color::RED), Ok(true));
+        assert_eq!(t.fg(color::BRIGHT_RED), Ok(true));
+        assert_eq!(t.fg(208), Ok(true));
+        assert_eq!(t.fg_rgb(color::Rgb(0xff, 0x87, 0x00)), Ok(true));
+        assert_eq!(t.fg(256), Ok(false));
+        assert!(!t.supports_truecolor());
+        assert_eq!(output(t), "\x1b[31m\x1b[91m\x1b[38;5;208m\x1b[38;5;208m");
+
+        // Downgraded to the 16 and 8 basic colors.
+        let mut t = colored_terminal(16, RgbMode::Palette, &[("setaf", SETAF)]);
+        assert_eq!(t.fg(196), Ok(true));
+        assert_eq!(t.fg_rgb(color::Rgb(0x10, 0x10, 0xf0)), Ok(true));
+        assert_eq!(output(t), "\x1b[91m\x1b[34m");
+        let mut t = colored_terminal(8, RgbMode::Palette, &[("setaf", SETAF)]);
+        assert_eq!(t.fg(color::BRIGHT_GREEN), Ok(true));
+        assert_eq!(t.fg(231), Ok(true));
+        assert_eq!(output(t), "\x1b[32m\x1b[37m");
+
+        let mut t = terminal(&[]);
+        assert_eq!(t.fg(color::RED), Ok(false));
+        assert_eq!(t.fg_rgb(color::Rgb(0, 0, 0)), Ok(false));
+    }
+
+    #[test]
+    fn test_truecolor() {
+        let mut t = colored_terminal(8, RgbMode::Sgr, &[("setaf", SETAF)]);
+        assert_eq!(t.fg_rgb(color::Rgb(1, 2, 3)), Ok(true));
+        assert_eq!(t.bg_rgb(color::Rgb(4, 5, 6)), Ok(true));
+        assert_eq!(t.fg(color::RED), Ok(true));
+        assert_eq!(t.fg(208), Ok(true));
+        assert!(t.supports_truecolor());
+        assert_eq!(output(t), "\x1b[38;2;1;2;3m\x1b[48;2;4;5;6m\x1b[31m\x1b[38;2;255;135;0m");
+
+        // From the xterm-direct entry.
+        let setaf = "\x1b[%?%p1%{8}%<%t3%p1%d%e38:2::%p1%{65536}%/%d:%p1%{256}%/%{255}%&%d\
+                     :%p1%{255}%&%d%;m";
+        let mut t = colored_terminal(0xFFFF, RgbMode::Direct, &[("setaf", setaf)]);
+        assert_eq!(t.fg_rgb(color::Rgb(1, 2, 3)), Ok(true));
+        assert_eq!(t.fg(color::RED), Ok(true));
+        assert_eq!(t.fg(color::BRIGHT_RED), Ok(true));
+        assert_eq!(output(t), "\x1b[38:2::1:2:3m\x1b[31m\x1b[38:2::255:0:0m");
+    }
+
+    #[test]
+    fn test_rgb_mode() {
+        let mut ti = TermInfo {
+            names: vec!["test".to_string()],
+            bools: HashMap::new(),
+            numbers: HashMap::new(),
+            strings: HashMap::new(),
+        };
+        assert!(rgb_mode(&ti, 256, None) == RgbMode::Palette);
+        assert!(rgb_mode(&ti, 256, Some("truecolor")) == RgbMode::Sgr);
+        assert!(rgb_mode(&ti, 8, Some("24bit")) == RgbMode::Sgr);
+        assert!(rgb_mode(&ti, 0, Some("truecolor")) == RgbMode::Palette);
+        assert!(rgb_mode(&ti, 256, Some("yes")) == RgbMode::Palette);
+        ti.bools.insert("Tc".to_string(), true);
+        assert!(rgb_mode(&ti, 256, None) == RgbMode::Sgr);
+        ti.bools.insert("RGB".to_string(), true);
+        assert!(rgb_mode(&ti, 0xFFFF, None) == RgbMode::Direct);
+        assert!(rgb_mode(&ti, 256, None) == RgbMode::Sgr);
+    }
+
+    #[test]
+    fn test_nearest_color() {
+        assert_eq!(color::to_rgb(color::BRIGHT_WHITE), color::Rgb(0xff, 0xff, 0xff));
+        assert_eq!(color::to_rgb(208), color::Rgb(0xff, 0x87, 0x00));
+        assert_eq!(color::to_rgb(232), color::Rgb(8, 8, 8));
+        assert_eq!(color::to_rgb(255), color::Rgb(238, 238, 238));
+        for c in range(16u16, 256) {
+            assert_eq!(color::nearest(color::to_rgb(c), 256), c);
+        }
+        assert_eq!(color::nearest(color::Rgb(0x80, 0x80, 0x80), 256), 244);
+        assert_eq!(color::nearest(color::Rgb(0xff, 0x87, 0x00), 16), color::BRIGHT_YELLOW);
+        assert_eq!(color::nearest(color::Rgb(0xff, 0x87, 0x00), 8), color::YELLOW);
+        assert_eq!(color::nearest(color::Rgb(0xff, 0xff, 0xff), 8), color::WHITE);
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        // From the xterm entry.
+        let mut t = terminal(&[("cup", "\x1b[%i%p1%d;%p2%dH"),
+                               ("cuu", "\x1b[%p1%dA"), ("cuu1", "\x1b[A"),
+                               ("cud1", "\n"),
+                               ("el", "\x1b[K"), ("ed", "\x1b[J"),
+                               ("sc", "\x1b7"), ("rc", "\x1b8")]);
+        assert_eq!(t.cursor_to(4, 0), Ok(true));
+        assert_eq!(t.cursor_up(1), Ok(true));
+        assert_eq!(t.cursor_up(3), Ok(true));
+        assert_eq!(t.cursor_up(0), Ok(true));
+        assert_eq!(t.cursor_down(2), Ok(true));
+        assert_eq!(t.clear_to_end_of_line(), Ok(true));
+        assert_eq!(t.clear_to_end_of_screen(), Ok(true));
+        assert_eq!(t.save_cursor(), Ok(true));
+        assert_eq!(t.restore_cursor(), Ok(true));
+        assert_eq!(t.out.get_ref(),
+                   b"\x1b[5;1H\x1b[A\x1b[3A\n\n\x1b[K\x1b[J\x1b7\x1b8");
+    }
+
+    #[test]
+    fn test_dumb_terminal() {
+        let mut t = terminal(&[]);
+        assert_eq!(t.cursor_to(4, 0), Ok(false));
+        assert_eq!(t.cursor_up(1), Ok(false));
+        assert_eq!(t.cursor_down(0), Ok(false));
+        assert_eq!(t.clear_to_end_of_line(), Ok(false));
+        assert_eq!(t.clear_to_end_of_screen(), Ok(false));
+        assert_eq!(t.save_cursor(), Ok(false));
+        assert_eq!(t.restore_cursor(), Ok(false));
+        assert!(t.out.get_ref().is_empty());
+    }
+}
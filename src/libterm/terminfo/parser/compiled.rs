@@ -12,8 +12,10 @@
 
 //! ncurses-compatible compiled terminfo format parsing (term(5))
 
+use std::cmp;
 use std::collections::HashMap;
 use std::io;
+use std::io::{IoError, IoResult};
 use super::super::TermInfo;
 
 // These are the orders ncurses uses in its compiled format (as of 5.9). Not sure if portable.
@@ -181,12 +183,15 @@ pub fn parse(file: &mut io::Reader, longnames: bool)
         nnames = numnames;
     }
 
-    // Check magic number
+    // Check magic number. Entries with numbers that don't fit in 16 bits
+    // (like `colors` for a direct color terminal) use 32-bit numbers.
     let magic = try!(file.read_le_u16());
-    if magic != 0x011A {
-        return Err(format!("invalid magic number: expected {:x}, found {:x}",
-                           0x011Au, magic as uint));
-    }
+    let wide_numbers = match magic {
+        0x011A => false,
+        0x021E => true,
+        _ => return Err(format!("invalid magic number: expected {:x} or {:x}, found {:x}",
+                                0x011Au, 0x021Eu, magic as uint)),
+    };
 
     let names_bytes          = try!(file.read_le_i16()) as int;
     let bools_bytes          = try!(file.read_le_i16()) as int;
@@ -241,9 +246,9 @@ pub fn parse(file: &mut io::Reader, longnames: bool)
     let mut numbers_map = HashMap::new();
     if numbers_count != 0 {
         for i in range(0, numbers_count) {
-            let n = try!(file.read_le_u16());
-            if n != 0xFFFF {
-                numbers_map.insert(nnames[i as uint].to_string(), n);
+            let n = try!(read_number(file, wide_numbers));
+            if n >= 0 {
+                numbers_map.insert(nnames[i as uint].to_string(), clamp_number(n));
             }
         }
     }
@@ -300,6 +305,9 @@ pub fn parse(file: &mut io::Reader, longnames: bool)
         }
     }
 
+    try!(parse_extended(file, wide_numbers, string_table_bytes % 2 == 1,
+                        &mut bools_map, &mut numbers_map, &mut string_map));
+
     // And that's all there is to it
     Ok(box TermInfo {
         names: term_names,
@@ -309,6 +317,125 @@ pub fn parse(file: &mut io::Reader, longnames: bool)
     })
 }
 
+fn read_number(file: &mut io::Reader, wide: bool) -> IoResult<i32> {
+    if wide {
+        file.read_le_i32()
+    } else {
+        file.read_le_i16().map(|n| n as i32)
+    }
+}
+
+// Numbers are stored as u16 in a TermInfo, so bigger ones are clamped.
+fn clamp_number(n: i32) -> u16 {
+    cmp::min(n, 0xFFFF) as u16
+}
+
+fn invalid_extended(desc: &'static str) -> IoError {
+    IoError { kind: io::InvalidInput, desc: desc, detail: None }
+}
+
+fn nul_terminated<'a>(table: &'a [u8], start: uint) -> IoResult<&'a [u8]> {
+    if start > table.len() {
+        return Err(invalid_extended("extended string offset out of range"))
+    }
+    match table[start..].iter().position(|&b| b == 0) {
+        Some(len) => Ok(table[start..start + len]),
+        None => Err(invalid_extended("missing NUL in extended string table")),
+    }
+}
+
+/// Parse the extended capabilities, which ncurses writes after the standard
+/// ones, into the given maps. These are the user-defined capabilities, like
+/// `RGB` and `Tc`, which are named in the entry itself. Older entries may
+/// not have any, in which case the file ends after the string table.
+fn parse_extended(file: &mut io::Reader, wide_numbers: bool, padded: bool,
+                  bools_map: &mut HashMap<String, bool>,
+                  numbers_map: &mut HashMap<String, u16>,
+                  string_map: &mut HashMap<String, Vec<u8>>) -> IoResult<()> {
+    if padded {
+        match file.read_byte() {
+            Err(ref e) if e.kind == io::EndOfFile => return Ok(()),
+            r => { try!(r); }
+        }
+    }
+    let bools_count = match file.read_le_i16() {
+        Err(ref e) if e.kind == io::EndOfFile => return Ok(()),
+        r => try!(r),
+    };
+    let numbers_count = try!(file.read_le_i16());
+    let strings_count = try!(file.read_le_i16());
+    let offsets_count = try!(file.read_le_i16());
+    let table_bytes = try!(file.read_le_i16());
+    if bools_count < 0 || numbers_count < 0 || strings_count < 0 || table_bytes < 0
+        || offsets_count as int != bools_count as int + numbers_count as int
+                                   + 2 * strings_count as int {
+        return Err(invalid_extended("invalid extended capabilities header"))
+    }
+    let (bools_count, numbers_count) = (bools_count as uint, numbers_count as uint);
+    let strings_count = strings_count as uint;
+
+    let bools = try!(file.read_exact(bools_count));
+    if bools_count % 2 == 1 {
+        try!(file.read_byte()); // padding
+    }
+    let mut numbers = Vec::with_capacity(numbers_count);
+    for _ in range(0, numbers_count) {
+        numbers.push(try!(read_number(file, wide_numbers)));
+    }
+    let mut offsets = Vec::with_capacity(offsets_count as uint);
+    for _ in range(0, offsets_count) {
+        offsets.push(try!(file.read_le_i16()));
+    }
+    let table = try!(file.read_exact(table_bytes as uint));
+
+    // The table holds the values of the strings, followed by the names of all
+    // the capabilities, whose offsets are relative to the end of the values.
+    let mut values = Vec::with_capacity(strings_count);
+    let mut names_start = 0;
+    for &offset in offsets[..strings_count].iter() {
+        if offset < 0 { // absent or cancelled
+            values.push(None);
+            continue
+        }
+        let value = try!(nul_terminated(table.as_slice(), offset as uint));
+        names_start = cmp::max(names_start, offset as uint + value.len() + 1);
+        values.push(Some(value));
+    }
+    let mut names = Vec::with_capacity(offsets.len() - strings_count);
+    for &offset in offsets[strings_count..].iter() {
+        if offset < 0 {
+            return Err(invalid_extended("missing extended capability name"))
+        }
+        let name = try!(nul_terminated(table.as_slice(), names_start + offset as uint));
+        match String::from_utf8(name.to_vec()) {
+            Ok(name) => names.push(name),
+            Err(_) => return Err(invalid_extended("extended capability name not utf-8")),
+        }
+    }
+
+    let mut names = names.into_iter();
+    for &b in bools.iter() {
+        let name = names.next().unwrap();
+        if b == 1 {
+            bools_map.insert(name, true);
+        }
+    }
+    for &n in numbers.iter() {
+        let name = names.next().unwrap();
+        if n >= 0 {
+            numbers_map.insert(name, clamp_number(n));
+        }
+    }
+    for value in values.into_iter() {
+        let name = names.next().unwrap();
+        match value {
+            Some(value) => { string_map.insert(name, value.to_vec()); }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
 /// Create a dummy TermInfo struct for msys terminals
 pub fn msys_terminfo() -> Box<TermInfo> {
     let mut strings = HashMap::new();
@@ -327,7 +454,8 @@ pub fn msys_terminfo() -> Box<TermInfo> {
 #[cfg(test)]
 mod test {
 
-    use super::{boolnames, boolfnames, numnames, numfnames, stringnames, stringfnames};
+    use std::io::{MemReader, MemWriter};
+    use super::{boolnames, boolfnames, numnames, numfnames, stringnames, stringfnames, parse};
 
     #[test]
     fn test_veclens() {
@@ -336,6 +464,70 @@ mod test {
         assert_eq!(stringfnames.len(), stringnames.len());
     }
 
+    // An entry for a terminal named "test" with `bw`, `cols` (in 32-bit
+    // numbers, if `wide`) and `bell`, optionally followed by extended
+    // capabilities.
+    fn entry(wide: bool, extended: bool) -> Vec<u8> {
+        let mut w = MemWriter::new();
+        w.write_le_u16(if wide { 0x021E } else { 0x011A }).unwrap();
+        for &n in [5i16, 1, 1, 2, 2].iter() {
+            w.write_le_i16(n).unwrap();
+        }
+        w.write(b"test\0").unwrap();
+        w.write_u8(1).unwrap(); // bw
+        if wide {
+            w.write_le_i32(0x1000000).unwrap();
+        } else {
+            w.write_le_i16(80).unwrap();
+        }
+        w.write_le_i16(-1).unwrap(); // cbt
+        w.write_le_i16(0).unwrap(); // bell
+        w.write(b"\x07\0").unwrap();
+        if extended {
+            for &n in [2i16, 1, 1, 5, 18].iter() {
+                w.write_le_i16(n).unwrap();
+            }
+            w.write(&[1, 0]).unwrap(); // RGB, XT
+            if wide {
+                w.write_le_i32(70000).unwrap();
+            } else {
+                w.write_le_i16(7).unwrap();
+            }
+            for &n in [0i16, 0, 4, 7, 10].iter() {
+                w.write_le_i16(n).unwrap();
+            }
+            w.write(b"\x1b[1m\0RGB\0XT\0NN\0Sx\0").unwrap();
+        }
+        w.unwrap()
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        let ti = parse(&mut MemReader::new(entry(false, false)), false).unwrap();
+        assert_eq!(ti.names, vec!["test".to_string()]);
+        assert_eq!(ti.bools.get("bw"), Some(&true));
+        assert_eq!(ti.numbers.get("cols"), Some(&80));
+        assert_eq!(ti.strings.get("cbt"), None);
+        assert_eq!(ti.strings.get("bell"), Some(&b"\x07".to_vec()));
+        assert_eq!(ti.bools.len() + ti.numbers.len() + ti.strings.len(), 3);
+
+        let ti = parse(&mut MemReader::new(entry(false, true)), false).unwrap();
+        assert_eq!(ti.bools.get("RGB"), Some(&true));
+        assert_eq!(ti.bools.get("XT"), None);
+        assert_eq!(ti.numbers.get("NN"), Some(&7));
+        assert_eq!(ti.strings.get("Sx"), Some(&b"\x1b[1m".to_vec()));
+
+        let ti = parse(&mut MemReader::new(entry(true, true)), false).unwrap();
+        assert_eq!(ti.numbers.get("cols"), Some(&0xFFFF));
+        assert_eq!(ti.numbers.get("NN"), Some(&0xFFFF));
+        assert_eq!(ti.bools.get("RGB"), Some(&true));
+        assert_eq!(ti.strings.get("bell"), Some(&b"\x07".to_vec()));
+
+        let mut bad = entry(false, false);
+        bad[0] = 0x1B;
+        assert!(parse(&mut MemReader::new(bad), false).is_err());
+    }
+
     #[test]
     #[ignore(reason = "no ncurses on buildbots, needs a bundled terminfo file to test against")]
     fn test_parse() {
@@ -51,6 +51,12 @@ extern "system" {
 fn color_to_bits(color: color::Color) -> u16 {
     // magic numbers from mingw-w64's wincon.h
 
+    // The console only has the 16 basic colors.
+    let color = if color >= 16 && color < 256 {
+        color::nearest(color::to_rgb(color), 16)
+    } else {
+        color
+    };
     let bits = match color % 8 {
         color::BLACK   => 0,
         color::BLUE    => 0x1,
@@ -86,6 +92,20 @@ fn bits_to_color(bits: u16) -> color::Color {
     color | (bits & 0x8) // copy the hi-intensity bit
 }
 
+/// Returns the size of the console window of stdout as `(columns, rows)`.
+pub fn size() -> Option<(u16, u16)> {
+    unsafe {
+        let mut buffer_info: CONSOLE_SCREEN_BUFFER_INFO = ::std::mem::uninitialized();
+        if GetConsoleScreenBufferInfo(GetStdHandle(-11), &mut buffer_info) != 0 {
+            // srWindow is left, top, right, bottom, inclusive.
+            let window = buffer_info.srWindow;
+            Some(((window[2] - window[0] + 1) as u16, (window[3] - window[1] + 1) as u16))
+        } else {
+            None
+        }
+    }
+}
+
 impl<T: Writer+Send> WinConsole<T> {
     fn apply(&mut self) {
         let _unused = self.buf.flush();
@@ -187,6 +207,9 @@ impl<T: Writer+Send> Terminal<T> for WinConsole<T> {
         Ok(())
     }
 
+    // FIXME (#13400): the cursor and clearing operations could be done with
+    // SetConsoleCursorPosition and FillConsoleOutputCharacter
+
     fn get_ref<'a>(&'a self) -> &'a T { &self.buf }
 
     fn get_mut<'a>(&'a mut self) -> &'a mut T { &mut self.buf }
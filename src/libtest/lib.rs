@@ -35,13 +35,17 @@
 #![feature(unboxed_closures, default_type_params)]
 
 extern crate getopts;
+extern crate libc;
+extern crate rand;
 extern crate regex;
 extern crate serialize;
 extern crate term;
 
 pub use self::TestFn::*;
 pub use self::MetricChange::*;
+pub use self::SampleChange::*;
 pub use self::ColorConfig::*;
+pub use self::OutputFormat::*;
 pub use self::TestResult::*;
 pub use self::TestName::*;
 use self::TestEvent::*;
@@ -52,6 +56,7 @@ use std::any::{Any, AnyRefExt};
 use std::collections::TreeMap;
 use stats::Stats;
 use getopts::{OptGroup, optflag, optopt};
+use rand::{Isaac64Rng, Rng, SeedableRng};
 use regex::Regex;
 use serialize::{json, Decodable, Encodable};
 use term::Terminal;
@@ -64,11 +69,14 @@ use std::fmt;
 use std::io::fs::PathExtensions;
 use std::io::stdio::StdWriter;
 use std::io::{File, ChanReader, ChanWriter};
+use std::io::process::{Command, InheritFd, ProcessExit, ExitStatus};
+use std::io::timer::Timer;
 use std::io;
 use std::num::{Float, FloatMath, Int};
 use std::os;
 use std::str::FromStr;
 use std::string::String;
+use std::sync::{Arc, Mutex};
 use std::task::TaskBuilder;
 use std::time::Duration;
 use std::thunk::{Thunk, Invoke};
@@ -76,9 +84,10 @@ use std::thunk::{Thunk, Invoke};
 // to be used by rustc to compile tests in libtest
 pub mod test {
     pub use {Bencher, TestName, TestResult, TestDesc,
-             TestDescAndFn, TestOpts, TrFailed, TrIgnored, TrOk,
+             TestDescAndFn, TestOpts, TrFailed, TrIgnored, TrOk, TrTimedOut,
              Metric, MetricMap, MetricAdded, MetricRemoved,
              MetricChange, Improvement, Regression, LikelyNoise,
+             SampleChange, Faster, Slower, NotSignificant,
              StaticTestFn, StaticTestName, DynTestName, DynTestFn,
              run_test, test_main, test_main_static, filter_tests,
              parse_opts, StaticBenchFn, ShouldFail};
@@ -207,6 +216,8 @@ pub struct TestDesc {
     pub name: TestName,
     pub ignore: bool,
     pub should_fail: ShouldFail,
+    // Overrides `TestOpts::test_timeout`, set with `#[timeout = "SECS"]`.
+    pub timeout: Option<u64>,
 }
 
 #[deriving(Show)]
@@ -218,14 +229,17 @@ pub struct TestDescAndFn {
 #[deriving(Clone, Encodable, Decodable, PartialEq, Show)]
 pub struct Metric {
     value: f64,
-    noise: f64
+    noise: f64,
+    // Only recorded for benchmarks, so that they can be compared with
+    // `MetricMap::compare_samples`. The test runner records the samples after
+    // winsorizing them, as it does for the summary.
+    summary: Option<stats::Summary<f64>>,
+    samples: Option<Vec<f64>>,
 }
 
-impl Copy for Metric {}
-
 impl Metric {
     pub fn new(value: f64, noise: f64) -> Metric {
-        Metric {value: value, noise: noise}
+        Metric {value: value, noise: noise, summary: None, samples: None}
     }
 }
 
@@ -253,15 +267,42 @@ impl Copy for MetricChange {}
 
 pub type MetricDiff = TreeMap<String,MetricChange>;
 
+/// Analysis of the change in a benchmark's samples, made by comparing them
+/// with a significance test. The confidence is a percentage.
+#[deriving(PartialEq, Show)]
+pub enum SampleChange {
+    /// The samples differ with less than the requested confidence.
+    NotSignificant(f64),
+    /// The median went down by the given percentage, with this confidence.
+    Faster(f64, f64),
+    /// The median went up by the given percentage, with this confidence.
+    Slower(f64, f64),
+}
+
+impl Copy for SampleChange {}
+
+pub type SampleDiff = TreeMap<String,SampleChange>;
+
+// The environment variable that tells a test executable started by
+// `--isolate` which test to run.
+static ISOLATED_TEST_VAR: &'static str = "RUST_TEST_ISOLATED";
+
 // The default console test runner. It accepts the command line
 // arguments and a vector of test_descs.
 pub fn test_main(args: &[String], tests: Vec<TestDescAndFn> ) {
-    let opts =
+    match os::getenv(ISOLATED_TEST_VAR) {
+        Some(name) => return run_isolated_test(name.as_slice(), tests),
+        None => {}
+    }
+    let mut opts =
         match parse_opts(args) {
             Some(Ok(o)) => o,
             Some(Err(msg)) => panic!("{}", msg),
             None => return
         };
+    // This executable runs a single test when it's started by
+    // `run_test_in_process`, so a test that times out can be killed.
+    opts.isolate_timeouts = true;
     match run_tests_console(&opts, tests) {
         Ok(true) => {}
         Ok(false) => panic!("Some tests failed"),
@@ -295,6 +336,18 @@ pub enum ColorConfig {
 
 impl Copy for ColorConfig {}
 
+/// How the progress and the results of a test run are written to stdout.
+#[deriving(PartialEq, Show)]
+pub enum OutputFormat {
+    /// Text for people to read.
+    PrettyFormat,
+    /// A JSON object on a line of its own for every event of the run, for
+    /// other programs to read.
+    JsonFormat,
+}
+
+impl Copy for OutputFormat {}
+
 pub struct TestOpts {
     pub filter: Option<Regex>,
     pub run_ignored: bool,
@@ -303,13 +356,24 @@ pub struct TestOpts {
     pub ratchet_metrics: Option<Path>,
     pub ratchet_noise_percent: Option<f64>,
     pub save_metrics: Option<Path>,
+    pub compare_metrics: Option<Path>,
+    pub compare_confidence: f64,
     pub test_shard: Option<(uint,uint)>,
+    pub shuffle_seed: Option<u64>,
     pub logfile: Option<Path>,
     pub nocapture: bool,
+    pub isolate: bool,
+    // Run the tests that have a timeout as `isolate` does, so that they can
+    // be killed once they time out. Set by `test_main`.
+    pub isolate_timeouts: bool,
     pub color: ColorConfig,
     pub show_boxplot: bool,
     pub boxplot_width: uint,
     pub show_all_stats: bool,
+    pub format: OutputFormat,
+    pub junit: Option<Path>,
+    pub test_timeout: Option<u64>,
+    pub slow_threshold: Option<u64>,
 }
 
 impl TestOpts {
@@ -323,13 +387,22 @@ impl TestOpts {
             ratchet_metrics: None,
             ratchet_noise_percent: None,
             save_metrics: None,
+            compare_metrics: None,
+            compare_confidence: 95.0,
             test_shard: None,
+            shuffle_seed: None,
             logfile: None,
             nocapture: false,
+            isolate: false,
+            isolate_timeouts: false,
             color: AutoColor,
             show_boxplot: false,
             boxplot_width: 50,
             show_all_stats: false,
+            format: PrettyFormat,
+            junit: None,
+            test_timeout: None,
+            slow_threshold: None,
         }
     }
 }
@@ -351,19 +424,40 @@ fn optgroups() -> Vec<getopts::OptGroup> {
       getopts::optopt("", "ratchet-noise-percent",
                      "Tests within N% of the recorded metrics will be \
                       considered as passing", "PERCENTAGE"),
+      getopts::optopt("", "compare-metrics",
+                     "Compare the benchmark samples with those saved to PATH \
+                      by --save-metrics, and report the significant changes",
+                      "PATH"),
+      getopts::optopt("", "compare-confidence",
+                     "Confidence needed for --compare-metrics to report a \
+                      change (default 95)", "PERCENTAGE"),
       getopts::optopt("", "logfile", "Write logs to the specified file instead \
                           of stdout", "PATH"),
       getopts::optopt("", "test-shard", "run shard A, of B shards, worth of the testsuite",
                      "A.B"),
+      getopts::optflag("", "shuffle", "run the tests in a random order"),
+      getopts::optopt("", "shuffle-seed", "run the tests in the random order given \
+                                           by SEED, as printed by --shuffle", "SEED"),
       getopts::optflag("", "nocapture", "don't capture stdout/stderr of each \
                                          task, allow printing directly"),
+      getopts::optflag("", "isolate", "run each test in a process of its own, so \
+                                       that crashes don't stop the other tests"),
       getopts::optopt("", "color", "Configure coloring of output:
             auto   = colorize if stdout is a tty and tests are run on serially (default);
             always = always colorize output;
             never  = never colorize output;", "auto|always|never"),
       getopts::optflag("", "boxplot", "Display a boxplot of the benchmark statistics"),
       getopts::optopt("", "boxplot-width", "Set the boxplot width (default 50)", "WIDTH"),
-      getopts::optflag("", "stats", "Display the benchmark min, max, and quartiles"))
+      getopts::optflag("", "stats", "Display the benchmark min, max, and quartiles"),
+      getopts::optopt("", "format", "Configure the format of the output:
+            pretty = text for people to read (default);
+            json   = a JSON object on each line for every event;", "pretty|json"),
+      getopts::optopt("", "junit", "Also write the results to the specified file \
+                                    as JUnit XML", "PATH"),
+      getopts::optopt("", "test-timeout", "Report tests that run for longer than \
+                                           SECS seconds as timed out", "SECS"),
+      getopts::optopt("", "slow-threshold", "Warn about tests that run for longer \
+                                             than SECS seconds", "SECS"))
 }
 
 fn usage(binary: &str) {
@@ -380,6 +474,18 @@ All tests have their standard output and standard error captured by default.
 This can be overridden with the --nocapture flag or the RUST_TEST_NOCAPTURE=1
 environment variable. Logging is not captured by default.
 
+With the --shuffle flag, the tests are run in a random order instead of
+alphabetically, to find tests that depend on each other. The order is given
+by a seed which is printed before the tests run; passing it back with
+--shuffle-seed runs the tests in the same order again.
+
+With the --isolate flag, each test is run in a new process of the test
+executable instead of in a task, so that a test that crashes, e.g., with a
+segmentation fault, an abort or a stack overflow, is reported as a failure
+instead of taking the other tests down with it. Tests with a timeout (see
+--test-timeout) are always run this way, so that they can be killed once they
+time out.
+
 Test Attributes:
 
     #[test]        - Indicates a function is a test to be run. This function
@@ -393,7 +499,10 @@ Test Attributes:
     #[ignore]      - When applied to a function which is already attributed as a
                      test, then the test runner will ignore these tests during
                      normal test runs. Running with --ignored will run these
-                     tests."#,
+                     tests.
+    #[timeout = "SECS"] - The test is reported as timed out if it runs for
+                     longer than SECS seconds, instead of after the time
+                     given with --test-timeout."#,
              usage = getopts::usage(message.as_slice(),
                                     optgroups().as_slice()));
 }
@@ -438,9 +547,32 @@ pub fn parse_opts(args: &[String]) -> Option<OptRes> {
     let save_metrics = matches.opt_str("save-metrics");
     let save_metrics = save_metrics.map(|s| Path::new(s));
 
+    let compare_metrics = matches.opt_str("compare-metrics");
+    let compare_metrics = compare_metrics.map(|s| Path::new(s));
+
+    let compare_confidence = match matches.opt_str("compare-confidence") {
+        Some(pct) => match from_str::<f64>(pct.as_slice()) {
+            Some(pct) if pct > 0.0 && pct < 100.0 => pct,
+            _ => return Some(Err(format!("argument for --compare-confidence must \
+                                          be a percentage between 0 and 100 (was {})",
+                                         pct))),
+        },
+        None => 95.0,
+    };
+
     let test_shard = matches.opt_str("test-shard");
     let test_shard = opt_shard(test_shard);
 
+    let shuffle_seed = match matches.opt_str("shuffle-seed") {
+        Some(seed) => match from_str::<u64>(seed.as_slice()) {
+            Some(seed) => Some(seed),
+            None => return Some(Err(format!("argument for --shuffle-seed must be \
+                                             a number (was {})", seed))),
+        },
+        None if matches.opt_present("shuffle") => Some(std::rand::random()),
+        None => None,
+    };
+
     let mut nocapture = matches.opt_present("nocapture");
     if !nocapture {
         nocapture = os::getenv("RUST_TEST_NOCAPTURE").is_some();
@@ -471,6 +603,37 @@ pub fn parse_opts(args: &[String]) -> Option<OptRes> {
 
     let show_all_stats = matches.opt_present("stats");
 
+    let format = match matches.opt_str("format").as_ref().map(|s| s.as_slice()) {
+        Some("pretty") | None => PrettyFormat,
+        Some("json") => JsonFormat,
+
+        Some(v) => return Some(Err(format!("argument for --format must be \
+                                            pretty or json (was {})", v))),
+    };
+
+    let junit = matches.opt_str("junit");
+    let junit = junit.map(|s| Path::new(s));
+
+    let test_timeout = match matches.opt_str("test-timeout") {
+        Some(secs) => match from_str::<u64>(secs.as_slice()) {
+            Some(secs) if secs > 0 => Some(secs),
+            _ => return Some(Err(format!("argument for --test-timeout must be \
+                                          a positive number of seconds (was {})",
+                                         secs))),
+        },
+        None => None,
+    };
+
+    let slow_threshold = match matches.opt_str("slow-threshold") {
+        Some(secs) => match from_str::<u64>(secs.as_slice()) {
+            Some(secs) if secs > 0 => Some(secs),
+            _ => return Some(Err(format!("argument for --slow-threshold must be \
+                                          a positive number of seconds (was {})",
+                                         secs))),
+        },
+        None => None,
+    };
+
     let test_opts = TestOpts {
         filter: filter,
         run_ignored: run_ignored,
@@ -479,13 +642,22 @@ pub fn parse_opts(args: &[String]) -> Option<OptRes> {
         ratchet_metrics: ratchet_metrics,
         ratchet_noise_percent: ratchet_noise_percent,
         save_metrics: save_metrics,
+        compare_metrics: compare_metrics,
+        compare_confidence: compare_confidence,
         test_shard: test_shard,
+        shuffle_seed: shuffle_seed,
         logfile: logfile,
         nocapture: nocapture,
+        isolate: matches.opt_present("isolate"),
+        isolate_timeouts: false,
         color: color,
         show_boxplot: show_boxplot,
         boxplot_width: boxplot_width,
         show_all_stats: show_all_stats,
+        format: format,
+        junit: junit,
+        test_timeout: test_timeout,
+        slow_threshold: slow_threshold,
     };
 
     Some(Ok(test_opts))
@@ -515,6 +687,7 @@ pub fn opt_shard(maybestr: Option<String>) -> Option<(uint,uint)> {
 #[deriving(Clone, PartialEq)]
 pub struct BenchSamples {
     ns_iter_summ: stats::Summary<f64>,
+    ns_iter_samples: Vec<f64>,
     mb_s: uint,
 }
 
@@ -522,6 +695,7 @@ pub struct BenchSamples {
 pub enum TestResult {
     TrOk,
     TrFailed,
+    TrTimedOut,
     TrIgnored,
     TrMetrics(MetricMap),
     TrBench(BenchSamples),
@@ -539,13 +713,19 @@ struct ConsoleTestState<T> {
     show_boxplot: bool,
     boxplot_width: uint,
     show_all_stats: bool,
+    format: OutputFormat,
+    junit: Option<Path>,
+    shuffle_seed: Option<u64>,
     total: uint,
     passed: uint,
     failed: uint,
+    timed_out: uint,
     ignored: uint,
     measured: uint,
     metrics: MetricMap,
     failures: Vec<(TestDesc, Vec<u8> )> ,
+    timeouts: Vec<TestDesc>,
+    results: Vec<MonitorMsg>, // kept for the JUnit report
     max_name_len: uint, // number of columns to fill when aligning names
 }
 
@@ -568,13 +748,19 @@ impl<T: Writer> ConsoleTestState<T> {
             show_boxplot: opts.show_boxplot,
             boxplot_width: opts.boxplot_width,
             show_all_stats: opts.show_all_stats,
+            format: opts.format,
+            junit: opts.junit.clone(),
+            shuffle_seed: opts.shuffle_seed,
             total: 0u,
             passed: 0u,
             failed: 0u,
+            timed_out: 0u,
             ignored: 0u,
             measured: 0u,
             metrics: MetricMap::new(),
             failures: Vec::new(),
+            timeouts: Vec::new(),
+            results: Vec::new(),
             max_name_len: 0u,
         })
     }
@@ -587,6 +773,10 @@ impl<T: Writer> ConsoleTestState<T> {
         self.write_pretty("FAILED", term::color::RED)
     }
 
+    pub fn write_timed_out(&mut self) -> io::IoResult<()> {
+        self.write_pretty("TIMEOUT", term::color::RED)
+    }
+
     pub fn write_ignored(&mut self) -> io::IoResult<()> {
         self.write_pretty("ignored", term::color::YELLOW)
     }
@@ -640,22 +830,113 @@ impl<T: Writer> ConsoleTestState<T> {
         }
     }
 
+    /// Writes a JSON object with the given fields on a line of its own. The
+    /// values must already be JSON.
+    pub fn write_json(&mut self, fields: &[(&str, String)]) -> io::IoResult<()> {
+        let fields: Vec<String> = fields.iter()
+            .map(|&(k, ref v)| format!("{}:{}", json_str(k), v))
+            .collect();
+        self.write_plain(format!("{{{}}}\n", fields.connect(",")).as_slice())
+    }
+
     pub fn write_run_start(&mut self, len: uint) -> io::IoResult<()> {
         self.total = len;
+        if self.format == JsonFormat {
+            let mut fields = vec!(("type", json_str("suite")),
+                                  ("event", json_str("started")),
+                                  ("test_count", len.to_string()));
+            match self.shuffle_seed {
+                Some(seed) => fields.push(("shuffle_seed", seed.to_string())),
+                None => {}
+            }
+            return self.write_json(fields.as_slice());
+        }
         let noun = if len != 1 { "tests" } else { "test" };
-        self.write_plain(format!("\nrunning {} {}\n", len, noun).as_slice())
+        try!(self.write_plain(format!("\nrunning {} {}\n", len, noun).as_slice()));
+        match self.shuffle_seed {
+            Some(seed) => self.write_plain(format!("shuffled with --shuffle-seed {}\n",
+                                                   seed).as_slice()),
+            None => Ok(()),
+        }
+    }
+
+    pub fn write_test_started(&mut self, test: &TestDesc) -> io::IoResult<()> {
+        match self.format {
+            // The name is written by `write_test_start` instead, next to
+            // the result.
+            PrettyFormat => Ok(()),
+            JsonFormat => self.write_json(&[("type", json_str("test")),
+                                            ("event", json_str("started")),
+                                            ("name", json_str(test.name.as_slice()))]),
+        }
     }
 
     pub fn write_test_start(&mut self, test: &TestDesc,
                             align: NamePadding) -> io::IoResult<()> {
+        if self.format == JsonFormat {
+            return Ok(());
+        }
         let name = test.padded_name(self.max_name_len, align);
         self.write_plain(format!("test {} ... ", name).as_slice())
     }
 
-    pub fn write_result(&mut self, result: &TestResult) -> io::IoResult<()> {
+    pub fn write_result(&mut self, test: &TestDesc, result: &TestResult,
+                        stdout: &[u8], exec_time: Duration) -> io::IoResult<()> {
+        match self.format {
+            PrettyFormat => self.write_pretty_result(result),
+            JsonFormat => self.write_json_result(test, result, stdout, exec_time),
+        }
+    }
+
+    fn write_json_result(&mut self, test: &TestDesc, result: &TestResult,
+                         stdout: &[u8], exec_time: Duration) -> io::IoResult<()> {
+        let name = json_str(test.name.as_slice());
+        let exec_time = format!("{:.6}", duration_secs(exec_time));
+        match *result {
+            TrMetrics(MetricMap(ref mm)) => {
+                let metrics: Vec<String> = mm.iter()
+                    .map(|(k, v)| format!("{}:{{\"value\":{},\"noise\":{}}}",
+                                          json_str(k.as_slice()), json_f64(v.value),
+                                          json_f64(v.noise)))
+                    .collect();
+                self.write_json(&[("type", json_str("metric")),
+                                  ("name", name),
+                                  ("metrics", format!("{{{}}}", metrics.connect(",")))])
+            }
+            TrBench(ref bs) => {
+                self.write_json(&[("type", json_str("bench")),
+                                  ("name", name),
+                                  ("median", (bs.ns_iter_summ.median as u64).to_string()),
+                                  ("deviation", ((bs.ns_iter_summ.max - bs.ns_iter_summ.min)
+                                                 as u64).to_string()),
+                                  ("mb_per_second", bs.mb_s.to_string()),
+                                  ("exec_time", exec_time)])
+            }
+            _ => {
+                let event = match *result {
+                    TrOk => "ok",
+                    TrFailed => "failed",
+                    TrTimedOut => "timeout",
+                    _ => "ignored",
+                };
+                let mut fields = vec![("type", json_str("test")),
+                                      ("event", json_str(event)),
+                                      ("name", name),
+                                      ("exec_time", exec_time)];
+                if stdout.len() > 0 {
+                    let output = String::from_utf8_lossy(stdout);
+                    fields.push(("stdout", json_str(output.as_slice())));
+                }
+                self.write_json(fields.as_slice())
+            }
+        }
+    }
+
+    fn write_pretty_result(&mut self, result: &TestResult) -> io::IoResult<()> {
         try!(match *result {
             TrOk => self.write_ok(),
             TrFailed => self.write_failed(),
+            TrTimedOut => self.write_timed_out(),
             TrIgnored => self.write_ignored(),
             TrMetrics(ref mm) => {
                 try!(self.write_metric());
@@ -701,6 +982,7 @@ impl<T: Writer> ConsoleTestState<T> {
                 let s = format!("{} {}\n", match *result {
                         TrOk => "ok".to_string(),
                         TrFailed => "failed".to_string(),
+                        TrTimedOut => "timeout".to_string(),
                         TrIgnored => "ignored".to_string(),
                         TrMetrics(ref mm) => fmt_metrics(mm),
                         TrBench(ref bs) => fmt_bench_samples(bs)
@@ -738,7 +1020,55 @@ impl<T: Writer> ConsoleTestState<T> {
         Ok(())
     }
 
+    pub fn write_timeouts(&mut self) -> io::IoResult<()> {
+        try!(self.write_plain("\ntimeouts:\n"));
+        let mut timeouts: Vec<String> = self.timeouts.iter()
+            .map(|t| t.name.to_string())
+            .collect();
+        timeouts.sort();
+        for name in timeouts.iter() {
+            try!(self.write_plain(format!("    {}\n",
+                                          name.as_slice()).as_slice()));
+        }
+        Ok(())
+    }
+
     pub fn write_metric_diff(&mut self, diff: &MetricDiff) -> io::IoResult<()> {
+        if self.format == JsonFormat {
+            let mut regressed = false;
+            for (k, v) in diff.iter() {
+                let name = json_str(k.as_slice());
+                try!(match *v {
+                    LikelyNoise | MetricAdded | MetricRemoved => {
+                        let event = match *v {
+                            LikelyNoise => "noise",
+                            MetricAdded => "added",
+                            _ => "removed",
+                        };
+                        self.write_json(&[("type", json_str("ratchet")),
+                                          ("event", json_str(event)),
+                                          ("name", name)])
+                    }
+                    Improvement(pct) | Regression(pct) => {
+                        let event = match *v {
+                            Improvement(..) => "improved",
+                            _ => {
+                                regressed = true;
+                                "regressed"
+                            }
+                        };
+                        self.write_json(&[("type", json_str("ratchet")),
+                                          ("event", json_str(event)),
+                                          ("name", name),
+                                          ("change", json_f64(pct))])
+                    }
+                });
+            }
+            let event = if regressed { "untouched" } else { "updated" };
+            return self.write_json(&[("type", json_str("ratchet")),
+                                     ("event", json_str(event))]);
+        }
+
         let mut noise = 0u;
         let mut improved = 0u;
         let mut regressed = 0u;
@@ -787,35 +1117,105 @@ impl<T: Writer> ConsoleTestState<T> {
         Ok(())
     }
 
+    pub fn write_sample_diff(&mut self, pth: &Path,
+                             diff: &SampleDiff) -> io::IoResult<()> {
+        if self.format == JsonFormat {
+            for (k, v) in diff.iter() {
+                let name = json_str(k.as_slice());
+                try!(match *v {
+                    NotSignificant(conf) =>
+                        self.write_json(&[("type", json_str("comparison")),
+                                          ("event", json_str("unchanged")),
+                                          ("name", name),
+                                          ("confidence", format!("{:.2}", conf))]),
+                    Faster(pct, conf) | Slower(pct, conf) => {
+                        let event = match *v { Faster(..) => "faster", _ => "slower" };
+                        self.write_json(&[("type", json_str("comparison")),
+                                          ("event", json_str(event)),
+                                          ("name", name),
+                                          ("change", format!("{:.2}", pct)),
+                                          ("confidence", format!("{:.2}", conf))])
+                    }
+                });
+            }
+            return Ok(());
+        }
+
+        try!(self.write_plain(format!("\ncomparing bench samples with: {}\n",
+                                      pth.display()).as_slice()));
+        let mut unchanged = 0u;
+        let mut faster = 0u;
+        let mut slower = 0u;
+        for (k, v) in diff.iter() {
+            match *v {
+                NotSignificant(_) => unchanged += 1,
+                Faster(pct, conf) => {
+                    faster += 1;
+                    try!(self.write_plain(format!("{}: ", *k).as_slice()));
+                    try!(self.write_pretty("faster", term::color::GREEN));
+                    try!(self.write_plain(format!(" by {:.2}% ({:.1}% confidence)\n",
+                                                  pct, conf).as_slice()));
+                }
+                Slower(pct, conf) => {
+                    slower += 1;
+                    try!(self.write_plain(format!("{}: ", *k).as_slice()));
+                    try!(self.write_pretty("slower", term::color::RED));
+                    try!(self.write_plain(format!(" by {:.2}% ({:.1}% confidence)\n",
+                                                  pct, conf).as_slice()));
+                }
+            }
+        }
+        self.write_plain(format!("result of comparison: {} faster, {} slower, \
+                                  {} without significant change\n",
+                                 faster, slower, unchanged).as_slice())
+    }
+
     pub fn write_run_finish(&mut self,
                             ratchet_metrics: &Option<Path>,
                             ratchet_pct: Option<f64>) -> io::IoResult<bool> {
-        assert!(self.passed + self.failed + self.ignored + self.measured == self.total);
+        assert!(self.passed + self.failed + self.timed_out + self.ignored +
+                self.measured == self.total);
 
         let ratchet_success = match *ratchet_metrics {
             None => true,
             Some(ref pth) => {
-                try!(self.write_plain(format!("\nusing metrics ratchet: {}\n",
-                                              pth.display()).as_slice()));
-                match ratchet_pct {
-                    None => (),
-                    Some(pct) =>
-                        try!(self.write_plain(format!("with noise-tolerance \
-                                                         forced to: {}%\n",
-                                                        pct).as_slice()))
-                }
                 let (diff, ok) = self.metrics.ratchet(pth, ratchet_pct);
+                if self.format == PrettyFormat {
+                    try!(self.write_plain(format!("\nusing metrics ratchet: {}\n",
+                                                  pth.display()).as_slice()));
+                    match ratchet_pct {
+                        None => (),
+                        Some(pct) =>
+                            try!(self.write_plain(format!("with noise-tolerance \
+                                                             forced to: {}%\n",
+                                                            pct).as_slice()))
+                    }
+                }
                 try!(self.write_metric_diff(&diff));
                 ok
             }
         };
 
-        let test_success = self.failed == 0u;
-        if !test_success {
-            try!(self.write_failures());
+        let test_success = self.failed == 0u && self.timed_out == 0u;
+        let success = ratchet_success && test_success;
+
+        if self.format == JsonFormat {
+            try!(self.write_json(&[("type", json_str("suite")),
+                                   ("event", json_str(if success { "ok" } else { "failed" })),
+                                   ("passed", self.passed.to_string()),
+                                   ("failed", self.failed.to_string()),
+                                   ("timed_out", self.timed_out.to_string()),
+                                   ("ignored", self.ignored.to_string()),
+                                   ("measured", self.measured.to_string())]));
+            return Ok(success);
         }
 
-        let success = ratchet_success && test_success;
+        if self.failed > 0 {
+            try!(self.write_failures());
+        }
+        if self.timed_out > 0 {
+            try!(self.write_timeouts());
+        }
 
         try!(self.write_plain("\ntest result: "));
         if success {
@@ -824,11 +1224,108 @@ impl<T: Writer> ConsoleTestState<T> {
         } else {
             try!(self.write_failed());
         }
-        let s = format!(". {} passed; {} failed; {} ignored; {} measured\n\n",
-                        self.passed, self.failed, self.ignored, self.measured);
+        let timed_out = if self.timed_out > 0 {
+            format!("; {} timed out", self.timed_out)
+        } else {
+            String::new()
+        };
+        let s = format!(". {} passed; {} failed{}; {} ignored; {} measured\n\n",
+                        self.passed, self.failed, timed_out, self.ignored, self.measured);
         try!(self.write_plain(s.as_slice()));
         return Ok(success);
     }
+
+    pub fn write_junit(&mut self) -> io::IoResult<()> {
+        match self.junit {
+            None => Ok(()),
+            Some(ref path) => {
+                let suite = os::self_exe_name()
+                    .and_then(|p| p.filestem_str().map(|s| s.to_string()))
+                    .unwrap_or("test".to_string());
+                let mut file = try!(File::create(path));
+                write_junit(&mut file, suite.as_slice(), self.results.as_slice())
+            }
+        }
+    }
+}
+
+fn json_str(s: &str) -> String {
+    json::encode(&s.to_string())
+}
+
+// JSON has no NaN or infinity, so those are written as `null`.
+fn json_f64(v: f64) -> String {
+    if v.is_finite() { v.to_string() } else { "null".to_string() }
+}
+
+fn duration_secs(d: Duration) -> f64 {
+    match d.num_nanoseconds() {
+        Some(ns) => ns as f64 / 1e9,
+        None => d.num_seconds() as f64,
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            // Other control characters aren't allowed in XML 1.0 at all.
+            '\x00' ... '\x1f' => escaped.push('\u{fffd}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes the results of a test run as a JUnit XML report with a single
+/// test suite. A test's class is the module path in its name, or the suite
+/// if there's none. Tests that timed out are reported as errors.
+pub fn write_junit<W: Writer>(w: &mut W, suite: &str,
+                              results: &[MonitorMsg]) -> io::IoResult<()> {
+    let failed = results.iter().filter(|r| r.1 == TrFailed).count();
+    let timed_out = results.iter().filter(|r| r.1 == TrTimedOut).count();
+    let ignored = results.iter().filter(|r| r.1 == TrIgnored).count();
+    let time = results.iter().fold(0.0, |t, r| t + duration_secs(r.3));
+
+    try!(w.write_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n"));
+    try!(write!(w, "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" \
+                    skipped=\"{}\" time=\"{:.6}\">\n",
+                xml_escape(suite), results.len(), failed, timed_out, ignored, time));
+    for &(ref desc, ref result, ref stdout, exec_time) in results.iter() {
+        let name = desc.name.as_slice();
+        let (class, name) = match name.split_str("::").last() {
+            Some(last) if last.len() < name.len() => {
+                (name.slice_to(name.len() - last.len() - 2), last)
+            }
+            _ => (suite, name),
+        };
+        try!(write!(w, "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.6}\"",
+                    xml_escape(class), xml_escape(name), duration_secs(exec_time)));
+        match *result {
+            TrFailed => {
+                try!(w.write_str(">\n      <failure message=\"test failed\"/>\n"));
+                if stdout.len() > 0 {
+                    let output = String::from_utf8_lossy(stdout.as_slice());
+                    try!(write!(w, "      <system-out>{}</system-out>\n",
+                                xml_escape(output.as_slice())));
+                }
+                try!(w.write_str("    </testcase>\n"));
+            }
+            TrTimedOut => {
+                try!(w.write_str(">\n      <error message=\"test timed out\"/>\n    \
+                                  </testcase>\n"))
+            }
+            TrIgnored => try!(w.write_str(">\n      <skipped/>\n    </testcase>\n")),
+            TrOk | TrMetrics(_) | TrBench(_) => try!(w.write_str("/>\n")),
+        }
+    }
+    w.write_str("  </testsuite>\n</testsuites>\n")
 }
 
 pub fn fmt_metrics(mm: &MetricMap) -> String {
@@ -854,15 +1351,23 @@ pub fn fmt_bench_samples(bs: &BenchSamples) -> String {
 }
 
 // A simple console test runner
+//
+// Unless `opts.isolate` or `opts.isolate_timeouts` is set, a test that times
+// out is reported as such but its task can't be stopped: it keeps running in
+// this process, and the runtime waits for it before the process exits.
 pub fn run_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn> ) -> io::IoResult<bool> {
 
     fn callback<T: Writer>(event: &TestEvent, st: &mut ConsoleTestState<T>) -> io::IoResult<()> {
         match (*event).clone() {
             TeFiltered(ref filtered_tests) => st.write_run_start(filtered_tests.len()),
+            TeStarted(ref test) => st.write_test_started(test),
             TeWait(ref test, padding) => st.write_test_start(test, padding),
-            TeResult(test, result, stdout) => {
+            TeResult(test, result, stdout, exec_time) => {
                 try!(st.write_log(&test, &result));
-                try!(st.write_result(&result));
+                try!(st.write_result(&test, &result, stdout.as_slice(), exec_time));
+                if st.junit.is_some() {
+                    st.results.push((test.clone(), result.clone(), stdout.clone(), exec_time));
+                }
                 match result {
                     TrOk => st.passed += 1,
                     TrIgnored => st.ignored += 1,
@@ -880,21 +1385,37 @@ pub fn run_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn> ) -> io::IoR
                         st.measured += 1
                     }
                     TrBench(bs) => {
-                        st.metrics.insert_metric(test.name.as_slice(),
-                                                 bs.ns_iter_summ.median,
-                                                 bs.ns_iter_summ.max - bs.ns_iter_summ.min);
+                        st.metrics.insert_samples(test.name.as_slice(),
+                                                  bs.ns_iter_samples.as_slice());
                         st.measured += 1
                     }
                     TrFailed => {
                         st.failed += 1;
                         st.failures.push((test, stdout));
                     }
+                    TrTimedOut => {
+                        st.timed_out += 1;
+                        st.timeouts.push(test);
+                    }
                 }
                 Ok(())
             }
         }
     }
 
+    // Find out about a mistyped path before running the benchmarks, rather
+    // than after.
+    match opts.compare_metrics {
+        Some(ref pth) if !pth.exists() => {
+            return Err(io::IoError {
+                kind: io::FileNotFound,
+                desc: "no metrics to compare with",
+                detail: Some(format!("{} does not exist", pth.display())),
+            })
+        }
+        _ => {}
+    }
+
     let mut st = try!(ConsoleTestState::new(opts, None::<StdWriter>));
     fn len_if_padded(t: &TestDescAndFn) -> uint {
         match t.testfn.padding() {
@@ -910,47 +1431,69 @@ pub fn run_tests_console(opts: &TestOpts, tests: Vec<TestDescAndFn> ) -> io::IoR
         None => {}
     }
     try!(run_tests(opts, tests, |x| callback(&x, &mut st)));
+    // Compare before saving, so that both options can name the same file.
+    match opts.compare_metrics {
+        None => (),
+        Some(ref pth) => {
+            let old = MetricMap::load(pth);
+            let diff = st.metrics.compare_samples(&old, opts.compare_confidence);
+            try!(st.write_sample_diff(pth, &diff));
+        }
+    }
     match opts.save_metrics {
         None => (),
         Some(ref pth) => {
             try!(st.metrics.save(pth));
-            try!(st.write_plain(format!("\nmetrics saved to: {}",
-                                          pth.display()).as_slice()));
+            if st.format == PrettyFormat {
+                try!(st.write_plain(format!("\nmetrics saved to: {}",
+                                              pth.display()).as_slice()));
+            }
         }
     }
+    try!(st.write_junit());
     return st.write_run_finish(&opts.ratchet_metrics, opts.ratchet_noise_percent);
 }
 
-#[test]
-fn should_sort_failures_before_printing_them() {
-    let test_a = TestDesc {
-        name: StaticTestName("a"),
-        ignore: false,
-        should_fail: ShouldFail::No
-    };
-
-    let test_b = TestDesc {
-        name: StaticTestName("b"),
+#[cfg(test)]
+fn desc(name: &'static str) -> TestDesc {
+    TestDesc {
+        name: StaticTestName(name),
         ignore: false,
-        should_fail: ShouldFail::No
-    };
+        should_fail: ShouldFail::No,
+        timeout: None
+    }
+}
 
-    let mut st = ConsoleTestState {
+#[cfg(test)]
+fn test_state(format: OutputFormat) -> ConsoleTestState<Vec<u8>> {
+    ConsoleTestState {
         log_out: None,
         out: Raw(Vec::new()),
         use_color: false,
         show_boxplot: false,
         boxplot_width: 0,
         show_all_stats: false,
+        format: format,
+        junit: None,
+        shuffle_seed: None,
         total: 0u,
         passed: 0u,
         failed: 0u,
+        timed_out: 0u,
         ignored: 0u,
         measured: 0u,
         max_name_len: 10u,
         metrics: MetricMap::new(),
-        failures: vec!((test_b, Vec::new()), (test_a, Vec::new()))
-    };
+        failures: Vec::new(),
+        timeouts: Vec::new(),
+        results: Vec::new(),
+    }
+}
+
+#[test]
+fn should_sort_failures_before_printing_them() {
+    let mut st = test_state(PrettyFormat);
+    st.failures = vec!((desc("b"), Vec::new()), (desc("a"), Vec::new()));
 
     st.write_failures().unwrap();
     let s = match st.out {
@@ -963,6 +1506,88 @@ fn should_sort_failures_before_printing_them() {
     assert!(apos < bpos);
 }
 
+#[test]
+fn should_write_json_events() {
+    let mut st = test_state(JsonFormat);
+    st.passed = 1;
+    st.failed = 1;
+
+    st.write_run_start(2).unwrap();
+    st.write_test_started(&desc("a")).unwrap();
+    st.write_test_start(&desc("a"), PadNone).unwrap();
+    st.write_result(&desc("a"), &TrOk, &[], Duration::microseconds(1500)).unwrap();
+    st.write_result(&desc("b"), &TrFailed, b"hi\n", Duration::seconds(2)).unwrap();
+    st.write_run_finish(&None, None).unwrap();
+    let s = match st.out {
+        Raw(ref m) => String::from_utf8_lossy(m[]),
+        Pretty(_) => unreachable!()
+    };
+
+    assert_eq!(s.as_slice(),
+               "{\"type\":\"suite\",\"event\":\"started\",\"test_count\":2}\n\
+                {\"type\":\"test\",\"event\":\"started\",\"name\":\"a\"}\n\
+                {\"type\":\"test\",\"event\":\"ok\",\"name\":\"a\",\"exec_time\":0.001500}\n\
+                {\"type\":\"test\",\"event\":\"failed\",\"name\":\"b\",\"exec_time\":2.000000,\
+                 \"stdout\":\"hi\\n\"}\n\
+                {\"type\":\"suite\",\"event\":\"failed\",\"passed\":1,\"failed\":1,\
+                 \"timed_out\":0,\"ignored\":0,\"measured\":0}\n");
+}
+
+#[test]
+fn should_write_json_metrics() {
+    let mut st = test_state(JsonFormat);
+
+    let mut mm = MetricMap::new();
+    mm.insert_metric("x", f64::NAN, f64::INFINITY);
+    st.write_result(&desc("a"), &TrMetrics(mm), &[], Duration::zero()).unwrap();
+
+    let mut diff = TreeMap::new();
+    diff.insert("x".to_string(), MetricAdded);
+    diff.insert("y".to_string(), Regression(12.5));
+    st.write_metric_diff(&diff).unwrap();
+    let s = match st.out {
+        Raw(ref m) => String::from_utf8_lossy(m[]),
+        Pretty(_) => unreachable!()
+    };
+
+    assert_eq!(s.as_slice(),
+               "{\"type\":\"metric\",\"name\":\"a\",\
+                 \"metrics\":{\"x\":{\"value\":null,\"noise\":null}}}\n\
+                {\"type\":\"ratchet\",\"event\":\"added\",\"name\":\"x\"}\n\
+                {\"type\":\"ratchet\",\"event\":\"regressed\",\"name\":\"y\",\
+                 \"change\":12.5}\n\
+                {\"type\":\"ratchet\",\"event\":\"untouched\"}\n");
+}
+
+#[test]
+fn should_write_junit_report() {
+    let results = [(desc("module::a"), TrOk, Vec::new(), Duration::microseconds(1500)),
+                   (desc("b"), TrFailed, b"x < y".to_vec(), Duration::seconds(2)),
+                   (desc("module::c"), TrIgnored, Vec::new(), Duration::zero()),
+                   (desc("module::d"), TrTimedOut, Vec::new(), Duration::seconds(5))];
+
+    let mut w = Vec::new();
+    write_junit(&mut w, "suite", results[]).unwrap();
+    assert_eq!(String::from_utf8(w).unwrap().as_slice(),
+               "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                <testsuites>\n  \
+                  <testsuite name=\"suite\" tests=\"4\" failures=\"1\" errors=\"1\" \
+                             skipped=\"1\" time=\"7.001500\">\n    \
+                    <testcase classname=\"module\" name=\"a\" time=\"0.001500\"/>\n    \
+                    <testcase classname=\"suite\" name=\"b\" time=\"2.000000\">\n      \
+                      <failure message=\"test failed\"/>\n      \
+                      <system-out>x &lt; y</system-out>\n    \
+                    </testcase>\n    \
+                    <testcase classname=\"module\" name=\"c\" time=\"0.000000\">\n      \
+                      <skipped/>\n    \
+                    </testcase>\n    \
+                    <testcase classname=\"module\" name=\"d\" time=\"5.000000\">\n      \
+                      <error message=\"test timed out\"/>\n    \
+                    </testcase>\n  \
+                  </testsuite>\n\
+                </testsuites>\n");
+}
+
 fn use_color(opts: &TestOpts) -> bool {
     match opts.color {
         AutoColor => get_concurrency() == 1 && io::stdout().get_ref().isatty(),
@@ -974,11 +1599,12 @@ fn use_color(opts: &TestOpts) -> bool {
 #[deriving(Clone)]
 enum TestEvent {
     TeFiltered(Vec<TestDesc> ),
+    TeStarted(TestDesc),
     TeWait(TestDesc, NamePadding),
-    TeResult(TestDesc, TestResult, Vec<u8> ),
+    TeResult(TestDesc, TestResult, Vec<u8>, Duration),
 }
 
-pub type MonitorMsg = (TestDesc, TestResult, Vec<u8> );
+pub type MonitorMsg = (TestDesc, TestResult, Vec<u8>, Duration);
 
 fn run_tests<F>(opts: &TestOpts,
                 tests: Vec<TestDescAndFn> ,
@@ -1019,15 +1645,16 @@ fn run_tests<F>(opts: &TestOpts,
                 // that hang forever.
                 try!(callback(TeWait(test.desc.clone(), test.testfn.padding())));
             }
+            try!(callback(TeStarted(test.desc.clone())));
             run_test(opts, !opts.run_tests, test, tx.clone());
             pending += 1;
         }
 
-        let (desc, result, stdout) = rx.recv();
+        let (desc, result, stdout, exec_time) = rx.recv();
         if concurrency != 1 {
             try!(callback(TeWait(desc.clone(), PadNone)));
         }
-        try!(callback(TeResult(desc, result, stdout)));
+        try!(callback(TeResult(desc, result, stdout, exec_time)));
         pending -= 1;
     }
 
@@ -1035,9 +1662,10 @@ fn run_tests<F>(opts: &TestOpts,
     // (this includes metric fns)
     for b in filtered_benchs_and_metrics.into_iter() {
         try!(callback(TeWait(b.desc.clone(), b.testfn.padding())));
+        try!(callback(TeStarted(b.desc.clone())));
         run_test(opts, !opts.run_benchmarks, b, tx.clone());
-        let (test, result, stdout) = rx.recv();
-        try!(callback(TeResult(test, result, stdout)));
+        let (test, result, stdout, exec_time) = rx.recv();
+        try!(callback(TeResult(test, result, stdout, exec_time)));
     }
     Ok(())
 }
@@ -1092,7 +1720,7 @@ pub fn filter_tests(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> Vec<TestDescA
     filtered.sort_by(|t1, t2| t1.desc.name.as_slice().cmp(t2.desc.name.as_slice()));
 
     // Shard the remaining tests, if sharding requested.
-    match opts.test_shard {
+    filtered = match opts.test_shard {
         None => filtered,
         Some((a,b)) => {
             filtered.into_iter().enumerate()
@@ -1102,7 +1730,19 @@ pub fn filter_tests(opts: &TestOpts, tests: Vec<TestDescAndFn>) -> Vec<TestDescA
             .map(|(_,t)| t)
             .collect()
         }
+    };
+
+    // Shuffle the shard, if requested. Isaac64Rng is used directly, rather
+    // than `StdRng`, so that a seed gives the same order on every platform.
+    match opts.shuffle_seed {
+        None => {}
+        Some(seed) => {
+            let mut rng: Isaac64Rng = SeedableRng::from_seed([seed].as_slice());
+            rng.shuffle(filtered.as_mut_slice());
+        }
     }
+
+    filtered
 }
 
 pub fn run_test(opts: &TestOpts,
@@ -1111,19 +1751,46 @@ pub fn run_test(opts: &TestOpts,
                 monitor_ch: Sender<MonitorMsg>) {
 
     let TestDescAndFn {desc, testfn} = test;
+    let timeout = desc.timeout.or(opts.test_timeout);
 
     if force_ignore || desc.ignore {
-        monitor_ch.send((desc, TrIgnored, Vec::new()));
+        monitor_ch.send((desc, TrIgnored, Vec::new(), Duration::zero()));
         return;
     }
 
     fn run_test_inner(desc: TestDesc,
                       monitor_ch: Sender<MonitorMsg>,
                       nocapture: bool,
+                      timeout: Option<u64>,
+                      slow_threshold: Option<u64>,
                       testfn: Thunk) {
+        // Runs the test, collecting its output into `output` as it comes, so
+        // that what was captured is still there if the test times out.
+        fn run_and_capture(task: TaskBuilder, testfn: Thunk, mut reader: ChanReader,
+                           output: Arc<Mutex<Vec<u8>>>)
+                           -> (Result<(), Box<Any + Send>>, Duration) {
+            let mut task = Some(task);
+            let mut testfn = Some(testfn);
+            let mut task_result = None;
+            let exec_time = Duration::span(|| {
+                let testfn = testfn.take().unwrap();
+                let result_future = task.take().unwrap()
+                                        .try_future(move || testfn.invoke(()));
+                let mut buf = [0u8, ..4096];
+                loop {
+                    match reader.read(&mut buf) {
+                        Ok(n) => output.lock().push_all(buf[..n]),
+                        Err(..) => break,
+                    }
+                }
+                task_result = Some(result_future.into_inner());
+            });
+            (task_result.unwrap(), exec_time)
+        }
+
         spawn(move || {
             let (tx, rx) = channel();
-            let mut reader = ChanReader::new(rx);
+            let reader = ChanReader::new(rx);
             let stdout = ChanWriter::new(tx.clone());
             let stderr = ChanWriter::new(tx);
             let mut task = TaskBuilder::new().named(match desc.name {
@@ -1136,41 +1803,229 @@ pub fn run_test(opts: &TestOpts,
                 task = task.stdout(box stdout as Box<Writer + Send>);
                 task = task.stderr(box stderr as Box<Writer + Send>);
             }
-            let result_future = task.try_future(move || testfn.invoke(()));
 
-            let stdout = reader.read_to_end().unwrap().into_iter().collect();
-            let task_result = result_future.into_inner();
-            let test_result = calc_result(&desc, task_result);
-            monitor_ch.send((desc.clone(), test_result, stdout));
+            let output = Arc::new(Mutex::new(Vec::new()));
+            if timeout.is_none() && slow_threshold.is_none() {
+                let (task_result, exec_time) = run_and_capture(task, testfn, reader,
+                                                               output.clone());
+                let stdout = output.lock().clone();
+                monitor_ch.send((desc.clone(), calc_result(&desc, task_result),
+                                 stdout, exec_time));
+                return;
+            }
+
+            // The test is waited for in a task of its own, so that this one
+            // can give up on it when it times out.
+            let (result_tx, result_rx) = channel();
+            let captured = output.clone();
+            spawn(move || {
+                let _ = result_tx.send_opt(run_and_capture(task, testfn, reader, captured));
+            });
+
+            let mut timer = Timer::new().unwrap();
+            let ticks = timer.periodic(Duration::seconds(1));
+            let mut secs = 0u64;
+            let mut result = None;
+            while result.is_none() {
+                select! {
+                    r = result_rx.recv() => result = Some(r),
+                    () = ticks.recv() => {
+                        secs += 1;
+                        if Some(secs) == slow_threshold {
+                            let _ = writeln!(&mut io::stderr(),
+                                             "test {} has been running for over {} seconds",
+                                             desc.name, secs);
+                        }
+                        if Some(secs) == timeout {
+                            break;
+                        }
+                    }
+                }
+            }
+            let stdout = output.lock().clone();
+            let msg = match result {
+                Some((task_result, exec_time)) => {
+                    (desc.clone(), calc_result(&desc, task_result), stdout, exec_time)
+                }
+                None => (desc.clone(), TrTimedOut, stdout, Duration::seconds(secs as i64)),
+            };
+            monitor_ch.send(msg);
         })
     }
 
     match testfn {
         DynBenchFn(bencher) => {
-            let bs = ::bench::benchmark(|harness| bencher.run(harness));
-            monitor_ch.send((desc, TrBench(bs), Vec::new()));
+            let mut bs = None;
+            let exec_time = Duration::span(|| {
+                bs = Some(::bench::benchmark(|harness| bencher.run(harness)));
+            });
+            monitor_ch.send((desc, TrBench(bs.unwrap()), Vec::new(), exec_time));
             return;
         }
         StaticBenchFn(benchfn) => {
-            let bs = ::bench::benchmark(|harness| (benchfn.clone())(harness));
-            monitor_ch.send((desc, TrBench(bs), Vec::new()));
+            let mut bs = None;
+            let exec_time = Duration::span(|| {
+                bs = Some(::bench::benchmark(|harness| (benchfn.clone())(harness)));
+            });
+            monitor_ch.send((desc, TrBench(bs.unwrap()), Vec::new(), exec_time));
             return;
         }
         DynMetricFn(f) => {
             let mut mm = MetricMap::new();
             f.invoke(&mut mm);
-            monitor_ch.send((desc, TrMetrics(mm), Vec::new()));
+            monitor_ch.send((desc, TrMetrics(mm), Vec::new(), Duration::zero()));
             return;
         }
         StaticMetricFn(f) => {
             let mut mm = MetricMap::new();
             f(&mut mm);
-            monitor_ch.send((desc, TrMetrics(mm), Vec::new()));
+            monitor_ch.send((desc, TrMetrics(mm), Vec::new(), Duration::zero()));
             return;
         }
-        DynTestFn(f) => run_test_inner(desc, monitor_ch, opts.nocapture, f),
-        StaticTestFn(f) => run_test_inner(desc, monitor_ch, opts.nocapture,
-                                          Thunk::new(move|| f()))
+        DynTestFn(_) | StaticTestFn(_)
+                if opts.isolate || (opts.isolate_timeouts && timeout.is_some()) => {
+            run_test_in_process(desc, monitor_ch, opts.nocapture, timeout,
+                                opts.slow_threshold)
+        }
+        DynTestFn(f) => run_test_inner(desc, monitor_ch, opts.nocapture, timeout,
+                                       opts.slow_threshold, f),
+        StaticTestFn(f) => run_test_inner(desc, monitor_ch, opts.nocapture, timeout,
+                                          opts.slow_threshold, Thunk::new(move|| f()))
+    }
+}
+
+// Runs a test in a new process of the test executable, which runs it with
+// `run_isolated_test`, so that a crash only takes that test down.
+fn run_test_in_process(desc: TestDesc,
+                       monitor_ch: Sender<MonitorMsg>,
+                       nocapture: bool,
+                       timeout: Option<u64>,
+                       slow_threshold: Option<u64>) {
+    fn read_stream(stream: Option<io::PipeStream>) -> Receiver<Vec<u8>> {
+        let (tx, rx) = channel();
+        spawn(move || {
+            let mut stream = stream;
+            tx.send(match stream {
+                Some(ref mut stream) => stream.read_to_end().unwrap_or(Vec::new()),
+                None => Vec::new(),
+            })
+        });
+        rx
+    }
+
+    spawn(move || {
+        let mut cmd = match os::self_exe_name() {
+            Some(exe) => Command::new(exe),
+            None => {
+                let msg = "couldn't find the test executable".as_bytes().to_vec();
+                monitor_ch.send((desc, TrFailed, msg, Duration::zero()));
+                return;
+            }
+        };
+        cmd.env(ISOLATED_TEST_VAR, desc.name.as_slice());
+        if nocapture {
+            cmd.stdout(InheritFd(libc::STDOUT_FILENO));
+            cmd.stderr(InheritFd(libc::STDERR_FILENO));
+        }
+
+        let mut secs = 0u64;
+        let mut status = None;
+        let mut output = Vec::new();
+        let exec_time = Duration::span(|| {
+            let mut process = match cmd.spawn() {
+                Ok(process) => process,
+                Err(e) => {
+                    output = format!("couldn't start the test process: {}", e).into_bytes();
+                    return;
+                }
+            };
+            drop(process.stdin.take());
+            let stdout = read_stream(process.stdout.take());
+            let stderr = read_stream(process.stderr.take());
+
+            // Wait a second at a time, to warn once the test is slow and to
+            // kill it once it times out.
+            loop {
+                process.set_timeout(Some(1000));
+                match process.wait() {
+                    Ok(exit) => {
+                        status = Some(exit);
+                        break;
+                    }
+                    Err(ref e) if e.kind == io::TimedOut => {}
+                    Err(e) => {
+                        output = format!("couldn't wait for the test process: {}", e)
+                                 .into_bytes();
+                        let _ = process.signal_kill();
+                        break;
+                    }
+                }
+                secs += 1;
+                if Some(secs) == slow_threshold {
+                    let _ = writeln!(&mut io::stderr(),
+                                     "test {} has been running for over {} seconds",
+                                     desc.name, secs);
+                }
+                if Some(secs) == timeout {
+                    let _ = process.signal_kill();
+                    break;
+                }
+            }
+
+            // A killed process has to be reaped before its pipes are closed,
+            // and whatever it wrote before then is still reported.
+            if status.is_none() {
+                process.set_timeout(None);
+                let _ = process.wait();
+            }
+            output.push_all(stdout.recv().as_slice());
+            output.push_all(stderr.recv().as_slice());
+        });
+
+        let result = match status {
+            Some(status) => isolated_result(status, &mut output),
+            None if Some(secs) == timeout => TrTimedOut,
+            None => TrFailed,
+        };
+        monitor_ch.send((desc, result, output, exec_time));
+    })
+}
+
+// The result of a test run by `run_test_in_process`, from the exit status of
+// its process. Anything but a normal failure is noted in the output.
+fn isolated_result(status: ProcessExit, output: &mut Vec<u8>) -> TestResult {
+    match status {
+        ExitStatus(0) => TrOk,
+        ExitStatus(101) => TrFailed,
+        status => {
+            output.push_all(format!("\ntest process terminated abnormally ({})\n",
+                                    status).as_bytes());
+            TrFailed
+        }
+    }
+}
+
+// Runs the test with the given name in this task, for a process started by
+// `run_test_in_process`. Its output isn't captured, and the process exits
+// with status 101 if the test fails, like a failed test run.
+fn run_isolated_test(name: &str, tests: Vec<TestDescAndFn>) {
+    // Any test harness that the test itself runs shouldn't think it's isolated
+    // too.
+    os::unsetenv(ISOLATED_TEST_VAR);
+    let TestDescAndFn {desc, testfn} =
+        match tests.into_iter().find(|t| t.desc.name.as_slice() == name) {
+            Some(test) => test,
+            None => panic!("there is no test named `{}`", name),
+        };
+    let testfn = match testfn {
+        StaticTestFn(f) => Thunk::new(move|| f()),
+        DynTestFn(f) => f,
+        _ => panic!("`{}` is not a test", name),
+    };
+    let task = TaskBuilder::new().named(name.to_string());
+    let task_result = task.try_future(move || testfn.invoke(())).into_inner();
+    if calc_result(&desc, task_result) != TrOk {
+        os::set_exit_status(101);
     }
 }
 
@@ -1290,14 +2145,65 @@ impl MetricMap {
     /// you want to see grow larger, so a change larger than `noise` in the
     /// negative direction represents a regression.
     pub fn insert_metric(&mut self, name: &str, value: f64, noise: f64) {
+        let MetricMap(ref mut map) = *self;
+        map.insert(name.to_string(), Metric::new(value, noise));
+    }
+
+    /// Insert a named metric for the timings of a benchmark, in ns/iter.
+    /// Its value is the median of the `samples` and its noise their range,
+    /// and the samples themselves are kept for `compare_samples`.
+    ///
+    /// The samples are stored as given. The test runner passes the samples of
+    /// a benchmark after winsorizing them at 5% (see `stats::winsorize`), so
+    /// the metrics it saves don't have the raw extremes.
+    pub fn insert_samples(&mut self, name: &str, samples: &[f64]) {
+        let summ = stats::Summary::new(samples);
         let m = Metric {
-            value: value,
-            noise: noise
+            value: summ.median,
+            noise: summ.max - summ.min,
+            summary: Some(summ),
+            samples: Some(samples.to_vec()),
         };
         let MetricMap(ref mut map) = *self;
         map.insert(name.to_string(), m);
     }
 
+    /// Compare the benchmark samples against those in another MetricMap,
+    /// using a Mann-Whitney U test. A change is only reported as `Faster` or
+    /// `Slower` when the samples differ with at least `confidence_pct`
+    /// percent confidence. Metrics without samples in both maps are left
+    /// out of the diff.
+    pub fn compare_samples(&self, old: &MetricMap, confidence_pct: f64) -> SampleDiff {
+        let mut diff : SampleDiff = TreeMap::new();
+        let MetricMap(ref selfmap) = *self;
+        let MetricMap(ref old) = *old;
+        for (k, v) in selfmap.iter() {
+            let vold = match old.get(k) {
+                Some(vold) => vold,
+                None => continue,
+            };
+            let (samples, old_samples) = match (&v.samples, &vold.samples) {
+                (&Some(ref s), &Some(ref o)) => (s.as_slice(), o.as_slice()),
+                _ => continue,
+            };
+            let (u, p) = stats::mann_whitney_u(samples, old_samples);
+            let confidence = (1.0 - p) * 100.0;
+            let pct = (v.value - vold.value).abs() / vold.value.max(f64::EPSILON) * 100.0;
+            // A U below its mean means the new samples tend to be the
+            // smaller ones.
+            let mean_u = (samples.len() * old_samples.len()) as f64 / 2.0;
+            let r = if confidence < confidence_pct {
+                NotSignificant(confidence)
+            } else if u < mean_u {
+                Faster(pct, confidence)
+            } else {
+                Slower(pct, confidence)
+            };
+            diff.insert((*k).clone(), r);
+        }
+        diff
+    }
+
     /// Attempt to "ratchet" an external metric file. This involves loading
     /// metrics from a metric file (if it exists), comparing against
     /// the metrics in `self` using `compare_to_old`, and rewriting the
@@ -1370,7 +2276,14 @@ impl Bencher {
     }
 
     // This is a more statistics-driven benchmark algorithm
-    pub fn auto_bench<F>(&mut self, mut f: F) -> stats::Summary<f64> where F: FnMut(&mut Bencher) {
+    pub fn auto_bench<F>(&mut self, f: F) -> stats::Summary<f64> where F: FnMut(&mut Bencher) {
+        self.auto_bench_samples(f).0
+    }
+
+    // Like `auto_bench`, but also returns the samples the summary was
+    // made from.
+    fn auto_bench_samples<F>(&mut self, mut f: F) -> (stats::Summary<f64>, Vec<f64>)
+                             where F: FnMut(&mut Bencher) {
         // Initial bench run to get ballpark figure.
         let mut n = 1_u64;
         self.bench_n(n, |x| f(x));
@@ -1421,13 +2334,13 @@ impl Bencher {
             if loop_run.num_milliseconds() > 100 &&
                 summ.median_abs_dev_pct < 1.0 &&
                 summ.median - summ5.median < summ5.median_abs_dev {
-                return summ5;
+                return (summ5, samples.to_vec());
             }
 
             total_run = total_run + loop_run;
             // Longest we ever run for is 3s.
             if total_run.num_seconds() > 3 {
-                return summ5;
+                return (summ5, samples.to_vec());
             }
 
             n *= 2;
@@ -1447,7 +2360,7 @@ pub mod bench {
             bytes: 0
         };
 
-        let ns_iter_summ = bs.auto_bench(f);
+        let (ns_iter_summ, ns_iter_samples) = bs.auto_bench_samples(f);
 
         let ns_iter = cmp::max(ns_iter_summ.median as u64, 1);
         let iter_s = 1_000_000_000 / ns_iter;
@@ -1455,6 +2368,7 @@ pub mod bench {
 
         BenchSamples {
             ns_iter_summ: ns_iter_summ,
+            ns_iter_samples: ns_iter_samples,
             mb_s: mb_s as uint
         }
     }
@@ -1462,12 +2376,16 @@ pub mod bench {
 
 #[cfg(test)]
 mod tests {
-    use test::{TrFailed, TrIgnored, TrOk, filter_tests, parse_opts,
+    use test::{TrFailed, TrIgnored, TrOk, TrTimedOut, filter_tests, parse_opts,
                TestDesc, TestDescAndFn, TestOpts, run_test,
                Metric, MetricMap, MetricAdded, MetricRemoved,
                Improvement, Regression, LikelyNoise,
+               Faster, Slower, NotSignificant,
                StaticTestName, DynTestName, DynTestFn, ShouldFail};
+    use {JsonFormat, isolated_result, run_tests_console};
+    use std::io;
     use std::io::TempDir;
+    use std::io::process::{ExitStatus, ExitSignal};
     use std::thunk::Thunk;
 
     #[test]
@@ -1478,12 +2396,13 @@ mod tests {
                 name: StaticTestName("whatever"),
                 ignore: true,
                 should_fail: ShouldFail::No,
+                timeout: None,
             },
             testfn: DynTestFn(Thunk::new(move|| f())),
         };
         let (tx, rx) = channel();
         run_test(&TestOpts::new(), false, desc, tx);
-        let (_, res, _) = rx.recv();
+        let (_, res, _, _) = rx.recv();
         assert!(res != TrOk);
     }
 
@@ -1495,12 +2414,13 @@ mod tests {
                 name: StaticTestName("whatever"),
                 ignore: true,
                 should_fail: ShouldFail::No,
+                timeout: None,
             },
             testfn: DynTestFn(Thunk::new(move|| f())),
         };
         let (tx, rx) = channel();
         run_test(&TestOpts::new(), false, desc, tx);
-        let (_, res, _) = rx.recv();
+        let (_, res, _, _) = rx.recv();
         assert!(res == TrIgnored);
     }
 
@@ -1511,13 +2431,14 @@ mod tests {
             desc: TestDesc {
                 name: StaticTestName("whatever"),
                 ignore: false,
-                should_fail: ShouldFail::Yes(None)
+                should_fail: ShouldFail::Yes(None),
+                timeout: None
             },
             testfn: DynTestFn(Thunk::new(move|| f())),
         };
         let (tx, rx) = channel();
         run_test(&TestOpts::new(), false, desc, tx);
-        let (_, res, _) = rx.recv();
+        let (_, res, _, _) = rx.recv();
         assert!(res == TrOk);
     }
 
@@ -1528,13 +2449,14 @@ mod tests {
             desc: TestDesc {
                 name: StaticTestName("whatever"),
                 ignore: false,
-                should_fail: ShouldFail::Yes(Some("error message"))
+                should_fail: ShouldFail::Yes(Some("error message")),
+                timeout: None
             },
             testfn: DynTestFn(Thunk::new(move|| f())),
         };
         let (tx, rx) = channel();
         run_test(&TestOpts::new(), false, desc, tx);
-        let (_, res, _) = rx.recv();
+        let (_, res, _, _) = rx.recv();
         assert!(res == TrOk);
     }
 
@@ -1545,13 +2467,14 @@ mod tests {
             desc: TestDesc {
                 name: StaticTestName("whatever"),
                 ignore: false,
-                should_fail: ShouldFail::Yes(Some("foobar"))
+                should_fail: ShouldFail::Yes(Some("foobar")),
+                timeout: None
             },
             testfn: DynTestFn(Thunk::new(move|| f())),
         };
         let (tx, rx) = channel();
         run_test(&TestOpts::new(), false, desc, tx);
-        let (_, res, _) = rx.recv();
+        let (_, res, _, _) = rx.recv();
         assert!(res == TrFailed);
     }
 
@@ -1562,16 +2485,37 @@ mod tests {
             desc: TestDesc {
                 name: StaticTestName("whatever"),
                 ignore: false,
-                should_fail: ShouldFail::Yes(None)
+                should_fail: ShouldFail::Yes(None),
+                timeout: None
             },
             testfn: DynTestFn(Thunk::new(move|| f())),
         };
         let (tx, rx) = channel();
         run_test(&TestOpts::new(), false, desc, tx);
-        let (_, res, _) = rx.recv();
+        let (_, res, _, _) = rx.recv();
         assert!(res == TrFailed);
     }
 
+    #[test]
+    fn test_timeout() {
+        // The test blocks until it's timed out, and then finishes.
+        let (done_tx, done_rx) = channel::<()>();
+        let desc = TestDescAndFn {
+            desc: TestDesc {
+                name: StaticTestName("whatever"),
+                ignore: false,
+                should_fail: ShouldFail::No,
+                timeout: Some(1),
+            },
+            testfn: DynTestFn(Thunk::new(move|| { let _ = done_rx.recv_opt(); })),
+        };
+        let (tx, rx) = channel();
+        run_test(&TestOpts::new(), false, desc, tx);
+        let (_, res, _, _) = rx.recv();
+        assert!(res == TrTimedOut);
+        drop(done_tx);
+    }
+
     #[test]
     fn first_free_arg_should_be_a_filter() {
         let args = vec!("progname".to_string(), "some_regex_filter".to_string());
@@ -1582,6 +2526,101 @@ mod tests {
         assert!(opts.filter.expect("should've found filter").is_match("some_regex_filter"))
     }
 
+    #[test]
+    fn parse_format_options() {
+        let args = vec!("progname".to_string(),
+                        "--format=json".to_string(),
+                        "--junit".to_string(),
+                        "report.xml".to_string());
+        let opts = match parse_opts(args.as_slice()) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_format_options")
+        };
+        assert_eq!(opts.format, JsonFormat);
+        assert_eq!(opts.junit, Some(Path::new("report.xml")));
+        assert_eq!(opts.test_timeout, None);
+        assert_eq!(opts.slow_threshold, None);
+
+        let args = vec!("progname".to_string(), "--format=yaml".to_string());
+        assert!(match parse_opts(args.as_slice()) {
+            Some(Err(_)) => true,
+            _ => false
+        });
+    }
+
+    #[test]
+    fn parse_isolate_flag() {
+        let args = vec!("progname".to_string(), "--isolate".to_string());
+        let opts = match parse_opts(args.as_slice()) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_isolate_flag")
+        };
+        assert!(opts.isolate);
+    }
+
+    #[test]
+    fn parse_compare_options() {
+        let args = vec!("progname".to_string(),
+                        "--compare-metrics".to_string(), "old.json".to_string(),
+                        "--compare-confidence".to_string(), "99".to_string());
+        let opts = match parse_opts(args.as_slice()) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_compare_options")
+        };
+        assert_eq!(opts.compare_metrics, Some(Path::new("old.json")));
+        assert_eq!(opts.compare_confidence, 99.0);
+
+        let args = vec!("progname".to_string(),
+                        "--compare-confidence".to_string(), "100".to_string());
+        assert!(parse_opts(args.as_slice()).unwrap().is_err());
+    }
+
+    #[test]
+    fn compare_with_missing_metrics() {
+        let dir = TempDir::new("test-metrics").ok().expect("missing test for tmpdir");
+        let mut opts = TestOpts::new();
+        opts.compare_metrics = Some(dir.path().join("missing.json"));
+        match run_tests_console(&opts, vec![]) {
+            Err(e) => assert_eq!(e.kind, io::FileNotFound),
+            Ok(_) => panic!("compared with metrics that don't exist"),
+        }
+    }
+
+    #[test]
+    fn isolated_test_results() {
+        let mut output = Vec::new();
+        assert!(isolated_result(ExitStatus(0), &mut output) == TrOk);
+        assert!(isolated_result(ExitStatus(101), &mut output) == TrFailed);
+        assert!(output.is_empty());
+        assert!(isolated_result(ExitSignal(11), &mut output) == TrFailed);
+        assert_eq!(String::from_utf8(output).unwrap().as_slice(),
+                   "\ntest process terminated abnormally (signal: 11)\n");
+    }
+
+    #[test]
+    fn parse_timeout_options() {
+        let args = vec!("progname".to_string(),
+                        "--test-timeout=30".to_string(),
+                        "--slow-threshold=5".to_string());
+        let opts = match parse_opts(args.as_slice()) {
+            Some(Ok(o)) => o,
+            _ => panic!("Malformed arg in parse_timeout_options")
+        };
+        assert_eq!(opts.test_timeout, Some(30));
+        assert_eq!(opts.slow_threshold, Some(5));
+
+        let args = vec!("progname".to_string(), "--test-timeout=0".to_string());
+        assert!(match parse_opts(args.as_slice()) {
+            Some(Err(_)) => true,
+            _ => false
+        });
+        let args = vec!("progname".to_string(), "--slow-threshold=0".to_string());
+        assert!(match parse_opts(args.as_slice()) {
+            Some(Err(_)) => true,
+            _ => false
+        });
+    }
+
     #[test]
     fn parse_ignored_flag() {
         let args = vec!("progname".to_string(),
@@ -1609,6 +2648,7 @@ mod tests {
                     name: StaticTestName("1"),
                     ignore: true,
                     should_fail: ShouldFail::No,
+                    timeout: None,
                 },
                 testfn: DynTestFn(Thunk::new(move|| {})),
             },
@@ -1617,6 +2657,7 @@ mod tests {
                     name: StaticTestName("2"),
                     ignore: false,
                     should_fail: ShouldFail::No,
+                    timeout: None,
                 },
                 testfn: DynTestFn(Thunk::new(move|| {})),
             });
@@ -1653,6 +2694,7 @@ mod tests {
                         name: DynTestName((*name).clone()),
                         ignore: false,
                         should_fail: ShouldFail::No,
+                        timeout: None,
                     },
                     testfn: DynTestFn(Thunk::new(testfn)),
                 };
@@ -1678,6 +2720,56 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn shuffle_tests() {
+        fn testfn() { }
+        fn make_tests() -> Vec<TestDescAndFn> {
+            range(0u, 20).map(|i| {
+                TestDescAndFn {
+                    desc: TestDesc {
+                        name: DynTestName(format!("test{:02}", i)),
+                        ignore: false,
+                        should_fail: ShouldFail::No,
+                        timeout: None,
+                    },
+                    testfn: DynTestFn(Thunk::new(testfn)),
+                }
+            }).collect()
+        }
+        fn names(tests: Vec<TestDescAndFn>) -> Vec<String> {
+            tests.iter().map(|t| t.desc.name.to_string()).collect()
+        }
+
+        let mut opts = TestOpts::new();
+        opts.run_tests = true;
+        let sorted = names(filter_tests(&opts, make_tests()));
+
+        opts.shuffle_seed = Some(1234);
+        let shuffled = names(filter_tests(&opts, make_tests()));
+        assert!(shuffled != sorted);
+        assert_eq!(names(filter_tests(&opts, make_tests())), shuffled);
+
+        let mut resorted = shuffled.clone();
+        resorted.sort();
+        assert_eq!(resorted, sorted);
+    }
+
+    #[test]
+    fn parse_shuffle_options() {
+        let args = vec!("progname".to_string(), "--shuffle-seed".to_string(),
+                        "42".to_string());
+        let opts = parse_opts(args.as_slice()).unwrap().unwrap();
+        assert_eq!(opts.shuffle_seed, Some(42));
+
+        let args = vec!("progname".to_string(), "--shuffle".to_string());
+        let opts = parse_opts(args.as_slice()).unwrap().unwrap();
+        assert!(opts.shuffle_seed.is_some());
+
+        let args = vec!("progname".to_string());
+        let opts = parse_opts(args.as_slice()).unwrap().unwrap();
+        assert_eq!(opts.shuffle_seed, None);
+    }
+
     #[test]
     pub fn filter_tests_regex() {
         let mut opts = TestOpts::new();
@@ -1694,6 +2786,7 @@ mod tests {
                     name: DynTestName(name.to_string()),
                     ignore: false,
                     should_fail: ShouldFail::No,
+                    timeout: None,
                 },
                 testfn: DynTestFn(Thunk::new(test_fn))
             }
@@ -1807,4 +2900,48 @@ mod tests {
         assert_eq!(*(m4.get(&"runtime".to_string()).unwrap()), Metric::new(1100.0, 2.0));
         assert_eq!(*(m4.get(&"throughput".to_string()).unwrap()), Metric::new(50.0, 2.0));
     }
+    #[test]
+    pub fn test_compare_samples() {
+        let dpth = TempDir::new("test-compare-samples").ok().expect("missing test for samples");
+        let pth = dpth.path().join("metrics.json");
+
+        let mut m1 = MetricMap::new();
+        m1.insert_samples("faster", &[97.0, 98.0, 99.0, 100.0, 101.0, 102.0, 103.0]);
+        m1.insert_samples("slower", &[97.0, 98.0, 99.0, 100.0, 101.0, 102.0, 103.0]);
+        m1.insert_samples("noise", &[97.0, 98.0, 99.0, 100.0, 101.0, 102.0, 103.0]);
+        m1.insert_samples("in-first", &[100.0, 101.0]);
+        m1.insert_metric("no-samples", 1000.0, 2.0);
+
+        // The samples must survive a round trip through the metrics file.
+        m1.save(&pth).unwrap();
+        let m1 = MetricMap::load(&pth);
+
+        let mut m2 = MetricMap::new();
+        m2.insert_samples("faster", &[47.0, 48.0, 49.0, 50.0, 51.0, 52.0, 53.0]);
+        m2.insert_samples("slower", &[197.0, 198.0, 199.0, 200.0, 201.0, 202.0, 203.0]);
+        m2.insert_samples("noise", &[96.0, 98.0, 99.0, 100.0, 101.0, 103.0, 104.0]);
+        m2.insert_samples("in-second", &[100.0, 101.0]);
+        m2.insert_metric("no-samples", 2000.0, 2.0);
+
+        let diff = m2.compare_samples(&m1, 95.0);
+        assert_eq!(diff.len(), 3);
+        match *diff.get(&"faster".to_string()).unwrap() {
+            Faster(pct, conf) => {
+                assert_eq!(pct, 50.0);
+                assert!(conf > 99.0);
+            }
+            c => panic!("expected faster, got {}", c),
+        }
+        match *diff.get(&"slower".to_string()).unwrap() {
+            Slower(pct, conf) => {
+                assert_eq!(pct, 100.0);
+                assert!(conf > 99.0);
+            }
+            c => panic!("expected slower, got {}", c),
+        }
+        match *diff.get(&"noise".to_string()).unwrap() {
+            NotSignificant(conf) => assert!(conf < 95.0),
+            c => panic!("expected no significant change, got {}", c),
+        }
+    }
 }
@@ -11,6 +11,7 @@
 #![allow(missing_docs)]
 
 use std::collections::hash_map;
+use std::f64;
 use std::collections::hash_map::{Occupied, Vacant};
 use std::fmt::Show;
 use std::hash::Hash;
@@ -126,7 +127,7 @@ pub trait Stats <T: FloatMath + FromPrimitive> for Sized? {
 }
 
 /// Extracted collection of all the summary statistics of a sample set.
-#[deriving(Clone, PartialEq)]
+#[deriving(Clone, PartialEq, Show, Encodable, Decodable)]
 #[allow(missing_docs)]
 pub struct Summary<T> {
     pub sum: T,
@@ -448,12 +449,80 @@ pub fn freq_count<T: Iterator<U>, U: Eq+Hash>(mut iter: T) -> hash_map::HashMap<
     map
 }
 
+/// Mann-Whitney U test of whether the samples in `x` and `y` are drawn from
+/// the same distribution, without assuming anything about its shape. Returns
+/// the U statistic of `x` and the two-sided p-value.
+///
+/// The p-value uses the normal approximation to the distribution of U, with
+/// corrections for ties and continuity, so it is only meaningful when both
+/// sample sets have more than a handful of elements.
+pub fn mann_whitney_u(x: &[f64], y: &[f64]) -> (f64, f64) {
+    let n1 = x.len() as f64;
+    let n2 = y.len() as f64;
+    let mut all: Vec<(f64, bool)> = x.iter().map(|&v| (v, true))
+                                     .chain(y.iter().map(|&v| (v, false)))
+                                     .collect();
+    all.sort_by(|a, b| local_cmp(a.0, b.0));
+
+    // Rank the combined samples; a run of tied samples all get the mean of
+    // the ranks they span.
+    let mut rank_sum = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0u;
+    while i < all.len() {
+        let mut j = i + 1;
+        while j < all.len() && all[j].0 == all[i].0 {
+            j += 1;
+        }
+        let rank = (i + j + 1) as f64 / 2.0;
+        for &(_, from_x) in all[i..j].iter() {
+            if from_x {
+                rank_sum += rank;
+            }
+        }
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+
+    let u = rank_sum - n1 * (n1 + 1.0) / 2.0;
+    if n1 == 0.0 || n2 == 0.0 {
+        return (u, 1.0);
+    }
+    let n = n1 + n2;
+    let var = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if var <= 0.0 {
+        return (u, 1.0);
+    }
+    let z = ((u - n1 * n2 / 2.0).abs() - 0.5).max(0.0) / var.sqrt();
+    (u, erfc(z / f64::consts::SQRT2).min(1.0))
+}
+
+// Complementary error function, with a fractional error below 1.2e-7
+// (Numerical Recipes, section 6.2).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z - 1.26551223 +
+                 t * (1.00002368 +
+                 t * (0.37409196 +
+                 t * (0.09678418 +
+                 t * (-0.18628806 +
+                 t * (0.27886807 +
+                 t * (-1.13520398 +
+                 t * (1.48851587 +
+                 t * (-0.82215223 +
+                 t * 0.17087277))))))))).exp();
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
 // Test vectors generated from R, using the script src/etc/stat-test-vectors.r.
 
 #[cfg(test)]
 mod tests {
     use stats::Stats;
     use stats::Summary;
+    use stats::mann_whitney_u;
     use stats::write_5_number_summary;
     use stats::write_boxplot;
     use std::io;
@@ -1042,6 +1111,30 @@ mod tests {
 
     }
     #[test]
+    fn test_mann_whitney_u() {
+        let (u, p) = mann_whitney_u(&[1.0, 2.0, 3.0, 4.0, 5.0],
+                                    &[6.0, 7.0, 8.0, 9.0, 10.0]);
+        assert_eq!(u, 0.0);
+        assert_approx_eq!(p, 0.012185780355344818);
+
+        let (u, p) = mann_whitney_u(&[6.0, 7.0, 8.0, 9.0, 10.0],
+                                    &[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(u, 25.0);
+        assert_approx_eq!(p, 0.012185780355344818);
+
+        // With ties.
+        let (u, p) = mann_whitney_u(&[1.0, 2.0, 2.0, 3.0, 5.0, 8.0],
+                                    &[2.0, 4.0, 6.0, 6.0, 9.0, 10.0, 12.0]);
+        assert_eq!(u, 8.0);
+        assert_approx_eq!(p, 0.07216011300239515);
+
+        let (u, p) = mann_whitney_u(&[1.0, 2.0, 3.0], &[1.0, 2.0, 3.0]);
+        assert_eq!(u, 4.5);
+        assert_eq!(p, 1.0);
+
+        assert_eq!(mann_whitney_u(&[], &[1.0, 2.0]), (0.0, 1.0));
+    }
+    #[test]
     fn test_sum_f64s() {
         assert_eq!([0.5f64, 3.2321f64, 1.5678f64].sum(), 5.2999);
     }
@@ -20,7 +20,7 @@
        html_favicon_url = "http://www.rust-lang.org/favicon.ico",
        html_root_url = "http://doc.rust-lang.org/nightly/",
        html_playground_url = "http://play.rust-lang.org/")]
-#![feature(phase, globs)]
+#![feature(phase, globs, macro_rules)]
 
 #[cfg(test)] #[phase(plugin, link)] extern crate log;
 
@@ -28,8 +28,10 @@ extern crate serialize;
 extern crate libc;
 
 pub use self::ParseError::*;
+pub use zone::{TimeZone, LocalResult, Single, Ambiguous, Nonexistent};
 use self::Fmt::*;
 
+use std::cmp;
 use std::fmt::Show;
 use std::fmt;
 use std::num::SignedInt;
@@ -38,6 +40,8 @@ use std::time::Duration;
 
 static NSEC_PER_SEC: i32 = 1_000_000_000_i32;
 
+mod zone;
+
 mod rustrt {
     use super::Tm;
 
@@ -101,24 +105,31 @@ impl Timespec {
 
 impl Add<Duration, Timespec> for Timespec {
     fn add(&self, other: &Duration) -> Timespec {
-        let d_sec = other.num_seconds();
-        // It is safe to unwrap the nanoseconds, because there cannot be
-        // more than one second left, which fits in i64 and in i32.
-        let d_nsec = (*other - Duration::seconds(d_sec))
-                     .num_nanoseconds().unwrap() as i32;
-        let mut sec = self.sec + d_sec;
-        let mut nsec = self.nsec + d_nsec;
-        if nsec >= NSEC_PER_SEC {
-            nsec -= NSEC_PER_SEC;
-            sec += 1;
-        } else if nsec < 0 {
-            nsec += NSEC_PER_SEC;
-            sec -= 1;
-        }
+        let (sec, nsec) = add_duration(self.sec, self.nsec, other);
         Timespec::new(sec, nsec)
     }
 }
 
+/// Adds a duration to a time given in seconds and nanoseconds, returning
+/// the new time with the nanoseconds normalized to `[0, NSEC_PER_SEC)`.
+fn add_duration(sec: i64, nsec: i32, d: &Duration) -> (i64, i32) {
+    let d_sec = d.num_seconds();
+    // It is safe to unwrap the nanoseconds, because there cannot be
+    // more than one second left, which fits in i64 and in i32.
+    let d_nsec = (*d - Duration::seconds(d_sec))
+                 .num_nanoseconds().unwrap() as i32;
+    let mut sec = sec + d_sec;
+    let mut nsec = nsec + d_nsec;
+    if nsec >= NSEC_PER_SEC {
+        nsec -= NSEC_PER_SEC;
+        sec += 1;
+    } else if nsec < 0 {
+        nsec += NSEC_PER_SEC;
+        sec -= 1;
+    }
+    (sec, nsec)
+}
+
 impl Sub<Timespec, Duration> for Timespec {
     fn sub(&self, other: &Timespec) -> Duration {
         let sec = self.sec - other.sec;
@@ -230,6 +241,78 @@ pub fn tzset() {
     }
 }
 
+static SECS_PER_DAY: i64 = 86400;
+
+fn mod_floor(a: i64, b: i64) -> i64 {
+    ((a % b) + b) % b
+}
+
+fn div_floor(a: i64, b: i64) -> i64 {
+    (a - mod_floor(a, b)) / b
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to the given date in the
+/// proleptic Gregorian calendar, with `month` and `day` counted from 1.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // From http://howardhinnant.github.io/date_algorithms.html
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = div_floor(y, 400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Returns the date `(year, month, day)` that's the given number of days
+/// from 1970-01-01, with `month` and `day` counted from 1.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = div_floor(z, 146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Builds a `Tm` for a time given in seconds from 1970-01-01T00:00:00 in the
+/// time zone with the given offset.
+fn tm_from_local_secs(secs: i64, nsec: i32, gmtoff: i32, isdst: i32) -> Tm {
+    let days = div_floor(secs, SECS_PER_DAY);
+    let secs_of_day = mod_floor(secs, SECS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    Tm {
+        tm_sec: (secs_of_day % 60) as i32,
+        tm_min: (secs_of_day / 60 % 60) as i32,
+        tm_hour: (secs_of_day / 3600) as i32,
+        tm_mday: day as i32,
+        tm_mon: month as i32 - 1,
+        tm_year: year as i32 - 1900,
+        // 1970-01-01 was a Thursday.
+        tm_wday: mod_floor(days + 4, 7) as i32,
+        tm_yday: (days - days_from_civil(year, 1, 1)) as i32,
+        tm_isdst: isdst,
+        tm_gmtoff: gmtoff,
+        tm_nsec: nsec,
+    }
+}
+
 /// Holds a calendar date and time broken down into its components (year, month, day, and so on),
 /// also called a broken-down time value.
 // FIXME: use c_int instead of i32?
@@ -336,6 +419,57 @@ impl Tm {
         }
     }
 
+    /// Returns the seconds from 1970-01-01T00:00:00 to the date and time of
+    /// this `Tm`, in its own time zone. Fields that are out of range carry
+    /// over into the next bigger one, e.g., a `tm_mday` of 32 in January is
+    /// February 1, and `tm_wday` and `tm_yday` are ignored.
+    fn local_secs(&self) -> i64 {
+        let months = self.tm_year as i64 * 12 + self.tm_mon as i64;
+        let year = div_floor(months, 12) + 1900;
+        let month = mod_floor(months, 12) + 1;
+        days_from_civil(year, month, 1) * SECS_PER_DAY
+            + (self.tm_mday as i64 - 1) * SECS_PER_DAY
+            + self.tm_hour as i64 * 3600 + self.tm_min as i64 * 60 + self.tm_sec as i64
+    }
+
+    /// Adds a number of months (which may be negative) to the date, keeping
+    /// the time of day and the offset from UTC. If the day of the month
+    /// doesn't exist in the new month, the last day of that month is used:
+    /// January 31 plus one month is February 28 (or 29).
+    pub fn add_months(&self, months: i32) -> Tm {
+        let months = self.tm_year as i64 * 12 + self.tm_mon as i64 + months as i64;
+        let year = div_floor(months, 12) + 1900;
+        let month = mod_floor(months, 12) + 1;
+        let day = cmp::min(self.tm_mday as i64, days_in_month(year, month));
+        let secs = (days_from_civil(year, month, day) * SECS_PER_DAY)
+            + self.tm_hour as i64 * 3600 + self.tm_min as i64 * 60 + self.tm_sec as i64;
+        tm_from_local_secs(secs, self.tm_nsec, self.tm_gmtoff, self.tm_isdst)
+    }
+
+    /// Adds a number of years (which may be negative) to the date, keeping
+    /// the time of day and the offset from UTC. February 29 becomes February
+    /// 28 if the new year isn't a leap year.
+    pub fn add_years(&self, years: i32) -> Tm {
+        self.add_months(years * 12)
+    }
+
+    /// Returns the ISO 8601 week date of this date: the year that the week
+    /// belongs to (which differs from `tm_year + 1900` around the new year),
+    /// the week, from 1 to 53, and the day of the week, from 1 (Monday) to 7
+    /// (Sunday).
+    ///
+    /// Weeks start on Monday, and the first week of a year is the one with
+    /// the year's first Thursday in it.
+    pub fn iso_week_date(&self) -> (i32, u32, u32) {
+        let days = div_floor(self.local_secs(), SECS_PER_DAY);
+        // 1970-01-01 was a Thursday.
+        let weekday = mod_floor(days + 3, 7) + 1;
+        let thursday = days + 4 - weekday;
+        let (year, _, _) = civil_from_days(thursday);
+        let week = (thursday - days_from_civil(year, 1, 1)) / 7 + 1;
+        (year as i32, week as u32, weekday as u32)
+    }
+
     /// Convert time to the local timezone
     pub fn to_local(&self) -> Tm {
         at(self.to_timespec())
@@ -416,6 +550,29 @@ impl Tm {
             format: FmtRfc3339,
         }
     }
+
+    /// Returns a TmFmt that outputs according to RFC 3339, like `rfc3339`,
+    /// but with the fractional seconds, if there are any. As many digits as
+    /// needed are used, up to nanoseconds, so `parse_rfc3339` gives the same
+    /// time back.
+    ///
+    /// local: "2012-02-22T07:53:18.25-07:00"
+    /// utc:   "2012-02-22T14:53:18.000001Z"
+    pub fn rfc3339_nsec<'a>(&'a self) -> TmFmt {
+        TmFmt {
+            tm: self,
+            format: FmtRfc3339Nsec,
+        }
+    }
+}
+
+impl Add<Duration, Tm> for Tm {
+    /// Adds a duration, keeping the offset from UTC. Use `TimeZone::at` to
+    /// get the local time in a time zone with daylight saving time.
+    fn add(&self, other: &Duration) -> Tm {
+        let (sec, nsec) = add_duration(self.local_secs(), self.tm_nsec, other);
+        tm_from_local_secs(sec, nsec, self.tm_gmtoff, self.tm_isdst)
+    }
 }
 
 #[deriving(PartialEq)]
@@ -429,6 +586,7 @@ pub enum ParseError {
     InvalidDayOfWeek,
     InvalidDayOfMonth,
     InvalidDayOfYear,
+    InvalidWeek,
     InvalidZoneOffset,
     InvalidTime,
     MissingFormatConverter,
@@ -450,6 +608,7 @@ impl Show for ParseError {
             InvalidDayOfWeek => write!(f, "Invalid day of the week."),
             InvalidDayOfMonth => write!(f, "Invalid day of the month."),
             InvalidDayOfYear => write!(f, "Invalid day of the year."),
+            InvalidWeek => write!(f, "Invalid week."),
             InvalidZoneOffset => write!(f, "Invalid zone offset."),
             InvalidTime => write!(f, "Invalid time."),
             MissingFormatConverter => write!(f, "Missing format converter after `%`"),
@@ -468,6 +627,7 @@ pub struct TmFmt<'a> {
 enum Fmt<'a> {
     FmtStr(&'a str),
     FmtRfc3339,
+    FmtRfc3339Nsec,
     FmtCtime,
 }
 
@@ -783,22 +943,29 @@ impl<'a> fmt::Show for TmFmt<'a> {
             FmtCtime => {
                 self.tm.to_local().asctime().fmt(fmt)
             }
-            FmtRfc3339 => {
+            FmtRfc3339 | FmtRfc3339Nsec => {
+                try!(TmFmt {
+                    tm: self.tm,
+                    format: FmtStr("%Y-%m-%dT%H:%M:%S"),
+                }.fmt(fmt));
+                match self.format {
+                    FmtRfc3339Nsec if self.tm.tm_nsec != 0 => {
+                        let mut digits = format!("{:09}", self.tm.tm_nsec);
+                        while digits.as_slice().ends_with("0") {
+                            digits.pop();
+                        }
+                        try!(write!(fmt, ".{}", digits));
+                    }
+                    _ => {}
+                }
                 if self.tm.tm_gmtoff == 0_i32 {
-                    TmFmt {
-                        tm: self.tm,
-                        format: FmtStr("%Y-%m-%dT%H:%M:%SZ"),
-                    }.fmt(fmt)
+                    write!(fmt, "Z")
                 } else {
-                    let s = TmFmt {
-                        tm: self.tm,
-                        format: FmtStr("%Y-%m-%dT%H:%M:%S"),
-                    };
                     let sign = if self.tm.tm_gmtoff > 0_i32 { '+' } else { '-' };
                     let mut m = self.tm.tm_gmtoff.abs() / 60_i32;
                     let h = m / 60_i32;
                     m -= h * 60_i32;
-                    write!(fmt, "{}{}{:02}:{:02}", s, sign, h as int, m as int)
+                    write!(fmt, "{}{:02}:{:02}", sign, h as int, m as int)
                 }
             }
         }
@@ -1264,12 +1431,122 @@ pub fn strftime(format: &str, tm: &Tm) -> Result<String, ParseError> {
     tm.strftime(format).map(|fmt| fmt.to_string())
 }
 
+/// Parses a date and time in the format of RFC 3339, like
+/// `2012-02-22T07:53:18.25-07:00` or `2012-02-22T14:53:18Z`.
+///
+/// Unlike `strptime`, the whole string must be a valid RFC 3339 date and
+/// time: every field must be there with the right number of digits and in
+/// range (a leap second is allowed), the day must exist in the month, and
+/// there may be nothing after the offset. Fractional seconds are kept, up to
+/// nanoseconds, and the offset is kept in `tm_gmtoff`. `tm_wday` and
+/// `tm_yday` are filled in.
+pub fn parse_rfc3339(s: &str) -> Result<Tm, ParseError> {
+    fn digits(b: &[u8], pos: &mut uint, n: uint, min: i32, max: i32, err: ParseError)
+              -> Result<i32, ParseError> {
+        let mut value = 0;
+        for _ in range(0, n) {
+            match b.get(*pos) {
+                Some(&c) if c >= b'0' && c <= b'9' => value = value * 10 + (c - b'0') as i32,
+                _ => return Err(err),
+            }
+            *pos += 1;
+        }
+        if value < min || value > max {
+            return Err(err)
+        }
+        Ok(value)
+    }
+
+    fn expect(b: &[u8], pos: &mut uint, cs: &[u8]) -> Result<u8, ParseError> {
+        match b.get(*pos) {
+            Some(&c) if cs.contains(&c) => {
+                *pos += 1;
+                Ok(c)
+            }
+            Some(&c) => Err(UnexpectedCharacter(cs[0] as char, c as char)),
+            None => Err(InvalidTime),
+        }
+    }
+
+    let b = s.as_bytes();
+    let mut pos = 0;
+    let year = try!(digits(b, &mut pos, 4, 0, 9999, InvalidYear));
+    try!(expect(b, &mut pos, b"-"));
+    let month = try!(digits(b, &mut pos, 2, 1, 12, InvalidMonth));
+    try!(expect(b, &mut pos, b"-"));
+    let max_day = days_in_month(year as i64, month as i64) as i32;
+    let day = try!(digits(b, &mut pos, 2, 1, max_day, InvalidDayOfMonth));
+    try!(expect(b, &mut pos, b"Tt"));
+    let hour = try!(digits(b, &mut pos, 2, 0, 23, InvalidHour));
+    try!(expect(b, &mut pos, b":"));
+    let min = try!(digits(b, &mut pos, 2, 0, 59, InvalidMinute));
+    try!(expect(b, &mut pos, b":"));
+    let sec = try!(digits(b, &mut pos, 2, 0, 60, InvalidSecond));
+
+    let mut nsec = 0;
+    if b.get(pos) == Some(&b'.') {
+        pos += 1;
+        let start = pos;
+        let mut multiplier = NSEC_PER_SEC / 10;
+        while pos < b.len() && b[pos] >= b'0' && b[pos] <= b'9' {
+            // This will drop digits after the nanoseconds place
+            nsec += (b[pos] - b'0') as i32 * multiplier;
+            multiplier /= 10;
+            pos += 1;
+        }
+        if pos == start {
+            return Err(InvalidSecond)
+        }
+    }
+
+    let gmtoff = match try!(expect(b, &mut pos, b"Zz+-")) {
+        b'Z' | b'z' => 0,
+        sign => {
+            let h = try!(digits(b, &mut pos, 2, 0, 23, InvalidZoneOffset));
+            try!(expect(b, &mut pos, b":"));
+            let m = try!(digits(b, &mut pos, 2, 0, 59, InvalidZoneOffset));
+            let offset = h * 3600 + m * 60;
+            if sign == b'-' { -offset } else { offset }
+        }
+    };
+    if pos != b.len() {
+        return Err(InvalidTime)
+    }
+
+    let mut tm = tm_from_local_secs(days_from_civil(year as i64, month as i64, day as i64)
+                                    * SECS_PER_DAY + hour as i64 * 3600 + min as i64 * 60,
+                                    nsec, gmtoff, 0);
+    // Normalizing a leap second would make it the next minute.
+    tm.tm_sec = sec;
+    Ok(tm)
+}
+
+/// Returns the `Tm` at midnight UTC on the date with the given ISO 8601 week
+/// date: the year the week belongs to, the week, from 1 to 53, and the day of
+/// the week, from 1 (Monday) to 7 (Sunday). See `Tm::iso_week_date`.
+pub fn from_iso_week_date(year: i32, week: u32, weekday: u32) -> Result<Tm, ParseError> {
+    if weekday < 1 || weekday > 7 {
+        return Err(InvalidDayOfWeek)
+    }
+    // Week 1 is the one with January 4 in it.
+    let jan4 = days_from_civil(year as i64, 1, 4);
+    let monday = jan4 - mod_floor(jan4 + 3, 7);
+    let days = monday + (week as i64 - 1) * 7 + weekday as i64 - 1;
+    let tm = tm_from_local_secs(days * SECS_PER_DAY, 0, 0, 0);
+    if week < 1 || tm.iso_week_date().0 != year {
+        return Err(InvalidWeek)
+    }
+    Ok(tm)
+}
+
 #[cfg(test)]
 mod tests {
     extern crate test;
     use super::{Timespec, InvalidTime, InvalidYear, get_time, precise_time_ns,
                 precise_time_s, tzset, at_utc, at, strptime, MissingFormatConverter,
-                InvalidFormatSpecifier};
+                InvalidFormatSpecifier, InvalidSecond, InvalidDayOfMonth, InvalidDayOfWeek,
+                InvalidWeek, InvalidZoneOffset, InvalidMonth, UnexpectedCharacter, parse_rfc3339,
+                from_iso_week_date, days_from_civil, civil_from_days};
 
     use std::f64;
     use std::result::Result::{Err, Ok};
@@ -1742,6 +2019,110 @@ mod tests {
         assert_eq!(w.num_nanoseconds(), Some(-super::NSEC_PER_SEC as i64 - 1));
     }
 
+    #[test]
+    fn test_civil() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        for &days in [-719468i64, -1, 0, 11016, 11017, 2932896].iter() {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn test_parse_rfc3339() {
+        let tm = parse_rfc3339("2012-02-22T07:53:18.25-07:00").unwrap();
+        assert_eq!(tm.tm_year, 112);
+        assert_eq!(tm.tm_mon, 1);
+        assert_eq!(tm.tm_mday, 22);
+        assert_eq!(tm.tm_hour, 7);
+        assert_eq!(tm.tm_min, 53);
+        assert_eq!(tm.tm_sec, 18);
+        assert_eq!(tm.tm_nsec, 250_000_000);
+        assert_eq!(tm.tm_gmtoff, -7 * 3600);
+        assert_eq!(tm.tm_wday, 3);
+        assert_eq!(tm.tm_yday, 52);
+        assert_eq!(tm.rfc3339().to_string(), "2012-02-22T07:53:18-07:00".to_string());
+
+        for &s in ["2012-02-22T07:53:18.25-07:00", "2012-02-22T14:53:18.000001Z",
+                   "2012-02-22T14:53:18.123456789+05:30", "2012-02-22T14:53:18Z"].iter() {
+            assert_eq!(parse_rfc3339(s).unwrap().rfc3339_nsec().to_string(), s.to_string());
+        }
+        assert_eq!(parse_rfc3339("2012-02-22t14:53:18.1234567891z").unwrap()
+                       .rfc3339_nsec().to_string(),
+                   "2012-02-22T14:53:18.123456789Z".to_string());
+
+        let leap = parse_rfc3339("2016-12-31T23:59:60Z").unwrap();
+        assert_eq!(leap.tm_sec, 60);
+        assert_eq!(leap.to_timespec(), Timespec::new(1483228800, 0));
+
+        assert_eq!(parse_rfc3339("2013-02-29T00:00:00Z"), Err(InvalidDayOfMonth));
+        assert_eq!(parse_rfc3339("2012-02-22T07:53:18"), Err(InvalidTime));
+        assert_eq!(parse_rfc3339("2012-02-22T07:53:18Zx"), Err(InvalidTime));
+        assert_eq!(parse_rfc3339("2012-02-22 07:53:18Z"), Err(UnexpectedCharacter('T', ' ')));
+        assert_eq!(parse_rfc3339("2012-02-22T07:53:18.Z"), Err(InvalidSecond));
+        assert_eq!(parse_rfc3339("2012-02-22T07:53:18+24:00"), Err(InvalidZoneOffset));
+        assert_eq!(parse_rfc3339("2012-2-22T07:53:18Z"), Err(InvalidMonth));
+    }
+
+    #[test]
+    fn test_tm_add() {
+        let tm = parse_rfc3339("2012-02-28T23:00:00-07:00").unwrap();
+        assert_eq!((tm + Duration::hours(2)).rfc3339().to_string(),
+                   "2012-02-29T01:00:00-07:00".to_string());
+        assert_eq!((tm + Duration::days(-59)).rfc3339().to_string(),
+                   "2011-12-31T23:00:00-07:00".to_string());
+
+        let tm = parse_rfc3339("2012-02-29T23:59:59.5Z").unwrap();
+        let later = tm + Duration::milliseconds(500);
+        assert_eq!(later.rfc3339_nsec().to_string(), "2012-03-01T00:00:00Z".to_string());
+        assert_eq!(later.tm_wday, 4);
+        assert_eq!(later.tm_yday, 60);
+        assert_eq!((tm + Duration::nanoseconds(-500_000_001)).rfc3339_nsec().to_string(),
+                   "2012-02-29T23:59:58.999999999Z".to_string());
+    }
+
+    #[test]
+    fn test_add_months() {
+        let date = |s: &str| parse_rfc3339(s).unwrap();
+        let fmt = |tm: super::Tm| tm.rfc3339().to_string();
+
+        assert_eq!(fmt(date("2012-01-31T10:00:00Z").add_months(1)),
+                   "2012-02-29T10:00:00Z".to_string());
+        assert_eq!(fmt(date("2013-01-31T10:00:00Z").add_months(1)),
+                   "2013-02-28T10:00:00Z".to_string());
+        assert_eq!(fmt(date("2012-01-31T10:00:00+01:00").add_months(-2)),
+                   "2011-11-30T10:00:00+01:00".to_string());
+        assert_eq!(fmt(date("2012-01-15T10:00:00Z").add_months(13)),
+                   "2013-02-15T10:00:00Z".to_string());
+        assert_eq!(fmt(date("2012-02-29T10:00:00Z").add_years(1)),
+                   "2013-02-28T10:00:00Z".to_string());
+        assert_eq!(fmt(date("2012-02-29T10:00:00Z").add_years(4)),
+                   "2016-02-29T10:00:00Z".to_string());
+    }
+
+    #[test]
+    fn test_iso_week_date() {
+        let week = |s: &str| parse_rfc3339(s).unwrap().iso_week_date();
+        assert_eq!(week("2008-12-29T00:00:00Z"), (2009, 1, 1));
+        assert_eq!(week("2010-01-03T23:59:59Z"), (2009, 53, 7));
+        assert_eq!(week("2012-02-22T07:53:18-07:00"), (2012, 8, 3));
+        assert_eq!(week("2005-01-01T00:00:00Z"), (2004, 53, 6));
+
+        let fmt = |tm: super::Tm| tm.rfc3339().to_string();
+        assert_eq!(fmt(from_iso_week_date(2009, 1, 1).unwrap()),
+                   "2008-12-29T00:00:00Z".to_string());
+        assert_eq!(fmt(from_iso_week_date(2009, 53, 7).unwrap()),
+                   "2010-01-03T00:00:00Z".to_string());
+        assert_eq!(fmt(from_iso_week_date(2015, 53, 5).unwrap()),
+                   "2016-01-01T00:00:00Z".to_string());
+        assert_eq!(from_iso_week_date(2010, 53, 1), Err(InvalidWeek));
+        assert_eq!(from_iso_week_date(2010, 0, 1), Err(InvalidWeek));
+        assert_eq!(from_iso_week_date(2010, 1, 8), Err(InvalidDayOfWeek));
+    }
+
     #[test]
     #[cfg_attr(target_os = "android", ignore)] // FIXME #10958
     fn run_tests() {
@@ -0,0 +1,761 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Time zones from the IANA time zone database, as compiled into TZif files
+//! (see tzfile(5)) by zic.
+
+pub use self::LocalResult::*;
+use self::RuleDate::*;
+
+use std::io::{File, IoError, IoResult, InvalidInput};
+use std::num::Int;
+use std::os;
+use std::str;
+
+use {Tm, Timespec, SECS_PER_DAY, at_utc, civil_from_days, days_from_civil, div_floor,
+     is_leap_year, mod_floor};
+
+macro_rules! try_opt(
+    ($e:expr) => (match $e { Some(e) => e, None => return None })
+)
+
+/// An offset from UTC, as used in some period of a time zone's history.
+#[deriving(Clone, PartialEq, Show)]
+struct LocalType {
+    // Seconds east of UTC.
+    offset: i32,
+    isdst: bool,
+    abbr: String,
+}
+
+/// When daylight saving time starts or ends in a year, as given in a POSIX
+/// `TZ` string.
+#[deriving(Clone, PartialEq, Show)]
+enum RuleDate {
+    /// `Jn`: day n of the year, from 1 to 365, never counting February 29.
+    Julian1(i64),
+    /// `n`: day n of the year, from 0 to 365, counting February 29.
+    Julian0(i64),
+    /// `Mm.w.d`: day d (0 is Sunday) of week w (5 is the last) of month m.
+    MonthWeekDay(i64, i64, i64),
+}
+
+/// A POSIX `TZ` string, like `EST5EDT,M3.2.0,M11.1.0`, which gives the rule
+/// for times after the last transition of a TZif file.
+#[deriving(Clone, PartialEq, Show)]
+struct PosixRule {
+    std: LocalType,
+    // The DST type, and when it starts and ends, in seconds after midnight
+    // of local standard and daylight time respectively.
+    dst: Option<(LocalType, RuleDate, i64, RuleDate, i64)>,
+}
+
+/// A time zone, which converts between instants and local times.
+///
+/// # Example
+///
+/// ```no_run
+/// use time::{TimeZone, Timespec};
+///
+/// let new_york = TimeZone::named("America/New_York").unwrap();
+/// let mut tm = new_york.at(Timespec::new(1414904400, 0));
+/// assert_eq!((tm.tm_hour, tm.tm_min), (1, 0));
+/// assert_eq!(tm.tm_gmtoff, -4 * 3600);
+///
+/// // Clocks were turned back from 2:00 to 1:00 later that night, so 1:30
+/// // happened twice.
+/// tm.tm_min = 30;
+/// let result = new_york.from_local(&tm);
+/// assert_eq!(result.earliest(), Timespec::new(1414906200, 0));
+/// assert_eq!(result.latest(), Timespec::new(1414909800, 0));
+/// ```
+#[deriving(Clone, PartialEq, Show)]
+pub struct TimeZone {
+    name: String,
+    // The instants at which the offset changes, and the index of the new
+    // type in `types`.
+    transitions: Vec<(i64, uint)>,
+    types: Vec<LocalType>,
+    rule: Option<PosixRule>,
+}
+
+/// The result of converting a local time to an instant in a time zone.
+#[deriving(Clone, PartialEq, Show)]
+pub enum LocalResult {
+    /// The local time happens once.
+    Single(Timespec),
+    /// The local time happens twice, because clocks were turned back. The
+    /// earlier and the later instant are given.
+    Ambiguous(Timespec, Timespec),
+    /// The local time never happens, because clocks were turned forward
+    /// past it. The instant at which they were turned forward is given.
+    Nonexistent(Timespec),
+}
+
+impl Copy for LocalResult {}
+
+impl LocalResult {
+    /// Returns the instant if there's exactly one.
+    pub fn single(self) -> Option<Timespec> {
+        match self {
+            Single(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Returns the earlier instant of an ambiguous local time, or the
+    /// instant at which a nonexistent local time was skipped.
+    pub fn earliest(self) -> Timespec {
+        match self {
+            Single(t) | Ambiguous(t, _) | Nonexistent(t) => t,
+        }
+    }
+
+    /// Returns the later instant of an ambiguous local time, or the instant
+    /// at which a nonexistent local time was skipped.
+    pub fn latest(self) -> Timespec {
+        match self {
+            Single(t) | Ambiguous(_, t) | Nonexistent(t) => t,
+        }
+    }
+}
+
+fn invalid(desc: &'static str) -> IoError {
+    IoError { kind: InvalidInput, desc: desc, detail: None }
+}
+
+impl TimeZone {
+    /// Returns the UTC time zone.
+    pub fn utc() -> TimeZone {
+        TimeZone {
+            name: "UTC".to_string(),
+            transitions: vec![],
+            types: vec![LocalType { offset: 0, isdst: false, abbr: "UTC".to_string() }],
+            rule: None,
+        }
+    }
+
+    /// Loads the time zone with the given name, like `"America/New_York"`,
+    /// from the directory named by the `TZDIR` environment variable, or else
+    /// from `/usr/share/zoneinfo`.
+    pub fn named(name: &str) -> IoResult<TimeZone> {
+        let dir = os::getenv("TZDIR").unwrap_or("/usr/share/zoneinfo".to_string());
+        let rel = Path::new(name);
+        if name.len() == 0 || rel.is_absolute()
+            || rel.components().any(|c| c == "..".as_bytes()) {
+            return Err(invalid("invalid time zone name"))
+        }
+        let mut zone = try!(TimeZone::from_file(&Path::new(dir).join(rel)));
+        zone.name = name.to_string();
+        Ok(zone)
+    }
+
+    /// Loads the system's local time zone: the one named by the `TZ`
+    /// environment variable if it names a file in the time zone database,
+    /// or else the one in `/etc/localtime`.
+    pub fn local() -> IoResult<TimeZone> {
+        match os::getenv("TZ") {
+            Some(ref tz) if tz.as_slice().trim_left_chars(':').len() > 0 => {
+                let tz = tz.as_slice().trim_left_chars(':');
+                if tz.starts_with("/") {
+                    return TimeZone::from_file(&Path::new(tz))
+                }
+                return TimeZone::named(tz)
+            }
+            _ => TimeZone::from_file(&Path::new("/etc/localtime")),
+        }
+    }
+
+    /// Loads a time zone from a TZif file.
+    pub fn from_file(path: &Path) -> IoResult<TimeZone> {
+        let data = try!(File::open(path).read_to_end());
+        let name = String::from_utf8_lossy(path.as_vec()).into_string();
+        TimeZone::from_tzif(name.as_slice(), data.as_slice())
+    }
+
+    /// Parses the contents of a TZif file, giving the time zone `name`.
+    pub fn from_tzif(name: &str, data: &[u8]) -> IoResult<TimeZone> {
+        let mut p = Parser { data: data, pos: 0 };
+        let (version, counts) = try!(p.header());
+        let mut zone = if version >= b'2' {
+            // Skip the data with 32-bit times, which comes first for the
+            // benefit of old readers.
+            try!(p.skip_block(counts, 4));
+            let (_, counts) = try!(p.header());
+            try!(p.block(name, counts, 8))
+        } else {
+            try!(p.block(name, counts, 4))
+        };
+        if version >= b'2' {
+            zone.rule = try!(p.footer());
+        }
+        Ok(zone)
+    }
+
+    /// Returns the name of the time zone.
+    pub fn name<'a>(&'a self) -> &'a str {
+        self.name.as_slice()
+    }
+
+    fn local_type<'a>(&'a self, sec: i64) -> &'a LocalType {
+        let ts = &self.transitions;
+        if ts.len() == 0 || sec < ts[0].0 {
+            // Before the first transition, or if there are none, the rule
+            // (if any) tells what the offset is, or else the first type does.
+            if ts.len() == 0 {
+                match self.rule {
+                    Some(ref rule) => return rule.local_type(sec),
+                    None => {}
+                }
+            }
+            return &self.types[0]
+        }
+        if sec >= ts[ts.len() - 1].0 {
+            match self.rule {
+                Some(ref rule) => return rule.local_type(sec),
+                None => return &self.types[ts[ts.len() - 1].1],
+            }
+        }
+        // The last transition at or before `sec`.
+        let (mut lo, mut hi) = (0, ts.len() - 1);
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if ts[mid].0 <= sec {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        &self.types[ts[lo].1]
+    }
+
+    fn offset(&self, sec: i64) -> i64 {
+        self.local_type(sec).offset as i64
+    }
+
+    /// Returns the local time of the given instant in this time zone.
+    pub fn at(&self, clock: Timespec) -> Tm {
+        let ty = self.local_type(clock.sec);
+        let mut tm = at_utc(Timespec::new(clock.sec + ty.offset as i64, clock.nsec));
+        tm.tm_gmtoff = ty.offset;
+        tm.tm_isdst = if ty.isdst { 1 } else { 0 };
+        tm
+    }
+
+    /// Returns the abbreviation of the time zone at the given instant, like
+    /// `"EST"` or `"EDT"`.
+    pub fn abbreviation<'a>(&'a self, clock: Timespec) -> &'a str {
+        self.local_type(clock.sec).abbr.as_slice()
+    }
+
+    /// Converts a local time in this time zone to an instant.
+    ///
+    /// Only the date, the time of day and `tm_nsec` are used: `tm_gmtoff`,
+    /// `tm_isdst`, `tm_wday` and `tm_yday` are ignored. Around a transition,
+    /// a local time may happen twice or not at all, which is reported rather
+    /// than resolved arbitrarily.
+    pub fn from_local(&self, tm: &Tm) -> LocalResult {
+        let local = tm.local_secs();
+
+        // The local time is `sec + offset(sec)` for the instants `sec` we're
+        // after, and the offset is one of the zone's offsets.
+        let mut offsets: Vec<i64> = self.types.iter().map(|t| t.offset as i64).collect();
+        match self.rule {
+            Some(ref rule) => {
+                offsets.push(rule.std.offset as i64);
+                match rule.dst {
+                    Some((ref dst, _, _, _, _)) => offsets.push(dst.offset as i64),
+                    None => {}
+                }
+            }
+            None => {}
+        }
+        let mut found: Vec<i64> = offsets.iter()
+                                         .map(|&o| local - o)
+                                         .filter(|&sec| sec + self.offset(sec) == local)
+                                         .collect();
+        found.sort();
+        found.dedup();
+        let nsec = tm.tm_nsec;
+        match found.len() {
+            0 => {}
+            1 => return Single(Timespec::new(found[0], nsec)),
+            n => return Ambiguous(Timespec::new(found[0], nsec),
+                                  Timespec::new(found[n - 1], nsec)),
+        }
+
+        // The local time was skipped, so it's in a gap between `T +
+        // offset(T - 1)` and `T + offset(T)` for some transition `T`. As the
+        // local time goes from below `local` to above it there, `T` can be
+        // found by bisection between instants whose local time is surely
+        // below and above `local`.
+        let lo_offset = *offsets.iter().min().unwrap();
+        let hi_offset = *offsets.iter().max().unwrap();
+        let (mut lo, mut hi) = (local - hi_offset, local - lo_offset);
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if mid + self.offset(mid) > local {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        Nonexistent(Timespec::new(hi, 0))
+    }
+}
+
+impl PosixRule {
+    fn local_type<'a>(&'a self, sec: i64) -> &'a LocalType {
+        let (dst, start, start_time, end, end_time) = match self.dst {
+            Some((ref dst, ref start, start_time, ref end, end_time)) => {
+                (dst, start, start_time, end, end_time)
+            }
+            None => return &self.std,
+        };
+        let (year, _, _) = civil_from_days(div_floor(sec + self.std.offset as i64,
+                                                     SECS_PER_DAY));
+        let start = start.day(year) * SECS_PER_DAY + start_time - self.std.offset as i64;
+        let end = end.day(year) * SECS_PER_DAY + end_time - dst.offset as i64;
+        let in_dst = if start <= end {
+            start <= sec && sec < end
+        } else {
+            // Southern hemisphere: DST spans the new year.
+            !(end <= sec && sec < start)
+        };
+        if in_dst { dst } else { &self.std }
+    }
+}
+
+impl RuleDate {
+    // Returns the day, counted from 1970-01-01, that the rule gives in `year`.
+    fn day(&self, year: i64) -> i64 {
+        let jan1 = days_from_civil(year, 1, 1);
+        match *self {
+            Julian1(n) => jan1 + n - 1 + if is_leap_year(year) && n >= 60 { 1 } else { 0 },
+            Julian0(n) => jan1 + n,
+            MonthWeekDay(m, w, d) => {
+                let first = days_from_civil(year, m, 1);
+                let next = if m == 12 {
+                    days_from_civil(year + 1, 1, 1)
+                } else {
+                    days_from_civil(year, m + 1, 1)
+                };
+                // 1970-01-01 was a Thursday.
+                let first_wday = mod_floor(first + 4, 7);
+                let mut day = first + mod_floor(d - first_wday, 7) + (w - 1) * 7;
+                while day >= next {
+                    day -= 7;
+                }
+                day
+            }
+        }
+    }
+}
+
+// The counts in a TZif header.
+struct Counts {
+    isutcnt: uint,
+    isstdcnt: uint,
+    leapcnt: uint,
+    timecnt: uint,
+    typecnt: uint,
+    charcnt: uint,
+}
+
+impl Copy for Counts {}
+
+impl Counts {
+    // The size of the data block these counts describe, or `None` if it
+    // overflows.
+    fn block_len(&self, time_size: uint) -> Option<uint> {
+        let parts = [(self.timecnt, time_size + 1), (self.typecnt, 6), (self.charcnt, 1),
+                     (self.leapcnt, time_size + 4), (self.isstdcnt, 1), (self.isutcnt, 1)];
+        parts.iter().fold(Some(0u), |len, &(count, size)| {
+            len.and_then(|len| count.checked_mul(size).and_then(|n| len.checked_add(n)))
+        })
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: uint,
+}
+
+impl<'a> Parser<'a> {
+    fn bytes(&mut self, n: uint) -> IoResult<&'a [u8]> {
+        if self.data.len() - self.pos < n {
+            return Err(invalid("truncated TZif file"))
+        }
+        let bytes = self.data.slice(self.pos, self.pos + n);
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn be(&mut self, n: uint) -> IoResult<i64> {
+        let bytes = try!(self.bytes(n));
+        let mut v = 0u64;
+        for &b in bytes.iter() {
+            v = (v << 8) | b as u64;
+        }
+        // Sign extend.
+        let shift = 64 - 8 * n;
+        Ok(((v << shift) as i64) >> shift)
+    }
+
+    fn header(&mut self) -> IoResult<(u8, Counts)> {
+        if try!(self.bytes(4)) != "TZif".as_bytes() {
+            return Err(invalid("not a TZif file"))
+        }
+        let version = try!(self.bytes(1))[0];
+        try!(self.bytes(15));
+        let mut counts = [0u, ..6];
+        for c in counts.iter_mut() {
+            let n = try!(self.be(4));
+            if n < 0 {
+                return Err(invalid("invalid TZif header"))
+            }
+            *c = n as uint;
+        }
+        Ok((version, Counts {
+            isutcnt: counts[0],
+            isstdcnt: counts[1],
+            leapcnt: counts[2],
+            timecnt: counts[3],
+            typecnt: counts[4],
+            charcnt: counts[5],
+        }))
+    }
+
+    // Checks that the whole block described by `c` is there, so that its
+    // counts can be trusted when allocating.
+    fn check_block(&self, c: Counts, time_size: uint) -> IoResult<uint> {
+        match c.block_len(time_size) {
+            Some(len) if len <= self.data.len() - self.pos => Ok(len),
+            _ => Err(invalid("truncated TZif file")),
+        }
+    }
+
+    fn skip_block(&mut self, c: Counts, time_size: uint) -> IoResult<()> {
+        let len = try!(self.check_block(c, time_size));
+        try!(self.bytes(len));
+        Ok(())
+    }
+
+    fn block(&mut self, name: &str, c: Counts, time_size: uint) -> IoResult<TimeZone> {
+        if c.typecnt == 0 {
+            return Err(invalid("TZif file has no local time types"))
+        }
+        try!(self.check_block(c, time_size));
+        let mut times = Vec::with_capacity(c.timecnt);
+        for _ in range(0, c.timecnt) {
+            times.push(try!(self.be(time_size)));
+        }
+        let indices = try!(self.bytes(c.timecnt));
+        let mut raw_types = Vec::with_capacity(c.typecnt);
+        for _ in range(0, c.typecnt) {
+            let offset = try!(self.be(4));
+            let isdst = try!(self.bytes(1))[0] != 0;
+            let abbr_index = try!(self.bytes(1))[0] as uint;
+            raw_types.push((offset as i32, isdst, abbr_index));
+        }
+        let chars = try!(self.bytes(c.charcnt));
+        // Leap seconds, and whether transitions were given in standard or
+        // UTC time, aren't needed to convert times.
+        try!(self.bytes(c.leapcnt * (time_size + 4) + c.isstdcnt + c.isutcnt));
+
+        let mut types = Vec::with_capacity(c.typecnt);
+        for &(offset, isdst, abbr_index) in raw_types.iter() {
+            if abbr_index >= chars.len() {
+                return Err(invalid("invalid time zone abbreviation index"))
+            }
+            let abbr = chars.slice_from(abbr_index);
+            let len = abbr.iter().position(|&b| b == 0).unwrap_or(abbr.len());
+            types.push(LocalType {
+                offset: offset,
+                isdst: isdst,
+                abbr: String::from_utf8_lossy(abbr.slice_to(len)).into_string(),
+            });
+        }
+        let mut transitions = Vec::with_capacity(c.timecnt);
+        for (&at, &index) in times.iter().zip(indices.iter()) {
+            if index as uint >= types.len() {
+                return Err(invalid("invalid local time type index"))
+            }
+            transitions.push((at, index as uint));
+        }
+        Ok(TimeZone {
+            name: name.to_string(),
+            transitions: transitions,
+            types: types,
+            rule: None,
+        })
+    }
+
+    fn footer(&mut self) -> IoResult<Option<PosixRule>> {
+        let rest = self.data.slice_from(self.pos);
+        if rest.len() < 2 || rest[0] != b'\n' {
+            return Err(invalid("missing TZif footer"))
+        }
+        let end = match rest.iter().skip(1).position(|&b| b == b'\n') {
+            Some(end) => end + 1,
+            None => return Err(invalid("missing TZif footer")),
+        };
+        let tz = match str::from_utf8(rest.slice(1, end)) {
+            Some(tz) => tz,
+            None => return Err(invalid("invalid TZif footer")),
+        };
+        if tz.len() == 0 {
+            return Ok(None)
+        }
+        match parse_posix_tz(tz) {
+            Some(rule) => Ok(Some(rule)),
+            None => Err(invalid("invalid TZif footer")),
+        }
+    }
+}
+
+// A cursor over a POSIX TZ string.
+struct TzParser<'a> {
+    s: &'a str,
+    pos: uint,
+}
+
+impl<'a> TzParser<'a> {
+    fn peek(&self) -> Option<char> {
+        if self.pos < self.s.len() { Some(self.s.char_at(self.pos)) } else { None }
+    }
+
+    fn bump(&mut self) {
+        self.pos = self.s.char_range_at(self.pos).next;
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let start;
+        let end;
+        if self.eat('<') {
+            start = self.pos;
+            while self.peek().map_or(false, |c| c != '>') {
+                self.bump();
+            }
+            end = self.pos;
+            if !self.eat('>') {
+                return None
+            }
+        } else {
+            start = self.pos;
+            while self.peek().map_or(false, |c| c.is_alphabetic()) {
+                self.bump();
+            }
+            end = self.pos;
+        }
+        if end - start < 3 {
+            return None
+        }
+        Some(self.s.slice(start, end).to_string())
+    }
+
+    fn number(&mut self) -> Option<i64> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_digit(10)) {
+            self.bump();
+        }
+        from_str(self.s.slice(start, self.pos))
+    }
+
+    // `[+-]hh[:mm[:ss]]`, in seconds.
+    fn time(&mut self) -> Option<i64> {
+        let sign = if self.eat('-') { -1 } else { self.eat('+'); 1 };
+        let mut secs = try_opt!(self.number()) * 3600;
+        if self.eat(':') {
+            secs += try_opt!(self.number()) * 60;
+            if self.eat(':') {
+                secs += try_opt!(self.number());
+            }
+        }
+        Some(sign * secs)
+    }
+
+    // `,date[/time]`
+    fn rule(&mut self) -> Option<(RuleDate, i64)> {
+        if !self.eat(',') {
+            return None
+        }
+        let date = if self.eat('J') {
+            let n = try_opt!(self.number());
+            if n < 1 || n > 365 { return None }
+            Julian1(n)
+        } else if self.eat('M') {
+            let m = try_opt!(self.number());
+            if !self.eat('.') { return None }
+            let w = try_opt!(self.number());
+            if !self.eat('.') { return None }
+            let d = try_opt!(self.number());
+            if m < 1 || m > 12 || w < 1 || w > 5 || d > 6 { return None }
+            MonthWeekDay(m, w, d)
+        } else {
+            let n = try_opt!(self.number());
+            if n > 365 { return None }
+            Julian0(n)
+        };
+        let time = if self.eat('/') { try_opt!(self.time()) } else { 2 * 3600 };
+        Some((date, time))
+    }
+}
+
+/// Parses a POSIX `TZ` string, like `EST5EDT,M3.2.0,M11.1.0`. The offsets
+/// in it are west of UTC.
+fn parse_posix_tz(s: &str) -> Option<PosixRule> {
+    let mut p = TzParser { s: s, pos: 0 };
+    let std_abbr = try_opt!(p.name());
+    let std_offset = -try_opt!(p.time());
+    let std = LocalType { offset: std_offset as i32, isdst: false, abbr: std_abbr };
+    if p.peek().is_none() {
+        return Some(PosixRule { std: std, dst: None })
+    }
+    let dst_abbr = try_opt!(p.name());
+    let dst_offset = match p.peek() {
+        Some(',') | None => std_offset + 3600,
+        Some(_) => -try_opt!(p.time()),
+    };
+    let dst = LocalType { offset: dst_offset as i32, isdst: true, abbr: dst_abbr };
+    let (start, start_time, end, end_time) = if p.peek().is_none() {
+        // The rule that the US used when POSIX was written.
+        (MonthWeekDay(3, 2, 0), 2 * 3600, MonthWeekDay(11, 1, 0), 2 * 3600)
+    } else {
+        let (start, start_time) = try_opt!(p.rule());
+        let (end, end_time) = try_opt!(p.rule());
+        (start, start_time, end, end_time)
+    };
+    if p.peek().is_some() {
+        return None
+    }
+    Some(PosixRule { std: std, dst: Some((dst, start, start_time, end, end_time)) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeZone, Single, Ambiguous, Nonexistent, parse_posix_tz};
+    use {Timespec, empty_tm};
+
+    fn local(zone: &TimeZone, year: i32, mon: i32, mday: i32, hour: i32, min: i32)
+             -> super::LocalResult {
+        let mut tm = empty_tm();
+        tm.tm_year = year - 1900;
+        tm.tm_mon = mon - 1;
+        tm.tm_mday = mday;
+        tm.tm_hour = hour;
+        tm.tm_min = min;
+        zone.from_local(&tm)
+    }
+
+    #[test]
+    fn test_posix_rule() {
+        let rule = parse_posix_tz("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(rule.std.offset, -5 * 3600);
+        // 2014-03-09T06:59:59Z and 2014-03-09T07:00:00Z
+        assert_eq!(rule.local_type(1394348399).abbr.as_slice(), "EST");
+        assert_eq!(rule.local_type(1394348400).abbr.as_slice(), "EDT");
+        // 2014-11-02T05:59:59Z and 2014-11-02T06:00:00Z
+        assert_eq!(rule.local_type(1414907999).abbr.as_slice(), "EDT");
+        assert_eq!(rule.local_type(1414908000).abbr.as_slice(), "EST");
+
+        // Southern hemisphere, with a quoted name and minutes.
+        let rule = parse_posix_tz("<+1030>-10:30<+11>-11,M10.1.0,M4.1.0").unwrap();
+        assert_eq!(rule.std.offset, 10 * 3600 + 1800);
+        // 2014-01-01T00:00:00Z and 2014-07-01T00:00:00Z
+        assert_eq!(rule.local_type(1388534400).abbr.as_slice(), "+11");
+        assert_eq!(rule.local_type(1404172800).abbr.as_slice(), "+1030");
+
+        let rule = parse_posix_tz("JST-9").unwrap();
+        assert_eq!(rule.local_type(0).offset, 9 * 3600);
+        assert!(parse_posix_tz("EST5EDT,M3.2.0").is_none());
+        assert!(parse_posix_tz("E5").is_none());
+        assert!(parse_posix_tz("<ÉST>5").is_some());
+        assert!(parse_posix_tz("ÉST5ÉDT").is_some());
+        assert!(parse_posix_tz("<É").is_none());
+    }
+
+    #[test]
+    fn test_bad_tzif() {
+        let mut data = b"TZif2".to_vec();
+        data.grow(15, 0);
+        // A header claiming a huge number of transitions, with no data.
+        for &n in [0u8, 0, 0, 0x7f, 1, 0].iter() {
+            data.push_all(&[n, 0xff, 0xff, 0xff]);
+        }
+        assert!(TimeZone::from_tzif("bad", data.as_slice()).is_err());
+        assert!(TimeZone::from_tzif("bad", b"TZif").is_err());
+    }
+
+    #[test]
+    fn test_utc() {
+        let utc = TimeZone::utc();
+        let tm = utc.at(Timespec::new(1234567890, 54321));
+        assert_eq!(tm.tm_hour, 23);
+        assert_eq!(tm.tm_gmtoff, 0);
+        assert_eq!(utc.from_local(&tm), Single(Timespec::new(1234567890, 54321)));
+    }
+
+    #[test]
+    fn test_named() {
+        assert!(TimeZone::named("../../etc/passwd").is_err());
+        assert!(TimeZone::named("/etc/localtime").is_err());
+        let zone = match TimeZone::named("America/New_York") {
+            Ok(zone) => zone,
+            // No time zone database.
+            Err(_) => return,
+        };
+        assert_eq!(zone.name(), "America/New_York");
+
+        // 2014-07-01T12:00:00Z
+        let tm = zone.at(Timespec::new(1404216000, 0));
+        assert_eq!((tm.tm_hour, tm.tm_gmtoff, tm.tm_isdst), (8, -4 * 3600, 1));
+        assert_eq!(zone.abbreviation(Timespec::new(1404216000, 0)), "EDT");
+        // 2014-01-01T12:00:00Z
+        let tm = zone.at(Timespec::new(1388577600, 0));
+        assert_eq!((tm.tm_hour, tm.tm_gmtoff, tm.tm_isdst), (7, -5 * 3600, 0));
+        // 2100-07-01T12:00:00Z, after the last transition in the file.
+        let tm = zone.at(Timespec::new(4118126400, 0));
+        assert_eq!((tm.tm_year, tm.tm_hour, tm.tm_isdst), (200, 8, 1));
+
+        assert_eq!(local(&zone, 2014, 7, 1, 8, 0), Single(Timespec::new(1404216000, 0)));
+        // Clocks went back from 2:00 EDT to 1:00 EST on 2014-11-02.
+        assert_eq!(local(&zone, 2014, 11, 2, 1, 30),
+                   Ambiguous(Timespec::new(1414906200, 0), Timespec::new(1414909800, 0)));
+        // Clocks went forward from 2:00 EST to 3:00 EDT on 2014-03-09.
+        assert_eq!(local(&zone, 2014, 3, 9, 2, 30), Nonexistent(Timespec::new(1394348400, 0)));
+        assert_eq!(local(&zone, 2014, 3, 9, 3, 0), Single(Timespec::new(1394348400, 0)));
+        // The same in 2100.
+        assert_eq!(local(&zone, 2100, 3, 14, 2, 30), Nonexistent(Timespec::new(4108690800, 0)));
+    }
+
+    #[test]
+    fn test_southern_hemisphere() {
+        let zone = match TimeZone::named("Australia/Sydney") {
+            Ok(zone) => zone,
+            Err(_) => return,
+        };
+        // 2014-01-01T00:00:00Z and 2014-07-01T00:00:00Z
+        assert_eq!(zone.at(Timespec::new(1388534400, 0)).tm_gmtoff, 11 * 3600);
+        assert_eq!(zone.at(Timespec::new(1404172800, 0)).tm_gmtoff, 10 * 3600);
+        // 2100-01-01T00:00:00Z and 2100-07-01T00:00:00Z
+        assert_eq!(zone.at(Timespec::new(4102444800, 0)).tm_gmtoff, 11 * 3600);
+        assert_eq!(zone.at(Timespec::new(4118083200, 0)).tm_gmtoff, 10 * 3600);
+    }
+}
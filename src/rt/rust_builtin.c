@@ -330,6 +330,37 @@ rust_unset_sigprocmask() {
 
 #endif
 
+// miniz doesn't allocate its compressor and decompressor states for callers
+// that can't know their sizes, which libflate's streaming reader and writer
+// need. Only the declarations of miniz are included here.
+#define MINIZ_HEADER_FILE_ONLY
+#include "miniz.c"
+
+tdefl_compressor*
+rust_tdefl_compressor_alloc() {
+    return (tdefl_compressor*)malloc(sizeof(tdefl_compressor));
+}
+
+void
+rust_tdefl_compressor_free(tdefl_compressor* comp) {
+    free(comp);
+}
+
+tinfl_decompressor*
+rust_tinfl_decompressor_alloc() {
+    tinfl_decompressor* decomp =
+        (tinfl_decompressor*)malloc(sizeof(tinfl_decompressor));
+    if (decomp != NULL) {
+        tinfl_init(decomp);
+    }
+    return decomp;
+}
+
+void
+rust_tinfl_decompressor_free(tinfl_decompressor* decomp) {
+    free(decomp);
+}
+
 #if defined(__DragonFly__)
 #include <errno.h>
 // In DragonFly __error() is an inline function and as such
@@ -0,0 +1,33 @@
+// Copyright 2014 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::intrinsics;
+use std::os;
+
+#[test]
+fn aborts() {
+    unsafe { intrinsics::abort() }
+}
+
+#[test]
+fn segfaults() {
+    unsafe { intrinsics::volatile_store(0 as *mut int, 1) }
+}
+
+#[test]
+fn passes() {
+}
+
+// The process a test runs in shouldn't look like one that runs an isolated
+// test to any harness the test starts itself.
+#[test]
+fn runs_unisolated_harness() {
+    assert!(os::getenv("RUST_TEST_ISOLATED").is_none());
+}